@@ -8,6 +8,21 @@ use semchunk_rs::Chunker;
 
 const CHUNK_SIZE: usize = 512;
 
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static GLOBAL: semchunk_rs::stats::TrackingAllocator =
+    semchunk_rs::stats::TrackingAllocator(std::alloc::System);
+
+/// Returns the number of allocations made chunking the texts once.
+#[cfg(feature = "alloc-stats")]
+fn count_allocations(texts: &[&str], chunker: &Chunker) -> usize {
+    texts
+        .iter()
+        .filter_map(|text| chunker.chunk_with_stats(text).1.allocations)
+        .map(|stats| stats.allocations)
+        .sum()
+}
+
 fn chunk_texts(texts: Vec<&str>, chunker: &Chunker) {
     for text in texts {
         let _ = chunker.chunk(text);
@@ -39,6 +54,18 @@ fn benchmark_chunk_texts(c: &mut Criterion) {
     let chunker = Chunker::new(CHUNK_SIZE, token_counter);
     let texts = read_gutenberg_texts();
     let text_refs: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
+
+    // The whitespace counter does not allocate, so its count is the chunker's own allocations.
+    #[cfg(feature = "alloc-stats")]
+    {
+        let word_chunker =
+            Chunker::new(CHUNK_SIZE, Box::new(|s: &str| s.split_whitespace().count()));
+        println!(
+            "gutenberg allocations: {} with RoBERTa, {} with a whitespace counter",
+            count_allocations(&text_refs, &chunker),
+            count_allocations(&text_refs, &word_chunker),
+        );
+    }
     c.bench_function("gutenberg", |b| {
         b.iter(|| chunk_texts(black_box(text_refs.clone()), black_box(&chunker)));
     });
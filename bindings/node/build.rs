@@ -0,0 +1,4 @@
+fn main() {
+    // Node.js addons resolve N-API symbols when loaded, which needs platform-specific linker flags.
+    napi_build::setup();
+}
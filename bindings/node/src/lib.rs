@@ -0,0 +1,165 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Node.js bindings for semchunk-rs, built with napi-rs.
+//!
+//! ```js
+//! const { chunk, chunkSync } = require("semchunk-rs");
+//!
+//! // Chunking runs on the libuv thread pool, so the event loop is not blocked.
+//! const chunks = await chunk(text, { chunkSize: 512, counter: "words" });
+//! ```
+
+use napi::bindgen_prelude::{AsyncTask, Error, Result};
+use napi::{Env, Task};
+use napi_derive::napi;
+
+use semchunk_rs::chunk::OffsetConverter;
+use semchunk_rs::chunker::ConfigError;
+use semchunk_rs::Chunker;
+
+/// Options for `chunk` and `chunkSync`.
+#[napi(object)]
+pub struct ChunkOptions {
+    /// The maximum number of tokens in a chunk.
+    pub chunk_size: u32,
+    /// The token counter: `"words"` (the default) for whitespace-separated words, or `"chars"` for Unicode code points.
+    pub counter: Option<String>,
+}
+
+/// A chunk with the range it covers in the source text, in UTF-16 code units.
+#[napi(object)]
+pub struct NodeChunk {
+    pub text: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Chunks text off the main thread.
+pub struct ChunkTask {
+    text: String,
+    chunk_size: usize,
+    token_counter: fn(&str) -> usize,
+}
+
+impl Task for ChunkTask {
+    type Output = Vec<NodeChunk>;
+    type JsValue = Vec<NodeChunk>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(chunk_text(&self.text, self.chunk_size, self.token_counter))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+fn count_words(s: &str) -> usize {
+    s.split_whitespace().count()
+}
+
+fn count_chars(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Returns the built-in token counter with the given name.
+fn token_counter(name: Option<&str>) -> Result<fn(&str) -> usize> {
+    match name.unwrap_or("words") {
+        "words" => Ok(count_words),
+        "chars" => Ok(count_chars),
+        name => Err(Error::from_reason(format!(
+            "unknown token counter '{}'",
+            name
+        ))),
+    }
+}
+
+/// Returns the chunk size of the options, which must be at least 1.
+fn chunk_size(options: &ChunkOptions) -> Result<usize> {
+    match options.chunk_size {
+        0 => Err(Error::from_reason(ConfigError::ZeroChunkSize.to_string())),
+        chunk_size => Ok(chunk_size as usize),
+    }
+}
+
+fn chunk_text(text: &str, chunk_size: usize, token_counter: fn(&str) -> usize) -> Vec<NodeChunk> {
+    let chunker = Chunker::new(chunk_size, Box::new(token_counter));
+    let mut chunks = Vec::new();
+    let mut offsets = OffsetConverter::utf16(text);
+    chunker.chunk_to(text, |chunk| {
+        chunks.push(NodeChunk {
+            start: offsets.convert(chunk.start) as u32,
+            end: offsets.convert(chunk.end) as u32,
+            text: chunk.text,
+        })
+    });
+    chunks
+}
+
+/// Chunks text on the libuv thread pool, resolving to an array of `{ text, start, end }` objects.
+#[napi]
+pub fn chunk(text: String, options: ChunkOptions) -> Result<AsyncTask<ChunkTask>> {
+    Ok(AsyncTask::new(ChunkTask {
+        text,
+        chunk_size: chunk_size(&options)?,
+        token_counter: token_counter(options.counter.as_deref())?,
+    }))
+}
+
+/// Chunks text on the calling thread, returning an array of `{ text, start, end }` objects.
+#[napi]
+pub fn chunk_sync(text: String, options: ChunkOptions) -> Result<Vec<NodeChunk>> {
+    let token_counter = token_counter(options.counter.as_deref())?;
+    Ok(chunk_text(&text, chunk_size(&options)?, token_counter))
+}
+
+#[cfg(test)]
+mod node_tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        let text = "Übermäßig 🦀 große Äpfel";
+        let chunks = chunk_text(text, 2, count_words);
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(texts, vec!["Übermäßig 🦀", "große Äpfel"]);
+        // Offsets index the UTF-16 code units of the text, in which the crab takes two.
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        for chunk in &chunks {
+            let slice = &utf16[chunk.start as usize..chunk.end as usize];
+            assert_eq!(String::from_utf16(slice).unwrap(), chunk.text);
+        }
+        assert_eq!((chunks[1].start, chunks[1].end), (13, 24));
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let options = |chunk_size| ChunkOptions {
+            chunk_size,
+            counter: None,
+        };
+        assert_eq!(chunk_size(&options(512)).unwrap(), 512);
+        assert!(chunk_size(&options(0)).is_err());
+        assert!(token_counter(Some("bytes")).is_err());
+    }
+}
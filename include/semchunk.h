@@ -0,0 +1,69 @@
+/*
+ * C bindings for semchunk-rs, available when the crate is built with the `ffi` feature.
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * Link against the resulting `libsemchunk_rs` shared library.
+ */
+
+#ifndef SEMCHUNK_H
+#define SEMCHUNK_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An opaque chunker. */
+typedef struct SemchunkChunker SemchunkChunker;
+
+/* An opaque list of chunks. */
+typedef struct SemchunkChunks SemchunkChunks;
+
+/*
+ * A chunk, with its text and the byte range [start, end) it covers in the source text.
+ * `text` points to `text_len` bytes of UTF-8 followed by a NUL byte, and lives as long as the list it came from.
+ */
+typedef struct SemchunkChunk {
+    const char *text;
+    size_t text_len;
+    size_t start;
+    size_t end;
+} SemchunkChunk;
+
+/*
+ * Counts the number of tokens in `len` bytes of UTF-8 text (not NUL-terminated).
+ * `user_data` is the pointer given to semchunk_new.
+ */
+typedef size_t (*SemchunkTokenCounter)(const char *text, size_t len, void *user_data);
+
+/*
+ * Creates a chunker, returning NULL if `chunk_size` is zero.
+ * If `counter` is NULL, tokens are counted as whitespace-separated words and `user_data` is ignored.
+ */
+SemchunkChunker *semchunk_new(size_t chunk_size, SemchunkTokenCounter counter, void *user_data);
+
+/*
+ * Chunks `len` bytes of UTF-8 text, returning NULL if the text is not valid UTF-8.
+ * A chunker keeps state between calls, so it must not be used from two threads at once.
+ */
+SemchunkChunks *semchunk_chunk(SemchunkChunker *chunker, const char *text, size_t len);
+
+/* Returns the number of chunks in the list. */
+size_t semchunk_chunks_len(const SemchunkChunks *chunks);
+
+/* Returns the chunk at `index`, or NULL if it is out of bounds. */
+const SemchunkChunk *semchunk_chunks_get(const SemchunkChunks *chunks, size_t index);
+
+/* Frees a list of chunks. Does nothing if `chunks` is NULL. */
+void semchunk_chunks_free(SemchunkChunks *chunks);
+
+/* Frees a chunker. Does nothing if `chunker` is NULL. */
+void semchunk_free(SemchunkChunker *chunker);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SEMCHUNK_H */
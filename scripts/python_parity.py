@@ -0,0 +1,57 @@
+#!/usr/bin/env python3
+"""Writes the chunks Python semchunk produces for a set of texts to python_parity.jsonl, for
+checking the parity of semchunk-rs' `python-compat` feature.
+
+Usage:
+    pip install semchunk
+    python scripts/python_parity.py --dir ./data
+    DATA_DIR=./data cargo test --features python-compat,serde test_python_parity -- --ignored
+"""
+
+import argparse
+import json
+import pathlib
+
+import semchunk
+
+TEXTS = {
+    "sentence": "The quick brown fox jumps over the lazy dog.",
+    "paragraphs": "First paragraph. It has two sentences.\n\nSecond paragraph!\r\n\r\nThird, and last; done.",
+    "tabs": "name\tvalue\tnotes\nfox\tquick\tbrown, jumps\n",
+    "punctuation": "a-b-c,d-e-f;g(h)i[j]k/l\\m&n–o—p…q",
+    "unicode": "Übermäßig große Äpfel fielen. Die Katze schlief… 猫は寝ていた。　終わり",
+    "separators": "one\x1ftwo\x1ethree  four five",
+    "whitespace": "   \n\n  \t  word  \n\n\n   ",
+    "no_separators": "abcdefghijklmnopqrstuvwxyz",
+}
+CHUNK_SIZES = [1, 2, 4, 16, 64]
+OVERLAPS = [None, 0.5, 3]
+
+
+def count_words(text: str) -> int:
+    return len(text.split())
+
+
+def main() -> None:
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("-d", "--dir", default=".", help="The data directory, which may contain gutenberg/.")
+    args = parser.parse_args()
+    data_dir = pathlib.Path(args.dir)
+
+    cases = [({"name": name, "text": text}, text) for name, text in TEXTS.items()]
+    for path in sorted((data_dir / "gutenberg").glob("*.txt"))[:3]:
+        # Read as the Rust test does, replacing invalid UTF-8.
+        text = path.read_bytes().decode("utf-8", errors="replace")
+        cases.append(({"name": path.name, "path": str(path.relative_to(data_dir))}, text))
+
+    with open(data_dir / "python_parity.jsonl", "w", encoding="utf-8") as out:
+        for case, text in cases:
+            for chunk_size in CHUNK_SIZES + ([512] if "path" in case else []):
+                for overlap in OVERLAPS:
+                    chunks, offsets = semchunk.chunk(text, chunk_size, count_words, offsets=True, overlap=overlap)
+                    record = {**case, "chunk_size": chunk_size, "overlap": overlap, "chunks": chunks, "offsets": offsets}
+                    out.write(json.dumps(record, ensure_ascii=False) + "\n")
+
+
+if __name__ == "__main__":
+    main()
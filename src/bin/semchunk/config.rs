@@ -0,0 +1,119 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Chunking settings read from a TOML file with `--config`, so teams can version-control them.
+
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use semchunk_rs::splitter::SeparatorProfile;
+use serde::Deserialize;
+
+use crate::Format;
+
+/// The separators text is split at, by the script or language it is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Separators {
+    /// Chosen for each text from the script most of its first letters are in.
+    #[default]
+    Auto,
+    /// Latin punctuation, which also suits Cyrillic, Greek and most other scripts.
+    Latin,
+    /// Full-width and ideographic punctuation, for Chinese, Japanese and Korean.
+    Cjk,
+    /// Arabic punctuation, for Arabic, Persian and Urdu.
+    Arabic,
+    /// Statement, block and operator punctuation, for source code.
+    Code,
+}
+
+impl From<Separators> for SeparatorProfile {
+    fn from(separators: Separators) -> Self {
+        match separators {
+            Separators::Auto => SeparatorProfile::Auto,
+            Separators::Latin => SeparatorProfile::Latin,
+            Separators::Cjk => SeparatorProfile::Cjk,
+            Separators::Arabic => SeparatorProfile::Arabic,
+            Separators::Code => SeparatorProfile::Code,
+        }
+    }
+}
+
+/// Chunking settings, each of which may be left out to fall back to the command line option or its default.
+///
+/// ```toml
+/// size = 512
+/// overlap = 64
+/// counter = "tiktoken:cl100k_base"
+/// separators = "latin"
+/// format = "jsonl"
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChunkerConfig {
+    pub size: Option<usize>,
+    pub overlap: Option<usize>,
+    pub counter: Option<String>,
+    pub separators: Option<Separators>,
+    pub format: Option<Format>,
+}
+
+impl ChunkerConfig {
+    /// Reads the settings from the TOML file at `path`, failing on keys that are not settings.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        let text = fs::read_to_string(path).map_err(|e| error(&e))?;
+        toml::from_str(&text).map_err(|e| error(&e))
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: ChunkerConfig = toml::from_str(
+            "size = 256\noverlap = 32\ncounter = \"chars\"\nseparators = \"code\"\nformat = \"openai-batch\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            ChunkerConfig {
+                size: Some(256),
+                overlap: Some(32),
+                counter: Some("chars".to_string()),
+                separators: Some(Separators::Code),
+                format: Some(Format::OpenaiBatch),
+            }
+        );
+        assert_eq!(
+            toml::from_str::<ChunkerConfig>("").unwrap(),
+            ChunkerConfig::default()
+        );
+        assert!(toml::from_str::<ChunkerConfig>("chunk_size = 256").is_err());
+        assert!(toml::from_str::<ChunkerConfig>("format = \"xml\"").is_err());
+        assert!(ChunkerConfig::read(Path::new("does-not-exist.toml")).is_err());
+    }
+}
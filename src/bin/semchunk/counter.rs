@@ -0,0 +1,138 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Token counters selectable with `--counter`.
+
+use std::rc::Rc;
+
+/// A function that counts the number of tokens in a string, shared between the chunker and the output.
+pub type TokenCounter = Rc<dyn Fn(&str) -> usize>;
+
+/// Builds a token counter from a specification of the form `kind[:argument]`.
+///
+/// Supported specifications:
+///
+/// * `words` - Whitespace-separated words.
+/// * `chars` - Unicode scalar values.
+/// * `tiktoken:<encoding>` - A tiktoken encoding such as `cl100k_base` (requires the `tiktoken-rs` feature).
+/// * `hf:<model or path>` - A Hugging Face tokenizer, loaded from a `tokenizer.json` path or the Hub (requires the `tokenizers` feature).
+/// * `roberta:<vocab>,<merges>` - A RoBERTa tokenizer loaded from vocab and merges files (requires the `rust_tokenizers` feature).
+pub fn parse_counter(spec: &str) -> Result<TokenCounter, String> {
+    let (kind, argument) = match spec.split_once(':') {
+        Some((kind, argument)) => (kind, Some(argument)),
+        None => (spec, None),
+    };
+    match (kind, argument) {
+        ("words", None) => Ok(Rc::new(count_words)),
+        ("chars", None) => Ok(Rc::new(|s: &str| s.chars().count())),
+        ("tiktoken", Some(encoding)) => tiktoken_counter(encoding),
+        ("hf", Some(model)) => hf_counter(model),
+        ("roberta", Some(files)) => match files.split_once(',') {
+            Some((vocab, merges)) => roberta_counter(vocab, merges),
+            None => Err(format!(
+                "invalid counter '{}': expected roberta:<vocab>,<merges>",
+                spec
+            )),
+        },
+        _ => Err(format!(
+            "invalid counter '{}': expected one of words, chars, tiktoken:<encoding>, hf:<model>, roberta:<vocab>,<merges>",
+            spec
+        )),
+    }
+}
+
+/// Counts the whitespace-separated words in a string.
+pub fn count_words(s: &str) -> usize {
+    s.split_whitespace().count()
+}
+
+#[cfg(feature = "tiktoken-rs")]
+fn tiktoken_counter(encoding: &str) -> Result<TokenCounter, String> {
+    let bpe = match encoding {
+        "o200k_base" => tiktoken_rs::o200k_base(),
+        "cl100k_base" => tiktoken_rs::cl100k_base(),
+        "p50k_base" => tiktoken_rs::p50k_base(),
+        "p50k_edit" => tiktoken_rs::p50k_edit(),
+        "r50k_base" => tiktoken_rs::r50k_base(),
+        _ => return Err(format!("unknown tiktoken encoding '{}'", encoding)),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(Rc::new(move |s: &str| bpe.encode_ordinary(s).len()))
+}
+
+#[cfg(not(feature = "tiktoken-rs"))]
+fn tiktoken_counter(_encoding: &str) -> Result<TokenCounter, String> {
+    Err("tiktoken counters require the `tiktoken-rs` feature".to_string())
+}
+
+#[cfg(feature = "tokenizers")]
+fn hf_counter(model: &str) -> Result<TokenCounter, String> {
+    let tokenizer = if std::path::Path::new(model).is_file() {
+        tokenizers::Tokenizer::from_file(model)
+    } else {
+        tokenizers::Tokenizer::from_pretrained(model, None)
+    }
+    .map_err(|e| e.to_string())?;
+    // A text that cannot be encoded is counted as over the chunk size rather than as empty.
+    Ok(Rc::new(move |s: &str| {
+        tokenizer
+            .encode(s, false)
+            .map_or(semchunk_rs::counters::ENCODE_FAILED, |encoding| {
+                encoding.len()
+            })
+    }))
+}
+
+#[cfg(not(feature = "tokenizers"))]
+fn hf_counter(_model: &str) -> Result<TokenCounter, String> {
+    Err("hf counters require the `tokenizers` feature".to_string())
+}
+
+#[cfg(feature = "rust_tokenizers")]
+fn roberta_counter(vocab: &str, merges: &str) -> Result<TokenCounter, String> {
+    use rust_tokenizers::tokenizer::{RobertaTokenizer, Tokenizer};
+
+    let tokenizer =
+        RobertaTokenizer::from_file(vocab, merges, false, false).map_err(|e| e.to_string())?;
+    Ok(Rc::new(move |s: &str| tokenizer.tokenize(s).len()))
+}
+
+#[cfg(not(feature = "rust_tokenizers"))]
+fn roberta_counter(_vocab: &str, _merges: &str) -> Result<TokenCounter, String> {
+    Err("roberta counters require the `rust_tokenizers` feature".to_string())
+}
+
+#[cfg(test)]
+mod counter_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_counter() {
+        let words = parse_counter("words").unwrap();
+        assert_eq!(words("The quick  brown fox"), 4);
+        let chars = parse_counter("chars").unwrap();
+        assert_eq!(chars("héllo"), 5);
+        assert!(parse_counter("words:extra").is_err());
+        assert!(parse_counter("roberta:vocab.json").is_err());
+        assert!(parse_counter("unknown").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Resolution and reading of the files, directories and glob patterns given on the command line.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+/// The input name that stands for stdin.
+const STDIN: &str = "-";
+
+/// Whether the path stands for stdin.
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN)
+}
+
+/// Expands the inputs into the list of files to chunk, in the order given.
+///
+/// Directories are searched recursively, and inputs containing `*`, `?` or `[` are treated as glob patterns.
+/// No inputs, or an input of `-`, means stdin.
+pub fn collect_inputs(inputs: &[String]) -> io::Result<Vec<PathBuf>> {
+    if inputs.is_empty() {
+        return Ok(vec![PathBuf::from(STDIN)]);
+    }
+    let mut files = Vec::new();
+    for input in inputs {
+        if input == STDIN {
+            files.push(PathBuf::from(STDIN));
+        } else if input.contains(['*', '?', '[']) {
+            files.extend(expand_glob(input)?);
+        } else if Path::new(input).is_dir() {
+            let pattern = Path::new(input).join("**").join("*");
+            files.extend(expand_glob(&pattern.to_string_lossy())?);
+        } else {
+            files.push(PathBuf::from(input));
+        }
+    }
+    Ok(files)
+}
+
+fn expand_glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Reads a file, or stdin for `-`, as text, decompressing it first if it ends in `.gz` or `.zst`.
+pub fn read_text(path: &Path) -> io::Result<String> {
+    if is_stdin(path) {
+        let mut text = String::new();
+        io::stdin().lock().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    let file = BufReader::new(File::open(path)?);
+    let mut reader: Box<dyn Read> = match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Box::new(MultiGzDecoder::new(file)),
+        Some("zst") => Box::new(zstd::Decoder::with_buffer(file)?),
+        _ => Box::new(file),
+    };
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Returns the path of the output file for `input` inside `out_dir`, mirroring the input's relative path.
+///
+/// A `.gz` or `.zst` extension on the input is kept, so that `book.txt` and `book.txt.gz` get different outputs. So that different inputs never share an output file, the root of an absolute path is written as an `@root` directory (or `@<drive>` on Windows), `..` as `@up`, and a component starting with `@` gets another `@`.
+pub fn output_path(out_dir: &Path, input: &Path, extension: &str) -> PathBuf {
+    if is_stdin(input) {
+        return out_dir.join(format!("stdin.{}", extension));
+    }
+    let relative: PathBuf = input
+        .components()
+        .filter_map(|component| match component {
+            Component::Prefix(prefix) => {
+                let prefix = prefix.as_os_str().to_string_lossy();
+                let drive: String = prefix.chars().filter(char::is_ascii_alphanumeric).collect();
+                Some(OsString::from(format!("@{}", drive)))
+            }
+            Component::RootDir => Some(OsString::from("@root")),
+            Component::CurDir => None,
+            Component::ParentDir => Some(OsString::from("@up")),
+            Component::Normal(name) if name.as_encoded_bytes().starts_with(b"@") => {
+                let mut escaped = OsString::from("@");
+                escaped.push(name);
+                Some(escaped)
+            }
+            Component::Normal(name) => Some(name.to_os_string()),
+        })
+        .collect();
+    let mut file_name = relative.into_os_string();
+    file_name.push(".");
+    file_name.push(extension);
+    out_dir.join(file_name)
+}
+
+#[cfg(test)]
+mod input_tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        let out_dir = Path::new("chunks");
+        assert_eq!(
+            output_path(out_dir, Path::new("data/a/book.txt"), "jsonl"),
+            PathBuf::from("chunks/data/a/book.txt.jsonl")
+        );
+        assert_eq!(
+            output_path(out_dir, Path::new("./book.txt"), "jsonl"),
+            PathBuf::from("chunks/book.txt.jsonl")
+        );
+        // Inputs that differ only in their root, `..` or `@` components get different outputs.
+        assert_eq!(
+            output_path(out_dir, Path::new("/srv/../book.txt"), "jsonl"),
+            PathBuf::from("chunks/@root/srv/@up/book.txt.jsonl")
+        );
+        assert_eq!(
+            output_path(out_dir, Path::new("@up/book.txt"), "jsonl"),
+            PathBuf::from("chunks/@@up/book.txt.jsonl")
+        );
+        assert_eq!(
+            output_path(out_dir, Path::new("crawl/page.txt.zst"), "jsonl.gz"),
+            PathBuf::from("chunks/crawl/page.txt.zst.jsonl.gz")
+        );
+        // A compressed input does not share its output with the uncompressed file of the same name.
+        assert_ne!(
+            output_path(out_dir, Path::new("data/book.txt"), "jsonl"),
+            output_path(out_dir, Path::new("data/book.txt.gz"), "jsonl")
+        );
+    }
+
+    #[test]
+    fn test_read_text_compressed() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("semchunk-input-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = "The quick brown fox jumps over the lazy dog.";
+
+        let gz_path = dir.join("doc.txt.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&gz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(read_text(&gz_path).unwrap(), text);
+
+        let zst_path = dir.join("doc.txt.zst");
+        std::fs::write(&zst_path, zstd::encode_all(text.as_bytes(), 0).unwrap()).unwrap();
+        assert_eq!(read_text(&zst_path).unwrap(), text);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,761 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Command line interface for chunking text files with semchunk-rs.
+
+mod config;
+mod counter;
+mod input;
+mod output;
+mod stats;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use semchunk_rs::checkpoint::Checkpoint;
+use semchunk_rs::chunker::ConfigError;
+use semchunk_rs::stats::{ChunkStats, CorpusReport};
+use semchunk_rs::{writers, Chunker, Splitter};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::{ChunkerConfig, Separators};
+use crate::counter::{parse_counter, TokenCounter};
+use crate::input::{collect_inputs, is_stdin, output_path, read_text};
+use crate::output::{Compression, Spill};
+use crate::stats::Stats;
+
+/// Split text files into semantically meaningful chunks.
+#[derive(Debug, Parser)]
+#[command(
+    name = "semchunk",
+    version,
+    about,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    chunk: ChunkArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Report chunk counts, the token count distribution, chunk size utilization, duplicates and the separators chunks end at over a corpus.
+    Stats(StatsArgs),
+    /// Serve `POST /chunk` over HTTP.
+    #[cfg(feature = "serve")]
+    Serve(ServeArgs),
+}
+
+/// Options for the serve command.
+#[cfg(feature = "serve")]
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// The address to listen on.
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    addr: String,
+}
+
+/// Options for the stats command.
+#[derive(Debug, Args)]
+struct StatsArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Print the report as JSON, with a histogram of token counts, instead of a summary.
+    #[arg(long)]
+    json: bool,
+
+    /// The number of token counts in each bucket of the histogram. Defaults to a tenth of the chunk size.
+    #[arg(long)]
+    bucket_size: Option<usize>,
+}
+
+/// Options shared by every command.
+#[derive(Debug, Args)]
+struct CommonArgs {
+    /// The files, directories or glob patterns to chunk. Files ending in .gz or .zst are decompressed.
+    /// Reads from stdin if no inputs are given or an input is `-`.
+    inputs: Vec<String>,
+
+    /// The maximum number of tokens in a chunk. Defaults to 512.
+    #[arg(short, long)]
+    size: Option<usize>,
+
+    /// The token counter: words, chars, tiktoken:<encoding>, hf:<model>, or roberta:<vocab>,<merges>. Defaults to words.
+    #[arg(short, long)]
+    counter: Option<String>,
+
+    /// The number of tokens consecutive chunks share. Defaults to 0.
+    #[arg(long)]
+    overlap: Option<usize>,
+
+    /// The separators to split text at. Defaults to auto.
+    #[arg(long, value_enum)]
+    separators: Option<Separators>,
+
+    /// A TOML file of settings: size, overlap, counter, separators and format. Options given on the command line take precedence.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// The number of files to chunk in parallel. Defaults to the number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+}
+
+/// Options for writing chunks.
+#[derive(Debug, Args)]
+struct ChunkArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// The output format. Defaults to text.
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Write one output file per input into this directory instead of writing all chunks to stdout.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+
+    /// The string written after each chunk in the text format. Supports the escapes \0, \n, \r, \t, \\ and \xHH.
+    #[arg(short, long, default_value = "\\n", value_parser = parse_delimiter)]
+    delimiter: String,
+
+    /// Separate chunks in the text format with NUL bytes, like --delimiter '\0'.
+    #[arg(short = '0', long, conflicts_with = "delimiter")]
+    null: bool,
+
+    /// Compress the output. Output files written with --out get a matching extension.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    output_compression: Compression,
+
+    /// Record the inputs written with --out to this state file, and skip those already recorded with the same settings, so that an interrupted run can be resumed.
+    #[arg(long, requires = "out")]
+    checkpoint: Option<PathBuf>,
+
+    /// The embedding model requested for each chunk in the openai-batch format.
+    #[arg(long, default_value = "text-embedding-3-small")]
+    embedding_model: String,
+
+    /// Tag each chunk in the jsonl format with the ISO 639-3 code of its detected language.
+    #[cfg(feature = "whatlang")]
+    #[arg(long)]
+    language: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Format {
+    /// The text of each chunk, followed by the delimiter.
+    Text,
+    /// One JSON object per chunk with its document, index, offsets and token count.
+    Jsonl,
+    /// One OpenAI Batch API request per chunk to embed it, identified by its document and index.
+    OpenaiBatch,
+}
+
+impl Format {
+    /// The extension given to output files written with `--out`.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "chunks.txt",
+            Format::Jsonl => "jsonl",
+            Format::OpenaiBatch => "batch.jsonl",
+        }
+    }
+}
+
+impl CommonArgs {
+    /// Fills in the options not given on the command line from the config file, if any, returning the config.
+    fn apply_config(&mut self) -> Result<ChunkerConfig, String> {
+        let mut config = match &self.config {
+            Some(path) => ChunkerConfig::read(path)?,
+            None => ChunkerConfig::default(),
+        };
+        self.size = self.size.or(config.size);
+        self.counter = self.counter.take().or(config.counter.take());
+        self.overlap = self.overlap.or(config.overlap);
+        self.separators = self.separators.or(config.separators);
+        Ok(config)
+    }
+
+    /// The maximum number of tokens in a chunk.
+    fn size(&self) -> usize {
+        self.size.unwrap_or(512)
+    }
+
+    /// The specification of the token counter.
+    fn counter(&self) -> &str {
+        self.counter.as_deref().unwrap_or("words")
+    }
+
+    /// Builds a chunker with the options, counting tokens with `token_counter`, failing on a chunk size of zero or an overlap that does not fit in it.
+    fn chunker(&self, token_counter: TokenCounter) -> Result<Chunker, ConfigError> {
+        let splitter = Splitter::default().profile(self.separators.unwrap_or_default().into());
+        Chunker::try_new(self.size(), Box::new(move |s: &str| token_counter(s)))?
+            .splitter(splitter)
+            .try_overlap(self.overlap.unwrap_or(0))
+    }
+}
+
+impl ChunkArgs {
+    /// Fills in the options not given on the command line from the config file, if any.
+    fn apply_config(&mut self) -> Result<(), String> {
+        let config = self.common.apply_config()?;
+        self.format = self.format.or(config.format);
+        Ok(())
+    }
+
+    /// The output format.
+    fn format(&self) -> Format {
+        self.format.unwrap_or(Format::Text)
+    }
+
+    /// The ID an input is recorded under in the checkpoint: its path and a hash of the settings its output depends on, so that a run with other settings does not skip the inputs chunked with the old ones.
+    fn checkpoint_id(&self, path: &Path) -> String {
+        let common = &self.common;
+        #[allow(unused_mut)]
+        let mut settings = format!(
+            "{:?}",
+            (
+                common.size(),
+                common.counter(),
+                common.overlap.unwrap_or(0),
+                common.separators.unwrap_or_default(),
+                self.format(),
+                self.delimiter(),
+                self.output_compression,
+                &self.embedding_model,
+            )
+        );
+        // Only added when set, so that builds without the option give the same IDs.
+        #[cfg(feature = "whatlang")]
+        if self.language {
+            settings.push_str(" language");
+        }
+        // SHA-256 is specified, unlike the standard library's hasher, so IDs match across runs and Rust releases.
+        let digest: String = Sha256::digest(settings.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("{}#{}", path.to_string_lossy(), digest)
+    }
+
+    /// The string written after each chunk in the text format.
+    fn delimiter(&self) -> &str {
+        match self.null {
+            true => "\0",
+            false => &self.delimiter,
+        }
+    }
+}
+
+/// Parses a delimiter, replacing escape sequences, since NUL bytes cannot be passed as arguments.
+fn parse_delimiter(s: &str) -> Result<String, String> {
+    let mut delimiter = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            delimiter.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => delimiter.push('\0'),
+            Some('n') => delimiter.push('\n'),
+            Some('r') => delimiter.push('\r'),
+            Some('t') => delimiter.push('\t'),
+            Some('\\') => delimiter.push('\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if byte.is_ascii() => delimiter.push(byte as char),
+                    _ => return Err(format!("invalid escape '\\x{}'", hex)),
+                }
+            }
+            Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+            None => return Err("trailing '\\'".to_string()),
+        }
+    }
+    Ok(delimiter)
+}
+
+/// Chunks a single file, writing its chunks to `out`.
+fn chunk_file(
+    args: &ChunkArgs,
+    chunker: &Chunker,
+    path: &Path,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let text = read_text(path)?;
+    let mut result = Ok(());
+    match args.format() {
+        Format::Text => {
+            chunker.chunk_to(&text, |chunk| {
+                if result.is_ok() {
+                    result = write!(out, "{}{}", chunk.text, args.delimiter());
+                }
+            });
+            result
+        }
+        Format::Jsonl => {
+            let doc = path.to_string_lossy();
+            let mut writer = writers::ndjson(&mut *out);
+            #[cfg(feature = "whatlang")]
+            if args.language {
+                writer = writer.detect_language();
+            }
+            // Token counts made while chunking are reused rather than counted again.
+            for (index, (chunk, tokens)) in chunker.chunk_with_tokens(&text).iter().enumerate() {
+                writer.write(&doc, index, chunk, *tokens)?;
+            }
+            writer.finish().map(drop)
+        }
+        Format::OpenaiBatch => {
+            let doc = path.to_string_lossy();
+            let mut writer = writers::openai_batch(&mut *out, &args.embedding_model);
+            let mut index = 0;
+            chunker.chunk_to(&text, |chunk| {
+                if result.is_ok() {
+                    result = writer.write(&doc, index, &chunk);
+                }
+                index += 1;
+            });
+            result.and_then(|()| writer.finish().map(drop))
+        }
+    }
+}
+
+/// Chunks the file at `path`, returning its output, or writing it to a file in `out_dir` if given.
+fn process_file(args: &ChunkArgs, chunker: &Chunker, path: &Path) -> Result<Spill, String> {
+    let error = |e: io::Error| format!("{}: {}", path.display(), e);
+    match &args.out {
+        Some(out_dir) => {
+            let extension = match args.output_compression.extension() {
+                Some(compression) => format!("{}.{}", args.format().extension(), compression),
+                None => args.format().extension().to_string(),
+            };
+            let output = output_path(out_dir, path, &extension);
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent).map_err(error)?;
+            }
+            let file = BufWriter::new(File::create(&output).map_err(error)?);
+            let mut out = args.output_compression.encoder(file).map_err(error)?;
+            chunk_file(args, chunker, path, &mut out).map_err(error)?;
+            out.finish()
+                .and_then(|mut file| file.flush())
+                .map_err(error)?;
+            Ok(Spill::default())
+        }
+        None => {
+            let mut out = Spill::default();
+            chunk_file(args, chunker, path, &mut out).map_err(error)?;
+            Ok(out)
+        }
+    }
+}
+
+/// Returns the statistics of chunking the file at `path`, and every chunk with its token count as counted while chunking.
+fn count_file(
+    chunker: &Chunker,
+    path: &Path,
+) -> Result<(ChunkStats, Vec<(String, usize)>), String> {
+    let text = read_text(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let (chunks, stats) = chunker.chunk_with_tokens_and_stats(&text);
+    Ok((stats, chunks))
+}
+
+/// Runs `process` over every file on a pool of worker threads, passing the results to `consume` in input order.
+///
+/// Workers do not start a file until the results of all but the last `jobs` files before it are consumed, so that the results waiting to be consumed are bounded by the number of workers.
+fn for_each_file<T: Send>(
+    args: &CommonArgs,
+    files: &[PathBuf],
+    process: impl Fn(&Chunker, &Path) -> Result<T, String> + Sync,
+    mut consume: impl FnMut(T) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let jobs = args
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(files.len().max(1));
+
+    // Workers claim files in order and send back their results, which are reordered before being consumed.
+    let next_file = AtomicUsize::new(0);
+    // The number of results consumed, which is usize::MAX once consuming has stopped.
+    let consumed = (Mutex::new(0usize), Condvar::new());
+    let (sender, receiver) = mpsc::channel::<(usize, Result<T, String>)>();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (process, next_file, consumed) = (&process, &next_file, &consumed);
+            scope.spawn(move || {
+                // Token counters are not thread-safe, so each worker builds its own.
+                let built = parse_counter(args.counter()).and_then(|token_counter| {
+                    args.chunker(token_counter).map_err(|e| e.to_string())
+                });
+                let chunker = match built {
+                    Ok(built) => built,
+                    Err(e) => {
+                        let _ = sender.send((next_file.fetch_add(1, Ordering::SeqCst), Err(e)));
+                        return;
+                    }
+                };
+                loop {
+                    let i = next_file.fetch_add(1, Ordering::SeqCst);
+                    if i >= files.len() {
+                        break;
+                    }
+                    let (lock, caught_up) = consumed;
+                    let n_consumed = caught_up
+                        .wait_while(lock.lock().unwrap(), |&mut n| i >= n.saturating_add(jobs))
+                        .unwrap();
+                    if *n_consumed == usize::MAX {
+                        break;
+                    }
+                    drop(n_consumed);
+                    let result = process(&chunker, &files[i]);
+                    if sender.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next_consume = 0;
+        let (lock, caught_up) = &consumed;
+        let consume_all = || {
+            for (i, result) in receiver {
+                pending.insert(i, result);
+                while let Some(result) = pending.remove(&next_consume) {
+                    consume(result?)?;
+                    next_consume += 1;
+                    *lock.lock().unwrap() = next_consume;
+                    caught_up.notify_all();
+                }
+            }
+            Ok(())
+        };
+        let result = consume_all();
+        // Workers waiting to start a file stop rather than wait for results that will not be consumed.
+        *lock.lock().unwrap() = usize::MAX;
+        caught_up.notify_all();
+        result
+    })
+}
+
+fn run_chunk(args: &ChunkArgs) -> Result<(), Box<dyn Error>> {
+    let mut files = collect_inputs(&args.common.inputs)?;
+    // Inputs are recorded once their output files are written, so those of an interrupted run are written again.
+    let mut checkpoint = match &args.checkpoint {
+        Some(path) => Some(Checkpoint::open(path)?),
+        None => None,
+    };
+    if let Some(checkpoint) = &checkpoint {
+        files.retain(|path| is_stdin(path) || !checkpoint.is_done(&args.checkpoint_id(path)));
+    }
+    let mut complete = |path: &Path| -> Result<(), Box<dyn Error>> {
+        match &mut checkpoint {
+            Some(checkpoint) if !is_stdin(path) => {
+                checkpoint.complete(&args.checkpoint_id(path))?
+            }
+            _ => (),
+        }
+        Ok(())
+    };
+    // With --out, nothing is written to stdout, so it is left uncompressed.
+    let stdout_compression = match args.out {
+        Some(_) => Compression::None,
+        None => args.output_compression,
+    };
+    let stdout = BufWriter::new(io::stdout().lock());
+    let mut out = stdout_compression.encoder(stdout)?;
+    match files.as_slice() {
+        // A single input is chunked straight to stdout, without holding its output back.
+        [path] if args.out.is_none() => {
+            let token_counter = parse_counter(args.common.counter())?;
+            let chunker = args.common.chunker(token_counter)?;
+            chunk_file(args, &chunker, path, &mut out)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            complete(path)?;
+        }
+        _ => {
+            let mut consumed = files.iter();
+            for_each_file(
+                &args.common,
+                &files,
+                |chunker, path| process_file(args, chunker, path),
+                |output| {
+                    output.copy_to(&mut out)?;
+                    consumed.next().map_or(Ok(()), |path| complete(path))
+                },
+            )?;
+        }
+    }
+    out.finish()?.flush()?;
+    Ok(())
+}
+
+fn run_stats(args: &StatsArgs) -> Result<(), Box<dyn Error>> {
+    let common = &args.common;
+    let files = collect_inputs(&common.inputs)?;
+    let mut report = CorpusReport::new(args.bucket_size.unwrap_or(common.size() / 10));
+    let mut token_counts = Vec::new();
+    for_each_file(common, &files, count_file, |(stats, chunks)| {
+        report.add_document(&stats);
+        for (chunk, tokens) in chunks {
+            report.add_chunk(&chunk, tokens);
+            token_counts.push(tokens);
+        }
+        Ok(())
+    })?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!("{}", Stats::new(files.len(), token_counts, common.size()));
+    println!(
+        "duplicates   {} ({:.1}%)",
+        report.duplicates,
+        100.0 * report.duplicate_rate()
+    );
+    let separators: Vec<String> = report
+        .separators
+        .iter()
+        .map(|(kind, count)| format!("{:?} {}", kind, count))
+        .collect();
+    println!("boundaries   {}", separators.join("  "));
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut cli = Cli::parse();
+    match &mut cli.command {
+        Some(Command::Stats(args)) => {
+            args.common.apply_config()?;
+            // Fail fast on an invalid counter or chunker before starting any workers.
+            let token_counter = parse_counter(args.common.counter())?;
+            args.common
+                .chunker(token_counter)
+                .map_err(|e| e.to_string())?;
+            run_stats(args)
+        }
+        #[cfg(feature = "serve")]
+        Some(Command::Serve(args)) => run_serve(args),
+        None => {
+            cli.chunk.apply_config()?;
+            let token_counter = parse_counter(cli.chunk.common.counter())?;
+            cli.chunk
+                .common
+                .chunker(token_counter)
+                .map_err(|e| e.to_string())?;
+            run_chunk(&cli.chunk)
+        }
+    }
+}
+
+#[cfg(feature = "serve")]
+fn run_serve(args: &ServeArgs) -> Result<(), Box<dyn Error>> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    eprintln!("listening on {}", args.addr);
+    runtime.block_on(semchunk_rs::serve::serve(args.addr.as_str()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod main_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter("\\n").unwrap(), "\n");
+        assert_eq!(parse_delimiter("\\0").unwrap(), "\0");
+        assert_eq!(parse_delimiter("--\\x1e--").unwrap(), "--\x1e--");
+        assert_eq!(parse_delimiter("a\\\\b").unwrap(), "a\\b");
+        assert!(parse_delimiter("\\q").is_err());
+        assert!(parse_delimiter("\\xZZ").is_err());
+        assert!(parse_delimiter("\\").is_err());
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let path =
+            std::env::temp_dir().join(format!("semchunk-config-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "size = 256\ncounter = \"chars\"\nseparators = \"code\"\nformat = \"jsonl\"\n",
+        )
+        .unwrap();
+        let config = path.to_string_lossy().to_string();
+        let mut cli =
+            Cli::try_parse_from(["semchunk", "--config", &config, "--size", "128"]).unwrap();
+        cli.chunk.apply_config().unwrap();
+        fs::remove_file(&path).unwrap();
+        // Options on the command line take precedence over the config file.
+        assert_eq!(cli.chunk.common.size(), 128);
+        assert_eq!(cli.chunk.common.counter(), "chars");
+        assert_eq!(cli.chunk.common.separators, Some(Separators::Code));
+        assert_eq!(cli.chunk.format(), Format::Jsonl);
+
+        let mut cli = Cli::try_parse_from(["semchunk", "stats"]).unwrap();
+        let Some(Command::Stats(args)) = &mut cli.command else {
+            panic!("expected the stats command");
+        };
+        args.common.apply_config().unwrap();
+        assert_eq!((args.common.size(), args.common.counter()), (512, "words"));
+    }
+
+    #[test]
+    fn test_chunker() {
+        let chunker = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["semchunk"], args].concat()).unwrap();
+            cli.chunk.common.chunker(parse_counter("words").unwrap())
+        };
+        assert!(chunker(&["--size", "2", "--overlap", "1"]).is_ok());
+        assert_eq!(
+            chunker(&["--size", "0"]).err(),
+            Some(ConfigError::ZeroChunkSize)
+        );
+        assert_eq!(
+            chunker(&["--size", "2", "--overlap", "5"]).err(),
+            Some(ConfigError::OverlapTooLarge {
+                overlap: 5,
+                chunk_size: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_for_each_file() {
+        let cli = Cli::try_parse_from(["semchunk", "--jobs", "3"]).unwrap();
+        let files: Vec<PathBuf> = (0..20).map(|i| PathBuf::from(i.to_string())).collect();
+        let started = AtomicUsize::new(0);
+        let mut consumed = Vec::new();
+        for_each_file(
+            &cli.chunk.common,
+            &files,
+            |_, path| {
+                let i: usize = path.to_string_lossy().parse().unwrap();
+                started.fetch_add(1, Ordering::SeqCst);
+                Ok(i)
+            },
+            |i| {
+                // Workers get at most three files ahead of the results consumed.
+                assert!(started.load(Ordering::SeqCst) <= i + 3);
+                consumed.push(i);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(consumed, (0..20).collect::<Vec<_>>());
+
+        // Workers stop, rather than wait for their turn, once a result fails to be consumed.
+        let result = for_each_file(
+            &cli.chunk.common,
+            &files,
+            |_, _| Ok(()),
+            |()| Err("stdout closed".into()),
+        );
+        assert_eq!(result.unwrap_err().to_string(), "stdout closed");
+    }
+
+    #[test]
+    fn test_run_chunk_compressed_and_uncompressed() {
+        let dir = std::env::temp_dir().join(format!("semchunk-run-{}", std::process::id()));
+        let data = dir.join("data");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("book.txt"), "plain").unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(data.join("book.txt.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"compressed").unwrap();
+        encoder.finish().unwrap();
+
+        let (plain, compressed, out) = (
+            data.join("book.txt"),
+            data.join("book.txt.gz"),
+            dir.join("out"),
+        );
+        let mut cli = Cli::try_parse_from([
+            Path::new("semchunk"),
+            Path::new("--out"),
+            &out,
+            &plain,
+            &compressed,
+        ])
+        .unwrap();
+        cli.chunk.apply_config().unwrap();
+        run_chunk(&cli.chunk).unwrap();
+
+        // Each input gets its own output file, rather than one overwriting the other.
+        let chunks = |input: &Path| fs::read_to_string(output_path(&out, input, "chunks.txt"));
+        assert_eq!(chunks(&plain).unwrap(), "plain\n");
+        assert_eq!(chunks(&compressed).unwrap(), "compressed\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_id() {
+        let checkpoint_id = |args: &[&str]| {
+            let mut cli = Cli::try_parse_from([&["semchunk"], args].concat()).unwrap();
+            cli.chunk.apply_config().unwrap();
+            cli.chunk.checkpoint_id(Path::new("docs/a.txt"))
+        };
+        let id = checkpoint_id(&[]);
+        assert!(id.starts_with("docs/a.txt#"), "{}", id);
+        // Settings left at their defaults give the same ID as when given explicitly.
+        assert_eq!(id, checkpoint_id(&["--size", "512", "--counter", "words"]));
+        for settings in [
+            &["--size", "256"][..],
+            &["--counter", "chars"],
+            &["--overlap", "32"],
+            &["--separators", "code"],
+            &["--format", "jsonl"],
+            &["--delimiter", "\\0"],
+            &["-0"],
+            &["--output-compression", "zstd"],
+            &["--embedding-model", "text-embedding-3-large"],
+            #[cfg(feature = "whatlang")]
+            &["--language"],
+        ] {
+            assert_ne!(id, checkpoint_id(settings), "{:?}", settings);
+        }
+        // The same delimiter gives the same ID however it is given.
+        assert_eq!(
+            checkpoint_id(&["-0"]),
+            checkpoint_id(&["--delimiter", "\\0"])
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Optional compression of the chunks written by the CLI, and the buffering of chunks waiting to be written to stdout.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::ValueEnum;
+use semchunk_rs::writers::{self, Encoder};
+
+/// The compression applied to output, selected with `--output-compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The extension appended to compressed output files.
+    pub fn extension(self) -> Option<&'static str> {
+        writers::Compression::from(self).extension()
+    }
+
+    /// Wraps a writer so that everything written to it is compressed.
+    pub fn encoder<W: Write>(self, writer: W) -> io::Result<Encoder<W>> {
+        writers::Compression::from(self).encoder(writer)
+    }
+}
+
+impl From<Compression> for writers::Compression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => writers::Compression::None,
+            Compression::Gzip => writers::Compression::Gzip,
+            Compression::Zstd => writers::Compression::Zstd,
+        }
+    }
+}
+
+/// The number of bytes of output held in memory before it is moved to a temporary file.
+const SPILL_BYTES: usize = 16 << 20;
+
+/// Output waiting to be written to stdout, held in memory until it grows past [`SPILL_BYTES`] and in a temporary file after that, so that output chunked ahead of stdout takes bounded memory.
+#[derive(Default)]
+pub struct Spill {
+    buffer: Vec<u8>,
+    file: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Spill {
+    /// Writes all the output to `out`.
+    pub fn copy_to(mut self, out: &mut impl Write) -> io::Result<()> {
+        match &mut self.file {
+            Some((_, file)) => {
+                file.seek(SeekFrom::Start(0))?;
+                io::copy(file.get_mut(), out).map(drop)
+            }
+            None => out.write_all(&self.buffer),
+        }
+    }
+
+    /// Moves the output held in memory to a new temporary file.
+    fn spill(&mut self) -> io::Result<()> {
+        static SPILLS: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "semchunk-{}-{}.tmp",
+            process::id(),
+            SPILLS.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // The file is removed when the output is dropped, even if writing to it fails.
+        let (_, file) = self.file.insert((path, BufWriter::new(file)));
+        file.write_all(&self.buffer)?;
+        self.buffer = Vec::new();
+        Ok(())
+    }
+}
+
+impl Write for Spill {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() && self.buffer.len() + buf.len() > SPILL_BYTES {
+            self.spill()?;
+        }
+        match &mut self.file {
+            Some((_, file)) => file.write(buf),
+            None => self.buffer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        if let Some((path, _)) = self.file.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+
+    #[test]
+    fn test_spill() {
+        let mut small = Spill::default();
+        small.write_all(b"The quick brown fox").unwrap();
+        assert!(small.file.is_none());
+        let mut out = Vec::new();
+        small.copy_to(&mut out).unwrap();
+        assert_eq!(out, b"The quick brown fox");
+
+        // Output past the limit is moved to a temporary file, which is removed once written.
+        let line = b"jumps over the lazy dog.\n".repeat(1 << 10);
+        let mut large = Spill::default();
+        while large.file.is_none() {
+            large.write_all(&line).unwrap();
+        }
+        large.write_all(b"The end.").unwrap();
+        let (path, _) = large.file.as_ref().unwrap();
+        let path = path.clone();
+        assert!(path.exists());
+        let mut out = Vec::new();
+        large.copy_to(&mut out).unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            out.len(),
+            SPILL_BYTES / line.len() * line.len() + line.len() + 8
+        );
+        assert!(out.ends_with(b"dog.\nThe end."));
+    }
+}
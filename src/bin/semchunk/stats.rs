@@ -0,0 +1,110 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Summary statistics over the chunks of a corpus, reported by `semchunk stats`.
+
+use std::fmt;
+
+/// The distribution of chunk token counts over a corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub files: usize,
+    pub chunks: usize,
+    pub min: usize,
+    pub median: usize,
+    pub p95: usize,
+    pub max: usize,
+    pub mean: f64,
+    /// The mean percentage of `chunk_size` used by a chunk.
+    pub utilization: f64,
+}
+
+impl Stats {
+    /// Computes statistics from the token counts of every chunk in `files` files.
+    pub fn new(files: usize, mut token_counts: Vec<usize>, chunk_size: usize) -> Self {
+        token_counts.sort_unstable();
+        let chunks = token_counts.len();
+        let total: usize = token_counts.iter().sum();
+        let mean = if chunks > 0 {
+            total as f64 / chunks as f64
+        } else {
+            0.0
+        };
+        Stats {
+            files,
+            chunks,
+            min: token_counts.first().copied().unwrap_or(0),
+            median: percentile(&token_counts, 50.0),
+            p95: percentile(&token_counts, 95.0),
+            max: token_counts.last().copied().unwrap_or(0),
+            mean,
+            utilization: if chunk_size > 0 {
+                100.0 * mean / chunk_size as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted values, or 0 if there are none.
+fn percentile(sorted: &[usize], p: f64) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "files        {}", self.files)?;
+        writeln!(f, "chunks       {}", self.chunks)?;
+        writeln!(
+            f,
+            "tokens       min {}  median {}  p95 {}  max {}  mean {:.1}",
+            self.min, self.median, self.p95, self.max, self.mean
+        )?;
+        write!(f, "utilization  {:.1}%", self.utilization)
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::new(2, vec![4, 1, 3, 4, 2], 4);
+        assert_eq!(stats.chunks, 5);
+        assert_eq!(
+            (stats.min, stats.median, stats.p95, stats.max),
+            (1, 3, 4, 4)
+        );
+        assert_eq!(stats.mean, 2.8);
+        assert_eq!(stats.utilization, 70.0);
+
+        let stats = Stats::new(0, Vec::new(), 4);
+        assert_eq!((stats.chunks, stats.min, stats.max), (0, 0, 0));
+        assert_eq!(stats.utilization, 0.0);
+    }
+}
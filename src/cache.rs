@@ -0,0 +1,320 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Caching the chunks of documents by their content, so that unchanged documents are not chunked again across pipeline runs. Enabled with the `cache` feature.
+//!
+//! A [`ChunkCache`] keys each document's chunks by the hash of its text, in a pluggable [`CacheBackend`]: [`MemoryBackend`] within a run, or to persist across runs, a `sled` tree with the `sled` feature or [`SqliteCache`] with the `sqlite` feature.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use crate::chunk::{sha256_hex, Chunk};
+use crate::chunker::Chunker;
+
+/// Storage for the chunks of documents, keyed by the hash of each document's text.
+pub trait CacheBackend {
+    /// The error returned when the storage fails.
+    type Error;
+
+    /// Returns the chunks stored under the key, if any.
+    fn get(&mut self, key: &str) -> Result<Option<Vec<Chunk>>, Self::Error>;
+
+    /// Stores chunks under the key, replacing any stored before.
+    fn insert(&mut self, key: &str, chunks: &[Chunk]) -> Result<(), Self::Error>;
+}
+
+/// A [`CacheBackend`] that keeps chunks in memory, for the duration of a run.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    entries: HashMap<String, Vec<Chunk>>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheBackend for MemoryBackend {
+    type Error = Infallible;
+
+    fn get(&mut self, key: &str) -> Result<Option<Vec<Chunk>>, Infallible> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn insert(&mut self, key: &str, chunks: &[Chunk]) -> Result<(), Infallible> {
+        self.entries.insert(key.to_string(), chunks.to_vec());
+        Ok(())
+    }
+}
+
+/// A `sled` tree as a [`CacheBackend`], with the `sled` feature, storing chunks across runs.
+#[cfg(feature = "sled")]
+impl CacheBackend for sled::Tree {
+    type Error = sled::Error;
+
+    fn get(&mut self, key: &str) -> Result<Option<Vec<Chunk>>, sled::Error> {
+        Ok(sled::Tree::get(self, key)?.and_then(|value| decode(&value)))
+    }
+
+    fn insert(&mut self, key: &str, chunks: &[Chunk]) -> Result<(), sled::Error> {
+        sled::Tree::insert(self, key, encode(chunks))?;
+        Ok(())
+    }
+}
+
+/// A [`CacheBackend`] that stores chunks in a `chunk_cache` table of a SQLite database, with the `sqlite` feature.
+#[cfg(feature = "sqlite")]
+pub struct SqliteCache {
+    connection: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteCache {
+    /// Opens the cache in the database at the given path, creating the database and the `chunk_cache` table if they do not exist.
+    pub fn open(path: impl AsRef<std::path::Path>) -> rusqlite::Result<Self> {
+        Self::new(rusqlite::Connection::open(path)?)
+    }
+
+    /// Creates a cache in an open database, creating the `chunk_cache` table if it does not exist.
+    pub fn new(connection: rusqlite::Connection) -> rusqlite::Result<Self> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS chunk_cache (key TEXT PRIMARY KEY, chunks BLOB NOT NULL)",
+            [],
+        )?;
+        Ok(SqliteCache { connection })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl CacheBackend for SqliteCache {
+    type Error = rusqlite::Error;
+
+    fn get(&mut self, key: &str) -> rusqlite::Result<Option<Vec<Chunk>>> {
+        use rusqlite::OptionalExtension;
+
+        let value: Option<Vec<u8>> = self
+            .connection
+            .prepare_cached("SELECT chunks FROM chunk_cache WHERE key = ?1")?
+            .query_row([key], |row| row.get(0))
+            .optional()?;
+        Ok(value.and_then(|value| decode(&value)))
+    }
+
+    fn insert(&mut self, key: &str, chunks: &[Chunk]) -> rusqlite::Result<()> {
+        self.connection
+            .prepare_cached("INSERT OR REPLACE INTO chunk_cache (key, chunks) VALUES (?1, ?2)")?
+            .execute(rusqlite::params![key, encode(chunks)])?;
+        Ok(())
+    }
+}
+
+/// Chunks documents through a cache, so that documents chunked before, in this run or with a persistent backend an earlier one, are not chunked again.
+///
+/// Cached chunks are only valid for chunkers configured the same way, with the same token counter, so a cache shared between configurations should give each its own [`ChunkCache::namespace`].
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::cache::{ChunkCache, MemoryBackend};
+/// use semchunk_rs::Chunker;
+///
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let mut cache = ChunkCache::new(MemoryBackend::new()).namespace("words-4");
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let chunks = cache.chunk(&chunker, text).unwrap();
+/// assert_eq!(cache.chunk(&chunker, text).unwrap(), chunks);
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+pub struct ChunkCache<B: CacheBackend> {
+    backend: B,
+    namespace: String,
+    hits: usize,
+    misses: usize,
+}
+
+impl<B: CacheBackend> ChunkCache<B> {
+    /// Creates a cache with the given backend.
+    pub fn new(backend: B) -> Self {
+        ChunkCache {
+            backend,
+            namespace: String::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Sets a prefix for the keys of cached chunks, such as a name for the chunker's configuration, so that chunks cached for one configuration are not returned for another.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    /// Returns the chunks of the text from the cache, or chunks it and caches its chunks if they are not cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunker` - The chunker to chunk the text with if it is not cached.
+    /// * `text` - The text of the document.
+    ///
+    /// # Returns
+    ///
+    /// The chunks of the text, or an error from the backend.
+    pub fn chunk(&mut self, chunker: &Chunker, text: &str) -> Result<Vec<Chunk>, B::Error> {
+        let key = self.key(text);
+        if let Some(chunks) = self.backend.get(&key)? {
+            self.hits += 1;
+            return Ok(chunks);
+        }
+        self.misses += 1;
+        let mut chunks = Vec::new();
+        chunker.chunk_into(text, &mut chunks);
+        self.backend.insert(&key, &chunks)?;
+        Ok(chunks)
+    }
+
+    /// The key the chunks of the text are cached under: the namespace, then the SHA-256 digest of the text in hexadecimal.
+    pub fn key(&self, text: &str) -> String {
+        format!("{}{}", self.namespace, sha256_hex(text))
+    }
+
+    /// The number of documents whose chunks were found in the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of documents that were chunked because their chunks were not in the cache.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// The backend.
+    pub fn backend(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Returns the backend, consuming the cache.
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+}
+
+/// Encodes chunks for a backend that stores bytes, as the start and end offsets and text length of each chunk, as little-endian 64-bit integers, followed by its text.
+#[cfg(any(feature = "sled", feature = "sqlite"))]
+fn encode(chunks: &[Chunk]) -> Vec<u8> {
+    let len = chunks.iter().map(|chunk| 24 + chunk.text.len()).sum();
+    let mut bytes = Vec::with_capacity(len);
+    for chunk in chunks {
+        for n in [chunk.start, chunk.end, chunk.text.len()] {
+            bytes.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        bytes.extend_from_slice(chunk.text.as_bytes());
+    }
+    bytes
+}
+
+/// Decodes chunks encoded with [`encode`], or returns `None` if they are malformed, so that they are treated as not cached.
+#[cfg(any(feature = "sled", feature = "sqlite"))]
+fn decode(mut bytes: &[u8]) -> Option<Vec<Chunk>> {
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = (bytes.get(..n)?, bytes.get(n..)?);
+        *bytes = rest;
+        Some(taken)
+    }
+    fn take_usize(bytes: &mut &[u8]) -> Option<usize> {
+        usize::try_from(u64::from_le_bytes(take(bytes, 8)?.try_into().ok()?)).ok()
+    }
+
+    let mut chunks = Vec::new();
+    while !bytes.is_empty() {
+        let start = take_usize(&mut bytes)?;
+        let end = take_usize(&mut bytes)?;
+        let len = take_usize(&mut bytes)?;
+        let text = String::from_utf8(take(&mut bytes, len)?.to_vec()).ok()?;
+        chunks.push(Chunk { text, start, end });
+    }
+    Some(chunks)
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    fn check_backend<B: CacheBackend>(backend: B)
+    where
+        B::Error: std::fmt::Debug,
+    {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut cache = ChunkCache::new(backend).namespace("words-4:");
+        let texts = [
+            "The quick brown fox jumps over the lazy dog.",
+            "Привет, мир! 👋",
+            "",
+        ];
+        for text in texts {
+            let mut expected = Vec::new();
+            chunker.chunk_into(text, &mut expected);
+            assert_eq!(cache.chunk(&chunker, text).unwrap(), expected);
+            assert_eq!(cache.chunk(&chunker, text).unwrap(), expected);
+        }
+        assert_eq!((cache.hits(), cache.misses()), (3, 3));
+
+        // Chunks cached in another namespace are not returned.
+        let mut cache = ChunkCache::new(cache.into_backend()).namespace("words-8:");
+        let chunker = Chunker::new(8, Box::new(|s: &str| s.split_whitespace().count()));
+        assert_eq!(cache.chunk(&chunker, texts[0]).unwrap().len(), 2);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        check_backend(MemoryBackend::new());
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn test_sled_backend() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        check_backend(db.open_tree("chunks").unwrap());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_backend() {
+        check_backend(SqliteCache::new(rusqlite::Connection::open_in_memory().unwrap()).unwrap());
+    }
+
+    #[cfg(any(feature = "sled", feature = "sqlite"))]
+    #[test]
+    fn test_malformed_entries_are_not_cached() {
+        let chunks = vec![Chunk {
+            text: "héllo".to_string(),
+            start: 3,
+            end: 9,
+        }];
+        let bytes = encode(&chunks);
+        assert_eq!(decode(&bytes), Some(chunks));
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(decode(&bytes[..10]), None);
+    }
+}
@@ -0,0 +1,351 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Checkpoints for resuming corpus-scale chunking jobs after a crash or interruption.
+//!
+//! A [`Checkpoint`] records which documents have been chunked, and how far into each unfinished document chunks have been written, in a small state file. Chunks are written at least once: those written after the last recorded progress are written again on resuming.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+
+/// The number of chunks of a document written between records of its progress by default.
+const DEFAULT_RECORD_EVERY: usize = 1000;
+
+/// The progress of a chunking job, persisted to a state file so the job can be resumed.
+///
+/// The state file is a log with a line per record, `done` or the byte offset into the document up to which its chunks have been written, followed by a tab and the document's ID. Lines are appended and flushed as progress is made, and the log is compacted when it is opened.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::checkpoint::Checkpoint;
+/// use semchunk_rs::Chunker;
+///
+/// let state = std::env::temp_dir().join("semchunk-checkpoint-example.state");
+/// # let _ = std::fs::remove_file(&state);
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let mut checkpoint = Checkpoint::open(&state).unwrap();
+/// let mut chunks = Vec::new();
+/// for (doc, text) in [("fox.txt", "The quick brown fox jumps over the lazy dog.")] {
+///     checkpoint
+///         .chunk(&chunker, doc, text, |chunk| {
+///             chunks.push(chunk.text);
+///             Ok(())
+///         })
+///         .unwrap();
+/// }
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+///
+/// // Once reopened, such as after a crash, finished documents are skipped.
+/// let checkpoint = Checkpoint::open(&state).unwrap();
+/// assert!(checkpoint.is_done("fox.txt"));
+/// # std::fs::remove_file(&state).unwrap();
+/// ```
+pub struct Checkpoint {
+    path: PathBuf,
+    log: BufWriter<File>,
+    done: HashSet<String>,
+    offsets: HashMap<String, usize>,
+    record_every: usize,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint with the given state file, creating it if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the state file.
+    ///
+    /// # Returns
+    ///
+    /// The checkpoint, with the progress recorded in the state file, or an error if it could not be read or written.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut done = HashSet::new();
+        let mut offsets = HashMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut line = Vec::new();
+                while reader.read_until(b'\n', &mut line)? > 0 {
+                    let record = match line.strip_suffix(b"\n") {
+                        Some(line) => parse_record(line),
+                        // A line cut off by a crash is ignored.
+                        None => None,
+                    };
+                    line.clear();
+                    let Some((progress, doc)) = record else {
+                        continue;
+                    };
+                    match progress {
+                        Some(offset) => {
+                            offsets.insert(doc, offset);
+                        }
+                        None => {
+                            offsets.remove(&doc);
+                            done.insert(doc);
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        // Compact the log into a record per document, replacing the state file only once it is complete.
+        let compacted = path.with_extension("compacting");
+        {
+            let mut log = BufWriter::new(File::create(&compacted)?);
+            for doc in &done {
+                write_record(&mut log, None, doc)?;
+            }
+            for (doc, &offset) in &offsets {
+                write_record(&mut log, Some(offset), doc)?;
+            }
+            log.into_inner()?.sync_all()?;
+        }
+        fs::rename(&compacted, &path)?;
+
+        let log = BufWriter::new(OpenOptions::new().append(true).open(&path)?);
+        Ok(Checkpoint {
+            path,
+            log,
+            done,
+            offsets,
+            record_every: DEFAULT_RECORD_EVERY,
+        })
+    }
+
+    /// Sets how many chunks of a document [`Checkpoint::chunk`] writes between records of its progress, which defaults to 1000.
+    ///
+    /// Recording progress more often means fewer chunks are written again on resuming, at the cost of more writes to the state file.
+    pub fn record_every(mut self, chunks: usize) -> Self {
+        self.record_every = chunks.max(1);
+        self
+    }
+
+    /// The path of the state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the document has been chunked.
+    pub fn is_done(&self, doc: &str) -> bool {
+        self.done.contains(doc)
+    }
+
+    /// The byte offset into the document up to which its chunks have been written, which is 0 if none have.
+    pub fn offset(&self, doc: &str) -> usize {
+        self.offsets.get(doc).copied().unwrap_or(0)
+    }
+
+    /// Records that the chunks of the document have been written up to the given byte offset.
+    pub fn record(&mut self, doc: &str, offset: usize) -> io::Result<()> {
+        write_record(&mut self.log, Some(offset), doc)?;
+        self.log.flush()?;
+        self.offsets.insert(doc.to_string(), offset);
+        Ok(())
+    }
+
+    /// Records that the document has been chunked.
+    pub fn complete(&mut self, doc: &str) -> io::Result<()> {
+        write_record(&mut self.log, None, doc)?;
+        self.log.flush()?;
+        self.offsets.remove(doc);
+        self.done.insert(doc.to_string());
+        Ok(())
+    }
+
+    /// Chunks a document, skipping it if it has been chunked and otherwise resuming after the chunks already written, then records it as done.
+    ///
+    /// Progress is recorded after every [`Checkpoint::record_every`] chunks written, so `write` should have made each chunk durable, such as by flushing it, before returning. Chunking stops at the first error.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunker` - The chunker to chunk the document with, which must be configured as it was before resuming.
+    /// * `doc` - The ID of the document, such as its path.
+    /// * `text` - The text of the document.
+    /// * `write` - A function that writes each chunk, in order.
+    ///
+    /// # Returns
+    ///
+    /// An error from `write` or from recording progress.
+    pub fn chunk(
+        &mut self,
+        chunker: &Chunker,
+        doc: &str,
+        text: &str,
+        mut write: impl FnMut(Chunk) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.is_done(doc) {
+            return Ok(());
+        }
+        // Chunks end in increasing order, so those written before are those ending by the offset.
+        let written = self.offset(doc);
+        let failed = AtomicBool::new(false);
+        let mut result = Ok(());
+        let mut unrecorded = 0;
+        let _ = chunker.chunk_to_cancellable(text, &failed, |chunk| {
+            if chunk.end <= written {
+                return;
+            }
+            let end = chunk.end;
+            result = write(chunk).and_then(|()| {
+                unrecorded += 1;
+                match unrecorded == self.record_every {
+                    true => {
+                        unrecorded = 0;
+                        self.record(doc, end)
+                    }
+                    false => Ok(()),
+                }
+            });
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+        });
+        result?;
+        self.complete(doc)
+    }
+}
+
+/// Writes a record of a document's progress, `None` meaning it is done, escaping backslashes and newlines in its ID.
+fn write_record(log: &mut impl Write, progress: Option<usize>, doc: &str) -> io::Result<()> {
+    match progress {
+        Some(offset) => write!(log, "{}\t", offset)?,
+        None => write!(log, "done\t")?,
+    }
+    let doc = doc.replace('\\', "\\\\").replace('\n', "\\n");
+    writeln!(log, "{}", doc)
+}
+
+/// Parses a record written by [`write_record`], without its newline, or returns `None` if it is malformed.
+fn parse_record(line: &[u8]) -> Option<(Option<usize>, String)> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (progress, escaped) = line.split_once('\t')?;
+    let progress = match progress {
+        "done" => None,
+        offset => Some(offset.parse().ok()?),
+    };
+    let mut doc = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => doc.push('\n'),
+                '\\' => doc.push('\\'),
+                _ => return None,
+            },
+            c => doc.push(c),
+        }
+    }
+    Some((progress, doc))
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use super::*;
+
+    fn state_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("semchunk-{}-{}.state", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_resume_after_interruption() {
+        let path = state_path("resume");
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let expected = chunker.chunk(text);
+
+        // The job is interrupted while writing the fourth chunk, with progress recorded after every two.
+        let mut written = Vec::new();
+        let mut checkpoint = Checkpoint::open(&path).unwrap().record_every(2);
+        let result = checkpoint.chunk(&chunker, "fox.txt", text, |chunk| {
+            if written.len() == 3 {
+                return Err(io::Error::other("interrupted"));
+            }
+            written.push(chunk.text);
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(checkpoint.offset("fox.txt"), 19);
+        assert!(!checkpoint.is_done("fox.txt"));
+        drop(checkpoint);
+
+        // Resuming writes the third chunk again, as it was written after the last record.
+        let mut checkpoint = Checkpoint::open(&path).unwrap();
+        assert_eq!(checkpoint.offset("fox.txt"), 19);
+        let mut resumed = Vec::new();
+        checkpoint
+            .chunk(&chunker, "fox.txt", text, |chunk| {
+                resumed.push(chunk.text);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(resumed, expected[2..]);
+        assert!(checkpoint.is_done("fox.txt"));
+
+        let mut checkpoint = Checkpoint::open(&path).unwrap();
+        checkpoint
+            .chunk(&chunker, "fox.txt", text, |_| {
+                panic!("chunked a finished document")
+            })
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_state_file() {
+        let path = state_path("state");
+        let mut checkpoint = Checkpoint::open(&path).unwrap();
+        for doc in ["a\\b.txt", "line\nbreak.txt", "tab\t.txt"] {
+            checkpoint.complete(doc).unwrap();
+        }
+        checkpoint.record("partial.txt", 10).unwrap();
+        checkpoint.record("partial.txt", 20).unwrap();
+        drop(checkpoint);
+        // A record cut off by a crash is ignored.
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(log, "done\tpartial").unwrap();
+        drop(log);
+
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        for doc in ["a\\b.txt", "line\nbreak.txt", "tab\t.txt"] {
+            assert!(checkpoint.is_done(doc), "{:?}", doc);
+        }
+        assert!(!checkpoint.is_done("partial"));
+        assert!(!checkpoint.is_done("partial.txt"));
+        assert_eq!(checkpoint.offset("partial.txt"), 20);
+        // The log is compacted to a record per document.
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+        fs::remove_file(&path).unwrap();
+    }
+}
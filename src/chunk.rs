@@ -0,0 +1,188 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+/// A chunk of text produced by a [`Chunker`](crate::Chunker), along with where it came from in the source text.
+///
+/// # Fields
+///
+/// * `text` - The text of the chunk, which is the source text from `start` to `end` verbatim when chunking with [`Chunker::lossless`](crate::Chunker::lossless).
+/// * `start` - The byte offset in the source text at which the chunk begins.
+/// * `end` - The byte offset in the source text at which the chunk ends (not inclusive).
+///
+/// # Example
+///
+/// ```
+/// use semchunk_rs::{Chunk, Chunker};
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let mut chunks: Vec<Chunk> = Vec::new();
+/// chunker.chunk_to(text, |chunk| chunks.push(chunk));
+/// assert_eq!(chunks[1].text, "jumps over the lazy");
+/// assert_eq!(&text[chunks[1].start..chunks[1].end], "jumps over the lazy");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Returns the ID of the chunk at `index` in the document `doc_id`, which exporters use to identify and link chunks.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::chunk::chunk_id;
+/// assert_eq!(chunk_id("report.txt", 3), "report.txt-3");
+/// ```
+pub fn chunk_id(doc_id: &str, index: usize) -> String {
+    format!("{}-{}", doc_id, index)
+}
+
+/// A chunk with the IDs of its document and of the chunks before and after it, so retrieval layers can fetch the context around a chunk without working out which chunks neighbour it from their offsets.
+///
+/// # Fields
+///
+/// * `id` - The [`chunk_id`] of the chunk.
+/// * `document_id` - The ID of the document the chunk is of.
+/// * `prev_id` - The ID of the chunk before it in the document, if any.
+/// * `next_id` - The ID of the chunk after it in the document, if any.
+/// * `chunk` - The chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkedChunk {
+    pub id: String,
+    pub document_id: String,
+    pub prev_id: Option<String>,
+    pub next_id: Option<String>,
+    pub chunk: Chunk,
+}
+
+/// Links the chunks of a document to the document and to each other by their [`chunk_id`]s.
+///
+/// # Arguments
+///
+/// * `doc_id` - The ID of the document.
+/// * `chunks` - The chunks of the document, in order.
+///
+/// # Returns
+///
+/// The linked chunks, in order.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::chunk::link_chunks;
+/// use semchunk_rs::Chunk;
+/// let chunks = vec![
+///     Chunk { text: "The quick brown fox".to_string(), start: 0, end: 19 },
+///     Chunk { text: "jumps.".to_string(), start: 20, end: 26 },
+/// ];
+/// let linked = link_chunks("fox", chunks);
+/// assert_eq!(linked[0].id, "fox-0");
+/// assert_eq!(linked[0].next_id.as_deref(), Some("fox-1"));
+/// assert_eq!(linked[1].prev_id.as_deref(), Some("fox-0"));
+/// assert_eq!(linked[1].next_id, None);
+/// ```
+pub fn link_chunks(doc_id: &str, chunks: impl IntoIterator<Item = Chunk>) -> Vec<LinkedChunk> {
+    let chunks: Vec<Chunk> = chunks.into_iter().collect();
+    let n_chunks = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| LinkedChunk {
+            id: chunk_id(doc_id, index),
+            document_id: doc_id.to_string(),
+            prev_id: index.checked_sub(1).map(|prev| chunk_id(doc_id, prev)),
+            next_id: (index + 1 < n_chunks).then(|| chunk_id(doc_id, index + 1)),
+            chunk,
+        })
+        .collect()
+}
+
+/// Returns the SHA-256 digest of a text in hexadecimal, which identifies texts across runs and platforms.
+#[cfg(any(feature = "cache", feature = "sqlite"))]
+pub(crate) fn sha256_hex(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Converts byte offsets into a text to offsets in the units another language indexes strings by, such as the characters of Python strings or the UTF-16 code units of JavaScript, Swift and Kotlin strings.
+///
+/// Offsets are converted incrementally from the last offset converted, so converting them in increasing order, or nearly so as the offsets of overlapping chunks are, takes linear time overall.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::chunk::OffsetConverter;
+/// let text = "🦀 crab";
+/// let mut utf16 = OffsetConverter::utf16(text);
+/// assert_eq!(utf16.convert(4), 2);
+/// assert_eq!(utf16.convert(text.len()), 7);
+/// let mut chars = OffsetConverter::chars(text);
+/// assert_eq!(chars.convert(text.len()), 6);
+/// assert_eq!(chars.convert(4), 1);
+/// ```
+pub struct OffsetConverter<'a> {
+    text: &'a str,
+    /// Counts the units in a slice of the text.
+    units: fn(&str) -> usize,
+    byte_offset: usize,
+    offset: usize,
+}
+
+impl<'a> OffsetConverter<'a> {
+    /// Creates a converter to offsets in Unicode scalar values, as Python strings are indexed.
+    pub fn chars(text: &'a str) -> Self {
+        OffsetConverter::new(text, |s| s.chars().count())
+    }
+
+    /// Creates a converter to offsets in UTF-16 code units, as JavaScript, Swift and Kotlin strings are indexed.
+    pub fn utf16(text: &'a str) -> Self {
+        OffsetConverter::new(text, |s| s.encode_utf16().count())
+    }
+
+    fn new(text: &'a str, units: fn(&str) -> usize) -> Self {
+        OffsetConverter {
+            text,
+            units,
+            byte_offset: 0,
+            offset: 0,
+        }
+    }
+
+    /// Returns the offset of a byte offset, which must be on a character boundary.
+    pub fn convert(&mut self, byte_offset: usize) -> usize {
+        if byte_offset < self.byte_offset {
+            self.offset -= (self.units)(&self.text[byte_offset..self.byte_offset]);
+        } else {
+            self.offset += (self.units)(&self.text[self.byte_offset..byte_offset]);
+        }
+        self.byte_offset = byte_offset;
+        self.offset
+    }
+}
@@ -0,0 +1,522 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! How the splits of a text are merged into chunks, by each [`MergeStrategy`].
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use smallvec::SmallVec;
+
+use super::{ends_sentence, is_closing, offset_of, Chunker, CowChunk, SplitTokens, MAX_PROBES};
+use crate::splitter::{
+    punctuation_kind, Separator, SeparatorProfile, NON_WHITESPACE_SEMANTIC_SEPARATORS,
+};
+
+/// The most chunk ends considered for each chunk start by [`MergeStrategy::Optimal`], largest first.
+const OPTIMAL_CANDIDATES: usize = 256;
+
+/// The number of chunks' worth of splits [`MergeStrategy::Optimal`] plans at a time.
+const OPTIMAL_WINDOW_CHUNKS: usize = 8;
+
+/// How a [`Chunker`] chooses where chunks end among the splits of a text, set with [`Chunker::merge_strategy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Fill each chunk with as many splits as fit, in order.
+    #[default]
+    Greedy,
+    /// Score every way of merging the splits of each level into chunks, by how full the chunks are and the punctuation they end at, and pick the best. Chunks are planned from the token counts of the splits, so about as many tokens are counted as with [`MergeStrategy::Greedy`], but planning takes longer.
+    Optimal,
+    /// Fill each chunk with as many splits as fit, then merge consecutive chunks that fit together, for the fewest chunks, such as when each chunk costs a call to an embedding API. Chunks cut short where a split too large for a chunk was chunked on its own are filled up with the chunks after them. Has no effect on overlapping chunks.
+    FewestChunks,
+}
+
+impl Chunker {
+    /// Returns the number of splits in the chunk starting at split `i` planned by the optimal strategy, which is 0 for a split over the chunk size, planning the chunks from `i` onwards if they have not been planned.
+    pub(super) fn planned_splits<'a, I: Iterator<Item = &'a str>>(
+        &self,
+        root: &'a str,
+        split_tokens: &mut SplitTokens<'a, '_, I>,
+        separator: Separator<'a>,
+        plan: &mut VecDeque<(usize, usize)>,
+        i: usize,
+    ) -> usize {
+        if plan.front().is_none_or(|&(start, _)| start != i) {
+            plan.clear();
+            self.plan_window(root, split_tokens, separator, i, plan);
+        }
+        plan.pop_front().map_or(0, |(_, n_splits)| n_splits)
+    }
+
+    /// Plans the chunks of a window of splits from split `start`, long enough for [`OPTIMAL_WINDOW_CHUNKS`] chunks, appending the start and number of splits of each chunk to `plan`. Unless the window ends with the text, its last chunk is left to be planned with the next window.
+    ///
+    /// Chunks are planned from estimated token counts, so only the planned chunks are counted. Should a planned chunk not fit, it is cut where it fits and the rest of the splits are planned again, with the estimate corrected. Chunks are planned to fit with the separator after them, where it is not whitespace, so that it is kept with them rather than left as a chunk of its own.
+    fn plan_window<'a, I: Iterator<Item = &'a str>>(
+        &self,
+        root: &'a str,
+        split_tokens: &mut SplitTokens<'a, '_, I>,
+        separator: Separator<'a>,
+        mut start: usize,
+        plan: &mut VecDeque<(usize, usize)>,
+    ) {
+        let chunk_size = self.local_chunk_size();
+        let n_splits =
+            start + split_tokens.take_fitting(start, 1, OPTIMAL_WINDOW_CHUNKS * chunk_size);
+        let ends_text = split_tokens.get(n_splits).is_none();
+        let window_start = start;
+        // Whether a chunk ending before split `end` is followed by a separator kept with it.
+        let separated = |end: usize| !separator.is_whitespace() && (end < n_splits || !ends_text);
+        // The offsets of the splits of the window, and of the split after it, if any.
+        let offsets: Vec<usize> = (window_start..n_splits + usize::from(!ends_text))
+            .map(|i| offset_of(root, split_tokens.merged(i, i + 1)[0]))
+            .collect();
+        // The offsets the splits of the window end at.
+        let ends: Vec<usize> = (window_start..n_splits)
+            .map(|i| offsets[i - window_start] + split_tokens.merged(i, i + 1)[0].len())
+            .collect();
+        // The text of the chunk of splits `chunk_start..chunk_end` of the window, which must not be empty.
+        let span = |chunk_start: usize, chunk_end: usize| {
+            let end = match separated(chunk_end) {
+                true => offsets[chunk_end - window_start],
+                false => ends[chunk_end - 1 - window_start],
+            };
+            &root[offsets[chunk_start - window_start]..end]
+        };
+        // Chunks ending at the same split end at the same punctuation.
+        let boundary_costs: Vec<f64> = (start..n_splits)
+            .map(|i| match separated(i + 1) {
+                true => boundary_cost(separator.text),
+                false => boundary_cost(split_tokens.merged(i, i + 1)[0]),
+            })
+            .collect();
+        'planning: while start < n_splits {
+            let separator_tokens = split_tokens.separator_tokens;
+            let trailing_tokens = |end: usize| match separated(end) {
+                true => separator_tokens,
+                false => 0.0,
+            };
+            for (chunk_start, chunk_end) in optimal_chunks(
+                split_tokens,
+                start,
+                n_splits,
+                chunk_size,
+                &|i| boundary_costs[i - window_start],
+                &trailing_tokens,
+            ) {
+                let n_tokens = match chunk_end > chunk_start {
+                    true => self.count_chunk_tokens(span(chunk_start, chunk_end)),
+                    false => 0,
+                };
+                match separated(chunk_end) && chunk_end > chunk_start {
+                    true => split_tokens.observe_separated(chunk_start, chunk_end, n_tokens),
+                    false => split_tokens.observe(chunk_start, chunk_end, n_tokens),
+                }
+                if n_tokens <= chunk_size {
+                    plan.push_back((chunk_start, chunk_end - chunk_start));
+                    continue;
+                }
+                let fitting = self.fitting_splits(
+                    split_tokens,
+                    chunk_start,
+                    chunk_size,
+                    self.probes(),
+                    |candidates, counts| {
+                        // Candidates are in increasing order, and those as long as the planned chunk do not fit either, which leaves the rest within the window.
+                        let texts: SmallVec<[&str; MAX_PROBES]> = candidates
+                            .iter()
+                            .map(|splits| chunk_start + splits.len())
+                            .take_while(|&end| end < chunk_end)
+                            .map(|end| span(chunk_start, end))
+                            .collect();
+                        self.count_candidates(&texts, counts);
+                        counts.resize(candidates.len(), n_tokens);
+                    },
+                );
+                plan.push_back((chunk_start, fitting));
+                start = chunk_start + fitting.max(1);
+                continue 'planning;
+            }
+            break;
+        }
+        if !ends_text && plan.len() > 1 {
+            plan.pop_back();
+        }
+    }
+}
+
+/// Merges each chunk into the chunk before it while the span of both still fits in a chunk.
+///
+/// For a given sequence of chunks, merging each into the one before it while it fits leaves the fewest chunks.
+pub(super) struct ChunkPacker<'a, 'c, F: FnMut(CowChunk<'a>)> {
+    text: &'a str,
+    sink: F,
+    /// Returns whether a span of the text fits in a chunk.
+    fits: &'c dyn Fn(&str) -> bool,
+    /// The chunk that later chunks may still be merged into.
+    pending: Option<CowChunk<'a>>,
+}
+
+impl<'a, 'c, F: FnMut(CowChunk<'a>)> ChunkPacker<'a, 'c, F> {
+    /// Returns a packer of chunks of `text` that passes them to `sink`, where `fits` returns whether a span of the text fits in a chunk.
+    pub(super) fn new(text: &'a str, sink: F, fits: &'c dyn Fn(&str) -> bool) -> Self {
+        ChunkPacker {
+            text,
+            sink,
+            fits,
+            pending: None,
+        }
+    }
+
+    pub(super) fn push(&mut self, chunk: CowChunk<'a>) {
+        match self.pending.take() {
+            Some(pending) if (self.fits)(&self.text[pending.start..chunk.end]) => {
+                self.pending = Some(CowChunk {
+                    text: Cow::Borrowed(&self.text[pending.start..chunk.end]),
+                    start: pending.start,
+                    end: chunk.end,
+                    tokens: None,
+                    boundary: chunk.boundary,
+                });
+            }
+            Some(pending) => {
+                (self.sink)(pending);
+                self.pending = Some(chunk);
+            }
+            None => self.pending = Some(chunk),
+        }
+    }
+
+    /// Passes the last chunk to the sink.
+    pub(super) fn finish(mut self) {
+        if let Some(pending) = self.pending.take() {
+            (self.sink)(pending);
+        }
+    }
+}
+
+/// Returns how many splits from `start` onwards, up to the `fitting` splits that fit in a chunk, end the chunk at the most desirable boundary while being estimated to have at least `min_chunk_size` tokens merged.
+///
+/// Boundaries are ranked by the punctuation ending the last split, and ties go to the larger chunk.
+pub(super) fn preferred_splits<'a, I: Iterator<Item = &'a str>>(
+    split_tokens: &SplitTokens<'a, '_, I>,
+    start: usize,
+    fitting: usize,
+    min_chunk_size: f64,
+) -> usize {
+    let rank = |n_splits: usize| boundary_rank(split_tokens.merged(start, start + n_splits));
+    let mut preferred = (fitting, rank(fitting));
+    for n_splits in (1..fitting).rev() {
+        if split_tokens.estimate(start, start + n_splits) < min_chunk_size {
+            break;
+        }
+        let rank = rank(n_splits);
+        if rank < preferred.1 {
+            preferred = (n_splits, rank);
+        }
+    }
+    preferred.0
+}
+
+/// Returns how many splits from `start` onwards, up to the `fitting` splits that fit in a chunk, end the chunk at the end of a sentence while being estimated to have at least `min_chunk_size` tokens merged, or `fitting` if none do.
+pub(super) fn sentence_final_splits<'a, I: Iterator<Item = &'a str>>(
+    split_tokens: &SplitTokens<'a, '_, I>,
+    start: usize,
+    fitting: usize,
+    min_chunk_size: f64,
+) -> usize {
+    (1..=fitting)
+        .rev()
+        .take_while(|&n_splits| {
+            n_splits == fitting || split_tokens.estimate(start, start + n_splits) >= min_chunk_size
+        })
+        .find(|&n_splits| ends_sentence(split_tokens.merged(start, start + n_splits)))
+        .unwrap_or(fitting)
+}
+
+/// Picks the chunks of splits `start..n_splits` with the lowest total [`chunk_cost`], from their estimated token counts, returning the span of splits of each chunk in order. Splits over the chunk size are returned as empty spans.
+///
+/// The cost of ending a chunk before each split is given by `boundary_costs`, and the tokens added to a chunk ending before each split, such as by the separator kept with it, by `trailing_tokens`.
+///
+/// The largest chunk estimated to fit starting at each split is found first, which only moves forwards as the start does. The best chunks ending at each split are then found by dynamic programming, considering up to [`OPTIMAL_CANDIDATES`] of the largest chunks from each start.
+fn optimal_chunks<'a, I: Iterator<Item = &'a str>>(
+    split_tokens: &SplitTokens<'a, '_, I>,
+    start: usize,
+    n_splits: usize,
+    chunk_size: usize,
+    boundary_costs: &dyn Fn(usize) -> f64,
+    trailing_tokens: &dyn Fn(usize) -> f64,
+) -> Vec<(usize, usize)> {
+    let estimate = |chunk_start: usize, chunk_end: usize| {
+        split_tokens.estimate(chunk_start, chunk_end) + trailing_tokens(chunk_end)
+    };
+    let mut ends = Vec::with_capacity(n_splits - start);
+    let mut end = start;
+    for chunk_start in start..n_splits {
+        end = end.max(chunk_start);
+        while end < n_splits && estimate(chunk_start, end + 1) <= chunk_size as f64 {
+            end += 1;
+        }
+        ends.push(end);
+    }
+
+    // The lowest total cost of chunks ending at each split, and where the last of them starts.
+    let mut costs = vec![(f64::INFINITY, start); n_splits - start + 1];
+    costs[0].0 = 0.0;
+    for (i, &end) in ends.iter().enumerate() {
+        let chunk_start = start + i;
+        let cost = costs[i].0;
+        if end == chunk_start {
+            // Splits over the chunk size are chunked on their own.
+            if cost < costs[i + 1].0 {
+                costs[i + 1] = (cost, chunk_start);
+            }
+            continue;
+        }
+        if cost == f64::INFINITY {
+            continue;
+        }
+        // Only the largest chunks are considered, which always include the chunk a greedy strategy would pick, so the end of the text is reached.
+        for chunk_end in (chunk_start + 1).max(end.saturating_sub(OPTIMAL_CANDIDATES) + 1)..=end {
+            let cost = cost
+                + match chunk_end == n_splits {
+                    // The last chunk is not penalized for being short.
+                    true => 0.0,
+                    false => chunk_cost(
+                        estimate(chunk_start, chunk_end) / chunk_size as f64,
+                        boundary_costs(chunk_end - 1),
+                    ),
+                };
+            if cost < costs[chunk_end - start].0 {
+                costs[chunk_end - start] = (cost, chunk_start);
+            }
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk_end = n_splits;
+    while chunk_end > start {
+        let chunk_start = costs[chunk_end - start].1;
+        match ends[chunk_start - start] == chunk_start {
+            true => chunks.push((chunk_start, chunk_start)),
+            false => chunks.push((chunk_start, chunk_end)),
+        }
+        chunk_end = chunk_start;
+    }
+    chunks.reverse();
+    chunks
+}
+
+/// Scores a chunk that is not the last of a text, filling `fill` of the chunk size and ending at a boundary costing `boundary_cost`, where lower scores are better.
+///
+/// The score is how far short of the chunk size the chunk falls, squared, plus the cost of its boundary.
+fn chunk_cost(fill: f64, boundary_cost: f64) -> f64 {
+    let underfill = 1.0 - fill.clamp(0.0, 1.0);
+    underfill * underfill + boundary_cost
+}
+
+/// The cost of ending a chunk after `last_split`, which is its [`boundary_rank`] scaled so that ending without punctuation costs as much as an empty chunk.
+fn boundary_cost(last_split: &str) -> f64 {
+    boundary_rank(&[last_split]) as f64 / NON_WHITESPACE_SEMANTIC_SEPARATORS.len() as f64
+}
+
+/// Ranks a chunk boundary after `splits` by the punctuation the last split ends with, ignoring closing quotes and brackets, where lower ranks are more desirable.
+///
+/// Latin punctuation ranks by its position in [`NON_WHITESPACE_SEMANTIC_SEPARATORS`], and the punctuation of other scripts ranks first among the Latin punctuation of its [`SeparatorKind`](crate::splitter::SeparatorKind).
+fn boundary_rank(splits: &[&str]) -> usize {
+    let last_split = splits
+        .last()
+        .map_or("", |split| split.trim_end_matches(is_closing));
+    // Opening quotes and brackets start the next chunk's text rather than end this one's.
+    if last_split.ends_with(['(', '[', '“', '‘', '（', '「', '『', '《', '【', '«']) {
+        return NON_WHITESPACE_SEMANTIC_SEPARATORS.len();
+    }
+    NON_WHITESPACE_SEMANTIC_SEPARATORS
+        .iter()
+        .position(|separator| last_split.ends_with(separator))
+        .or_else(|| {
+            let kind = punctuation_kind(last_split)?;
+            (0..NON_WHITESPACE_SEMANTIC_SEPARATORS.len())
+                .find(|&index| SeparatorProfile::Latin.kind(index) == kind)
+        })
+        .unwrap_or(NON_WHITESPACE_SEMANTIC_SEPARATORS.len())
+}
+
+#[cfg(test)]
+mod merge_tests {
+    use super::*;
+    use crate::chunker::TokenEstimate;
+
+    #[test]
+    fn test_sentence_backoff() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "One two three four five six seven, eight nine ten. Eleven twelve thirteen (fourteen) fifteen sixteen";
+        let chunker = Chunker::new(11, Box::new(words)).sentence_backoff(2);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "One two three four five six seven, eight nine ten.",
+                "Eleven twelve thirteen (fourteen) fifteen sixteen"
+            ]
+        );
+        // Chunks only end early at sentences, not clauses.
+        let chunker = Chunker::new(9, Box::new(words)).sentence_backoff(4);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "One two three four five six seven, eight nine",
+                "ten. Eleven twelve thirteen (fourteen) fifteen sixteen"
+            ]
+        );
+        // Sentences further back than the backoff are not backed off to.
+        let chunker = Chunker::new(11, Box::new(words)).sentence_backoff(0);
+        assert_eq!(
+            chunker.chunk(text)[0],
+            "One two three four five six seven, eight nine ten. Eleven"
+        );
+        let chunker = Chunker::new(11, Box::new(words))
+            .sentence_backoff(2)
+            .estimate_tokens(TokenEstimate::TokensPerWord(1.0));
+        assert_eq!(
+            chunker.chunk(text)[0],
+            "One two three four five six seven, eight nine ten."
+        );
+
+        // Full-width and Arabic sentence terminators are backed off to, and asterisks are not.
+        for terminator in ["。", "！", "？", "؟"] {
+            let text = format!("一 二 三 四 五 六 七，八 九 十{} 十一 十二", terminator);
+            let chunker = Chunker::new(10, Box::new(words)).sentence_backoff(2);
+            assert_eq!(
+                chunker.chunk(&text)[0],
+                format!("一 二 三 四 五 六 七，八 九 十{}", terminator)
+            );
+        }
+        let text = "One two three four five six seven, eight nine *ten* Eleven twelve";
+        let chunker = Chunker::new(11, Box::new(words)).sentence_backoff(2);
+        assert_eq!(
+            chunker.chunk(text)[0],
+            "One two three four five six seven, eight nine *ten* Eleven"
+        );
+    }
+
+    #[test]
+    fn test_optimal_strategy() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "One two three. Four five six seven, eight nine ten eleven. Twelve thirteen fourteen fifteen sixteen seventeen eighteen nineteen twenty. The end.";
+        let chunker = Chunker::new(8, Box::new(words)).merge_strategy(MergeStrategy::Optimal);
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "One two three. Four five six seven,",
+                "eight nine ten eleven. Twelve thirteen fourteen fifteen",
+                "sixteen seventeen eighteen nineteen twenty. The end."
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+            assert!(words(&chunk.text) <= 8);
+        }
+
+        // Splits over the chunk size are chunked on their own, and non-whitespace separators stay with the chunk they end.
+        let chunker = Chunker::new(3, Box::new(|s: &str| s.chars().count()))
+            .merge_strategy(MergeStrategy::Optimal);
+        assert_eq!(chunker.chunk("ab cdefg hi"), vec!["ab", "cde", "fg", "hi"]);
+        assert_eq!(chunker.chunk("a.b.cdef"), vec!["a.", "b.", "cde", "f"]);
+
+        // Chunks cut from a planned chunk that does not fit are spanned within the window of splits planned.
+        let chunker = Chunker::new(1, Box::new(|s: &str| s.chars().count()))
+            .merge_strategy(MergeStrategy::Optimal);
+        assert_eq!(
+            chunker.chunk("quick,;e.g."),
+            vec!["q", "u", "i", "c", "k", ",", ";", "e", ".", "g", "."]
+        );
+
+        // The greedy and optimal strategies agree on how many tokens fit.
+        let chunker = Chunker::new(8, Box::new(words)).overlap(4);
+        let optimal = Chunker::new(8, Box::new(words))
+            .overlap(4)
+            .merge_strategy(MergeStrategy::Optimal);
+        for chunk in optimal.chunk(text) {
+            assert!(words(&chunk) <= 8);
+        }
+        assert_eq!(
+            chunker.chunk("a b c d").len(),
+            optimal.chunk("a b c d").len()
+        );
+
+        // Chunks planned from estimates that turn out not to fit are cut where they fit.
+        let chars = |s: &str| s.chars().count();
+        for chunk_size in [5, 12, 40] {
+            let chunker =
+                Chunker::new(chunk_size, Box::new(chars)).merge_strategy(MergeStrategy::Optimal);
+            let mut end = 0;
+            chunker.chunk_to(text, |chunk| {
+                assert!(chars(&chunk.text) <= chunk_size, "{:?}", chunk);
+                assert!(text[end..chunk.start].trim().is_empty());
+                end = chunk.end;
+            });
+            assert_eq!(end, text.len());
+        }
+
+        // Texts longer than the planning window are planned a window at a time.
+        let long = text.repeat(20);
+        let chunker = Chunker::new(8, Box::new(words)).merge_strategy(MergeStrategy::Optimal);
+        let mut end = 0;
+        chunker.chunk_to(&long, |chunk| {
+            assert!(words(&chunk.text) <= 8, "{:?}", chunk);
+            assert!(long[end..chunk.start].trim().is_empty());
+            end = chunk.end;
+        });
+        assert_eq!(end, long.len());
+    }
+
+    #[test]
+    fn test_fewest_chunks() {
+        let chars = |s: &str| s.chars().count();
+        let text =
+            "The quick brown fox jumps over the lazy dog. Pack my box with five dozen liquor jugs!";
+        for chunk_size in [4, 7, 10, 25] {
+            let greedy = Chunker::new(chunk_size, Box::new(chars));
+            let fewest = Chunker::new(chunk_size, Box::new(chars))
+                .merge_strategy(MergeStrategy::FewestChunks);
+            let mut chunks = Vec::new();
+            fewest.chunk_to(text, |chunk| chunks.push(chunk));
+            assert!(chunks.len() <= greedy.chunk(text).len());
+            for pair in chunks.windows(2) {
+                // Consecutive chunks would not fit together.
+                assert!(chars(&text[pair[0].start..pair[1].end]) > chunk_size);
+            }
+            for chunk in &chunks {
+                assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+                assert!(chars(&chunk.text) <= chunk_size);
+            }
+        }
+
+        // Overlapping chunks are not merged.
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))
+            .overlap(2)
+            .merge_strategy(MergeStrategy::FewestChunks);
+        assert_eq!(chunker.chunk(text).len(), 8);
+    }
+}
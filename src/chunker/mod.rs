@@ -0,0 +1,3846 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SendError, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use smallvec::{smallvec, SmallVec};
+
+use self::merge::{preferred_splits, sentence_final_splits};
+use self::postprocess::ChunkBuffer;
+use crate::chunk::{link_chunks, Chunk, LinkedChunk};
+use crate::counters::TokenCounter;
+use crate::splitter::{Separator, SeparatorKind, SeparatorProfile, Splitter};
+
+mod merge;
+mod postprocess;
+
+pub use self::merge::MergeStrategy;
+pub use self::postprocess::{ChunkStrategy, EmptyChunks};
+
+
+/// A struct for chunking texts into segments based on a maximum number of tokens per chunk and a token counter function.
+/// 
+/// # Fields
+/// 
+/// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+/// * `min_chunk_size` - The number of tokens above which a chunk may end early at a more desirable boundary, if any.
+/// * `sentence_backoff` - How many tokens short of full a chunk may end early to end at a sentence, if any.
+/// * `token_counter` - A function that counts the number of tokens in a string, wrapping the [`TokenCounter`] the chunker was created with.
+/// * `splitter` - The Splitter instance used to split the text.
+/// * `overlap` - The number of tokens consecutive chunks should share.
+/// * `sentence_overlap` - Whether consecutive chunks share whole sentences instead of subchunks.
+/// * `separator_tokens` - The initial estimate of how many tokens a separator adds when splits are merged.
+/// * `concurrent_token_counter` - A thread-safe token counter and how many candidate chunks it may count at once.
+/// * `max_bytes` - The maximum number of bytes that can be in a chunk, if any.
+/// * `token_estimate` - A cheap estimate of the number of tokens in a text, used to find chunks before they are verified with the token counter.
+/// * `strategy` - Which parts of the text are chunked.
+/// * `merge_strategy` - How splits are merged into chunks.
+/// * `separator_attachment` - Where non-whitespace separators go when they do not fit at the end of the chunk before them.
+/// * `quote_integrity` - Whether opening quotes split at are kept with the chunk after them, where their closing quotes are.
+/// * `lossless` - Whether the text of each chunk is sliced verbatim from the text rather than joined from its splits.
+/// * `empty_chunks` - Which chunks without text are dropped.
+/// * `drop_chunks_below` - The number of tokens below which chunks are dropped, if any.
+/// * `special_tokens_overhead` - The number of special tokens, such as BOS and EOS tokens, the model adds to each chunk when encoding it.
+/// * `max_chunks` - The maximum number of chunks a text may be chunked into, if any.
+/// * `on_violation` - A function called with each chunk over the chunk size, if any.
+/// 
+/// # Example
+/// 
+/// ```
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let chunks = chunker.chunk(text);
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+/// ```
+///
+/// With `rust_tokenizers`:
+///
+/// ```
+/// use rust_tokenizers::tokenizer::{RobertaTokenizer, Tokenizer};
+/// use semchunk_rs::Chunker;
+/// let tokenizer = RobertaTokenizer::from_file("data/roberta-base-vocab.json", "data/roberta-base-merges.txt", false, false)
+///    .expect("Error loading tokenizer");
+/// let token_counter = Box::new(move |s: &str| {
+///    tokenizer.tokenize(s).len()
+/// });
+/// let chunker = Chunker::new(10, token_counter);
+/// ```
+pub struct Chunker {
+    chunk_size: usize,
+    min_chunk_size: Option<usize>,
+    sentence_backoff: Option<usize>,
+    token_counter: Box<dyn Fn(&str) -> usize>,
+    splitter: Splitter,
+    overlap: usize,
+    sentence_overlap: bool,
+    separator_tokens: f64,
+    concurrent_token_counter: Option<(ConcurrentTokenCounter, usize)>,
+    max_bytes: Option<usize>,
+    token_estimate: Option<TokenEstimate>,
+    strategy: ChunkStrategy,
+    merge_strategy: MergeStrategy,
+    separator_attachment: Attachment,
+    quote_integrity: bool,
+    lossless: Cell<bool>,
+    empty_chunks: EmptyChunks,
+    drop_chunks_below: Option<usize>,
+    special_tokens_overhead: usize,
+    max_chunks: Option<usize>,
+    on_violation: Option<ViolationCallback>,
+    reserved_tokens: Cell<usize>,
+    scratch: RefCell<Scratch>,
+    probe_pool: OnceCell<ProbePool>,
+}
+
+/// A rough number of bytes per token in English text, for estimating how many chunks a text will be chunked into.
+const BYTES_PER_TOKEN: usize = 4;
+
+/// A token counter that can be called from several threads at once.
+type ConcurrentTokenCounter = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
+/// A function called with each chunk over the chunk size.
+type ViolationCallback = Box<dyn Fn(&Violation)>;
+
+/// The length in bytes from which spans of text without separators are sliced into chunks rather than split into characters.
+const LONG_SPAN_BYTES: usize = 4096;
+
+/// The most candidate chunks counted at once.
+const MAX_PROBES: usize = 16;
+
+/// The separator chunks end at when they end with the text or are cut between words, grapheme clusters or characters.
+const NO_SEPARATOR: Separator<'static> = Separator {
+    text: "",
+    kind: SeparatorKind::None,
+};
+
+/// A cheap estimate of the number of tokens in a text, for use with [`Chunker::estimate_tokens`].
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::chunker::TokenEstimate;
+/// assert_eq!(TokenEstimate::CharsPerToken(4.0).estimate("The quick brown fox"), 5);
+/// assert_eq!(TokenEstimate::TokensPerWord(1.3).estimate("The quick brown fox"), 6);
+/// ```
+pub enum TokenEstimate {
+    /// The number of characters divided by the given number of characters per token, rounded up.
+    CharsPerToken(f64),
+    /// The number of whitespace-separated words multiplied by the given number of tokens per word, rounded up.
+    TokensPerWord(f64),
+    /// A custom function estimating the number of tokens in a string.
+    Custom(Box<dyn Fn(&str) -> usize>),
+}
+
+/// Where a [`Chunker`] puts a non-whitespace separator that does not fit at the end of the chunk before it, set with [`Chunker::separator_attachment`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Attachment {
+    /// Make the separator a chunk of its own.
+    #[default]
+    OwnChunk,
+    /// Prepend the separator to the chunk after it, or make it a chunk of its own if it does not fit there either.
+    NextChunk,
+    /// Drop the separator, so chunks no longer cover the whole text.
+    Drop,
+}
+
+/// A type of document, for which [`Chunker::preset`] sets a suitable splitter and chunking options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Markdown and other prose. Text is split at headings, around fenced code blocks and before list items before anything else, and chunks may end up to a quarter of the chunk size early to end at a sentence or clause.
+    Markdown,
+    /// Contracts, statutes and other legal text, in which a clause cut in two can change its meaning. Chunks may end up to half the chunk size early to end at a sentence or clause.
+    Legal,
+    /// Source code. Lines too long for a chunk are split at statement, block and operator punctuation rather than prose punctuation.
+    Code,
+    /// Chat transcripts. Consecutive chunks share an eighth of the chunk size, so each chunk carries some context from the previous messages.
+    Chat,
+    /// Logs. Chunks are filled with as many lines as fit, split at Latin punctuation without detecting the script of each line.
+    Logs,
+}
+
+/// The sending half of a channel, to which [`Chunker::chunk_to_channel`] sends chunks.
+///
+/// Implemented for the senders of the standard library's unbounded and bounded channels, and can be implemented for those of other channels.
+pub trait ChunkSender {
+    /// Sends a chunk, blocking until there is room in the channel if it is bounded, or returns the chunk if the receiver has been dropped.
+    fn send_chunk(&self, chunk: Chunk) -> Result<(), SendError<Chunk>>;
+}
+
+impl ChunkSender for Sender<Chunk> {
+    fn send_chunk(&self, chunk: Chunk) -> Result<(), SendError<Chunk>> {
+        self.send(chunk)
+    }
+}
+
+impl ChunkSender for SyncSender<Chunk> {
+    fn send_chunk(&self, chunk: Chunk) -> Result<(), SendError<Chunk>> {
+        self.send(chunk)
+    }
+}
+
+/// A signal that chunking should stop, which [`Chunker::chunk_to_cancellable`] checks before each split.
+///
+/// Implemented for [`AtomicBool`], which is cancelled once set to true, and with the `tokio-util` feature, for tokio's `CancellationToken`.
+pub trait Cancellation {
+    /// Whether chunking should stop.
+    fn is_cancelled(&self) -> bool;
+}
+
+impl Cancellation for AtomicBool {
+    fn is_cancelled(&self) -> bool {
+        self.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl Cancellation for tokio_util::sync::CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        tokio_util::sync::CancellationToken::is_cancelled(self)
+    }
+}
+
+/// Splits texts into chunks, so applications can swap chunkers at runtime or replace them with mocks in tests.
+///
+/// Implemented for [`Chunker`], and for boxed chunkers, so a `Box<dyn Chunking>` can be passed wherever a chunker is expected.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::{Chunk, Chunker, Chunking};
+///
+/// /// Puts the whole text in one chunk.
+/// struct Whole;
+///
+/// impl Chunking for Whole {
+///     fn chunk_spans(&self, text: &str) -> Vec<Chunk> {
+///         vec![Chunk { text: text.to_string(), start: 0, end: text.len() }]
+///     }
+/// }
+///
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// for (chunking, expected) in [
+///     (Box::new(Whole) as Box<dyn Chunking>, 1),
+///     (Box::new(Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))), 3),
+/// ] {
+///     assert_eq!(chunking.chunk_spans(text).len(), expected);
+/// }
+/// ```
+pub trait Chunking {
+    /// Splits `text` into chunks, with offsets into `text`.
+    ///
+    /// Named apart from [`Chunker::chunk`], which returns the chunks' text alone, so that importing the trait does not change what `chunker.chunk(text)` means.
+    fn chunk_spans(&self, text: &str) -> Vec<Chunk>;
+}
+
+impl Chunking for Chunker {
+    fn chunk_spans(&self, text: &str) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        self.chunk_into(text, &mut chunks);
+        chunks
+    }
+}
+
+impl<T: Chunking + ?Sized> Chunking for Box<T> {
+    fn chunk_spans(&self, text: &str) -> Vec<Chunk> {
+        (**self).chunk_spans(text)
+    }
+}
+
+/// The error returned by [`Chunker::chunk_to_cancellable`] when chunking was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "chunking was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// The error returned by [`Chunker::try_chunk`] when a text has more chunks than the limit set with [`Chunker::max_chunks`].
+///
+/// # Fields
+///
+/// * `max_chunks` - The limit.
+/// * `chunks` - The chunks before the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooManyChunks {
+    pub max_chunks: usize,
+    pub chunks: Vec<String>,
+}
+
+impl fmt::Display for TooManyChunks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the text has more than {} chunks", self.max_chunks)
+    }
+}
+
+impl std::error::Error for TooManyChunks {}
+
+/// The error returned by [`Chunker::try_build`], [`Chunker::try_new`] and [`Chunker::try_overlap`] when a Chunker would be misconfigured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The chunk size is zero, so no text fits in a chunk.
+    ZeroChunkSize,
+    /// The overlap is not less than the chunk size, so chunks would share all of their tokens.
+    OverlapTooLarge { overlap: usize, chunk_size: usize },
+    /// The chunk size range set with [`Chunker::chunk_size_range`] is empty, with its start over its end.
+    EmptyChunkSizeRange {
+        min_chunk_size: usize,
+        chunk_size: usize,
+    },
+    /// The splitter's list of separators to split text into words at is empty, so its separator hierarchy ends at punctuation and long sentences are split into characters.
+    EmptySeparators,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::ZeroChunkSize => write!(f, "the chunk size must be at least 1"),
+            ConfigError::OverlapTooLarge {
+                overlap,
+                chunk_size,
+            } => write!(
+                f,
+                "the overlap of {} tokens must be less than the chunk size of {} tokens",
+                overlap, chunk_size
+            ),
+            ConfigError::EmptyChunkSizeRange {
+                min_chunk_size,
+                chunk_size,
+            } => write!(
+                f,
+                "the chunk size range {}..={} is empty",
+                min_chunk_size, chunk_size
+            ),
+            ConfigError::EmptySeparators => {
+                write!(f, "the splitter's separators must not be empty")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A chunk with more tokens than the chunk size, such as one made of a single long token that cannot be split, reported by [`Chunker::violations`] and to the callback set with [`Chunker::on_violation`].
+///
+/// # Fields
+///
+/// * `index` - The index of the chunk.
+/// * `tokens` - The number of tokens in the chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub index: usize,
+    pub tokens: usize,
+}
+
+impl TokenEstimate {
+    /// Estimates the number of tokens in `text`.
+    pub fn estimate(&self, text: &str) -> usize {
+        match self {
+            TokenEstimate::CharsPerToken(chars_per_token) => {
+                (text.chars().count() as f64 / chars_per_token).ceil() as usize
+            }
+            TokenEstimate::TokensPerWord(tokens_per_word) => {
+                (text.split_whitespace().count() as f64 * tokens_per_word).ceil() as usize
+            }
+            TokenEstimate::Custom(estimate) => estimate(text),
+        }
+    }
+}
+
+impl Chunker {
+    /// Creates a new Chunker instance. Uses the default Splitter instance. S
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    /// * `token_counter` - A [`TokenCounter`] that counts the number of tokens in a string, such as a closure or a boxed closure. Closures may be `FnMut`, to keep caches or statistics.
+    ///
+    /// # Returns
+    ///
+    /// A new Chunker instance.
+    pub fn new(chunk_size: usize, token_counter: impl TokenCounter + 'static) -> Self {
+        let token_counter = RefCell::new(token_counter);
+        let token_counter: Box<dyn Fn(&str) -> usize> =
+            Box::new(move |text: &str| token_counter.borrow_mut().count_tokens(text));
+        Chunker {
+            chunk_size,
+            min_chunk_size: None,
+            sentence_backoff: None,
+            token_counter,
+            splitter: Splitter::default(),
+            overlap: 0,
+            sentence_overlap: false,
+            separator_tokens: 0.0,
+            concurrent_token_counter: None,
+            max_bytes: None,
+            token_estimate: None,
+            strategy: ChunkStrategy::default(),
+            merge_strategy: MergeStrategy::default(),
+            separator_attachment: Attachment::default(),
+            quote_integrity: false,
+            lossless: Cell::new(false),
+            empty_chunks: EmptyChunks::default(),
+            drop_chunks_below: None,
+            special_tokens_overhead: 0,
+            max_chunks: None,
+            on_violation: None,
+            reserved_tokens: Cell::new(0),
+            scratch: RefCell::default(),
+            probe_pool: OnceCell::new(),
+        }
+    }
+
+    /// Creates a new Chunker instance like [`Chunker::new`], but fails fast on a chunk size of zero rather than producing a chunk for every character.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    /// * `token_counter` - A [`TokenCounter`] that counts the number of tokens in a string.
+    ///
+    /// # Returns
+    ///
+    /// A new Chunker instance, or [`ConfigError::ZeroChunkSize`] if the chunk size is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::ConfigError;
+    /// use semchunk_rs::Chunker;
+    /// let words = |s: &str| s.split_whitespace().count();
+    /// assert!(Chunker::try_new(4, words).is_ok());
+    /// assert_eq!(Chunker::try_new(0, words).err(), Some(ConfigError::ZeroChunkSize));
+    /// ```
+    pub fn try_new(
+        chunk_size: usize,
+        token_counter: impl TokenCounter + 'static,
+    ) -> Result<Self, ConfigError> {
+        Chunker::new(chunk_size, token_counter).try_build()
+    }
+
+    /// Creates a new Chunker instance with a thread-safe token counter, which counts up to `probes` candidate chunks concurrently.
+    ///
+    /// Rather than counting one candidate chunk at a time, the chunker speculatively counts the candidates around its estimate of where the chunk ends on a pool of `probes` threads, kept for the chunker's lifetime, so fewer rounds of counting are needed to find it. This hides the latency of slow token counters, such as remote APIs, at the cost of counting more candidates overall.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    /// * `token_counter` - A thread-safe function that counts the number of tokens in a string.
+    /// * `probes` - The number of candidate chunks counted at once, from 1 to 16.
+    ///
+    /// # Returns
+    ///
+    /// A new Chunker instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new_concurrent(4, |s: &str| s.split_whitespace().count(), 4);
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn new_concurrent<F>(chunk_size: usize, token_counter: F, probes: usize) -> Self
+    where
+        F: Fn(&str) -> usize + Send + Sync + 'static,
+    {
+        let token_counter = Arc::new(token_counter);
+        let shared = Arc::clone(&token_counter);
+        let mut chunker = Chunker::new(chunk_size, move |text: &str| shared(text));
+        chunker.concurrent_token_counter = Some((token_counter, probes.clamp(1, MAX_PROBES)));
+        chunker
+    }
+
+    /// Checks the configuration of the Chunker instance, so that a misconfigured Chunker fails fast rather than misbehaving at chunk time.
+    ///
+    /// # Returns
+    ///
+    /// The Chunker instance, or a [`ConfigError`] if the chunk size is zero, the overlap is not less than the chunk size, the chunk size range is empty, or the splitter has no separators to split text into words at.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::ConfigError;
+    /// use semchunk_rs::{Chunker, Splitter};
+    /// let words = |s: &str| s.split_whitespace().count();
+    /// assert!(Chunker::new(4, words).overlap(2).try_build().is_ok());
+    /// let chunker = Chunker::new(4, words).splitter(Splitter::default().spaces(&[]));
+    /// assert_eq!(chunker.try_build().err(), Some(ConfigError::EmptySeparators));
+    /// ```
+    pub fn try_build(self) -> Result<Self, ConfigError> {
+        if self.chunk_size == 0 {
+            return Err(ConfigError::ZeroChunkSize);
+        }
+        if self.overlap >= self.chunk_size {
+            return Err(ConfigError::OverlapTooLarge {
+                overlap: self.overlap,
+                chunk_size: self.chunk_size,
+            });
+        }
+        if let Some(min_chunk_size) = self.min_chunk_size.filter(|&min| min > self.chunk_size) {
+            return Err(ConfigError::EmptyChunkSizeRange {
+                min_chunk_size,
+                chunk_size: self.chunk_size,
+            });
+        }
+        if self.splitter.separators_empty() {
+            return Err(ConfigError::EmptySeparators);
+        }
+        Ok(self)
+    }
+
+    /// Sets the splitter for the Chunker instance.
+    pub fn splitter(mut self, splitter: Splitter) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// Sets the splitter and chunking options to suit a type of document, relative to the chunk size.
+    ///
+    /// The options set by the preset replace any set before it, and can be changed by builder methods called after it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::Preset;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(8, Box::new(|s: &str| s.chars().count())).preset(Preset::Code);
+    /// let chunks = chunker.chunk("let x=f(a);x+1");
+    /// assert_eq!(chunks, vec!["let", "x=f(a);", "x+1"]);
+    /// ```
+    pub fn preset(self, preset: Preset) -> Self {
+        let chunk_size = self.chunk_size;
+        let (profile, min_chunk_size, overlap) = match preset {
+            Preset::Markdown => (SeparatorProfile::Auto, Some(chunk_size - chunk_size / 4), 0),
+            Preset::Legal => (SeparatorProfile::Auto, Some(chunk_size - chunk_size / 2), 0),
+            Preset::Code => (SeparatorProfile::Code, None, 0),
+            Preset::Chat => (SeparatorProfile::Auto, None, chunk_size / 8),
+            Preset::Logs => (SeparatorProfile::Latin, None, 0),
+        };
+        let splitter = Splitter::default()
+            .profile(profile)
+            .markdown(preset == Preset::Markdown);
+        let mut chunker = self.splitter(splitter).overlap(overlap);
+        chunker.min_chunk_size = min_chunk_size;
+        chunker
+    }
+
+    /// Sets the chunk size to a range of tokens, so that chunks fill up to the end of the range but may end early, anywhere in the range, at a more desirable boundary.
+    ///
+    /// Where a chunk would end between words, it instead ends after the last word in the range that ends a sentence, or failing that a clause, so that chunks are cut mid-clause less often. Has no effect on overlapping chunks.
+    ///
+    /// An empty range, with its start over its end, is reported by [`Chunker::try_build`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let text = "The quick brown fox jumps. Over the lazy dog, it leaps.";
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()));
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps. Over", "the lazy dog, it leaps."]);
+    /// let chunker = chunker.chunk_size_range(4..=6);
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps.", "Over the lazy dog, it leaps."]);
+    /// ```
+    pub fn chunk_size_range(mut self, chunk_size: RangeInclusive<usize>) -> Self {
+        let (min_chunk_size, chunk_size) = chunk_size.into_inner();
+        self.chunk_size = chunk_size;
+        self.min_chunk_size = Some(min_chunk_size);
+        self
+    }
+
+    /// Lets chunks end up to `backoff` tokens short of full to end at a sentence.
+    ///
+    /// Where a chunk would not end a sentence, it instead ends after the last sentence terminator within its final `backoff` tokens, a full stop, question mark or exclamation mark in Latin, full-width or Arabic script, rather than at a lower-priority separator mid-clause. Unlike [`Chunker::chunk_size_range`], which ends chunks at the best boundary in the range and falls back to clauses, chunks only end early at sentence ends. Has no effect on overlapping chunks, or with a chunk size range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let text = "The quick brown fox jumps. Over the lazy dog, it leaps.";
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()));
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps. Over", "the lazy dog, it leaps."]);
+    /// let chunker = chunker.sentence_backoff(2);
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps.", "Over the lazy dog, it leaps."]);
+    /// ```
+    pub fn sentence_backoff(mut self, backoff: usize) -> Self {
+        self.sentence_backoff = Some(backoff);
+        self
+    }
+
+    /// Sets the number of tokens consecutive chunks should share. Capped at `chunk_size - 1`.
+    ///
+    /// The text is chunked into subchunks of at most `min(overlap, chunk_size - overlap)` tokens, which are then merged into overlapping windows of up to `chunk_size` tokens, as in the Python semchunk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).overlap(2);
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let chunks = chunker.chunk(text);
+    /// assert_eq!(chunks, vec!["The quick brown fox", "brown fox jumps over", "jumps over the lazy", "the lazy dog."]);
+    /// ```
+    pub fn overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap.min(self.chunk_size.saturating_sub(1));
+        self
+    }
+
+    /// Sets the number of tokens consecutive chunks should share like [`Chunker::overlap`], but fails on an overlap that is not less than the chunk size rather than capping it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::ConfigError;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let error = chunker.try_overlap(4).err();
+    /// assert_eq!(error, Some(ConfigError::OverlapTooLarge { overlap: 4, chunk_size: 4 }));
+    /// ```
+    pub fn try_overlap(mut self, overlap: usize) -> Result<Self, ConfigError> {
+        self.overlap = overlap;
+        self.try_build()
+    }
+
+    /// Sets whether consecutive chunks share whole sentences rather than subchunks, which defaults to false.
+    ///
+    /// The text is chunked into chunks of up to `chunk_size - overlap` tokens, and each chunk after the first is extended back to start with the last sentences of the chunk before it that add up to at most `overlap` tokens. Chunks start with no overlap where no sentence of the chunk before fits. Has no effect without [`Chunker::overlap`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(9, Box::new(|s: &str| s.split_whitespace().count()))
+    ///     .overlap(3)
+    ///     .sentence_overlap(true);
+    /// let text = "The fox jumps. The dog sleeps. The cat naps. The end.";
+    /// let chunks = chunker.chunk(text);
+    /// assert_eq!(chunks, vec!["The fox jumps. The dog sleeps.", "The dog sleeps. The cat naps. The end."]);
+    /// ```
+    pub fn sentence_overlap(mut self, sentence_overlap: bool) -> Self {
+        self.sentence_overlap = sentence_overlap;
+        self
+    }
+
+    /// Sets the initial estimate of how many tokens a separator adds when splits are merged, which defaults to 0.
+    ///
+    /// Chunks are found by estimating the number of tokens in merged splits from their individual token counts, so a good estimate saves token counter calls. The estimate is corrected as chunks are counted, and the correction for each separator carries over to later splits of the same text. Tokenizers that split on whitespace add no tokens for whitespace separators, whereas character-based tokenizers add one token per character of the separator.
+    ///
+    /// The estimate only affects how many times tokens are counted, not the chunks produced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(10, Box::new(|s: &str| s.chars().count())).separator_tokens(1.0);
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick", "brown fox", "jumps over", "the lazy", "dog."]);
+    /// ```
+    pub fn separator_tokens(mut self, separator_tokens: f64) -> Self {
+        self.separator_tokens = separator_tokens;
+        self
+    }
+
+    /// Sets the maximum number of bytes that can be in a chunk, in addition to the maximum number of tokens. Raised to at least 4, so that every character fits.
+    ///
+    /// Chunks end at whichever limit is reached first. Texts over the byte limit are not counted by the token counter. With [`Chunker::overlap`], consecutive chunks share less text where sharing all of it would put them over the byte limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).max_bytes(12);
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick", "brown fox", "jumps over", "the lazy", "dog."]);
+    /// ```
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes.max(4));
+        self
+    }
+
+    /// Sets which parts of the text are chunked, which defaults to [`ChunkStrategy::Full`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::ChunkStrategy;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))
+    ///     .strategy(ChunkStrategy::HeadTail { head: 3, tail: 2 });
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown", "lazy dog."]);
+    /// ```
+    pub fn strategy(mut self, strategy: ChunkStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets how splits are merged into chunks, which defaults to [`MergeStrategy::Greedy`].
+    ///
+    /// With [`MergeStrategy::FewestChunks`], chunks are filled greedily and then merged where consecutive chunks fit together.
+    ///
+    /// With [`MergeStrategy::Optimal`], each chunk is scored by how far short of the chunk size it falls, squared, plus a penalty for ending at less desirable punctuation, and the chunks with the lowest total score are picked. The last chunk of a text is not penalized for being short. Chunk size ranges and sentence backoff have no effect, as the score already weighs fill against punctuation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::MergeStrategy;
+    /// use semchunk_rs::Chunker;
+    /// let text = "The quick brown fox jumps. Over the lazy dog, it leaps.";
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()));
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps. Over", "the lazy dog, it leaps."]);
+    /// let chunker = chunker.merge_strategy(MergeStrategy::Optimal);
+    /// assert_eq!(chunker.chunk(text), vec!["The quick brown fox jumps.", "Over the lazy dog, it leaps."]);
+    ///
+    /// let chunker = Chunker::new(10, Box::new(|s: &str| s.chars().count()));
+    /// assert_eq!(chunker.chunk("ab cdefghijklmno pq"), vec!["ab", "cdefghijkl", "mno", "pq"]);
+    /// let chunker = chunker.merge_strategy(MergeStrategy::FewestChunks);
+    /// assert_eq!(chunker.chunk("ab cdefghijklmno pq"), vec!["ab", "cdefghijkl", "mno pq"]);
+    /// ```
+    pub fn merge_strategy(mut self, merge_strategy: MergeStrategy) -> Self {
+        self.merge_strategy = merge_strategy;
+        self
+    }
+
+    /// Sets where a non-whitespace separator goes when it does not fit at the end of the chunk before it, which by default is a chunk of its own, such as `"."`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::Attachment;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(5, Box::new(|s: &str| s.chars().count()));
+    /// assert_eq!(chunker.chunk("abcde.fgh"), vec!["abcde", ".", "fgh"]);
+    /// let chunker = chunker.separator_attachment(Attachment::NextChunk);
+    /// assert_eq!(chunker.chunk("abcde.fgh"), vec!["abcde", ".fgh"]);
+    /// let chunker = chunker.separator_attachment(Attachment::Drop);
+    /// assert_eq!(chunker.chunk("abcde.fgh"), vec!["abcde", "fgh"]);
+    /// ```
+    pub fn separator_attachment(mut self, separator_attachment: Attachment) -> Self {
+        self.separator_attachment = separator_attachment;
+        self
+    }
+
+    /// Sets whether opening quotes that text is split at are kept with the chunk after them, rather than stranded at the end of the chunk before them while their closing quotes start the next chunk.
+    ///
+    /// Curly quotes, guillemets and corner brackets always open quotes, while straight quotes open them when they follow whitespace or the start of the text. An opening quote that does not fit in the chunk after it becomes a chunk of its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(5, Box::new(|s: &str| s.chars().count()));
+    /// assert_eq!(chunker.chunk("abc“de”f"), vec!["abc“", "de”f"]);
+    /// let chunker = chunker.quote_integrity(true);
+    /// assert_eq!(chunker.chunk("abc“de”f"), vec!["abc", "“de”f"]);
+    /// ```
+    pub fn quote_integrity(mut self, quote_integrity: bool) -> Self {
+        self.quote_integrity = quote_integrity;
+        self
+    }
+
+    /// Sets whether the text of each chunk is sliced verbatim from the text, whitespace and all, rather than joined from its splits.
+    ///
+    /// By default, a non-whitespace separator appended to a chunk that does not end right before it is joined to it directly, so the whitespace between them is dropped and the chunk's text is not the text from its `start` to its `end`. Chunking losslessly keeps that whitespace, which may also change how many tokens the chunk has.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).lossless(true);
+    /// let text = "The quick  brown fox ,\tjumps over the lazy dog .";
+    /// let mut chunks = Vec::new();
+    /// chunker.chunk_to(text, |chunk| chunks.push(chunk));
+    /// assert!(chunks.iter().all(|chunk| chunk.text == text[chunk.start..chunk.end]));
+    /// ```
+    pub fn lossless(self, lossless: bool) -> Self {
+        self.lossless.set(lossless);
+        self
+    }
+
+    /// Sets which chunks without text are dropped, which by default are empty chunks, wherever in the text they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::EmptyChunks;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(5, Box::new(|s: &str| s.chars().count()));
+    /// assert_eq!(chunker.chunk("abcde\n\n"), vec!["abcde"]);
+    /// assert_eq!(chunker.chunk("abcde\n\n  "), vec!["abcde", "  "]);
+    /// let chunker = chunker.empty_chunks(EmptyChunks::DropWhitespace);
+    /// assert_eq!(chunker.chunk("abcde\n\n  "), vec!["abcde"]);
+    /// let chunker = chunker.empty_chunks(EmptyChunks::Keep);
+    /// assert_eq!(chunker.chunk("abcde\n\n"), vec!["abcde", ""]);
+    /// ```
+    pub fn empty_chunks(mut self, empty_chunks: EmptyChunks) -> Self {
+        self.empty_chunks = empty_chunks;
+        self
+    }
+
+    /// Drops chunks with fewer than `min_tokens` tokens, such as lone page numbers and orphaned headings that only add noise to vector indexes.
+    ///
+    /// Dropped chunks do not count towards [`Chunker::max_chunks`], so chunks no longer cover the whole text. To merge short chunks into the chunks next to them where they fit instead, use [`MergeStrategy::FewestChunks`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let text = "The fox jumps high.\n\n12\n\nOver the lazy dog.";
+    /// assert_eq!(chunker.chunk(text), vec!["The fox jumps high.", "12", "Over the lazy dog."]);
+    /// let chunker = chunker.drop_chunks_below(2);
+    /// assert_eq!(chunker.chunk(text), vec!["The fox jumps high.", "Over the lazy dog."]);
+    /// ```
+    pub fn drop_chunks_below(mut self, min_tokens: usize) -> Self {
+        self.drop_chunks_below = Some(min_tokens);
+        self
+    }
+
+    /// Sets the number of special tokens the model adds to each chunk when encoding it, such as BOS, EOS, `[CLS]` and `[SEP]` tokens, which defaults to 0.
+    ///
+    /// Token counters count the tokens of a text without special tokens, so chunks of exactly the chunk size overflow the model's context once it adds its own. The overhead is reserved from every chunk, so chunks with their special tokens fit in the chunk size. For Hugging Face tokenizers, [`counters::special_tokens`](crate::counters::special_tokens) finds the overhead by encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()))
+    ///     .special_tokens_overhead(2);
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn special_tokens_overhead(mut self, special_tokens: usize) -> Self {
+        self.special_tokens_overhead = special_tokens;
+        self
+    }
+
+    /// Finds chunks with a cheap estimate of the number of tokens, only counting tokens with the token counter to verify each chunk.
+    ///
+    /// Each chunk is found by estimating the tokens in its splits, then verified by counting its tokens. Should a chunk not fit, it is shrunk by bisection with the token counter, and the ratio of counted to estimated tokens is learned to scale later estimates. Chunks never exceed the chunk size, but may be smaller than they could be, so this trades a little accuracy for far fewer calls to slow token counters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// use semchunk_rs::chunker::TokenEstimate;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))
+    ///     .estimate_tokens(TokenEstimate::TokensPerWord(1.0));
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn estimate_tokens(mut self, token_estimate: TokenEstimate) -> Self {
+        self.token_estimate = Some(token_estimate);
+        self
+    }
+
+    /// Sets the maximum number of chunks a text may be chunked into, to protect services that chunk untrusted input from texts that would be chunked into millions of chunks.
+    ///
+    /// Chunking stops as soon as a text is found to have more chunks, so it takes time and memory bounded by the limit. [`Chunker::try_chunk`] then returns an error with the chunks before the limit, while other methods return or pass on those chunks alone, except [`Chunker::_chunk`], the bare recursion, which is not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).max_chunks(2);
+    /// let error = chunker.try_chunk("The quick brown fox jumps over the lazy dog.").unwrap_err();
+    /// assert_eq!(error.chunks, vec!["The quick brown fox", "jumps over the lazy"]);
+    /// ```
+    pub fn max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = Some(max_chunks);
+        self
+    }
+
+    /// Sets a function called with each chunk that has more tokens than the chunk size, such as to log a warning.
+    ///
+    /// Chunks are only over the chunk size where a single grapheme cluster, or a token that cannot be split, is. With a callback set, the tokens of every chunk are counted once more to verify it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use semchunk_rs::Chunker;
+    /// let violations = Rc::new(RefCell::new(Vec::new()));
+    /// let reported = violations.clone();
+    /// let chunker = Chunker::new(1, Box::new(|s: &str| s.len()))
+    ///     .on_violation(move |violation| reported.borrow_mut().push(*violation));
+    /// assert_eq!(chunker.chunk("a é"), vec!["a", "é"]);
+    /// assert_eq!(violations.borrow()[0].index, 1);
+    /// assert_eq!(violations.borrow()[0].tokens, 2);
+    /// ```
+    pub fn on_violation(mut self, on_violation: impl Fn(&Violation) + 'static) -> Self {
+        self.on_violation = Some(Box::new(on_violation));
+        self
+    }
+
+    /// Verifies chunks against the chunk size, returning those with more tokens than it.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunks` - The chunks to verify, such as those returned by [`Chunker::chunk`].
+    ///
+    /// # Returns
+    ///
+    /// The index and number of tokens of each chunk over the chunk size, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::chunker::Violation;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(2, Box::new(|s: &str| s.len()));
+    /// let chunks = chunker.chunk("The 🦀 crab");
+    /// assert_eq!(chunks, vec!["Th", "e", "🦀", "cr", "ab"]);
+    /// assert_eq!(chunker.violations(&chunks), vec![Violation { index: 2, tokens: 4 }]);
+    /// ```
+    pub fn violations<S: AsRef<str>>(&self, chunks: &[S]) -> Vec<Violation> {
+        chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| self.violation(index, self.count_tokens(chunk.as_ref())))
+            .collect()
+    }
+
+    /// Returns the violation of the chunk at `index`, which has `tokens` tokens, if it has more tokens than the chunk size.
+    fn violation(&self, index: usize, tokens: usize) -> Option<Violation> {
+        (tokens > self.content_size()).then_some(Violation { index, tokens })
+    }
+
+    /// The maximum number of tokens that can be in a chunk.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// The maximum number of tokens in the text of a chunk, which is the chunk size less the special tokens added to each chunk and any tokens reserved for context prepended to each chunk, but at least 1.
+    pub(crate) fn content_size(&self) -> usize {
+        self.chunk_size
+            .saturating_sub(self.special_tokens_overhead + self.reserved_tokens.get())
+            .max(1)
+    }
+
+    /// The number of tokens consecutive chunks share, capped to fit in the content size.
+    fn content_overlap(&self) -> usize {
+        self.overlap.min(self.content_size() - 1)
+    }
+
+    /// Counts the tokens in `text` with the token counter.
+    pub(crate) fn count_tokens(&self, text: &str) -> usize {
+        (self.token_counter)(text)
+    }
+
+    /// Counts the tokens in `text` as a chunk, which is more than the chunk size if `text` is over the byte limit, so that it does not fit.
+    pub(crate) fn count_chunk_tokens(&self, text: &str) -> usize {
+        if self.over_max_bytes(text) {
+            self.content_size() + 1
+        } else {
+            (self.token_counter)(text)
+        }
+    }
+
+    /// Whether `text` is over the byte limit.
+    fn over_max_bytes(&self, text: &str) -> bool {
+        self.max_bytes
+            .is_some_and(|max_bytes| text.len() > max_bytes)
+    }
+
+    /// The maximum number of tokens in the chunks produced by recursion, which are the subchunks of overlapping chunks.
+    fn local_chunk_size(&self) -> usize {
+        match self.content_overlap() {
+            0 => self.content_size(),
+            overlap if self.sentence_overlap => self.content_size() - overlap,
+            overlap => overlap.min(self.content_size() - overlap),
+        }
+    }
+
+    /// Recursively chunks the given text into segments based on the maximum number of tokens per chunk.
+    ///
+    /// This is the bare recursion the other chunking methods are built on, so it ignores the options they apply to whole texts: the chunks do not overlap, [`Chunker::strategy`] is not applied, [`Chunker::max_chunks`] does not limit them, [`Chunker::drop_chunks_below`] does not drop any, and [`Chunker::on_violation`] is not called. Use [`Chunker::chunk`] or [`Chunker::try_chunk`] to apply them.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `recursion_depth` - The current recursion depth.
+    /// 
+    /// # Returns
+    /// 
+    /// A vector of string slices representing the chunks of the split text.
+    pub fn _chunk(&self, text: &str, recursion_depth: usize) -> Vec<String> {
+        let mut chunks: Vec<String> = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_recursive_into(text, recursion_depth, &mut chunks);
+        chunks
+    }
+
+    /// Recursively chunks the given text, appending the chunks to `chunks`.
+    fn chunk_recursive_into(&self, text: &str, recursion_depth: usize, chunks: &mut Vec<String>) {
+        let mut buffer = ChunkBuffer::new(
+            |chunk: CowChunk| chunks.push(chunk.text.into_owned()),
+            &|| false,
+            self.empty_chunks,
+        );
+        self.with_scratch(|scratch| {
+            self.chunk_recursive(
+                text,
+                text,
+                recursion_depth,
+                NO_SEPARATOR,
+                &mut buffer,
+                scratch,
+            )
+        });
+        buffer.finish();
+    }
+
+    /// Estimates how many chunks the given text will be chunked into, from its length and the chunk size, to preallocate the chunks.
+    fn estimated_chunks(&self, text: &str) -> usize {
+        let stride = self
+            .content_size()
+            .saturating_sub(self.content_overlap())
+            .max(1);
+        let chunks = text.len() / BYTES_PER_TOKEN / stride + 1;
+        let chunks = match self.max_chunks {
+            Some(max_chunks) => chunks.min(max_chunks),
+            None => chunks,
+        };
+        match self.strategy {
+            ChunkStrategy::Full => chunks,
+            ChunkStrategy::HeadTail { head, tail } => chunks.min((head + tail) / stride + 2),
+        }
+    }
+
+    /// Recursively chunks `text`, a slice of `root` that ends at the separator `boundary`, pushing the chunks into `buffer`.
+    fn chunk_recursive<'a, F: FnMut(CowChunk<'a>)>(
+        &self,
+        root: &'a str,
+        text: &'a str,
+        recursion_depth: usize,
+        boundary: Separator<'a>,
+        buffer: &mut ChunkBuffer<'a, '_, F>,
+        scratch: &mut Scratch,
+    ) {
+        let (separator, text_splits) = self.splitter.splits(text);
+        if separator.text.is_empty() && text.len() >= LONG_SPAN_BYTES && text.is_ascii() {
+            // Long spans without separators, such as base64 blobs, are sliced rather than split into characters.
+            self.chunk_long_span(root, text, recursion_depth, boundary, buffer);
+            return;
+        }
+        self.chunk_splits(
+            root,
+            text,
+            separator,
+            text_splits,
+            false,
+            recursion_depth,
+            boundary,
+            buffer,
+            scratch,
+        );
+    }
+
+    /// Chunks the top level of `text`, the whole of `root`, which is split by the splitter, or into `segments` where given.
+    fn chunk_root<'a, F: FnMut(CowChunk<'a>)>(
+        &self,
+        text: &'a str,
+        segments: Option<&Segments<'a>>,
+        buffer: &mut ChunkBuffer<'a, '_, F>,
+        scratch: &mut Scratch,
+    ) {
+        match segments {
+            Some(segments) => self.chunk_splits(
+                text,
+                text,
+                segments.joiner,
+                segments.splits.iter().copied(),
+                true,
+                0,
+                NO_SEPARATOR,
+                buffer,
+                scratch,
+            ),
+            None => self.chunk_recursive(text, text, 0, NO_SEPARATOR, buffer, scratch),
+        }
+    }
+
+    /// Merges `text_splits`, the splits of `text`, a slice of `root` that ends at the separator `boundary`, into chunks, recursively chunking splits over the chunk size, and pushes the chunks into `buffer`.
+    ///
+    /// Splits over the chunk size are only chunked recursively if they are shorter than the text, so that the recursion always ends, or if `split_further` is set, as for caller-provided segments.
+    #[allow(clippy::too_many_arguments)]
+    fn chunk_splits<'a, F: FnMut(CowChunk<'a>)>(
+        &self,
+        root: &'a str,
+        text: &'a str,
+        separator: Separator<'a>,
+        text_splits: impl Iterator<Item = &'a str>,
+        split_further: bool,
+        recursion_depth: usize,
+        boundary: Separator<'a>,
+        buffer: &mut ChunkBuffer<'a, '_, F>,
+        scratch: &mut Scratch,
+    ) {
+        // With a token estimate, splits are measured in estimated tokens.
+        let estimate = |text: &str| match &self.token_estimate {
+            Some(_) if self.over_max_bytes(text) => self.content_size() + 1,
+            Some(token_estimate) => token_estimate.estimate(text),
+            None => 0,
+        };
+        let count = |text: &str| self.count_chunk_tokens(text);
+        let split_counter: &dyn Fn(&str) -> usize = match self.token_estimate {
+            Some(_) => &estimate,
+            None => &count,
+        };
+        let mut split_tokens = SplitTokens::new(
+            text_splits,
+            split_counter,
+            scratch
+                .separator_tokens
+                .get(separator.text)
+                .copied()
+                .unwrap_or(self.separator_tokens),
+        );
+
+        // With the optimal strategy, where chunks end is planned a window of splits at a time.
+        let mut plan = match self.merge_strategy {
+            MergeStrategy::Greedy | MergeStrategy::FewestChunks => None,
+            MergeStrategy::Optimal => Some(VecDeque::new()),
+        };
+
+        // Iterate through the splits
+        let mut i = 0;
+        while !(buffer.stop)() {
+            let Some(split) = split_tokens.get(i) else {
+                break;
+            };
+            split_tokens.release_before(i);
+            // Add the split and any subsequent splits to a new chunk until the chunk size is reached.
+            // Merged splits are a slice of the text, so they can be counted without being joined.
+            let start = offset_of(root, split);
+            let end_of = |splits: &[&str]| match splits.last() {
+                Some(last_split) => offset_of(root, last_split) + last_split.len(),
+                None => start,
+            };
+            let split_idx = match (&mut plan, &self.token_estimate) {
+                (Some(plan), _) => self.planned_splits(root, &mut split_tokens, separator, plan, i),
+                (None, Some(token_estimate)) => self.verified_splits(
+                    &mut split_tokens,
+                    i,
+                    token_estimate,
+                    &mut scratch.estimate_scale,
+                    |splits| &root[start..end_of(splits)],
+                ),
+                (None, None) => self.fitting_splits(
+                    &mut split_tokens,
+                    i,
+                    self.local_chunk_size(),
+                    self.probes(),
+                    |candidates, counts| {
+                        let texts: SmallVec<[&str; MAX_PROBES]> = candidates
+                            .iter()
+                            .map(|splits| &root[start..end_of(splits)])
+                            .collect();
+                        self.count_candidates(&texts, counts);
+                    },
+                ),
+            };
+            // Chunks that end with the text already end at the most desirable boundary.
+            let ends_text = split_tokens.get(i + split_idx).is_none();
+            // Splits are counted in estimated tokens with a token estimate.
+            let estimated = |tokens: usize| match self.token_estimate {
+                Some(_) => tokens as f64 / scratch.estimate_scale,
+                None => tokens as f64,
+            };
+            let split_idx = match (self.min_chunk_size, self.sentence_backoff) {
+                _ if plan.is_some()
+                    || self.content_overlap() > 0
+                    || split_idx <= 1
+                    || ends_text =>
+                {
+                    split_idx
+                }
+                (Some(min_chunk_size), _) => {
+                    preferred_splits(&split_tokens, i, split_idx, estimated(min_chunk_size))
+                }
+                (None, Some(backoff)) => {
+                    let min_chunk_size = self.local_chunk_size().saturating_sub(backoff);
+                    sentence_final_splits(&split_tokens, i, split_idx, estimated(min_chunk_size))
+                }
+                (None, None) => split_idx,
+            };
+            // Chunks end at this level's separator, unless they end with the text.
+            let chunk_boundary = match split_tokens.get(i + split_idx.max(1)) {
+                Some(_) => separator,
+                None => boundary,
+            };
+            let chunk_end = if split_idx == 0 && (split_further || split.len() < text.len()) {
+                // If the split is over the chunk size, recursively chunk it.
+                // The split is shorter than the text, or a segment split by the splitter, so the recursion always ends.
+                self.chunk_recursive(
+                    root,
+                    split,
+                    recursion_depth + 1,
+                    chunk_boundary,
+                    buffer,
+                    scratch,
+                );
+                i += 1;
+                start + split.len()
+            } else if split_idx == 0 {
+                // The split cannot be split any further, such as a single grapheme cluster over the chunk size, so it becomes a chunk of its own.
+                let end = start + split.len();
+                self.push_chunk(
+                    root,
+                    buffer,
+                    CowChunk {
+                        text: Cow::Borrowed(&root[start..end]),
+                        start,
+                        end,
+                        tokens: None,
+                        boundary: chunk_boundary,
+                    },
+                    recursion_depth,
+                );
+                i += 1;
+                end
+            } else {
+                let end = end_of(split_tokens.merged(i, i + split_idx));
+                // Estimated tokens are not exact, so only counted splits give the tokens of the chunk.
+                let tokens = match self.token_estimate {
+                    Some(_) => None,
+                    None => split_tokens.counted(i, i + split_idx),
+                };
+                self.push_chunk(
+                    root,
+                    buffer,
+                    CowChunk {
+                        text: Cow::Borrowed(&root[start..end]),
+                        start,
+                        end,
+                        tokens,
+                        boundary: chunk_boundary,
+                    },
+                    recursion_depth,
+                );
+                i += split_idx;
+                end
+            };
+
+            // If the separator is not whitespace and the split is not the last split, add the separator to the end of the last chunk if doing so would not cause it to exceed the chunk size otherwise add the splitter as a new chunk.
+            if separator.is_whitespace() {
+                continue;
+            }
+            if let Some(next_split) = split_tokens.get(i) {
+                // The separator is whatever lies between the splits, which may be a run of separators.
+                let separator_start = chunk_end;
+                let separator_end = offset_of(root, next_split);
+                // Opening quotes are held back for the chunk after them, with their closing quotes.
+                if self.quote_integrity && opens_quote(root, separator_start..separator_end) {
+                    let separator = CowChunk {
+                        text: Cow::Borrowed(&root[separator_start..separator_end]),
+                        start: separator_start,
+                        end: separator_end,
+                        tokens: None,
+                        boundary: separator,
+                    };
+                    buffer.pending_separator = Some((separator, recursion_depth));
+                    continue;
+                }
+                // A chunk is extended over the text up to the end of the separator if it ends where the separator starts, or always when chunking losslessly, so that it keeps borrowing the text, whitespace and all.
+                let lossless = self.lossless.get();
+                let extends = |last_chunk: &CowChunk| {
+                    lossless
+                        || matches!(last_chunk.text, Cow::Borrowed(_))
+                            && last_chunk.end == separator_start
+                };
+                let count_appended = |last_chunk: &CowChunk, scratch: &mut String| {
+                    if extends(last_chunk) {
+                        self.count_chunk_tokens(&root[last_chunk.start..separator_end])
+                    } else {
+                        scratch.clear();
+                        scratch.push_str(&last_chunk.text);
+                        scratch.push_str(&root[separator_start..separator_end]);
+                        self.count_chunk_tokens(scratch)
+                    }
+                };
+                let fitting_tokens = buffer
+                    .last_mut()
+                    .map(|last_chunk| count_appended(last_chunk, &mut scratch.text))
+                    .filter(|&n_tokens| n_tokens <= self.local_chunk_size());
+                match (buffer.last_mut(), fitting_tokens) {
+                    (Some(last_chunk), Some(n_tokens)) => {
+                        if extends(last_chunk) {
+                            last_chunk.text = Cow::Borrowed(&root[last_chunk.start..separator_end]);
+                        } else {
+                            last_chunk
+                                .text
+                                .to_mut()
+                                .push_str(&root[separator_start..separator_end]);
+                        }
+                        last_chunk.end = separator_end;
+                        last_chunk.tokens = Some(n_tokens);
+                    }
+                    _ => {
+                        let separator = CowChunk {
+                            text: Cow::Borrowed(&root[separator_start..separator_end]),
+                            start: separator_start,
+                            end: separator_end,
+                            tokens: None,
+                            boundary: separator,
+                        };
+                        match self.separator_attachment {
+                            Attachment::OwnChunk => buffer.push(separator, recursion_depth),
+                            Attachment::NextChunk => {
+                                buffer.pending_separator = Some((separator, recursion_depth))
+                            }
+                            Attachment::Drop => {}
+                        }
+                    }
+                }
+            }
+        }
+        match scratch.separator_tokens.get_mut(separator.text) {
+            Some(separator_tokens) => *separator_tokens = split_tokens.separator_tokens,
+            None => {
+                scratch
+                    .separator_tokens
+                    .insert(separator.text.to_string(), split_tokens.separator_tokens);
+            }
+        }
+        if recursion_depth > 0 {
+            buffer.discard_empty_last();
+        }
+    }
+
+    /// Chunks `text`, a slice of `root` without separators, by slicing it where chunks fit, without splitting it into characters.
+    ///
+    /// The text must be ASCII, so that every byte offset is a character boundary. The length of the first chunk is estimated from the tokens per byte of the whole text, and that of each later chunk from the chunk before it, then corrected by counting.
+    fn chunk_long_span<'a, F: FnMut(CowChunk<'a>)>(
+        &self,
+        root: &'a str,
+        text: &'a str,
+        recursion_depth: usize,
+        boundary: Separator<'a>,
+        buffer: &mut ChunkBuffer<'a, '_, F>,
+    ) {
+        let chunk_size = self.local_chunk_size();
+        let tokens = self.count_chunk_tokens(text).max(1);
+        let mut guess = (text.len() as f64 * chunk_size as f64 / tokens as f64) as usize;
+        let mut start = 0;
+        while start < text.len() && !(buffer.stop)() {
+            let fits =
+                |len: usize| self.count_chunk_tokens(&text[start..start + len]) <= chunk_size;
+            // A single character over the chunk size becomes a chunk of its own.
+            let len = largest_fitting(text.len() - start, guess, fits).max(1);
+            let chunk_start = offset_of(root, text) + start;
+            self.push_chunk(
+                root,
+                buffer,
+                CowChunk {
+                    text: Cow::Borrowed(&root[chunk_start..chunk_start + len]),
+                    start: chunk_start,
+                    end: chunk_start + len,
+                    tokens: None,
+                    boundary: match start + len == text.len() {
+                        true => boundary,
+                        false => NO_SEPARATOR,
+                    },
+                },
+                recursion_depth,
+            );
+            guess = len;
+            start += len;
+        }
+    }
+
+    /// Pushes a chunk of `root` into `buffer`, prepending the separator held back for it, if any, where the separator fits.
+    fn push_chunk<'a, F: FnMut(CowChunk<'a>)>(
+        &self,
+        root: &'a str,
+        buffer: &mut ChunkBuffer<'a, '_, F>,
+        chunk: CowChunk<'a>,
+        recursion_depth: usize,
+    ) {
+        let chunk = match buffer.pending_separator.take() {
+            Some((separator, separator_depth)) => {
+                let tokens = (separator.end == chunk.start)
+                    .then(|| self.count_chunk_tokens(&root[separator.start..chunk.end]))
+                    .filter(|&n_tokens| n_tokens <= self.local_chunk_size());
+                match tokens {
+                    Some(_) => CowChunk {
+                        text: Cow::Borrowed(&root[separator.start..chunk.end]),
+                        start: separator.start,
+                        end: chunk.end,
+                        tokens,
+                        boundary: chunk.boundary,
+                    },
+                    None => {
+                        buffer.push(separator, separator_depth);
+                        chunk
+                    }
+                }
+            }
+            None => chunk,
+        };
+        buffer.push(chunk, recursion_depth);
+    }
+
+    /// Merges first N splits into a chunk that has <= chunk_size tokens.
+    ///
+    /// Each split is counted once, and the boundary is estimated from those counts so that only merged chunks close to it are counted.
+    ///
+    /// Splits may be any strings, such as the `&str` splits of a [`Splitter`] or the `String` splits of a caller's own splitter, so the merging logic can be used on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `splits` - A slice of the splits to merge.
+    /// * `separator` - The separator used to split the text, which joins merged splits.
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing:
+    /// * The index merging stopped at (not inclusive).
+    /// * The merged text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+    /// let splits = vec!["The", "quick", "brown", "fox", "jumps", "over", "the", "lazy", "dog"];
+    /// let separator = " ";
+    /// let (split_idx, merged) = chunker.merge_splits(&splits, separator);
+    /// assert_eq!(split_idx, 4);
+    /// assert_eq!(merged, "The quick brown fox");
+    ///
+    /// let sentences: Vec<String> = vec!["It rained.".into(), "It poured.".into(), "It stopped.".into()];
+    /// let (split_idx, merged) = chunker.merge_splits(&sentences, " ");
+    /// assert_eq!(split_idx, 2);
+    /// assert_eq!(merged, "It rained. It poured.");
+    /// ```
+    pub fn merge_splits<S: AsRef<str>>(&self, splits: &[S], separator: &str) -> (usize, String) {
+        let count = |text: &str| self.count_chunk_tokens(text);
+        let mut split_tokens = SplitTokens::new(
+            splits.iter().map(AsRef::as_ref),
+            &count,
+            self.separator_tokens,
+        );
+        let mut merged: SmallVec<[String; MAX_PROBES]> = SmallVec::new();
+        let chunk_size = self.local_chunk_size();
+        let split_idx = self.fitting_splits(
+            &mut split_tokens,
+            0,
+            chunk_size,
+            self.probes(),
+            |candidates, counts| {
+                merged.resize(candidates.len(), String::new());
+                for (merged, splits) in merged.iter_mut().zip(candidates) {
+                    join_into(merged, splits, separator);
+                }
+                let texts: SmallVec<[&str; MAX_PROBES]> =
+                    merged.iter().map(String::as_str).collect();
+                self.count_candidates(&texts, counts);
+            },
+        );
+        let mut merged = String::new();
+        join_into(&mut merged, &splits[..split_idx], separator);
+        (split_idx, merged)
+    }
+
+    /// Chunks caller-provided segments, such as lines, sentences or transcript turns, packing as many consecutive segments as fit into each chunk, joined by `joiner`.
+    ///
+    /// The segments are chunked as one text, the segments joined by `joiner`, that is split into the segments first. Segments are merged like the splits of a text, with the joiner kept between segments in a chunk and dropped between chunks, and are never split further unless a segment alone is over the chunk size, in which case it is split with the splitter and merged like any other text. Options that apply to whole texts, such as [`Chunker::overlap`], [`Chunker::max_chunks`], [`Chunker::drop_chunks_below`] and [`Chunker::empty_chunks`], apply once to the chunks of all the segments. [`ChunkStrategy::HeadTail`] is not applied, so every segment is chunked.
+    ///
+    /// # Arguments
+    ///
+    /// * `segments` - The segments to chunk, in order.
+    /// * `joiner` - The string that joins consecutive segments in a chunk.
+    ///
+    /// # Returns
+    ///
+    /// A vector of strings representing the chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let turns = ["Alice: Hi there.", "Bob: Hello!", "Alice: How was the trip to the coast?", "Bob: Great."];
+    /// let chunks = chunker.chunk_segments(turns, "\n");
+    /// assert_eq!(chunks, vec!["Alice: Hi there.\nBob: Hello!", "Alice: How was the trip to", "the coast?", "Bob: Great."]);
+    /// ```
+    pub fn chunk_segments<'a>(
+        &self,
+        segments: impl IntoIterator<Item = &'a str>,
+        joiner: &str,
+    ) -> Vec<String> {
+        let mut text = String::new();
+        let mut ranges = Vec::new();
+        for (i, segment) in segments.into_iter().enumerate() {
+            if i > 0 {
+                text.push_str(joiner);
+            }
+            ranges.push(text.len()..text.len() + segment.len());
+            text.push_str(segment);
+        }
+        if ranges.is_empty() {
+            return Vec::new();
+        }
+        let segments = Segments {
+            splits: ranges.into_iter().map(|range| &text[range]).collect(),
+            // The joiner is never kept at the end of a chunk, as whitespace separators are not.
+            joiner: Separator {
+                text: joiner,
+                kind: SeparatorKind::None,
+            },
+        };
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(&text));
+        self.chunk_cow_until(&text, Some(&segments), &|| false, |chunk| {
+            chunks.push(chunk.text.into_owned())
+        });
+        chunks
+    }
+
+    /// Returns how many splits from `start` onwards can be merged into a chunk that has <= `chunk_size` tokens, given `count_merged`, which counts the tokens in each candidate of splits merged, `probes` candidates at a time.
+    ///
+    /// Splits are only taken until they are estimated not to fit, and the largest number of them estimated to fit is probed first, so usually only it and the split after it are counted. Should the estimates be off, the boundary is found by bisection. With several probes, each round probes several candidates around the estimate, or evenly between the bounds when bisecting.
+    fn fitting_splits<'a, I: Iterator<Item = &'a str>>(
+        &self,
+        split_tokens: &mut SplitTokens<'a, '_, I>,
+        start: usize,
+        chunk_size: usize,
+        probes: usize,
+        mut count_merged: impl FnMut(&[&[&'a str]], &mut SmallVec<[usize; MAX_PROBES]>),
+    ) -> usize {
+        if split_tokens.get(start).is_none() || split_tokens.split(start) > chunk_size {
+            return 0;
+        }
+
+        // `low` splits are known to fit in a chunk and `high` splits, once counted, are known not to.
+        let mut low = 1;
+        let mut high = None;
+        let mut estimated_rounds = 0;
+        let mut candidates: SmallVec<[usize; MAX_PROBES]> = SmallVec::new();
+        let mut counts: SmallVec<[usize; MAX_PROBES]> = SmallVec::new();
+        loop {
+            let taken = split_tokens.take_fitting(start, low + 1, chunk_size);
+            // Whether more splits than those taken fit is unknown until they are taken.
+            let mut upper = high.unwrap_or(taken + 1);
+            while upper - low > 1 {
+                candidates.clear();
+                if estimated_rounds < 2 {
+                    estimated_rounds += 1;
+                    let estimated = split_tokens.fitting(start, low, upper, chunk_size);
+                    // If only the known splits are estimated to fit, confirm the next split does not.
+                    let estimated = std::cmp::max(estimated, low + 1);
+                    // Probe the estimate, then the candidates nearest to it.
+                    for distance in 0..upper - low {
+                        // The bounds can be far apart, such as when many empty splits are estimated to fit.
+                        if candidates.len() == probes {
+                            break;
+                        }
+                        for candidate in [estimated + distance, estimated.wrapping_sub(distance)] {
+                            if candidates.len() < probes
+                                && low < candidate
+                                && candidate < upper
+                                && !candidates.contains(&candidate)
+                            {
+                                candidates.push(candidate);
+                            }
+                        }
+                    }
+                    candidates.sort_unstable();
+                } else {
+                    for i in 1..=probes {
+                        let candidate = low + (upper - low) * i / (probes + 1);
+                        if low < candidate && candidates.last() != Some(&candidate) {
+                            candidates.push(candidate);
+                        }
+                    }
+                    if candidates.is_empty() {
+                        candidates.push(low + 1);
+                    }
+                }
+
+                let merged: SmallVec<[&[&'a str]; MAX_PROBES]> = candidates
+                    .iter()
+                    .map(|&n_splits| split_tokens.merged(start, start + n_splits))
+                    .collect();
+                counts.clear();
+                count_merged(&merged, &mut counts);
+                drop(merged);
+                // Candidates are in increasing order, so those after one that does not fit do not either.
+                for (&n_splits, &n_tokens) in candidates.iter().zip(&counts) {
+                    split_tokens.observe(start, start + n_splits, n_tokens);
+                    if n_tokens > chunk_size {
+                        high = Some(n_splits);
+                        upper = n_splits;
+                        break;
+                    }
+                    low = n_splits;
+                }
+            }
+            if high.is_some() || split_tokens.exhausted {
+                return low;
+            }
+        }
+    }
+
+    /// Returns how many splits from `start` onwards, whose tokens `split_tokens` estimates with `token_estimate`, can be merged into a chunk that has <= chunk_size tokens, given `text_of`, which returns the text of merged splits.
+    ///
+    /// The chunk is found with estimated tokens, scaled by `scale`, the ratio of counted to estimated tokens in the last chunk found. Only the chunk found is counted, and should it not fit, it is shrunk by bisection.
+    fn verified_splits<'a, I: Iterator<Item = &'a str>>(
+        &self,
+        split_tokens: &mut SplitTokens<'a, '_, I>,
+        start: usize,
+        token_estimate: &TokenEstimate,
+        scale: &mut f64,
+        text_of: impl Fn(&[&'a str]) -> &'a str,
+    ) -> usize {
+        let chunk_size = self.local_chunk_size();
+        let estimated_chunk_size = (chunk_size as f64 / *scale) as usize;
+        let estimated = self.fitting_splits(
+            split_tokens,
+            start,
+            estimated_chunk_size,
+            1,
+            |candidates, counts| {
+                counts.extend(
+                    candidates
+                        .iter()
+                        .map(|splits| token_estimate.estimate(text_of(splits))),
+                );
+            },
+        );
+        if estimated == 0 {
+            return 0;
+        }
+
+        let text = text_of(split_tokens.merged(start, start + estimated));
+        let n_tokens = self.count_chunk_tokens(text);
+        let estimated_tokens = token_estimate.estimate(text);
+        if n_tokens > 0 && estimated_tokens > 0 {
+            *scale = n_tokens as f64 / estimated_tokens as f64;
+        }
+        if n_tokens <= chunk_size {
+            return estimated;
+        }
+
+        // `low` splits are known to fit in a chunk and `high` splits are known not to.
+        let (mut low, mut high) = (0, estimated);
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            let text = text_of(split_tokens.merged(start, start + mid));
+            if self.count_chunk_tokens(text) <= chunk_size {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    /// The number of candidate chunks counted at once.
+    fn probes(&self) -> usize {
+        self.concurrent_token_counter
+            .as_ref()
+            .map_or(1, |(_, probes)| *probes)
+    }
+
+    /// Counts the tokens in each text into `counts`, concurrently on the chunker's pool of probe workers if there is a concurrent token counter.
+    fn count_candidates(&self, texts: &[&str], counts: &mut SmallVec<[usize; MAX_PROBES]>) {
+        match &self.concurrent_token_counter {
+            Some((token_counter, probes)) if texts.len() > 1 => {
+                let pool = self
+                    .probe_pool
+                    .get_or_init(|| ProbePool::new(token_counter, *probes));
+                // Texts over the byte limit do not fit without being counted.
+                let start = counts.len();
+                counts.extend(texts.iter().map(|_| self.content_size() + 1));
+                pool.count(
+                    texts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, text)| !self.over_max_bytes(text)),
+                    &mut counts[start..],
+                );
+            }
+            _ => counts.extend(texts.iter().map(|text| self.count_chunk_tokens(text))),
+        }
+    }
+
+    /// Chunks the given text into segments based on the maximum number of tokens per chunk.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// 
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let chunks = chunker._chunk(text, 0);
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn chunk(&self, text: &str) -> Vec<String> {
+        self.chunk_with_capacity(text, self.estimated_chunks(text))
+    }
+
+    /// Chunks the given text like [`Chunker::chunk`], preallocating room for `expected_chunks` chunks.
+    ///
+    /// [`Chunker::chunk`] preallocates room for an estimate from the length of the text, assuming about four bytes per token. Where the number of chunks is better known, such as from previous runs over similar documents, a closer hint avoids reallocating the chunks of long texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `expected_chunks` - The number of chunks to preallocate room for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let chunks = chunker.chunk_with_capacity("The quick brown fox jumps over the lazy dog.", 3);
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn chunk_with_capacity(&self, text: &str, expected_chunks: usize) -> Vec<String> {
+        let mut chunks = Vec::with_capacity(expected_chunks);
+        self.chunk_cow(text, |chunk| chunks.push(chunk.text.into_owned()));
+        chunks
+    }
+
+    /// Chunks the given text like [`Chunker::chunk`], failing if it has more chunks than the limit set with [`Chunker::max_chunks`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks, or an error with the chunks before the limit if there are more.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).max_chunks(3);
+    /// let chunks = chunker.try_chunk("The quick brown fox jumps over the lazy dog.").unwrap();
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// let error = chunker
+    ///     .try_chunk("The quick brown fox jumps over the lazy dog while the cat sleeps in the sun.")
+    ///     .unwrap_err();
+    /// assert_eq!(error.to_string(), "the text has more than 3 chunks");
+    /// ```
+    pub fn try_chunk(&self, text: &str) -> Result<Vec<String>, TooManyChunks> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        match self.chunk_cow(text, |chunk| chunks.push(chunk.text.into_owned())) {
+            Some(max_chunks) => Err(TooManyChunks { max_chunks, chunks }),
+            None => Ok(chunks),
+        }
+    }
+
+    /// Chunks the given text and prepends `context` to each chunk, shrinking the room left for the text of each chunk so that chunks with their context still fit in the chunk size.
+    ///
+    /// This suits contextual retrieval, where each chunk is embedded with the title, section or source of its document. The tokens of `context` are counted once and reserved from each chunk; if a chunk with its context still counts more than the chunk size, as tokens at the join can merge, the text is chunked again with more reserved.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `context` - The string prepended to each chunk, including any separator from the chunk.
+    ///
+    /// # Returns
+    ///
+    /// The chunks, each starting with `context`. If `context` alone fills the chunk size, each chunk holds `context` and a single token of text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(6, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let chunks = chunker.chunk_with_context("The quick brown fox jumps over the lazy dog.", "Fables: ");
+    /// assert_eq!(
+    ///     chunks,
+    ///     vec!["Fables: The quick brown fox jumps", "Fables: over the lazy dog."]
+    /// );
+    /// ```
+    pub fn chunk_with_context(&self, text: &str, context: &str) -> Vec<String> {
+        self.chunk_framed(text, context, "")
+    }
+
+    /// Chunks the given text and wraps each chunk in `before` and `after`, reserving room for them from each chunk.
+    pub(crate) fn chunk_framed(&self, text: &str, before: &str, after: &str) -> Vec<String> {
+        let mut reserved: usize = [before, after]
+            .iter()
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| self.count_tokens(frame))
+            .sum();
+        loop {
+            let chunks: Vec<String> = {
+                let _reserve = Reserve::new(&self.reserved_tokens, reserved);
+                self.chunk(text)
+            }
+            .into_iter()
+            .map(|chunk| format!("{}{}{}", before, chunk, after))
+            .collect();
+            let frame_size = self.chunk_size.saturating_sub(self.special_tokens_overhead);
+            let overshoot = chunks
+                .iter()
+                .map(|chunk| self.count_tokens(chunk).saturating_sub(frame_size))
+                .max()
+                .unwrap_or(0);
+            if overshoot == 0 || reserved >= frame_size {
+                return chunks;
+            }
+            reserved += overshoot;
+        }
+    }
+
+    /// Chunks the given text, passing each chunk to `sink` as soon as it is final.
+    ///
+    /// Unlike [`Chunker::chunk`], the chunks are not collected, so memory used for chunks stays bounded by the chunk size regardless of the length of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `sink` - A function called with each chunk, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let mut offsets = Vec::new();
+    /// chunker.chunk_to(text, |chunk| offsets.push((chunk.start, chunk.end)));
+    /// assert_eq!(offsets, vec![(0, 19), (20, 39), (40, 44)]);
+    /// ```
+    pub fn chunk_to<F: FnMut(Chunk)>(&self, text: &str, mut sink: F) {
+        self.chunk_cow(text, |chunk| sink(chunk.into()));
+    }
+
+    /// Chunks the given text like [`Chunker::chunk_to`], stopping early if `cancellation` is cancelled, such as when the client of a web handler disconnects.
+    ///
+    /// Cancellation is checked before each split, so chunking stops soon after, and no chunks are passed to `sink` once it is cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `cancellation` - The signal to stop chunking, such as an [`AtomicBool`] set from another thread.
+    /// * `sink` - A function called with each chunk, in order.
+    ///
+    /// # Returns
+    ///
+    /// An error if chunking was cancelled before the whole text was chunked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let cancelled = AtomicBool::new(false);
+    /// let mut chunks = Vec::new();
+    /// let result = chunker.chunk_to_cancellable(text, &cancelled, |chunk| {
+    ///     chunks.push(chunk.text);
+    ///     cancelled.store(true, Ordering::Relaxed);
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(chunks, vec!["The quick brown fox"]);
+    /// ```
+    pub fn chunk_to_cancellable<F: FnMut(Chunk)>(
+        &self,
+        text: &str,
+        cancellation: &impl Cancellation,
+        mut sink: F,
+    ) -> Result<(), Cancelled> {
+        let cancelled = || cancellation.is_cancelled();
+        self.chunk_cow_until(text, None, &cancelled, |chunk| sink(chunk.into()));
+        match cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Chunks the given text, sending each chunk to a channel as soon as it is final, so that consumers on other threads can embed or upload chunks while the text is still being chunked.
+    ///
+    /// With a bounded channel, chunking waits whenever the channel is full, so a producer never gets more than the channel's capacity ahead of its consumers.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `sender` - The sending half of the channel, such as a [`Sender`] or [`SyncSender`] of chunks.
+    ///
+    /// # Returns
+    ///
+    /// An error with the first chunk that could not be sent if the receiver was dropped, after which no more chunks are sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc::sync_channel;
+    /// use std::thread;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let (sender, receiver) = sync_channel(2);
+    /// let producer = thread::spawn(move || {
+    ///     let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    ///     chunker.chunk_to_channel("The quick brown fox jumps over the lazy dog.", &sender)
+    /// });
+    /// let chunks: Vec<String> = receiver.iter().map(|chunk| chunk.text).collect();
+    /// assert!(producer.join().unwrap().is_ok());
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn chunk_to_channel(
+        &self,
+        text: &str,
+        sender: &impl ChunkSender,
+    ) -> Result<(), SendError<Chunk>> {
+        let mut result = Ok(());
+        self.chunk_cow(text, |chunk| {
+            if result.is_ok() {
+                result = sender.send_chunk(chunk.into());
+            }
+        });
+        result
+    }
+
+    /// Chunks the given text into segments that borrow from it wherever possible.
+    ///
+    /// Chunks are slices of the text unless a separator was appended to a chunk it does not directly follow, which never happens with [`Chunker::lossless`], so this avoids copying the text that [`Chunker::chunk`] does.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let chunks = chunker.chunk_borrowed(text);
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// assert!(matches!(chunks[0], Cow::Borrowed(_)));
+    /// ```
+    pub fn chunk_borrowed<'a>(&self, text: &'a str) -> Vec<Cow<'a, str>> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_cow(text, |chunk| chunks.push(chunk.text));
+        chunks
+    }
+
+    /// Chunks the given text into `chunks`, reusing its chunks' allocations.
+    ///
+    /// The vector is cleared before chunking, and the chunks it held are overwritten rather than reallocated, so chunking many documents into the same vector keeps allocations to a minimum.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `chunks` - The vector to replace the contents of with the chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1));
+    /// let mut chunks = Vec::new();
+    /// for text in ["The quick brown fox jumps over the lazy dog.", "Hello, world!"] {
+    ///     chunker.chunk_into(text, &mut chunks);
+    /// }
+    /// assert_eq!(chunks.len(), 1);
+    /// assert_eq!(chunks[0].text, "Hello, world!");
+    /// ```
+    pub fn chunk_into(&self, text: &str, chunks: &mut Vec<Chunk>) {
+        let mut len = 0;
+        self.chunk_cow(text, |chunk| {
+            match chunks.get_mut(len) {
+                Some(reused) => {
+                    reused.text.clear();
+                    reused.text.push_str(&chunk.text);
+                    reused.start = chunk.start;
+                    reused.end = chunk.end;
+                }
+                None => chunks.push(chunk.into()),
+            }
+            len += 1;
+        });
+        chunks.truncate(len);
+    }
+
+    /// Chunks the given text into chunks along with the number of tokens in each.
+    ///
+    /// The tokens of most chunks are counted while finding where they end, so those counts are reused rather than counted again. Counts are exact either way: each is what the token counter returns for the chunk's text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the chunks and their numbers of tokens.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let chunks = chunker.chunk_with_tokens("The quick brown fox jumps over the lazy dog.");
+    /// let tokens: Vec<usize> = chunks.iter().map(|(_, tokens)| *tokens).collect();
+    /// assert_eq!(tokens, vec![4, 4, 1]);
+    /// assert_eq!(chunks[2].0.text, "dog.");
+    /// ```
+    pub fn chunk_with_tokens(&self, text: &str) -> Vec<(Chunk, usize)> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_cow(text, |chunk| {
+            let tokens = chunk
+                .tokens
+                .unwrap_or_else(|| self.count_tokens(&chunk.text));
+            chunks.push((chunk.into(), tokens));
+        });
+        chunks
+    }
+
+    /// Chunks the given text into chunks along with the kind of separator each ends at.
+    ///
+    /// A chunk ends at the separator it was split from the text after, including any non-whitespace separator appended to it. The last chunk of the text, and chunks cut between words, grapheme clusters or characters, end at [`SeparatorKind::None`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the chunks and the kinds of separator they end at.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::splitter::SeparatorKind;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let chunks = chunker.chunk_with_boundaries("Hello there, friend.\n\nThe quick brown fox jumps.");
+    /// let boundaries: Vec<SeparatorKind> = chunks.iter().map(|(_, kind)| *kind).collect();
+    /// assert_eq!(
+    ///     boundaries,
+    ///     vec![SeparatorKind::ParagraphBreak, SeparatorKind::Whitespace, SeparatorKind::None]
+    /// );
+    /// assert_eq!(chunks[1].0.text, "The quick brown fox");
+    /// ```
+    pub fn chunk_with_boundaries(&self, text: &str) -> Vec<(Chunk, SeparatorKind)> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_cow(text, |chunk| {
+            let boundary = chunk.boundary.kind;
+            chunks.push((chunk.into(), boundary));
+        });
+        chunks
+    }
+
+    /// Chunks the given text like [`Chunker::chunk`], along with the separator each chunk ends at and its number of tokens, if they were counted while chunking.
+    pub(crate) fn chunk_with_separators<'a>(
+        &self,
+        text: &'a str,
+    ) -> Vec<(String, Separator<'a>, Option<usize>)> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_cow(text, |chunk| {
+            chunks.push((chunk.text.into_owned(), chunk.boundary, chunk.tokens));
+        });
+        chunks
+    }
+
+    /// Chunks the given text like [`Chunker::chunk_to`], linking each chunk to its document and to the chunks before and after it with [`link_chunks`].
+    ///
+    /// # Arguments
+    ///
+    /// * `doc_id` - The ID of the document the text is of.
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The linked chunks, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let chunks = chunker.chunk_linked("fox", "The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks[1].chunk.text, "jumps over the lazy");
+    /// assert_eq!(chunks[1].prev_id.as_deref(), Some("fox-0"));
+    /// assert_eq!(chunks[1].next_id.as_deref(), Some("fox-2"));
+    /// assert_eq!(chunks[1].document_id, "fox");
+    /// ```
+    pub fn chunk_linked(&self, doc_id: &str, text: &str) -> Vec<LinkedChunk> {
+        let mut chunks = Vec::with_capacity(self.estimated_chunks(text));
+        self.chunk_to(text, |chunk| chunks.push(chunk));
+        link_chunks(doc_id, chunks)
+    }
+
+    /// Runs `f` with the chunker chunking losslessly, whatever it is set to.
+    pub(crate) fn with_lossless<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _lossless = ForceLossless::new(&self.lossless);
+        f()
+    }
+
+    /// Runs `f` with the scratch buffers of previous calls, or new ones if they are in use.
+    fn with_scratch<R>(&self, f: impl FnOnce(&mut Scratch) -> R) -> R {
+        match self.scratch.try_borrow_mut() {
+            Ok(mut scratch) => {
+                scratch.separator_tokens.clear();
+                scratch.estimate_scale = 1.0;
+                f(&mut scratch)
+            }
+            Err(_) => f(&mut Scratch::default()),
+        }
+    }
+
+    /// Chunks the given text, passing each chunk to `sink` as soon as it is final, and stopping once there are more chunks than the limit.
+    ///
+    /// Returns the limit if chunking stopped because of it.
+    fn chunk_cow<'a>(&self, text: &'a str, sink: impl FnMut(CowChunk<'a>)) -> Option<usize> {
+        self.chunk_cow_until(text, None, &|| false, sink)
+    }
+}
+
+/// Caller-provided segments of a text, which is split into them rather than by the splitter at the top level.
+struct Segments<'a> {
+    /// The segments, each a slice of the text.
+    splits: Vec<&'a str>,
+    /// The separator that joins consecutive segments in the text.
+    joiner: Separator<'a>,
+}
+
+/// A chunk whose text borrows from the source text where possible.
+struct CowChunk<'a> {
+    text: Cow<'a, str>,
+    start: usize,
+    end: usize,
+    /// The number of tokens in the text, if they were counted while chunking.
+    tokens: Option<usize>,
+    /// The separator the chunk ends at.
+    boundary: Separator<'a>,
+}
+
+impl From<CowChunk<'_>> for Chunk {
+    fn from(chunk: CowChunk<'_>) -> Self {
+        Chunk {
+            text: chunk.text.into_owned(),
+            start: chunk.start,
+            end: chunk.end,
+        }
+    }
+}
+
+/// Tokens reserved from the content size of each chunk, released when dropped, so that they are no longer reserved after chunking even if the token counter panics.
+struct Reserve<'a>(&'a Cell<usize>);
+
+impl<'a> Reserve<'a> {
+    fn new(reserved_tokens: &'a Cell<usize>, reserved: usize) -> Self {
+        reserved_tokens.set(reserved);
+        Reserve(reserved_tokens)
+    }
+}
+
+impl Drop for Reserve<'_> {
+    fn drop(&mut self) {
+        self.0.set(0);
+    }
+}
+
+/// Lossless chunking forced on, restoring the chunker's own setting when dropped, even if the token counter panics.
+struct ForceLossless<'a> {
+    lossless: &'a Cell<bool>,
+    was_lossless: bool,
+}
+
+impl<'a> ForceLossless<'a> {
+    fn new(lossless: &'a Cell<bool>) -> Self {
+        ForceLossless {
+            lossless,
+            was_lossless: lossless.replace(true),
+        }
+    }
+}
+
+impl Drop for ForceLossless<'_> {
+    fn drop(&mut self) {
+        self.lossless.set(self.was_lossless);
+    }
+}
+
+/// A pool of worker threads that count candidate chunks with a concurrent token counter, started the first time the chunker counts several candidates at once and kept until it is dropped.
+struct ProbePool {
+    /// The sending half of the channel of candidates to count, with their indices, which is closed to stop the workers.
+    jobs: Option<Sender<(usize, String)>>,
+    /// The receiving half of the channel of counts, or panics of the token counter, with the indices of their candidates.
+    results: Receiver<(usize, std::thread::Result<usize>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ProbePool {
+    /// Starts `probes` workers counting candidates with `token_counter`.
+    fn new(token_counter: &ConcurrentTokenCounter, probes: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<(usize, String)>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = mpsc::channel();
+        let workers = (0..probes)
+            .map(|_| {
+                let token_counter = Arc::clone(token_counter);
+                let jobs = Arc::clone(&job_receiver);
+                let results = result_sender.clone();
+                std::thread::spawn(move || loop {
+                    let job = jobs.lock().unwrap().recv();
+                    let Ok((index, text)) = job else { break };
+                    let count = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        token_counter(&text)
+                    }));
+                    if results.send((index, count)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        ProbePool {
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+
+    /// Counts the tokens in each indexed text into `counts` at its index, waiting for every count before resuming the first panic of the token counter, if any.
+    fn count<'a>(&self, texts: impl Iterator<Item = (usize, &'a &'a str)>, counts: &mut [usize]) {
+        let jobs = self.jobs.as_ref().expect("probe workers are running");
+        let mut pending = 0;
+        for (index, text) in texts {
+            jobs.send((index, text.to_string()))
+                .expect("probe workers are running");
+            pending += 1;
+        }
+        let mut panic = None;
+        for _ in 0..pending {
+            let (index, count) = self.results.recv().expect("probe workers are running");
+            match count {
+                Ok(count) => counts[index] = count,
+                Err(payload) => {
+                    panic.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = panic {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for ProbePool {
+    fn drop(&mut self) {
+        // Closing the channel of candidates stops the workers, which are idle between counts.
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Buffers reused across the recursion of a chunking call, and kept for the next call.
+struct Scratch {
+    /// The estimated number of tokens each separator adds to merged splits, as learned from previously merged splits.
+    separator_tokens: HashMap<String, f64>,
+    /// Text whose tokens are being counted.
+    text: String,
+    /// The ratio of counted to estimated tokens in the last chunk found with a token estimate.
+    estimate_scale: f64,
+}
+
+impl Default for Scratch {
+    fn default() -> Self {
+        Scratch {
+            separator_tokens: HashMap::new(),
+            text: String::new(),
+            estimate_scale: 1.0,
+        }
+    }
+}
+
+/// The number of splits held without allocating, which covers most texts split at each level of recursion.
+const INLINE_SPLITS: usize = 32;
+
+/// The splits of a text, taken as they are needed, and their token counts, for estimating how many tokens merged splits have without counting them.
+struct SplitTokens<'a, 't, I: Iterator<Item = &'a str>> {
+    splits: I,
+    token_counter: &'t dyn Fn(&str) -> usize,
+    /// Whether all splits have been taken.
+    exhausted: bool,
+    /// The index of the first split that is still held.
+    offset: usize,
+    /// The splits taken that are still held, from split `offset` onwards.
+    held: SmallVec<[&'a str; INLINE_SPLITS]>,
+    /// The number of tokens in each held split.
+    counts: SmallVec<[usize; INLINE_SPLITS]>,
+    /// The total number of tokens in the held splits before index `i`, at index `i`, saturating at `usize::MAX`.
+    prefix_sums: SmallVec<[usize; INLINE_SPLITS + 1]>,
+    /// The estimated number of tokens each separator adds to merged splits, last observed when they were counted. This may be negative, as tokens can span splits.
+    separator_tokens: f64,
+    /// The start, end and number of tokens of the merged splits observed, from the first split still needed onwards.
+    counted: SmallVec<[(usize, usize, usize); MAX_PROBES]>,
+}
+
+impl<'a, 't, I: Iterator<Item = &'a str>> SplitTokens<'a, 't, I> {
+    fn new(splits: I, token_counter: &'t dyn Fn(&str) -> usize, separator_tokens: f64) -> Self {
+        SplitTokens {
+            splits,
+            token_counter,
+            exhausted: false,
+            offset: 0,
+            held: SmallVec::new(),
+            counts: SmallVec::new(),
+            prefix_sums: smallvec![0],
+            separator_tokens,
+            counted: SmallVec::new(),
+        }
+    }
+
+    /// Takes and counts the next split, returning whether there was one.
+    fn take(&mut self) -> bool {
+        match self.splits.next() {
+            Some(split) => {
+                let count = (self.token_counter)(split);
+                let total = self.prefix_sums[self.held.len()].saturating_add(count);
+                self.held.push(split);
+                self.counts.push(count);
+                self.prefix_sums.push(total);
+                true
+            }
+            None => {
+                self.exhausted = true;
+                false
+            }
+        }
+    }
+
+    /// The number of splits taken.
+    fn taken(&self) -> usize {
+        self.offset + self.held.len()
+    }
+
+    /// Returns split `i`, taking the splits up to it if they have not been taken yet.
+    fn get(&mut self, i: usize) -> Option<&'a str> {
+        while self.taken() <= i {
+            if !self.take() {
+                return None;
+            }
+        }
+        Some(self.held[i - self.offset])
+    }
+
+    /// Stops holding the splits before split `i`, once they make up at least half of those held, and forgets the counts of merged splits starting before it.
+    fn release_before(&mut self, i: usize) {
+        self.counted.retain(|&mut (start, _, _)| start >= i);
+        let released = i - self.offset;
+        if released > 0 && released * 2 >= self.held.len() {
+            self.held.drain(..released);
+            self.counts.drain(..released);
+            // Sums that saturated may not once the released splits are left out, so they are summed again.
+            self.prefix_sums.truncate(1);
+            for &count in &self.counts {
+                let total = self.prefix_sums[self.prefix_sums.len() - 1].saturating_add(count);
+                self.prefix_sums.push(total);
+            }
+            self.offset = i;
+        }
+    }
+
+    /// The held splits `start..end`.
+    fn merged(&self, start: usize, end: usize) -> &[&'a str] {
+        &self.held[start - self.offset..end - self.offset]
+    }
+
+    /// The number of tokens in held splits `start..end`, counted individually, saturating at `usize::MAX`.
+    fn sum(&self, start: usize, end: usize) -> usize {
+        match self.prefix_sums[end - self.offset] {
+            usize::MAX => self.counts[start - self.offset..end - self.offset]
+                .iter()
+                .fold(0, |sum, &count| sum.saturating_add(count)),
+            total => total - self.prefix_sums[start - self.offset],
+        }
+    }
+
+    /// The number of tokens in held split `i`.
+    fn split(&self, i: usize) -> usize {
+        self.counts[i - self.offset]
+    }
+
+    /// The number of tokens in held splits `start..end` merged, if they were counted alone or observed.
+    fn counted(&self, start: usize, end: usize) -> Option<usize> {
+        match end - start {
+            1 => Some(self.split(start)),
+            _ => self
+                .counted
+                .iter()
+                .rev()
+                .find(|&&(counted_start, counted_end, _)| {
+                    (counted_start, counted_end) == (start, end)
+                })
+                .map(|&(_, _, n_tokens)| n_tokens),
+        }
+    }
+
+    /// The estimated number of tokens in held splits `start..end` merged.
+    fn estimate(&self, start: usize, end: usize) -> f64 {
+        let separators = end.saturating_sub(start + 1);
+        self.sum(start, end) as f64 + self.separator_tokens * separators as f64
+    }
+
+    /// Updates the separator estimate with the number of tokens counted in held splits `start..end` merged, remembering the count.
+    fn observe(&mut self, start: usize, end: usize, n_tokens: usize) {
+        self.counted.push((start, end, n_tokens));
+        let separators = end.saturating_sub(start + 1);
+        if separators > 0 {
+            let split_tokens = self.sum(start, end);
+            self.separator_tokens = (n_tokens as f64 - split_tokens as f64) / separators as f64;
+        }
+    }
+
+    /// Updates the separator estimate with the number of tokens counted in held splits `start..end` merged with the separator after them.
+    fn observe_separated(&mut self, start: usize, end: usize, n_tokens: usize) {
+        let split_tokens = self.sum(start, end);
+        self.separator_tokens = (n_tokens as f64 - split_tokens as f64) / (end - start) as f64;
+    }
+
+    /// Takes splits until at least `min_splits` splits from `start` are held and they are estimated to have more than `chunk_size` tokens merged, or until none are left, returning the number of splits held from `start`.
+    fn take_fitting(&mut self, start: usize, min_splits: usize, chunk_size: usize) -> usize {
+        while self.taken() - start < min_splits
+            || self.estimate(start, self.taken()) <= chunk_size as f64
+        {
+            if !self.take() {
+                break;
+            }
+        }
+        self.taken() - start
+    }
+
+    /// The largest number of held splits from `start`, between `low` and `high` (not inclusive), estimated to fit in `chunk_size` tokens.
+    fn fitting(&self, start: usize, mut low: usize, mut high: usize, chunk_size: usize) -> usize {
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if self.estimate(start, start + mid) > chunk_size as f64 {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        low
+    }
+}
+
+/// Returns the largest length up to `max_len` for which `fits` returns true, or 0 if none does, given that it does for every length up to the largest.
+///
+/// The search starts at `guess` and gallops away from it with doubling steps before bisecting, so a close guess takes few calls.
+fn largest_fitting(max_len: usize, guess: usize, fits: impl Fn(usize) -> bool) -> usize {
+    let guess = guess.clamp(1, max_len);
+    let mut step = (guess / 16).max(1);
+    // `low` is known to fit and `high` known not to.
+    let (mut low, mut high) = match fits(guess) {
+        true => {
+            let mut low = guess;
+            loop {
+                if low == max_len {
+                    return max_len;
+                }
+                let probe = (low + step).min(max_len);
+                if !fits(probe) {
+                    break (low, probe);
+                }
+                low = probe;
+                step *= 2;
+            }
+        }
+        false => {
+            let mut high = guess;
+            loop {
+                let probe = high.saturating_sub(step);
+                if probe == 0 {
+                    break (0, high);
+                }
+                if fits(probe) {
+                    break (probe, high);
+                }
+                high = probe;
+                step *= 2;
+            }
+        }
+    };
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        match fits(mid) {
+            true => low = mid,
+            false => high = mid,
+        }
+    }
+    low
+}
+
+/// Returns whether a chunk of `splits` ends a sentence, with a sentence terminator followed by any closing quotes and brackets.
+pub(crate) fn ends_sentence(splits: &[&str]) -> bool {
+    splits
+        .last()
+        .and_then(|split| split.trim_end_matches(is_closing).chars().next_back())
+        .is_some_and(is_sentence_terminator)
+}
+
+/// Returns whether `c` ends a sentence: a full stop, question mark or exclamation mark, Latin, full-width or Arabic.
+fn is_sentence_terminator(c: char) -> bool {
+    matches!(c, '.' | '?' | '!' | '。' | '！' | '？' | '؟' | '۔')
+}
+
+/// Returns whether `c` closes a quote or bracket, which may follow the punctuation ending a sentence or clause.
+fn is_closing(c: char) -> bool {
+    matches!(c, ')' | ']' | '”' | '’' | '\'' | '"' | '」' | '』')
+}
+
+/// Returns whether the separator at `range` of `text` opens a quote, which straight quotes only do after whitespace or at the start of the text.
+fn opens_quote(text: &str, range: Range<usize>) -> bool {
+    match &text[range.clone()] {
+        "“" | "‘" | "«" | "「" | "『" => true,
+        "\"" | "'" => text[..range.start]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace),
+        _ => false,
+    }
+}
+
+/// Returns the byte offsets within `range` of `text` at which sentences start, in order.
+///
+/// A sentence starts at the start of the text, and after a sentence terminator and any closing quotes and brackets, once followed by whitespace unless the terminator is full-width.
+pub(crate) fn sentence_starts(
+    text: &str,
+    range: Range<usize>,
+) -> impl DoubleEndedIterator<Item = usize> {
+    let mut starts = Vec::new();
+    if range.start == 0 {
+        starts.push(0);
+    }
+    // Scanning from the punctuation before the range finds a sentence starting at its start.
+    let scan_start = text[..range.start]
+        .trim_end_matches(|c: char| c.is_whitespace() || is_closing(c))
+        .char_indices()
+        .next_back()
+        .map_or(0, |(i, _)| i);
+    let mut chars = text[scan_start..range.end].char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if !is_sentence_terminator(c) {
+            continue;
+        }
+        let full_width = matches!(c, '。' | '！' | '？');
+        while chars.next_if(|&(_, c)| is_closing(c)).is_some() {}
+        let mut spaced = false;
+        while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {
+            spaced = true;
+        }
+        if let Some(&(i, _)) = chars.peek().filter(|_| spaced || full_width) {
+            starts.push(scan_start + i);
+        }
+    }
+    starts.into_iter()
+}
+
+/// Replaces the contents of `merged` with `splits` joined by `separator`.
+fn join_into<S: AsRef<str>>(merged: &mut String, splits: &[S], separator: &str) {
+    merged.clear();
+    for (i, split) in splits.iter().enumerate() {
+        if i > 0 {
+            merged.push_str(separator);
+        }
+        merged.push_str(split.as_ref());
+    }
+}
+
+/// Returns the byte offset of `sub`, a slice of `root`, within `root`.
+fn offset_of(root: &str, sub: &str) -> usize {
+    sub.as_ptr() as usize - root.as_ptr() as usize
+}
+
+
+#[cfg(test)]
+pub(crate) mod chunker_tests {
+    use super::*;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    #[cfg(feature = "rust_tokenizers")]
+    use rust_tokenizers::tokenizer::{RobertaTokenizer, Tokenizer};
+
+    fn get_data_path() -> PathBuf {
+        PathBuf::from(std::env::var("DATA_DIR").unwrap_or_else(|_| ".".to_string()))
+    }
+
+    fn get_roberta_vocab_path() -> PathBuf {
+        get_data_path().join("roberta-base-vocab.json")
+    }
+
+    fn get_roberta_merges_path() -> PathBuf {
+        get_data_path().join("roberta-base-merges.txt")
+    }
+
+    fn get_gutenberg_path() -> PathBuf {
+        get_data_path().join("gutenberg")
+    }
+
+    fn get_gutenberg_corpus_path(corpus_filename: &str) -> PathBuf {
+        get_gutenberg_path().join(corpus_filename)
+    }
+
+    fn read_gutenberg_corpus(corpus_filename: &str) -> String {
+        let mut file = std::fs::File::open(get_gutenberg_corpus_path(corpus_filename))
+            .expect("Error opening file");
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer).expect("Error reading file");
+        String::from_utf8_lossy(&buffer).to_string()
+    }
+
+    /// The configurations of the chunker that properties of its chunks, such as covering the text, must hold under.
+    pub(crate) const CONFIGS: &[fn(Chunker) -> Chunker] = &[
+        |chunker| chunker,
+        |chunker| chunker.overlap(3),
+        |chunker| chunker.overlap(3).sentence_overlap(true),
+        |chunker| chunker.merge_strategy(MergeStrategy::Optimal),
+        |chunker| chunker.merge_strategy(MergeStrategy::FewestChunks),
+        |chunker| chunker.estimate_tokens(TokenEstimate::CharsPerToken(1.5)),
+        |chunker| chunker.separator_attachment(Attachment::NextChunk),
+        |chunker| chunker.separator_attachment(Attachment::Drop),
+        |chunker| chunker.quote_integrity(true),
+        |chunker| chunker.sentence_backoff(2),
+    ];
+
+    /// Calls `check` with a chunker of each of `chunk_sizes` under each of [`CONFIGS`].
+    pub(crate) fn for_each_config(
+        chunk_sizes: &[usize],
+        token_counter: fn(&str) -> usize,
+        mut check: impl FnMut(Chunker),
+    ) {
+        for configure in CONFIGS {
+            for &chunk_size in chunk_sizes {
+                check(configure(Chunker::new(chunk_size, token_counter)));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rust_tokenizers")]
+    fn test_chunk_rust_tokenizers() {
+        let tokenizer = RobertaTokenizer::from_file(
+            get_roberta_vocab_path(),
+            get_roberta_merges_path(),
+            false,
+            false,
+        )
+        .expect("Error loading tokenizer");
+
+        let token_counter = Box::new(move |s: &str| tokenizer.tokenize(s).len());
+        let chunker = Chunker::new(10, token_counter);
+        let text = "The quick brown fox jumps over the lazy dog.\n\nThe subject is\n\t- \"The quick brown fox\"\n\t- \"jumps over\"\n\t- \"the lazy dog\"";
+        println!("Text: {}", text);
+        let chunks = chunker.chunk(text);
+        assert_eq!(
+            chunks,
+            vec![
+                "The quick brown fox jumps over the lazy dog.",
+                "The subject is",
+                "\t- \"The quick brown fox\"",
+                "\t- \"jumps over\"",
+                "\t- \"the lazy dog\"",
+            ]
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "rust_tokenizers")]
+    fn test_chunk_rust_tokenizers_gutenberg_austen_emma() {
+        let tokenizer = RobertaTokenizer::from_file(
+            get_roberta_vocab_path(),
+            get_roberta_merges_path(),
+            false,
+            false,
+        )
+        .expect("Error loading tokenizer");
+
+        let token_counter = Box::new(move |s: &str| tokenizer.tokenize(s).len());
+        let chunker = Chunker::new(10, token_counter);
+        let text = read_gutenberg_corpus("austen-emma.txt");
+        let chunks = chunker.chunk(&text);
+        assert!(!chunks.is_empty());
+        assert!(chunker.violations(&chunks).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "rust_tokenizers")]
+    fn test_chunk_rust_tokenizers_gutenberg_milton_paradise() {
+        let tokenizer = RobertaTokenizer::from_file(
+            get_roberta_vocab_path(),
+            get_roberta_merges_path(),
+            false,
+            false,
+        )
+        .expect("Error loading tokenizer");
+
+        let token_counter = Box::new(move |s: &str| tokenizer.tokenize(s).len());
+        let chunker = Chunker::new(10, token_counter);
+        let text = read_gutenberg_corpus("milton-paradise.txt");
+        let chunks = chunker.chunk(&text);
+        assert!(!chunks.is_empty());
+        assert!(chunker.violations(&chunks).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "rust_tokenizers")]
+    fn test_chunk_rust_tokenizers_gutenberg_shakespeare_hamlet() {
+        let tokenizer = RobertaTokenizer::from_file(
+            get_roberta_vocab_path(),
+            get_roberta_merges_path(),
+            false,
+            false,
+        )
+        .expect("Error loading tokenizer");
+
+        let token_counter = Box::new(move |s: &str| tokenizer.tokenize(s).len());
+        let chunker = Chunker::new(10, token_counter);
+        let text = read_gutenberg_corpus("shakespeare-hamlet.txt");
+        let chunks = chunker.chunk(&text);
+        assert!(!chunks.is_empty());
+        assert!(chunker.violations(&chunks).is_empty());
+    }
+
+    #[test]
+    fn test_merge_splits_simple() {
+        let chunker = Chunker::new(
+            2,
+            Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1),
+        );
+        let splits = vec!["Hello", "World", "Goodbye", "World"];
+        let separator = " ";
+        let (split_idx, merged) = chunker.merge_splits(&splits, separator);
+        assert_eq!(split_idx, 2);
+        assert_eq!(merged, "Hello World");
+
+        let (split_idx, merged) = chunker.merge_splits(splits.get(split_idx..).unwrap(), separator);
+        assert_eq!(split_idx, 2);
+        assert_eq!(merged, "Goodbye World");
+    }
+
+    #[test]
+    fn test_merge_splits_uneven() {
+        let chunker = Chunker::new(
+            4,
+            Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1),
+        );
+        let splits = vec![
+            "The", "quick", "brown", "fox", "jumps", "over", "the", "lazy", "dog",
+        ]; // 9 tokens
+        let separator = " ";
+        let (split_idx, merged) = chunker.merge_splits(&splits, separator);
+        assert_eq!(split_idx, 4);
+        assert_eq!(merged, "The quick brown fox");
+
+        let (split_idx_2, merged) =
+            chunker.merge_splits(splits.get(split_idx..).unwrap(), separator);
+        assert_eq!(split_idx_2, 4);
+        assert_eq!(merged, "jumps over the lazy");
+
+        let (split_idx_3, merged) =
+            chunker.merge_splits(splits.get(split_idx + split_idx_2..).unwrap(), separator);
+        assert_eq!(split_idx_3, 1);
+        assert_eq!(merged, "dog");
+    }
+
+    #[test]
+    fn test_merge_splits_fits_chunk_size() {
+        // Separators add tokens when counting characters, so the splits' token counts alone overestimate what fits.
+        let chunker = Chunker::new(10, Box::new(|s: &str| s.chars().count()));
+        let splits = vec!["The", "quick", "brown", "fox", "jumps"];
+        let (split_idx, merged) = chunker.merge_splits(&splits, " ");
+        assert_eq!(split_idx, 2);
+        assert_eq!(merged, "The quick");
+
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        for chunk in chunker.chunk(&text) {
+            assert!(chunk.chars().count() <= 10, "{:?}", chunk);
+        }
+
+        // A split that does not fit on its own is not merged.
+        let (split_idx, merged) = chunker.merge_splits(&["a very long split"], " ");
+        assert_eq!(split_idx, 0);
+        assert_eq!(merged, "");
+    }
+
+    #[test]
+    fn test_merge_splits_token_counter_calls() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter_calls = Rc::clone(&calls);
+        let chunker = Chunker::new(
+            64,
+            Box::new(move |s: &str| {
+                counter_calls.set(counter_calls.get() + 1);
+                s.split_whitespace().count()
+            }),
+        );
+        let text = "word ".repeat(6400);
+        let chunks = chunker.chunk(text.trim_end());
+        assert_eq!(chunks.len(), 100);
+        // Each split is counted once, plus about two counts to verify each chunk.
+        assert!(calls.get() <= 6400 + 2 * 100, "{} calls", calls.get());
+    }
+
+    #[test]
+    fn test_chunk_with_tokens() {
+        use std::rc::Rc;
+
+        let text = "The quick brown fox jumps. Over the lazy dog, it leaps!\n\n“Pack my box,” she said; five dozen liquor jugs.";
+        let chars = |s: &str| s.chars().count();
+        for_each_config(&[4, 9, 16, 40], chars, |chunker| {
+            let chunks = chunker.chunk_with_tokens(text);
+            let texts: Vec<&str> = chunks
+                .iter()
+                .map(|(chunk, _)| chunk.text.as_str())
+                .collect();
+            assert_eq!(texts, chunker.chunk(text));
+            for (chunk, tokens) in &chunks {
+                assert_eq!(*tokens, chars(&chunk.text), "{:?}", chunk.text);
+            }
+        });
+
+        // Counts made while merging are reused rather than counted again.
+        let calls = Rc::new(Cell::new(0));
+        let counter_calls = Rc::clone(&calls);
+        let chunker = Chunker::new(
+            16,
+            Box::new(move |s: &str| {
+                counter_calls.set(counter_calls.get() + 1);
+                s.split_whitespace().count()
+            }),
+        );
+        let text = "word ".repeat(1600);
+        chunker.chunk(text.trim_end());
+        let chunk_calls = calls.replace(0);
+        assert_eq!(chunker.chunk_with_tokens(text.trim_end()).len(), 100);
+        assert_eq!(calls.get(), chunk_calls);
+    }
+
+    #[test]
+    fn test_special_tokens_overhead() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let chunker = Chunker::new(5, Box::new(words)).special_tokens_overhead(2);
+        assert_eq!(
+            chunker.chunk(text),
+            ["The quick brown", "fox jumps over", "the lazy dog."]
+        );
+        assert_eq!(chunker.chunk_size(), 5);
+        assert_eq!(
+            chunker.violations(&["The quick brown fox"]),
+            [Violation {
+                index: 0,
+                tokens: 4
+            }]
+        );
+        // Context is reserved on top of the special tokens.
+        assert_eq!(
+            chunker.chunk_with_context(text, "Fable: "),
+            [
+                "Fable: The quick",
+                "Fable: brown fox",
+                "Fable: jumps over",
+                "Fable: the lazy",
+                "Fable: dog."
+            ]
+        );
+        // At least one token of text is left for each chunk.
+        let chunker = Chunker::new(2, Box::new(words)).special_tokens_overhead(2);
+        assert_eq!(chunker.chunk("The quick brown").len(), 3);
+    }
+
+    #[test]
+    fn test_chunk_linked() {
+        let chunker = Chunker::new(1, Box::new(|s: &str| s.split_whitespace().count()));
+        let chunks = chunker.chunk_linked("doc", "One two three");
+        let ids: Vec<(Option<&str>, &str, Option<&str>)> = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.prev_id.as_deref(),
+                    chunk.id.as_str(),
+                    chunk.next_id.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                (None, "doc-0", Some("doc-1")),
+                (Some("doc-0"), "doc-1", Some("doc-2")),
+                (Some("doc-1"), "doc-2", None),
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.document_id == "doc"));
+        assert_eq!(chunks[2].chunk.start, 8);
+
+        let chunks = chunker.chunk_linked("doc", "One");
+        assert_eq!(
+            (chunks[0].prev_id.clone(), chunks[0].next_id.clone()),
+            (None, None)
+        );
+        assert!(chunker.chunk_linked("doc", "").is_empty());
+    }
+
+    #[test]
+    fn test_chunk_with_boundaries() {
+        let text = "The quick brown fox jumps. Over the lazy dog, it leaps!\n\nPack my box with five dozen liquor jugs.";
+        let words = |s: &str| s.split_whitespace().count();
+        let chunker = Chunker::new(5, Box::new(words));
+        let chunks = chunker.chunk_with_boundaries(text);
+        let texts: Vec<&str> = chunks
+            .iter()
+            .map(|(chunk, _)| chunk.text.as_str())
+            .collect();
+        assert_eq!(texts, chunker.chunk(text));
+        let boundaries: Vec<SeparatorKind> = chunks.iter().map(|(_, kind)| *kind).collect();
+        assert_eq!(
+            boundaries,
+            [
+                SeparatorKind::Whitespace,
+                SeparatorKind::Whitespace,
+                SeparatorKind::ParagraphBreak,
+                SeparatorKind::Whitespace,
+                SeparatorKind::None,
+            ]
+        );
+
+        // Appended non-whitespace separators are where their chunks end.
+        let chunker = Chunker::new(6, Box::new(|s: &str| s.chars().count()));
+        let chunks = chunker.chunk_with_boundaries("ab,cd.efgh");
+        assert_eq!(chunks[0].0.text, "ab,cd.");
+        assert_eq!(chunks[0].1, SeparatorKind::SentenceTerminator);
+        assert_eq!(chunks[1].1, SeparatorKind::None);
+
+        // Whatever the configuration, the chunks are those of `chunk`.
+        for_each_config(&[5], words, |chunker| {
+            let chunks = chunker.chunk_with_boundaries(text);
+            let texts: Vec<&str> = chunks
+                .iter()
+                .map(|(chunk, _)| chunk.text.as_str())
+                .collect();
+            assert_eq!(texts, chunker.chunk(text));
+            assert_eq!(chunks.last().unwrap().1, SeparatorKind::None);
+        });
+
+        // Characters cut from a word over the chunk size end at no separator.
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.chars().count()));
+        let boundaries: Vec<SeparatorKind> = chunker
+            .chunk_with_boundaries("abcde fg")
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect();
+        assert_eq!(
+            boundaries,
+            [
+                SeparatorKind::None,
+                SeparatorKind::None,
+                SeparatorKind::Whitespace,
+                SeparatorKind::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunk_segments() {
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter_calls = Rc::clone(&calls);
+        let chunker = Chunker::new(
+            8,
+            Box::new(move |s: &str| {
+                counter_calls.set(counter_calls.get() + 1);
+                s.split_whitespace().count()
+            }),
+        );
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let chunks = chunker.chunk_segments(lines.iter().map(String::as_str), "\n");
+        assert_eq!(chunks.len(), 25);
+        assert_eq!(chunks[0], "line 0\nline 1\nline 2\nline 3");
+        // Each segment is counted once, plus a few counts to verify each chunk.
+        assert!(calls.get() <= 100 + 3 * 25, "{} calls", calls.get());
+
+        assert!(chunker.chunk_segments([], " ").is_empty());
+
+        // Whole-text options apply once to the chunks of all the segments, including those of a segment over the chunk size.
+        let words = |s: &str| s.split_whitespace().count();
+        let segments = ["a b", "c d e f g h", "i"];
+        let chunker = Chunker::new(4, Box::new(words)).overlap(2).max_chunks(3);
+        assert_eq!(
+            chunker.chunk_segments(segments, "\n"),
+            ["a b\nc d", "c d e f", "e f g h"]
+        );
+        let chunker = Chunker::new(4, Box::new(words)).max_chunks(2);
+        assert_eq!(chunker.chunk_segments(segments, "\n"), ["a b", "c d e f"]);
+    }
+
+    #[test]
+    fn test_separator_tokens() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let text = "The quick brown fox jumps over the lazy dog.\n".repeat(50);
+        let chunk = |separator_tokens: f64| {
+            let calls = Rc::new(Cell::new(0));
+            let counter_calls = Rc::clone(&calls);
+            let chunker = Chunker::new(
+                20,
+                Box::new(move |s: &str| {
+                    counter_calls.set(counter_calls.get() + 1);
+                    s.chars().count()
+                }),
+            )
+            .separator_tokens(separator_tokens);
+            (chunker.chunk(&text), calls.get())
+        };
+
+        let (chunks, calls) = chunk(1.0);
+        let (default_chunks, default_calls) = chunk(0.0);
+        let (bad_chunks, bad_calls) = chunk(-10.0);
+        assert_eq!(chunks, default_chunks);
+        assert_eq!(chunks, bad_chunks);
+        assert!(calls <= default_calls);
+        assert!(calls < bad_calls);
+    }
+
+    #[test]
+    fn test_splits_are_taken_lazily() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let counter = |s: &str| s.split_whitespace().count();
+        let mut split_tokens = SplitTokens::new(std::iter::repeat("word"), &counter, 0.0);
+        let split_idx = chunker.fitting_splits(&mut split_tokens, 0, 4, 1, |candidates, counts| {
+            counts.extend(candidates.iter().map(|splits| splits.len()))
+        });
+        assert_eq!(split_idx, 4);
+        let taken = split_tokens.taken();
+        assert!(taken <= 6, "{} splits taken", taken);
+    }
+
+    #[test]
+    fn test_concurrent_token_counter() {
+        use std::collections::HashSet;
+        use std::sync::Mutex;
+
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+        let counter_threads = Arc::clone(&threads);
+        let counter = move |s: &str| {
+            counter_threads
+                .lock()
+                .unwrap()
+                .insert(std::thread::current().id());
+            s.chars().count()
+        };
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        let sequential = Chunker::new(30, Box::new(|s: &str| s.chars().count()));
+        for probes in [1, 2, 4, 100] {
+            threads.lock().unwrap().clear();
+            let chunker = Chunker::new_concurrent(30, counter.clone(), probes);
+            for _ in 0..3 {
+                assert_eq!(chunker.chunk(&text), sequential.chunk(&text), "{}", probes);
+            }
+            let splits = [
+                "The", "quick", "brown", "fox", "jumps", "over", "the", "lazy", "dog",
+            ];
+            assert_eq!(
+                chunker.merge_splits(&splits, " "),
+                sequential.merge_splits(&splits, " ")
+            );
+            // The probe workers are reused across calls, alongside the calling thread.
+            let used = threads.lock().unwrap().len();
+            assert!(used <= probes.min(MAX_PROBES) + 1, "{} threads", used);
+            assert!(probes == 1 || used > 1);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_token_counter_panic() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        let chunker = Chunker::new_concurrent(
+            30,
+            |s: &str| {
+                assert!(
+                    !s.contains("fox jumps over the lazy dog. The"),
+                    "tokenizer failed"
+                );
+                s.chars().count()
+            },
+            4,
+        );
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| chunker.chunk(&text)));
+        assert!(result.is_err());
+        // The pool survives the panic.
+        assert_eq!(
+            chunker.chunk("The quick brown fox."),
+            vec!["The quick brown fox."]
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(50);
+        let counted_chunker = |token_estimate: Option<TokenEstimate>| {
+            let calls = Rc::new(Cell::new(0));
+            let counter_calls = Rc::clone(&calls);
+            let chunker = Chunker::new(
+                40,
+                Box::new(move |s: &str| {
+                    counter_calls.set(counter_calls.get() + 1);
+                    s.chars().count()
+                }),
+            );
+            let chunker = match token_estimate {
+                Some(token_estimate) => chunker.estimate_tokens(token_estimate),
+                None => chunker,
+            };
+            let chunks = chunker.chunk(&text);
+            (chunks, calls.get())
+        };
+
+        let (exact_chunks, exact_calls) = counted_chunker(None);
+        for token_estimate in [
+            TokenEstimate::CharsPerToken(1.0),
+            // Underestimates are corrected when chunks are verified.
+            TokenEstimate::TokensPerWord(1.0),
+            TokenEstimate::Custom(Box::new(|s: &str| s.len() * 2)),
+        ] {
+            let (chunks, calls) = counted_chunker(Some(token_estimate));
+            assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 40));
+            assert_eq!(chunks.join(" "), exact_chunks.join(" "));
+            assert!(calls < exact_calls, "{} >= {}", calls, exact_calls);
+        }
+        let (chunks, _) = counted_chunker(Some(TokenEstimate::CharsPerToken(1.0)));
+        assert_eq!(chunks, exact_chunks);
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let text =
+            "The quick brown fox jumps over the lazy dog. Ünïcödé wörds ärë wïdër. ".repeat(10);
+        let words = |s: &str| s.split_whitespace().count();
+        for chunker in [
+            Chunker::new(6, Box::new(words)).max_bytes(20),
+            Chunker::new_concurrent(6, words, 4).max_bytes(20),
+            Chunker::new(6, Box::new(words))
+                .max_bytes(20)
+                .estimate_tokens(TokenEstimate::CharsPerToken(5.0)),
+        ] {
+            let chunks = chunker.chunk(&text);
+            assert!(chunks
+                .iter()
+                .all(|chunk| chunk.len() <= 20 && words(chunk) <= 6));
+            assert_eq!(chunks.join(" ").split_whitespace().count(), words(&text));
+        }
+        let chunker = Chunker::new(6, Box::new(words)).max_bytes(0);
+        assert_eq!(chunker.chunk("Ü"), ["Ü"]);
+
+        // Overlapping chunks share fewer subchunks where all of them would be over the byte limit.
+        for (overlap, overlapping) in [(4, false), (2, true)] {
+            let chunker = Chunker::new(8, Box::new(words))
+                .max_bytes(30)
+                .overlap(overlap);
+            let mut chunks = Vec::new();
+            chunker.chunk_to(&text, |chunk| chunks.push(chunk));
+            assert!(chunks.iter().all(|chunk| chunk.text.len() <= 30));
+            assert_eq!(text[..chunks[0].start].trim(), "");
+            assert_eq!(text[chunks.last().unwrap().end..].trim(), "");
+            for pair in chunks.windows(2) {
+                let gap = text.get(pair[0].end..pair[1].start).unwrap_or("");
+                assert_eq!(
+                    gap.trim(),
+                    "",
+                    "{:?} and {:?} leave a gap",
+                    pair[0],
+                    pair[1]
+                );
+            }
+            assert_eq!(
+                chunks.windows(2).any(|pair| pair[1].start < pair[0].end),
+                overlapping
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunk_size_range() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "One two three four five six seven, eight nine ten. Eleven twelve thirteen (fourteen) fifteen sixteen";
+        let chunker = Chunker::new(10, Box::new(words)).chunk_size_range(5..=10);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "One two three four five six seven, eight nine ten.",
+                "Eleven twelve thirteen (fourteen) fifteen sixteen"
+            ]
+        );
+        let chunker = Chunker::new(10, Box::new(words)).chunk_size_range(8..=9);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "One two three four five six seven, eight nine",
+                "ten. Eleven twelve thirteen (fourteen) fifteen sixteen"
+            ]
+        );
+        let chunker = Chunker::new(10, Box::new(words)).chunk_size_range(5..=9);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "One two three four five six seven,",
+                "eight nine ten. Eleven twelve thirteen (fourteen) fifteen sixteen"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunk_size_range_empty() {
+        let (min, max) = (10, 5);
+        let chunker = Chunker::new(10, Box::new(|s: &str| s.len())).chunk_size_range(min..=max);
+        let error = chunker.try_build().err().unwrap();
+        assert_eq!(
+            error,
+            ConfigError::EmptyChunkSizeRange {
+                min_chunk_size: 10,
+                chunk_size: 5
+            }
+        );
+        assert_eq!(error.to_string(), "the chunk size range 10..=5 is empty");
+        let chunker = Chunker::new(10, Box::new(|s: &str| s.len())).chunk_size_range(0..=0);
+        assert_eq!(chunker.try_build().err(), Some(ConfigError::ZeroChunkSize));
+        let chunker = Chunker::new(10, Box::new(|s: &str| s.len())).chunk_size_range(5..=5);
+        assert!(chunker.try_build().is_ok());
+    }
+
+    #[test]
+    fn test_separator_runs() {
+        let text = "Wait...what?!Really";
+        let words = |s: &str| {
+            s.split(|c: char| !c.is_alphabetic())
+                .filter(|w| !w.is_empty())
+                .count()
+        };
+        let chunker = Chunker::new(1, Box::new(words));
+        assert_eq!(chunker.chunk(text), vec!["Wait...", "what?", "!Really"]);
+        let chunker = Chunker::new(1, Box::new(words))
+            .splitter(Splitter::default().separator_runs(&[".", "?", "!", "…"]));
+        assert_eq!(chunker.chunk(text), vec!["Wait...", "what?!", "Really"]);
+    }
+
+    #[test]
+    fn test_separator_attachment() {
+        let chunker = Chunker::new(5, Box::new(|s: &str| s.chars().count()))
+            .separator_attachment(Attachment::NextChunk);
+        let text = "abcde.fgh;abcde.fghij";
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        // A separator that fits in neither chunk is still a chunk of its own.
+        assert_eq!(texts, vec!["abcde", ".fgh;", "abcde", ".", "fghij"]);
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+
+        let chunker = chunker.separator_attachment(Attachment::Drop);
+        assert_eq!(chunker.chunk(text), vec!["abcde", "fgh;", "abcde", "fghij"]);
+    }
+
+    #[test]
+    fn test_quote_integrity() {
+        let chunker = Chunker::new(5, Box::new(|s: &str| s.chars().count())).quote_integrity(true);
+        assert_eq!(chunker.chunk("abc‘de’f"), vec!["abc", "‘de’f"]);
+        // An opening quote that fits in neither chunk is a chunk of its own.
+        assert_eq!(chunker.chunk("abc“defg”"), vec!["abc", "“", "defg”"]);
+
+        assert!(opens_quote("say \"hi\"", 4..5));
+        assert!(!opens_quote("say \"hi\"", 7..8));
+        assert!(opens_quote("'hi'", 0..1));
+        assert!(!opens_quote("don't", 3..4));
+        assert!(!opens_quote("a”b", 1..4));
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let words = |s: &str| s.split_whitespace().count();
+        let error = Chunker::try_new(0, words).err().unwrap();
+        assert_eq!(error.to_string(), "the chunk size must be at least 1");
+        let error = Chunker::try_new(4, words)
+            .unwrap()
+            .try_overlap(5)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "the overlap of 5 tokens must be less than the chunk size of 4 tokens"
+        );
+        let chunker = Chunker::try_new(4, words).unwrap().try_overlap(2).unwrap();
+        assert_eq!(
+            chunker.chunk("The quick brown fox jumps"),
+            vec!["The quick brown fox", "brown fox jumps"]
+        );
+        let error = Chunker::new(0, words).overlap(2).try_build().err().unwrap();
+        assert_eq!(error, ConfigError::ZeroChunkSize);
+        let chunker = Chunker::new(4, words).splitter(Splitter::default().spaces(&['_']));
+        let error = chunker.try_build().err().unwrap();
+        assert_eq!(error, ConfigError::EmptySeparators);
+        assert_eq!(
+            error.to_string(),
+            "the splitter's separators must not be empty"
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "The quick brown fox jumps. Over the lazy dog, it leaps.";
+        let chunker = Chunker::new(6, Box::new(words)).preset(Preset::Markdown);
+        assert_eq!(
+            chunker.chunk(text),
+            ["The quick brown fox jumps.", "Over the lazy dog, it leaps."]
+        );
+        let text =
+            "# Install\nRun the installer.\n```\n# no\n- no\n```\n# Usage\n- Open it.\n- Use it.";
+        let chunker = Chunker::new(8, Box::new(words)).preset(Preset::Markdown);
+        assert_eq!(
+            chunker.chunk(text),
+            [
+                "# Install\nRun the installer.",
+                "```\n# no\n- no\n```",
+                "# Usage\n- Open it.\n- Use it."
+            ]
+        );
+        // Options set after a preset take precedence.
+        let chunker = Chunker::new(6, Box::new(words))
+            .preset(Preset::Legal)
+            .chunk_size_range(6..=6);
+        assert_eq!(
+            chunker.chunk(text),
+            Chunker::new(6, Box::new(words)).chunk(text)
+        );
+
+        let chunker = Chunker::new(8, Box::new(words)).preset(Preset::Chat);
+        assert_eq!(
+            chunker.chunk("a: hi\nb: hello there\na: how are you?\nb: fine"),
+            ["a: hi\nb: hello there\na: how are", "are you?\nb: fine"]
+        );
+    }
+
+    #[test]
+    fn test_emoji_are_not_split() {
+        let text = "Just landed in Tokyo🇯🇵✈️ best trip ever!!👨\u{200D}👩\u{200D}👧\u{200D}👦💯 \
+            #travel #family 🙌🏽🙌🏽🙌🏽 can't wait to go back❤️\u{200D}🔥🔥";
+        let boundaries: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect();
+        // The family emoji, the largest cluster, is seven characters and 25 bytes.
+        let chars: fn(&str) -> usize = |s| s.chars().count();
+        for counter in [chars, str::len] {
+            for chunk_size in [1, 2, 3, 7, 8, 12, 20, 40] {
+                let chunker = Chunker::new(chunk_size, Box::new(counter));
+                let mut chunks = Vec::new();
+                chunker.chunk_to(text, |chunk| chunks.push(chunk));
+                for chunk in &chunks {
+                    assert!(boundaries.contains(&chunk.start), "{:?}", chunk);
+                    assert!(boundaries.contains(&chunk.end), "{:?}", chunk);
+                }
+                // Clusters larger than the chunk size are chunks of their own, reported as violations.
+                let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+                for violation in chunker.violations(&texts) {
+                    assert_eq!(texts[violation.index].graphemes(true).count(), 1);
+                }
+            }
+        }
+        let chunker = Chunker::new(3, Box::new(chars));
+        assert_eq!(
+            chunker.chunk("👨\u{200D}👩\u{200D}👧"),
+            ["👨\u{200D}👩\u{200D}👧"]
+        );
+        let chunker = Chunker::new(4, Box::new(str::len));
+        assert_eq!(chunker.chunk("👍🏽🇯🇵"), ["👍🏽", "🇯🇵"]);
+        assert_eq!(chunker.violations(&chunker.chunk("👍🏽🇯🇵")).len(), 2);
+    }
+
+    #[test]
+    fn test_degenerate_input() {
+        let chars: fn(&str) -> usize = |s| s.chars().count();
+        // Each case once overflowed the stack or took quadratic time.
+        let cases = [
+            (0, chars, "abc def".to_string()),
+            (1, str::len, "é".to_string()),
+            (1, str::len, "🇯🇵 naïve".to_string()),
+            (16, chars, ".".repeat(1 << 20)),
+            (16, chars, "\n".repeat(1 << 20)),
+            (8, chars, ". , ; - ".repeat(1 << 16)),
+        ];
+        for (chunk_size, counter, text) in cases {
+            let chunker = Chunker::new(chunk_size, Box::new(counter));
+            let mut chunks = Vec::new();
+            chunker.chunk_to(&text, |chunk| chunks.push(chunk));
+            let mut end = 0;
+            for chunk in chunks {
+                assert_eq!(chunk.text, &text[chunk.start..chunk.end]);
+                assert!(chunk.start >= end, "{:?}", chunk);
+                // Only splits that cannot be split any further may exceed the chunk size.
+                assert!(
+                    counter(&chunk.text) <= chunk_size || chunk.text.graphemes(true).count() == 1,
+                    "{:?}",
+                    chunk
+                );
+                end = chunk.end;
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_with_context() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let words = |s: &str| s.split_whitespace().count();
+        let chunker = Chunker::new(5, Box::new(words));
+        let chunks = chunker.chunk_with_context(text, "Fables, Aesop: ");
+        assert_eq!(
+            chunks,
+            vec![
+                "Fables, Aesop: The quick brown",
+                "Fables, Aesop: fox jumps over",
+                "Fables, Aesop: the lazy dog."
+            ]
+        );
+        // The reserve only applies while chunking with context.
+        assert_eq!(chunker.chunk(text).len(), 2);
+
+        // Overlap is capped to fit the room left by the context.
+        let chunker = Chunker::new(5, Box::new(words)).overlap(3);
+        for chunk in chunker.chunk_with_context(text, "Fables, Aesop: ") {
+            assert!(words(&chunk) <= 5, "{:?}", chunk);
+        }
+
+        // Chunks whose join with the context counts more tokens are chunked again with more room.
+        let tokens = move |s: &str| words(s) + 2 * s.matches("::").count();
+        let chunker = Chunker::new(4, Box::new(tokens));
+        let chunks = chunker.chunk_with_context(":The quick brown fox jumps.", "Fables:");
+        assert_eq!(
+            chunks,
+            vec!["Fables::The quick", "Fables:brown fox", "Fables:jumps."]
+        );
+
+        // A context filling the chunk size leaves a token of text in each chunk.
+        let chunker = Chunker::new(2, Box::new(words));
+        let chunks = chunker.chunk_with_context("The quick brown", "Fables, Aesop: ");
+        assert_eq!(
+            chunks,
+            vec![
+                "Fables, Aesop: The",
+                "Fables, Aesop: quick",
+                "Fables, Aesop: brown"
+            ]
+        );
+
+        // The reserve is released even if the token counter panics while chunking.
+        let panics = std::rc::Rc::new(Cell::new(false));
+        let chunker = {
+            let panics = panics.clone();
+            Chunker::new(
+                5,
+                Box::new(move |s: &str| {
+                    assert!(!panics.get() || s.contains("Fables"), "tokenizer failed");
+                    words(s)
+                }),
+            )
+        };
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            panics.set(true);
+            chunker.chunk_with_context(text, "Fables, Aesop: ")
+        }));
+        assert!(result.is_err());
+        panics.set(false);
+        assert_eq!(chunker.chunk(text).len(), 2);
+    }
+
+    #[test]
+    fn test_violations() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let text = "Crabs 🦀🦀 and 👍🏽 thumbs";
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let violations = reported.clone();
+        let chunker = Chunker::new(3, Box::new(|s: &str| s.len()))
+            .on_violation(move |violation| violations.borrow_mut().push(*violation));
+        let chunks = chunker.chunk(text);
+        let expected: Vec<Violation> = chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.len() > 3)
+            .map(|(index, chunk)| Violation {
+                index,
+                tokens: chunk.len(),
+            })
+            .collect();
+        assert_eq!(expected.len(), 3);
+        assert_eq!(*reported.borrow(), expected);
+        assert_eq!(chunker.violations(&chunks), expected);
+
+        let chunker = Chunker::new(3, Box::new(|s: &str| s.chars().count()));
+        assert!(chunker.violations(&chunker.chunk(text)).is_empty());
+    }
+
+    #[test]
+    fn test_long_span() {
+        use std::rc::Rc;
+
+        // A base64-like blob without separators, with a token every 3 bytes.
+        let blob: String = (0..200_000u32)
+            .map(|i| char::from(b'a' + (i * 7 % 26) as u8))
+            .collect();
+        let text = format!("Attached: {} (end)", blob);
+        let calls = Rc::new(Cell::new(0));
+        let counted = calls.clone();
+        let chunker = Chunker::new(
+            512,
+            Box::new(move |s: &str| {
+                counted.set(counted.get() + 1);
+                s.len().div_ceil(3)
+            }),
+        );
+        let chunks = chunker.chunk_borrowed(&text);
+        assert!(chunks.len() > 130);
+        assert!(chunks.iter().all(|chunk| chunk.len().div_ceil(3) <= 512));
+        assert!(chunks[2].len() > 1500);
+        assert_eq!(chunks.concat(), text.split_whitespace().collect::<String>());
+        // Each chunk takes a handful of counts rather than one per character.
+        assert!(calls.get() < 20 * chunks.len(), "{} calls", calls.get());
+    }
+
+    #[test]
+    fn test_stateful_token_counter() {
+        use std::rc::Rc;
+
+        // An `FnMut` counter that caches counts, recording how often it misses.
+        let misses = Rc::new(Cell::new(0));
+        let missed = misses.clone();
+        let mut cache: HashMap<String, usize> = HashMap::new();
+        let token_counter = move |s: &str| {
+            *cache.entry(s.to_string()).or_insert_with(|| {
+                missed.set(missed.get() + 1);
+                s.split_whitespace().count()
+            })
+        };
+        let chunker = Chunker::new(4, token_counter);
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let expected = vec!["The quick brown fox", "jumps over the lazy", "dog."];
+        assert_eq!(chunker.chunk(text), expected);
+        let first_misses = misses.get();
+        assert!(first_misses > 0);
+        // Chunking the same text again is answered from the cache.
+        assert_eq!(chunker.chunk(text), expected);
+        assert_eq!(misses.get(), first_misses);
+    }
+
+    #[test]
+    fn test_chunking() {
+        fn chunk_texts(chunking: &impl Chunking, text: &str) -> Vec<String> {
+            chunking
+                .chunk_spans(text)
+                .into_iter()
+                .map(|chunk| chunk.text)
+                .collect()
+        }
+
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let chunks = chunker.chunk_spans(text);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| &text[chunk.start..chunk.end])
+                .collect::<Vec<_>>(),
+            chunker.chunk(text)
+        );
+
+        let boxed: Box<dyn Chunking> = Box::new(chunker);
+        assert_eq!(
+            chunk_texts(&boxed, text),
+            vec!["The quick brown fox", "jumps over the lazy", "dog."]
+        );
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let words = |s: &str| s.split_whitespace().count();
+        let chunker = Chunker::new(2, Box::new(words)).max_chunks(3);
+        let error = chunker.try_chunk(text).unwrap_err();
+        assert_eq!(error.max_chunks, 3);
+        assert_eq!(error.chunks, vec!["The quick", "brown fox", "jumps over"]);
+        assert_eq!(chunker.chunk(text), error.chunks);
+        assert_eq!(
+            Chunker::new(2, Box::new(words))
+                .max_chunks(5)
+                .try_chunk(text)
+                .unwrap()
+                .len(),
+            5
+        );
+
+        // Overlapping chunks and chunks of the head and tail are limited too.
+        let chunker = Chunker::new(4, Box::new(words)).overlap(2).max_chunks(2);
+        assert_eq!(chunker.try_chunk(text).unwrap_err().chunks.len(), 2);
+        let chunker = Chunker::new(2, Box::new(words))
+            .strategy(ChunkStrategy::HeadTail { head: 4, tail: 4 })
+            .max_chunks(3);
+        assert_eq!(chunker.try_chunk(text).unwrap_err().chunks.len(), 3);
+
+        // Chunking stops soon after the limit rather than chunking the whole text.
+        use std::rc::Rc;
+        let counted = Rc::new(Cell::new(0));
+        let counted_by_counter = counted.clone();
+        let chunker = Chunker::new(
+            4,
+            Box::new(move |s: &str| {
+                counted_by_counter.set(counted_by_counter.get() + s.len());
+                s.split_whitespace().count()
+            }),
+        )
+        .max_chunks(10);
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(100_000);
+        assert_eq!(chunker.try_chunk(&text).unwrap_err().chunks.len(), 10);
+        assert!(counted.get() < 10_000, "{}", counted.get());
+    }
+
+    #[test]
+    fn test_chunk_to_cancellable() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut expected = Vec::new();
+        chunker.chunk_to(text, |chunk| expected.push(chunk));
+
+        let mut chunks = Vec::new();
+        let cancelled = AtomicBool::new(false);
+        let result = chunker.chunk_to_cancellable(text, &cancelled, |chunk| chunks.push(chunk));
+        assert_eq!(result, Ok(()));
+        assert_eq!(chunks, expected);
+
+        // Chunking stops before the first split once cancelled.
+        let chunker = Chunker::new(2, Box::new(|_: &str| panic!("counted after cancellation")));
+        let cancelled = AtomicBool::new(true);
+        let result =
+            chunker.chunk_to_cancellable(text, &cancelled, |_| panic!("chunk after cancellation"));
+        assert_eq!(result, Err(Cancelled));
+
+        #[cfg(feature = "tokio-util")]
+        {
+            let token = tokio_util::sync::CancellationToken::new();
+            token.cancel();
+            assert_eq!(
+                chunker.chunk_to_cancellable(text, &token, |_| ()),
+                Err(Cancelled)
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunk_borrowed() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "The quick brown fox, jumps over the lazy dog.\n\nThe end; or is it?";
+        let chunks = chunker.chunk_borrowed(text);
+        assert_eq!(chunks, chunker.chunk(text));
+        for chunk in &chunks {
+            match chunk {
+                Cow::Borrowed(chunk) => assert!(text.contains(chunk)),
+                Cow::Owned(chunk) => panic!("{:?} was copied", chunk),
+            }
+        }
+
+        let chunker = chunker.overlap(2);
+        assert_eq!(chunker.chunk_borrowed(text), chunker.chunk(text));
+    }
+
+    #[test]
+    fn test_huge_token_counts() {
+        // Counts that overflow when added, such as `ENCODE_FAILED` on 32-bit targets, never fit.
+        let text = "aa X bb X cc X dd. The quick X brown fox";
+        let huge = |s: &str| match s.contains('X') {
+            true => usize::MAX,
+            false => s.split_whitespace().count(),
+        };
+        for_each_config(&[1, 2, 3, 5, 8], huge, |chunker| {
+            let chunks = chunker.chunk(text);
+            let oversized: Vec<usize> = (0..chunks.len())
+                .filter(|&i| chunks[i].contains('X'))
+                .collect();
+            let violations: Vec<usize> = chunker
+                .violations(&chunks)
+                .iter()
+                .map(|violation| violation.index)
+                .collect();
+            assert_eq!(violations, oversized, "{:?}", chunks);
+        });
+    }
+
+    #[test]
+    fn test_chunks_are_verbatim() {
+        let text = "The  quick\u{A0}brown fox ,\tjumps over…the lazy dog .\r\n\r\n “Pack  my box,”  she said ;five dozen\u{2003}jugs . quick,;e.g.";
+        let chars = |s: &str| s.chars().count();
+        for_each_config(&[1, 2, 3, 5, 8, 13], chars, |chunker| {
+            let chunker = chunker.lossless(true);
+            let mut chunks = Vec::new();
+            chunker.chunk_to(text, |chunk| chunks.push(chunk));
+            for chunk in chunks {
+                assert_eq!(chunk.text, text[chunk.start..chunk.end], "{:?}", chunk);
+            }
+        });
+    }
+
+    #[test]
+    fn test_lossless() {
+        let text = "..  .b";
+        let chunker = Chunker::new(1, Box::new(|s: &str| s.chars().count()))
+            .merge_strategy(MergeStrategy::Optimal);
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        // The separator is joined to the whitespace before it, which is dropped from the chunk's text.
+        assert_eq!((chunks[2].text.as_str(), chunks[2].start), (".", 3));
+        let chunker = chunker.lossless(true);
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        assert_eq!((chunks[2].text.as_str(), chunks[2].start), (".", 4));
+        for chunk in chunks {
+            assert_eq!(chunk.text, text[chunk.start..chunk.end]);
+        }
+        // Chunking losslessly for exact coverage leaves the chunker's own setting as it was.
+        let chunker = chunker.lossless(false);
+        chunker.chunk_covering(text);
+        assert!(!chunker.lossless.get());
+    }
+
+    #[test]
+    fn test_chunk_with_capacity() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+        let chunks = chunker.chunk_with_capacity(&text, 300);
+        assert_eq!(chunks, chunker.chunk(&text));
+        assert!(chunks.capacity() >= 300);
+        assert_eq!(chunks.len(), 225);
+        // About 4,500 bytes at four bytes per token and four tokens per chunk.
+        assert_eq!(chunker.estimated_chunks(&text), 282);
+        let chunker = chunker.overlap(2);
+        assert_eq!(chunker.estimated_chunks(&text), 563);
+        assert_eq!(chunker.chunk_with_capacity(&text, 0), chunker.chunk(&text));
+    }
+
+    #[test]
+    fn test_chunk_to_channel() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        chunker.chunk_to_channel(text, &sender).unwrap();
+        drop(sender);
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), chunks);
+
+        // Chunks stop being sent once the receiver is dropped.
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        drop(receiver);
+        let error = chunker.chunk_to_channel(text, &sender).unwrap_err();
+        assert_eq!(error.0, chunks[0]);
+    }
+
+    #[test]
+    fn test_chunk_into() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut chunks = Vec::new();
+        for text in [
+            "The quick brown fox jumps over the lazy dog.",
+            "one two three four five six seven eight nine ten eleven twelve thirteen",
+            "Hello, world!",
+            "",
+        ] {
+            chunker.chunk_into(text, &mut chunks);
+            let mut expected = Vec::new();
+            chunker.chunk_to(text, |chunk| expected.push(chunk));
+            assert_eq!(chunks, expected);
+        }
+    }
+
+    #[test]
+    fn test_chunk_to_offsets() {
+        let chunker = Chunker::new(
+            4,
+            Box::new(|s: &str| s.len() - s.replace(" ", "").len() + 1),
+        );
+        let text = "The quick brown fox, jumps over the lazy dog.\n\nThe end.";
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, chunker.chunk(text));
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+}
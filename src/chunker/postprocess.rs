@@ -0,0 +1,594 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Processing of the chunks of a whole text: which parts of it are chunked, how chunks overlap, and which chunks are dropped.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use smallvec::SmallVec;
+
+use super::merge::{ChunkPacker, MergeStrategy};
+use super::{offset_of, sentence_starts, Chunker, CowChunk, Segments, SplitTokens, MAX_PROBES};
+use crate::splitter::Separator;
+
+/// Which parts of a text a [`Chunker`] chunks, set with [`Chunker::strategy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    /// Chunk the whole text.
+    #[default]
+    Full,
+    /// Chunk only the start of the text, up to `head` tokens, and the end of the text, up to `tail` tokens, or the whole text if they meet. Each is cut where a chunk of that many tokens would be, at the most desirable boundary.
+    HeadTail { head: usize, tail: usize },
+}
+
+/// Which chunks without text a [`Chunker`] drops, set with [`Chunker::empty_chunks`].
+///
+/// Chunks are only dropped once final, so an empty chunk that a separator is appended to is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyChunks {
+    /// Drop empty chunks, such as those left by a separator at the start or end of a text.
+    #[default]
+    Drop,
+    /// Drop empty chunks and chunks of only whitespace.
+    DropWhitespace,
+    /// Keep every chunk, even empty ones.
+    Keep,
+}
+
+impl EmptyChunks {
+    /// Returns whether a final chunk of the given text is dropped.
+    fn drops(self, text: &str) -> bool {
+        match self {
+            EmptyChunks::Drop => text.is_empty(),
+            EmptyChunks::DropWhitespace => text.trim().is_empty(),
+            EmptyChunks::Keep => false,
+        }
+    }
+}
+
+impl Chunker {
+    /// Chunks the given text like [`Chunker::chunk_cow`], or the segments it joins where given, also stopping once `cancelled` returns true, after which no more chunks are passed to `sink`.
+    pub(super) fn chunk_cow_until<'a>(
+        &self,
+        text: &'a str,
+        segments: Option<&Segments<'a>>,
+        cancelled: &dyn Fn() -> bool,
+        mut sink: impl FnMut(CowChunk<'a>),
+    ) -> Option<usize> {
+        let n_chunks = Cell::new(0);
+        let over_limit = || {
+            self.max_chunks
+                .filter(|&max_chunks| n_chunks.get() > max_chunks)
+        };
+        self.chunk_parts(
+            text,
+            segments,
+            &|| over_limit().is_some() || cancelled(),
+            |mut chunk| {
+                if let Some(min_tokens) = self.drop_chunks_below {
+                    let tokens = *chunk
+                        .tokens
+                        .get_or_insert_with(|| self.count_tokens(&chunk.text));
+                    if tokens < min_tokens {
+                        return;
+                    }
+                }
+                n_chunks.set(n_chunks.get() + 1);
+                if over_limit().is_none() && !cancelled() {
+                    if let Some(on_violation) = &self.on_violation {
+                        let tokens = *chunk
+                            .tokens
+                            .get_or_insert_with(|| self.count_tokens(&chunk.text));
+                        if let Some(violation) = self.violation(n_chunks.get() - 1, tokens) {
+                            on_violation(&violation);
+                        }
+                    }
+                    sink(chunk);
+                }
+            },
+        );
+        over_limit()
+    }
+
+    /// Chunks the parts of the given text the strategy selects, or the whole of the segments it joins, passing each chunk to `sink` as soon as it is final, until `stop` returns true.
+    fn chunk_parts<'a>(
+        &self,
+        text: &'a str,
+        segments: Option<&Segments<'a>>,
+        stop: &dyn Fn() -> bool,
+        mut sink: impl FnMut(CowChunk<'a>),
+    ) {
+        if segments.is_some() {
+            return self.chunk_text(text, segments, stop, sink);
+        }
+        match self.strategy {
+            ChunkStrategy::Full => self.chunk_text(text, None, stop, sink),
+            ChunkStrategy::HeadTail { head, tail } => {
+                let head_end = self.head_end(text, head);
+                let tail_start = self.tail_start(text, tail);
+                if tail_start <= head_end {
+                    return self.chunk_text(text, None, stop, sink);
+                }
+                if head_end > 0 {
+                    self.chunk_text(&text[..head_end], None, stop, &mut sink);
+                }
+                if tail_start < text.len() && !stop() {
+                    self.chunk_text(&text[tail_start..], None, stop, |mut chunk| {
+                        chunk.start += tail_start;
+                        chunk.end += tail_start;
+                        sink(chunk);
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns where the start of `text` that would be chunked with at most `max_tokens` tokens ends.
+    fn head_end(&self, text: &str, max_tokens: usize) -> usize {
+        let (_, splits) = self.splitter.splits(text);
+        let count = |text: &str| self.count_chunk_tokens(text);
+        let mut split_tokens = SplitTokens::new(splits, &count, self.separator_tokens);
+        let end_of = |splits: &[&str]| {
+            splits.last().map_or(0, |last_split| {
+                offset_of(text, last_split) + last_split.len()
+            })
+        };
+        let Some(first_split) = split_tokens.get(0) else {
+            return 0;
+        };
+        let n_splits = self.fitting_splits(
+            &mut split_tokens,
+            0,
+            max_tokens,
+            self.probes(),
+            |candidates, counts| {
+                let texts: SmallVec<[&str; MAX_PROBES]> = candidates
+                    .iter()
+                    .map(|splits| &text[..end_of(splits)])
+                    .collect();
+                self.count_candidates(&texts, counts);
+            },
+        );
+        match n_splits {
+            // A single grapheme cluster that does not fit cannot be cut any further.
+            0 if first_split.len() == text.len() => 0,
+            0 => self.head_end(first_split, max_tokens),
+            n_splits => end_of(split_tokens.merged(0, n_splits)),
+        }
+    }
+
+    /// Returns where the end of `text` that would be chunked with at most `max_tokens` tokens, were it chunked from the end, starts.
+    fn tail_start(&self, text: &str, max_tokens: usize) -> usize {
+        let (_, splits) = self.splitter.splits(text);
+        let splits: Vec<&str> = splits.collect();
+        let count = |text: &str| self.count_chunk_tokens(text);
+        let mut split_tokens =
+            SplitTokens::new(splits.iter().rev().copied(), &count, self.separator_tokens);
+        // Splits are taken from the end, so the last of merged splits starts them.
+        let start_of = |splits: &[&str]| {
+            splits
+                .last()
+                .map_or(text.len(), |first_split| offset_of(text, first_split))
+        };
+        let Some(last_split) = split_tokens.get(0) else {
+            return text.len();
+        };
+        let n_splits = self.fitting_splits(
+            &mut split_tokens,
+            0,
+            max_tokens,
+            self.probes(),
+            |candidates, counts| {
+                let texts: SmallVec<[&str; MAX_PROBES]> = candidates
+                    .iter()
+                    .map(|splits| &text[start_of(splits)..])
+                    .collect();
+                self.count_candidates(&texts, counts);
+            },
+        );
+        match n_splits {
+            0 if last_split.len() == text.len() => text.len(),
+            0 => offset_of(text, last_split) + self.tail_start(last_split, max_tokens),
+            n_splits => start_of(split_tokens.merged(0, n_splits)),
+        }
+    }
+
+    /// Returns the earliest start of a sentence in the `previous` chunk of `text` such that the sentences from it to the end of that chunk have at most `overlap` tokens, and a chunk from it to `end` still fits, if any.
+    fn overlap_start(&self, text: &str, previous: Range<usize>, end: usize) -> Option<usize> {
+        let mut overlap_start = None;
+        for start in sentence_starts(text, previous.clone()).rev() {
+            let fits = self.count_chunk_tokens(&text[start..previous.end])
+                <= self.content_overlap()
+                && self.count_chunk_tokens(&text[start..end]) <= self.content_size();
+            if !fits {
+                break;
+            }
+            overlap_start = Some(start);
+        }
+        overlap_start
+    }
+
+    /// Chunks the given text, or the segments it joins, passing each chunk to `sink` as soon as it is final, until `stop` returns true.
+    fn chunk_text<'a>(
+        &self,
+        text: &'a str,
+        segments: Option<&Segments<'a>>,
+        stop: &dyn Fn() -> bool,
+        sink: impl FnMut(CowChunk<'a>),
+    ) {
+        if self.content_overlap() == 0 && self.merge_strategy == MergeStrategy::FewestChunks {
+            let fits = |span: &str| self.count_chunk_tokens(span) <= self.content_size();
+            let mut packer = ChunkPacker::new(text, sink, &fits);
+            let mut buffer = ChunkBuffer::new(
+                |chunk: CowChunk<'a>| packer.push(chunk),
+                stop,
+                self.empty_chunks,
+            );
+            self.with_scratch(|scratch| self.chunk_root(text, segments, &mut buffer, scratch));
+            buffer.finish();
+            packer.finish();
+            return;
+        }
+        if self.content_overlap() == 0 {
+            let mut buffer = ChunkBuffer::new(sink, stop, self.empty_chunks);
+            self.with_scratch(|scratch| self.chunk_root(text, segments, &mut buffer, scratch));
+            buffer.finish();
+            return;
+        }
+        if self.sentence_overlap {
+            let mut sink = sink;
+            let mut previous: Option<(usize, usize)> = None;
+            let mut buffer = ChunkBuffer::new(
+                |chunk: CowChunk<'a>| {
+                    let start = previous.map_or(chunk.start, |(previous_start, previous_end)| {
+                        self.overlap_start(text, previous_start..previous_end, chunk.end)
+                            .unwrap_or(chunk.start)
+                    });
+                    previous = Some((chunk.start, chunk.end));
+                    match start < chunk.start {
+                        true => sink(CowChunk {
+                            text: Cow::Borrowed(&text[start..chunk.end]),
+                            start,
+                            end: chunk.end,
+                            tokens: None,
+                            boundary: chunk.boundary,
+                        }),
+                        false => sink(chunk),
+                    }
+                },
+                stop,
+                self.empty_chunks,
+            );
+            self.with_scratch(|scratch| self.chunk_root(text, segments, &mut buffer, scratch));
+            buffer.finish();
+            return;
+        }
+        let local_chunk_size = self.local_chunk_size();
+        let mut windows = OverlapWindows {
+            text,
+            sink,
+            subchunks: VecDeque::new(),
+            subchunks_per_chunk: self.content_size() / local_chunk_size,
+            stride: (self.content_size() - self.content_overlap()) / local_chunk_size,
+            covered: 0,
+            max_bytes: self.max_bytes,
+        };
+        let mut buffer = ChunkBuffer::new(
+            |chunk: CowChunk| windows.push(chunk),
+            stop,
+            self.empty_chunks,
+        );
+        self.with_scratch(|scratch| self.chunk_root(text, segments, &mut buffer, scratch));
+        buffer.finish();
+        windows.finish();
+    }
+}
+
+/// Merges subchunks into overlapping chunks of `subchunks_per_chunk` subchunks, starting a new chunk every `stride` subchunks.
+///
+/// With a byte limit, chunks start at a later subchunk where they would otherwise span more than `max_bytes` bytes, first emitting any subchunks not yet part of a chunk, so chunks share fewer subchunks but no text is left out.
+struct OverlapWindows<'a, F: FnMut(CowChunk<'a>)> {
+    text: &'a str,
+    sink: F,
+    /// The spans of the subchunks that may still be part of a chunk, and the separators they end at.
+    subchunks: VecDeque<(usize, usize, Separator<'a>)>,
+    subchunks_per_chunk: usize,
+    stride: usize,
+    /// The number of subchunks at the front of `subchunks` that are already part of an emitted chunk.
+    covered: usize,
+    /// The maximum number of bytes in a chunk, if any. Every subchunk fits on its own.
+    max_bytes: Option<usize>,
+}
+
+impl<'a, F: FnMut(CowChunk<'a>)> OverlapWindows<'a, F> {
+    fn push(&mut self, subchunk: CowChunk<'a>) {
+        if let Some(max_bytes) = self.max_bytes {
+            while let Some(&(start, _, _)) = self.subchunks.front() {
+                if subchunk.end - start <= max_bytes {
+                    break;
+                }
+                if self.subchunks.len() > self.covered {
+                    self.emit();
+                    self.covered = self.subchunks.len();
+                }
+                self.subchunks.pop_front();
+                self.covered -= 1;
+            }
+        }
+        self.subchunks
+            .push_back((subchunk.start, subchunk.end, subchunk.boundary));
+        if self.subchunks.len() == self.subchunks_per_chunk {
+            self.emit();
+            self.subchunks.drain(..self.stride);
+            self.covered = self.subchunks.len();
+        }
+    }
+
+    fn emit(&mut self) {
+        if let (Some(&(start, _, _)), Some(&(_, end, boundary))) =
+            (self.subchunks.front(), self.subchunks.back())
+        {
+            (self.sink)(CowChunk {
+                text: Cow::Borrowed(&self.text[start..end]),
+                start,
+                end,
+                tokens: None,
+                boundary,
+            });
+        }
+    }
+
+    /// Emits the final, possibly shorter, chunk if any subchunks are not yet part of a chunk.
+    fn finish(mut self) {
+        if self.subchunks.len() > self.covered {
+            self.emit();
+        }
+    }
+}
+
+/// Holds back the most recently produced chunks until they can no longer change.
+///
+/// The last chunk may still have a separator appended to it, or be discarded if it is empty and was produced during recursion, in which case the chunk before it becomes the last chunk again.
+pub(super) struct ChunkBuffer<'a, 's, F: FnMut(CowChunk<'a>)> {
+    sink: F,
+    previous: Option<CowChunk<'a>>,
+    last: Option<(CowChunk<'a>, usize)>,
+    /// A separator held back to be prepended to the next chunk, and the recursion depth it was produced at.
+    pub(super) pending_separator: Option<(CowChunk<'a>, usize)>,
+    /// Returns true once chunking should stop early, which is checked before each split.
+    pub(super) stop: &'s dyn Fn() -> bool,
+    /// Which final chunks are dropped rather than passed to the sink.
+    empty_chunks: EmptyChunks,
+}
+
+impl<'a, 's, F: FnMut(CowChunk<'a>)> ChunkBuffer<'a, 's, F> {
+    pub(super) fn new(sink: F, stop: &'s dyn Fn() -> bool, empty_chunks: EmptyChunks) -> Self {
+        ChunkBuffer {
+            sink,
+            previous: None,
+            last: None,
+            pending_separator: None,
+            stop,
+            empty_chunks,
+        }
+    }
+
+    /// Pushes a chunk produced at the given recursion depth, finalizing the current last chunk.
+    pub(super) fn push(&mut self, chunk: CowChunk<'a>, recursion_depth: usize) {
+        if let Some((last, _)) = self.last.take() {
+            // Chunks without text are dropped once final, as the chunker is set to.
+            if !self.empty_chunks.drops(&last.text) {
+                if let Some(previous) = self.previous.replace(last) {
+                    (self.sink)(previous);
+                }
+            }
+        }
+        self.last = Some((chunk, recursion_depth));
+    }
+
+    pub(super) fn last_mut(&mut self) -> Option<&mut CowChunk<'a>> {
+        self.last.as_mut().map(|(chunk, _)| chunk)
+    }
+
+    /// Discards the last chunk if it was produced during recursion and is dropped once final, so that separators are appended to the chunk before it instead.
+    pub(super) fn discard_empty_last(&mut self) {
+        let empty_chunks = self.empty_chunks;
+        if matches!(&self.last, Some((chunk, depth)) if *depth > 0 && empty_chunks.drops(&chunk.text))
+        {
+            // The previous chunk has already survived being finalized, so it is never discarded.
+            self.last = self.previous.take().map(|chunk| (chunk, 0));
+        }
+    }
+
+    /// Passes the remaining chunks to the sink, including any separator still held back, as a chunk of its own.
+    pub(super) fn finish(mut self) {
+        if let Some(previous) = self.previous.take() {
+            (self.sink)(previous);
+        }
+        if let Some((last, _)) = self.last.take() {
+            if !self.empty_chunks.drops(&last.text) {
+                (self.sink)(last);
+            }
+        }
+        if let Some((separator, _)) = self.pending_separator.take() {
+            (self.sink)(separator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod postprocess_tests {
+    use super::*;
+    use crate::chunker::chunker_tests::for_each_config;
+
+    #[test]
+    fn test_empty_chunks() {
+        let chars = |s: &str| s.chars().count();
+        let text = "\n\nabcdefgh ;\n\n  \n\n..ijk lmnop , \t\n\n";
+        for_each_config(&[1, 2, 3, 5, 8], chars, |chunker| {
+            let chunks = chunker.chunk(text);
+            assert!(chunks.iter().all(|chunk| !chunk.is_empty()), "{:?}", chunks);
+            let chunker = chunker.empty_chunks(EmptyChunks::DropWhitespace);
+            let chunks = chunker.chunk(text);
+            assert!(
+                chunks.iter().all(|chunk| !chunk.trim().is_empty()),
+                "{:?}",
+                chunks
+            );
+        });
+
+        // By default, empty chunks are dropped at the top level too, such as the one left after a text's last separator.
+        let chunker = Chunker::new(5, Box::new(chars));
+        assert_eq!(chunker.chunk("abcde\n\n"), ["abcde"]);
+
+        // Empty chunks are kept at the top level and during recursion alike.
+        let chunker = Chunker::new(3, Box::new(chars)).empty_chunks(EmptyChunks::Keep);
+        assert_eq!(chunker.chunk("abc\n\n"), ["abc", ""]);
+        assert_eq!(chunker.chunk("abcd e\n\nf"), ["abc", "d", "e", "f"]);
+        assert_eq!(chunker.chunk("abcd \n\nf"), ["abc", "d", "", "f"]);
+        let chunker = chunker.empty_chunks(EmptyChunks::Drop);
+        assert_eq!(chunker.chunk("abcd \n\nf"), ["abc", "d", "f"]);
+    }
+
+    #[test]
+    fn test_drop_chunks_below() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "1\n\nThe fox jumps high.\n\n12\n\nOver the lazy dog.\n\n- 13 -";
+        let chunker = Chunker::new(4, Box::new(words));
+        assert_eq!(chunker.chunk(text).len(), 5);
+        let chunker = chunker.drop_chunks_below(3);
+        assert_eq!(
+            chunker.chunk(text),
+            ["The fox jumps high.", "Over the lazy dog.", "- 13 -"]
+        );
+        let tokens: Vec<usize> = chunker
+            .chunk_with_tokens(text)
+            .into_iter()
+            .map(|(_, tokens)| tokens)
+            .collect();
+        assert_eq!(tokens, [4, 4, 3]);
+        // Dropped chunks do not count towards the maximum number of chunks.
+        let chunker = chunker.max_chunks(3);
+        assert_eq!(chunker.try_chunk(text).map(|chunks| chunks.len()), Ok(3));
+    }
+
+    #[test]
+    fn test_head_tail() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "The quick brown fox jumps over the lazy dog.\n\nThe end.";
+        let head_tail = |head, tail| {
+            let chunker =
+                Chunker::new(4, Box::new(words)).strategy(ChunkStrategy::HeadTail { head, tail });
+            let mut chunks = Vec::new();
+            chunker.chunk_to(text, |chunk| chunks.push(chunk));
+            for chunk in &chunks {
+                assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+            }
+            chunks
+                .into_iter()
+                .map(|chunk| chunk.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            head_tail(6, 2),
+            ["The quick brown fox", "jumps over", "The end."]
+        );
+        // Paragraphs are not cut to fill the tail.
+        assert_eq!(head_tail(0, 5), ["The end."]);
+        assert_eq!(head_tail(0, 1), ["end."]);
+        assert_eq!(head_tail(2, 0), ["The quick"]);
+        assert_eq!(
+            head_tail(11, 3),
+            Chunker::new(4, Box::new(words)).chunk(text)
+        );
+
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.chars().count()))
+            .strategy(ChunkStrategy::HeadTail { head: 0, tail: 0 });
+        assert!(chunker.chunk("abc").is_empty());
+    }
+
+    #[test]
+    fn test_overlap() {
+        let token_counter = Box::new(|s: &str| s.split_whitespace().count());
+        let chunker = Chunker::new(6, token_counter).overlap(2);
+        let text = "one two three four five six seven eight nine ten";
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "one two three four five six",
+                "five six seven eight nine ten",
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+
+        // Overlap is capped below the chunk size.
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count())).overlap(5);
+        assert_eq!(chunker.chunk("a b c"), vec!["a b", "b c"]);
+    }
+
+    #[test]
+    fn test_sentence_overlap() {
+        let words = |s: &str| s.split_whitespace().count();
+        let text = "The fox jumps. It is quick. The dog sleeps. It is lazy.";
+        let chunker = Chunker::new(8, Box::new(words))
+            .overlap(3)
+            .sentence_overlap(true);
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "The fox jumps. It is",
+                "It is quick. The dog sleeps. It",
+                "It is lazy."
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+
+        // Without a sentence that fits in the overlap, chunks do not overlap.
+        let chunker = Chunker::new(6, Box::new(words))
+            .overlap(2)
+            .sentence_overlap(true);
+        assert_eq!(
+            chunker.chunk("One two three four. Five six seven eight."),
+            vec!["One two three four.", "Five six seven eight."]
+        );
+
+        // Sentences end after closing quotes and at full-width terminators without spaces.
+        let text = "\"Stop.\" He ran. 走。跑！";
+        let starts: Vec<usize> = sentence_starts(text, 0..text.len()).collect();
+        assert_eq!(starts, vec![0, 8, 16, 22]);
+        assert_eq!(sentence_starts(text, 8..text.len()).next(), Some(8));
+        assert_eq!(sentence_starts(text, 9..text.len()).next(), Some(16));
+    }
+}
@@ -0,0 +1,461 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A port of Python semchunk's `chunk` function that produces byte-for-byte identical chunks and offsets, enabled with the `python-compat` feature.
+//!
+//! [`Chunker`](crate::Chunker) descends from an earlier version of semchunk and chooses separators, merges splits and drops empty chunks slightly differently. [`PythonChunker`] follows semchunk 3 exactly, for pipelines that must reproduce the output of a Python deployment.
+//!
+//! Parity is checked against fixtures generated with Python semchunk by `scripts/python_parity.py`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::chunk::OffsetConverter;
+use crate::splitter::NON_WHITESPACE_SEMANTIC_SEPARATORS;
+
+/// Returns whether Python's `str.isspace` and the regex class `\s` consider the character whitespace, which includes the ASCII information separators unlike [`char::is_whitespace`].
+pub fn is_python_whitespace(c: char) -> bool {
+    c.is_whitespace() || ('\x1c'..='\x1f').contains(&c)
+}
+
+/// How much consecutive chunks overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overlap {
+    Tokens(usize),
+    Ratio(f64),
+}
+
+/// A chunker that reproduces Python semchunk's `chunk(text, chunk_size, token_counter, memoize, offsets, overlap)`.
+///
+/// # Fields
+///
+/// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+/// * `token_counter` - A function that counts the number of tokens in a string.
+/// * `overlap` - The overlap between chunks, as a number of tokens or a ratio of the chunk size.
+/// * `memoize` - Whether token counts are cached, as semchunk does by default.
+///
+/// # Example
+///
+/// ```
+/// use semchunk_rs::compat::PythonChunker;
+/// let chunker = PythonChunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let (chunks, offsets) = chunker.chunk_with_offsets(text);
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+/// assert_eq!(offsets, vec![(0, 19), (20, 39), (40, 44)]);
+/// ```
+pub struct PythonChunker {
+    chunk_size: usize,
+    token_counter: Box<dyn Fn(&str) -> usize>,
+    overlap: Option<Overlap>,
+    memoize: bool,
+    cache: RefCell<HashMap<String, usize>>,
+}
+
+impl PythonChunker {
+    /// Creates a new PythonChunker instance without overlap and with memoization, as semchunk defaults to.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    /// * `token_counter` - A function that counts the number of tokens in a string.
+    ///
+    /// # Returns
+    ///
+    /// A new PythonChunker instance.
+    pub fn new(chunk_size: usize, token_counter: Box<dyn Fn(&str) -> usize>) -> Self {
+        PythonChunker {
+            chunk_size,
+            token_counter,
+            overlap: None,
+            memoize: true,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the overlap to a number of tokens, as when semchunk is passed an integer `overlap`.
+    pub fn overlap_tokens(mut self, overlap: usize) -> Self {
+        self.overlap = Some(Overlap::Tokens(overlap));
+        self
+    }
+
+    /// Sets the overlap to a ratio of the chunk size, as when semchunk is passed a float `overlap` under 1.
+    pub fn overlap_ratio(mut self, overlap: f64) -> Self {
+        self.overlap = Some(Overlap::Ratio(overlap));
+        self
+    }
+
+    /// Sets whether token counts are cached. The cache persists across calls, like semchunk's memoized token counters.
+    pub fn memoize(mut self, memoize: bool) -> Self {
+        self.memoize = memoize;
+        self
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        if !self.memoize {
+            return (self.token_counter)(text);
+        }
+        if let Some(&count) = self.cache.borrow().get(text) {
+            return count;
+        }
+        let count = (self.token_counter)(text);
+        self.cache.borrow_mut().insert(text.to_string(), count);
+        count
+    }
+
+    /// Chunks the given text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks, identical to `semchunk.chunk(text, chunk_size, token_counter, overlap=overlap)`.
+    pub fn chunk(&self, text: &str) -> Vec<String> {
+        self.chunk_with_offsets(text).0
+    }
+
+    /// Chunks the given text, returning the offsets of the chunks as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks and their start and end offsets in characters (not bytes), identical to `semchunk.chunk(text, chunk_size, token_counter, offsets=True, overlap=overlap)`.
+    pub fn chunk_with_offsets(&self, text: &str) -> (Vec<String>, Vec<(usize, usize)>) {
+        // Make relative overlaps absolute and keep overlaps under the chunk size.
+        let overlap = match self.overlap {
+            Some(Overlap::Ratio(ratio)) if ratio < 1.0 => {
+                (self.chunk_size as f64 * ratio).floor() as usize
+            }
+            Some(Overlap::Ratio(ratio)) => (ratio as usize).min(self.chunk_size.saturating_sub(1)),
+            Some(Overlap::Tokens(tokens)) => tokens.min(self.chunk_size.saturating_sub(1)),
+            None => 0,
+        };
+        let local_chunk_size = match overlap {
+            0 => self.chunk_size,
+            _ => overlap.min(self.chunk_size - overlap),
+        };
+
+        let (chunks, offsets) = self.chunk_recursive(text, local_chunk_size, 0);
+
+        // Remove empty chunks and chunks comprised entirely of whitespace.
+        let (mut chunks, mut offsets): (Vec<String>, Vec<(usize, usize)>) = chunks
+            .into_iter()
+            .zip(offsets)
+            .filter(|(chunk, _)| !chunk.is_empty() && !chunk.chars().all(is_python_whitespace))
+            .unzip();
+
+        if overlap > 0 && !chunks.is_empty() {
+            let subchunks_per_chunk = self.chunk_size / local_chunk_size;
+            let stride = (self.chunk_size - overlap) / local_chunk_size;
+            let num_subchunks = offsets.len();
+            let num_chunks = match num_subchunks.checked_sub(subchunks_per_chunk) {
+                Some(remaining) => remaining.div_ceil(stride) + 1,
+                None => 1,
+            };
+            offsets = (0..num_chunks)
+                .map(|j| {
+                    let i = j * stride;
+                    let last = (i + subchunks_per_chunk).min(num_subchunks) - 1;
+                    (offsets[i].0, offsets[last].1)
+                })
+                .collect();
+            chunks = offsets
+                .iter()
+                .map(|&(start, end)| text[start..end].to_string())
+                .collect();
+        }
+
+        // Python strings are indexed by character.
+        let mut char_offsets = OffsetConverter::chars(text);
+        let offsets = offsets
+            .iter()
+            .map(|&(start, end)| (char_offsets.convert(start), char_offsets.convert(end)))
+            .collect();
+        (chunks, offsets)
+    }
+
+    /// Recursively chunks `text`, which starts at byte `start` of the text being chunked, returning chunks and their byte offsets.
+    fn chunk_recursive(
+        &self,
+        text: &str,
+        chunk_size: usize,
+        start: usize,
+    ) -> (Vec<String>, Vec<(usize, usize)>) {
+        let (splitter, splitter_is_whitespace, splits) = split_text(text);
+        let splitter_len = splitter.len();
+        let mut cum_lens = vec![0];
+        let mut split_starts = vec![start];
+        for split in &splits {
+            cum_lens.push(cum_lens.last().unwrap() + split.chars().count());
+            split_starts.push(split_starts.last().unwrap() + split.len() + splitter_len);
+        }
+
+        let mut chunks: Vec<String> = Vec::new();
+        let mut offsets: Vec<(usize, usize)> = Vec::new();
+        // Splits before this index have already been merged into a chunk.
+        let mut skip_until = 0;
+        for (i, &split) in splits.iter().enumerate() {
+            if i < skip_until {
+                continue;
+            }
+            let split_start = split_starts[i];
+            let mut merged_to_end = false;
+            if self.count_tokens(split) > chunk_size {
+                let (new_chunks, new_offsets) =
+                    self.chunk_recursive(split, chunk_size, split_start);
+                chunks.extend(new_chunks);
+                offsets.extend(new_offsets);
+            } else {
+                let (end, merged) = self.merge_splits(&splits, &cum_lens, chunk_size, splitter, i);
+                skip_until = end;
+                merged_to_end = end == splits.len();
+                chunks.push(merged);
+                offsets.push((split_start, split_starts[end] - splitter_len));
+            }
+
+            // Add the splitter to the last chunk if it fits, otherwise add it as its own chunk.
+            if !splitter_is_whitespace && i != splits.len() - 1 && !merged_to_end {
+                let with_splitter = chunks.last().map(|chunk| chunk.clone() + splitter);
+                match with_splitter {
+                    Some(chunk) if self.count_tokens(&chunk) <= chunk_size => {
+                        *chunks.last_mut().unwrap() = chunk;
+                        offsets.last_mut().unwrap().1 += splitter_len;
+                    }
+                    _ => {
+                        let start = offsets.last().map_or(split_start, |&(_, end)| end);
+                        chunks.push(splitter.to_string());
+                        offsets.push((start, start + splitter_len));
+                    }
+                }
+            }
+        }
+        (chunks, offsets)
+    }
+
+    /// Merges splits from `start` until the chunk size is reached, returning the index after the last split merged and the merged chunk.
+    fn merge_splits(
+        &self,
+        splits: &[&str],
+        cum_lens: &[usize],
+        chunk_size: usize,
+        splitter: &str,
+        start: usize,
+    ) -> (usize, String) {
+        let mut average = 0.2;
+        let mut low = start;
+        let mut high = splits.len() + 1;
+        let offset = cum_lens[start];
+        let mut target = offset as f64 + chunk_size as f64 * average;
+        while low < high {
+            let i = low + cum_lens[low..high].partition_point(|&len| (len as f64) < target);
+            let midpoint = i.min(high - 1);
+            let tokens = self.count_tokens(&splits[start..midpoint].join(splitter));
+            let local_cum = cum_lens[midpoint] - offset;
+            if local_cum > 0 && tokens > 0 {
+                average = local_cum as f64 / tokens as f64;
+                target = offset as f64 + chunk_size as f64 * average;
+            }
+            if tokens > chunk_size {
+                high = midpoint;
+            } else {
+                low = midpoint + 1;
+            }
+        }
+        let end = low - 1;
+        (end, splits[start..end].join(splitter))
+    }
+}
+
+/// Splits text at the most desirable splitter found, as semchunk's `_split_text` does.
+fn split_text(text: &str) -> (&str, bool, Vec<&str>) {
+    let runs = |is_splitter: &dyn Fn(char) -> bool| -> Option<&str> {
+        // Python's `max` compares strings lexicographically, which UTF-8 byte order preserves.
+        let mut max: Option<&str> = None;
+        let mut run_start = None;
+        for (i, c) in text.char_indices().chain([(text.len(), 'x')]) {
+            match (is_splitter(c) && i < text.len(), run_start) {
+                (true, None) => run_start = Some(i),
+                (false, Some(s)) => {
+                    let run = &text[s..i];
+                    if Some(run) > max {
+                        max = Some(run);
+                    }
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        max
+    };
+
+    let splitter = if text.contains(['\n', '\r']) {
+        runs(&|c| c == '\n' || c == '\r')
+    } else if text.contains('\t') {
+        runs(&|c| c == '\t')
+    } else {
+        runs(&is_python_whitespace)
+    };
+    if let Some(splitter) = splitter {
+        if !text.contains(['\n', '\r', '\t']) && splitter.chars().count() == 1 {
+            // Prefer whitespace preceded by a semantically meaningful non-whitespace splitter, to avoid splitting mid-sentence.
+            for preceder in NON_WHITESPACE_SEMANTIC_SEPARATORS {
+                let found = text.match_indices(preceder).find_map(|(i, _)| {
+                    let after = i + preceder.len();
+                    text[after..]
+                        .chars()
+                        .next()
+                        .filter(|&c| is_python_whitespace(c))
+                        .map(|c| &text[after..after + c.len_utf8()])
+                });
+                if let Some(whitespace) = found {
+                    return (whitespace, true, split_after(text, whitespace, preceder));
+                }
+            }
+        }
+        return (splitter, true, text.split(splitter).collect());
+    }
+
+    match NON_WHITESPACE_SEMANTIC_SEPARATORS
+        .iter()
+        .find(|&&splitter| text.contains(splitter))
+    {
+        Some(&splitter) => (splitter, false, text.split(splitter).collect()),
+        None => (
+            "",
+            true,
+            text.char_indices()
+                .map(|(i, c)| &text[i..i + c.len_utf8()])
+                .collect(),
+        ),
+    }
+}
+
+/// Splits text at each occurrence of `splitter` that directly follows `preceder`.
+fn split_after<'a>(text: &'a str, splitter: &str, preceder: &str) -> Vec<&'a str> {
+    let mut splits = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices(splitter) {
+        if text[..i].ends_with(preceder) {
+            splits.push(&text[start..i]);
+            start = i + splitter.len();
+        }
+    }
+    splits.push(&text[start..]);
+    splits
+}
+
+#[cfg(test)]
+mod compat_tests {
+    use super::*;
+
+    fn python_words(s: &str) -> usize {
+        s.split(is_python_whitespace)
+            .filter(|w| !w.is_empty())
+            .count()
+    }
+
+    #[test]
+    fn test_split_text() {
+        // Python's max picks "\r\n" over the longer "\n\n".
+        assert_eq!(
+            split_text("a\n\nb\r\nc"),
+            ("\r\n", true, vec!["a\n\nb", "c"])
+        );
+        // Single spaces after sentence terminators are preferred.
+        assert_eq!(
+            split_text("Mr Smith left. He said hi"),
+            (" ", true, vec!["Mr Smith left.", "He said hi"])
+        );
+        assert_eq!(split_text("a-b,c"), (",", false, vec!["a-b", "c"]));
+        assert_eq!(split_text("añb"), ("", true, vec!["a", "ñ", "b"]));
+    }
+
+    #[test]
+    fn test_whitespace_chunks_are_removed() {
+        let chunker = PythonChunker::new(1, Box::new(|s: &str| s.chars().count()));
+        let (chunks, offsets) = chunker.chunk_with_offsets("é\n\x1f\nb");
+        assert_eq!(chunks, vec!["é", "b"]);
+        assert_eq!(offsets, vec![(0, 1), (4, 5)]);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let text = "one two three four five six seven eight nine ten";
+        let chunker = PythonChunker::new(6, Box::new(python_words)).overlap_ratio(0.5);
+        assert_eq!(
+            chunker.chunk(text),
+            vec![
+                "one two three four five six",
+                "four five six seven eight nine",
+                "seven eight nine ten",
+            ]
+        );
+        let chunker = PythonChunker::new(6, Box::new(python_words)).overlap_tokens(2);
+        assert_eq!(
+            chunker.chunk(text),
+            vec![
+                "one two three four five six",
+                "five six seven eight nine ten"
+            ]
+        );
+    }
+
+    /// Checks the fixtures generated by `scripts/python_parity.py` in `DATA_DIR`, which needs the Python package, so it only runs with `--ignored`.
+    #[test]
+    #[cfg(feature = "serde")]
+    #[ignore = "needs python_parity.jsonl generated by scripts/python_parity.py in DATA_DIR"]
+    fn test_python_parity() {
+        let data_path =
+            std::path::PathBuf::from(std::env::var("DATA_DIR").unwrap_or_else(|_| ".".to_string()));
+        let fixtures = std::fs::read_to_string(data_path.join("python_parity.jsonl"))
+            .expect("python_parity.jsonl not found in DATA_DIR, run scripts/python_parity.py");
+        for line in fixtures.lines() {
+            let case: serde_json::Value = serde_json::from_str(line).unwrap();
+            let text = match case["path"].as_str() {
+                Some(path) => {
+                    String::from_utf8_lossy(&std::fs::read(data_path.join(path)).unwrap())
+                        .to_string()
+                }
+                None => case["text"].as_str().unwrap().to_string(),
+            };
+            let chunk_size = case["chunk_size"].as_u64().unwrap() as usize;
+            let mut chunker = PythonChunker::new(chunk_size, Box::new(python_words));
+            if let Some(tokens) = case["overlap"].as_u64() {
+                chunker = chunker.overlap_tokens(tokens as usize);
+            } else if let Some(ratio) = case["overlap"].as_f64() {
+                chunker = chunker.overlap_ratio(ratio);
+            }
+            let expected_chunks: Vec<String> =
+                serde_json::from_value(case["chunks"].clone()).unwrap();
+            let expected_offsets: Vec<(usize, usize)> =
+                serde_json::from_value(case["offsets"].clone()).unwrap();
+            let (chunks, offsets) = chunker.chunk_with_offsets(&text);
+            assert_eq!(chunks, expected_chunks, "case: {}", case["name"]);
+            assert_eq!(offsets, expected_offsets, "case: {}", case["name"]);
+        }
+    }
+}
@@ -0,0 +1,571 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The [`TokenCounter`] trait, adapters that wrap token counters to change how chunks are budgeted, and [`TokenizerPool`] for sharing tokenizers across threads.
+
+use std::sync::{Arc, Mutex};
+
+/// Counts the tokens in a string.
+///
+/// Counting takes `&mut self`, so token counters may keep caches or statistics without interior mutability of their own. A [`Chunker`](crate::Chunker) owns its token counter and calls it one string at a time, never re-entrantly.
+///
+/// Implemented for every `FnMut(&str) -> usize`, including boxed closures, and with their features, for tiktoken encodings, Hugging Face tokenizers and `rust_tokenizers` tokenizers, so they can be passed to [`Chunker::new`](crate::Chunker::new) directly.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use semchunk_rs::counters::TokenCounter;
+/// use semchunk_rs::Chunker;
+///
+/// /// Counts words, caching the counts of strings it has seen.
+/// struct CachedWords {
+///     cache: HashMap<String, usize>,
+/// }
+///
+/// impl TokenCounter for CachedWords {
+///     fn count_tokens(&mut self, text: &str) -> usize {
+///         if let Some(&count) = self.cache.get(text) {
+///             return count;
+///         }
+///         let count = text.split_whitespace().count();
+///         self.cache.insert(text.to_string(), count);
+///         count
+///     }
+/// }
+///
+/// let chunker = Chunker::new(4, CachedWords { cache: HashMap::new() });
+/// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+/// ```
+pub trait TokenCounter {
+    /// Returns the number of tokens in `text`.
+    fn count_tokens(&mut self, text: &str) -> usize;
+}
+
+impl<F: FnMut(&str) -> usize> TokenCounter for F {
+    fn count_tokens(&mut self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// The number of tokens counted for a text a tokenizer fails to encode.
+///
+/// It is over any practical chunk size, so the text never fits in a chunk: the chunker splits it further, and any chunk that still cannot be encoded is reported by [`Chunker::violations`](crate::Chunker::violations) instead of silently passing as empty. On 32-bit targets it is `usize::MAX`, which the chunker's sums of counts saturate at rather than overflow.
+pub const ENCODE_FAILED: usize = u32::MAX as usize;
+
+/// Counts tokens with a tiktoken encoding, without special tokens.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "tiktoken-rs")]
+/// # {
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(512, tiktoken_rs::cl100k_base().unwrap());
+/// assert_eq!(chunker.chunk("Hello, world!"), vec!["Hello, world!"]);
+/// # }
+/// ```
+#[cfg(feature = "tiktoken-rs")]
+impl TokenCounter for tiktoken_rs::CoreBPE {
+    fn count_tokens(&mut self, text: &str) -> usize {
+        self.encode_ordinary(text).len()
+    }
+}
+
+/// Counts tokens with a Hugging Face tokenizer, without special tokens, or as [`ENCODE_FAILED`] if the text cannot be encoded.
+#[cfg(feature = "tokenizers")]
+impl TokenCounter for tokenizers::Tokenizer {
+    fn count_tokens(&mut self, text: &str) -> usize {
+        self.encode(text, false)
+            .map_or(ENCODE_FAILED, |encoding| encoding.len())
+    }
+}
+
+/// Implements [`TokenCounter`] for `rust_tokenizers` tokenizers, counting the tokens they tokenize a text into.
+#[cfg(feature = "rust_tokenizers")]
+macro_rules! impl_rust_tokenizers {
+    ($($tokenizer:ident),* $(,)?) => {
+        $(
+            impl TokenCounter for rust_tokenizers::tokenizer::$tokenizer {
+                fn count_tokens(&mut self, text: &str) -> usize {
+                    rust_tokenizers::tokenizer::Tokenizer::tokenize(self, text).len()
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "rust_tokenizers")]
+impl_rust_tokenizers!(
+    AlbertTokenizer,
+    BertTokenizer,
+    Gpt2Tokenizer,
+    OpenAiGptTokenizer,
+    RobertaTokenizer,
+    T5Tokenizer,
+    XLMRobertaTokenizer,
+    XLNetTokenizer,
+);
+
+/// Counts tokens with a text-splitter `ChunkSizer`, so that the sizers text-splitter provides, or any implemented for it, can count tokens for a [`Chunker`](crate::Chunker).
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "text-splitter")]
+/// # {
+/// use semchunk_rs::counters::SizerCounter;
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(9, SizerCounter(text_splitter::Characters));
+/// assert_eq!(chunker.chunk("The quick brown fox"), vec!["The quick", "brown fox"]);
+/// # }
+/// ```
+#[cfg(feature = "text-splitter")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizerCounter<S>(pub S);
+
+#[cfg(feature = "text-splitter")]
+impl<S: text_splitter::ChunkSizer> TokenCounter for SizerCounter<S> {
+    fn count_tokens(&mut self, text: &str) -> usize {
+        self.0.size(text)
+    }
+}
+
+/// Sizes text-splitter chunks with a token counter, so that any [`TokenCounter`] can be used with text-splitter's splitters.
+///
+/// text-splitter sizes chunks through a shared reference, so the token counter is kept behind a mutex.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "text-splitter")]
+/// # {
+/// use semchunk_rs::counters::CounterSizer;
+/// use text_splitter::{ChunkConfig, TextSplitter};
+/// let sizer = CounterSizer::new(|s: &str| s.split_whitespace().count());
+/// let splitter = TextSplitter::new(ChunkConfig::new(2).with_sizer(sizer));
+/// let chunks: Vec<_> = splitter.chunks("The quick brown fox").collect();
+/// assert_eq!(chunks, vec!["The quick", "brown fox"]);
+/// # }
+/// ```
+#[cfg(feature = "text-splitter")]
+#[derive(Debug, Default)]
+pub struct CounterSizer<C>(Mutex<C>);
+
+#[cfg(feature = "text-splitter")]
+impl<C: TokenCounter> CounterSizer<C> {
+    /// Wraps a token counter.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_counter` - A [`TokenCounter`] that counts the number of tokens in a string.
+    pub fn new(token_counter: C) -> Self {
+        CounterSizer(Mutex::new(token_counter))
+    }
+
+    /// Returns the wrapped token counter.
+    pub fn into_inner(self) -> C {
+        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "text-splitter")]
+impl<C: TokenCounter> text_splitter::ChunkSizer for CounterSizer<C> {
+    fn size(&self, chunk: &str) -> usize {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .count_tokens(chunk)
+    }
+}
+
+/// Returns the number of special tokens a Hugging Face tokenizer adds to a text when encoding it, such as `[CLS]` and `[SEP]`, for use with [`Chunker::special_tokens_overhead`](crate::Chunker::special_tokens_overhead).
+///
+/// The overhead is found by encoding an empty text with special tokens, on a copy of the tokenizer with padding and truncation turned off so that neither changes the count, or is 0 if it cannot be encoded.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "tokenizers")]
+/// # {
+/// use semchunk_rs::counters::special_tokens;
+/// use semchunk_rs::Chunker;
+/// let tokenizer = tokenizers::Tokenizer::from_pretrained("bert-base-uncased", None).unwrap();
+/// let overhead = special_tokens(&tokenizer);
+/// let chunker = Chunker::new(512, tokenizer).special_tokens_overhead(overhead);
+/// # }
+/// ```
+#[cfg(feature = "tokenizers")]
+pub fn special_tokens(tokenizer: &tokenizers::Tokenizer) -> usize {
+    let mut tokenizer = tokenizer.clone();
+    tokenizer.with_padding(None);
+    tokenizer
+        .with_truncation(None)
+        .ok()
+        .and_then(|tokenizer| tokenizer.encode("", true).ok())
+        .map_or(0, |encoding| encoding.len())
+}
+
+/// Wraps a token counter so that chunks only use `margin` of the chunk size, leaving the rest for prompt templates and special tokens.
+///
+/// Counts are divided by `margin` and rounded up, so a chunk fits when its tokens are at most `margin` times the chunk size.
+///
+/// # Arguments
+///
+/// * `token_counter` - A function that counts the number of tokens in a string.
+/// * `margin` - The fraction of the chunk size chunks may use, between 0 and 1.
+///
+/// # Returns
+///
+/// A token counter that inflates the counts of `token_counter`.
+///
+/// # Panics
+///
+/// If `margin` is not greater than 0 and at most 1, such as if it is NaN.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::counters::with_margin;
+/// use semchunk_rs::Chunker;
+/// let token_counter = with_margin(|s: &str| s.split_whitespace().count(), 0.5);
+/// assert_eq!(token_counter("The quick brown"), 6);
+/// let chunker = Chunker::new(8, Box::new(token_counter));
+/// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+/// ```
+pub fn with_margin<F>(token_counter: F, margin: f64) -> impl Fn(&str) -> usize
+where
+    F: Fn(&str) -> usize,
+{
+    assert!(
+        margin > 0.0 && margin <= 1.0,
+        "the margin must be greater than 0 and at most 1, not {}",
+        margin
+    );
+    move |text: &str| (token_counter(text) as f64 / margin).ceil() as usize
+}
+
+/// Wraps a token counter so that `reserved` tokens of every chunk are set aside, for prompt templates and special tokens.
+///
+/// # Arguments
+///
+/// * `token_counter` - A function that counts the number of tokens in a string.
+/// * `reserved` - The number of tokens to add to every count.
+///
+/// # Returns
+///
+/// A token counter that adds `reserved` to the counts of `token_counter`.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::counters::reserve_tokens;
+/// use semchunk_rs::Chunker;
+/// let token_counter = reserve_tokens(|s: &str| s.split_whitespace().count(), 2);
+/// let chunker = Chunker::new(5, Box::new(token_counter));
+/// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+/// assert_eq!(chunks, vec!["The quick brown", "fox jumps over", "the lazy dog."]);
+/// ```
+pub fn reserve_tokens<F>(token_counter: F, reserved: usize) -> impl Fn(&str) -> usize
+where
+    F: Fn(&str) -> usize,
+{
+    move |text: &str| token_counter(text) + reserved
+}
+
+/// Combines token counters into one that returns the largest of their counts, so chunks fit the limits of every tokenizer in a single chunking pass.
+///
+/// # Arguments
+///
+/// * `token_counters` - The token counters to combine, such as those of each model the chunks are for.
+///
+/// # Returns
+///
+/// A token counter that returns the maximum of the counts of `token_counters`, or 0 if there are none.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::counters::max_count;
+/// use semchunk_rs::Chunker;
+/// let words: Box<dyn Fn(&str) -> usize> = Box::new(|s: &str| s.split_whitespace().count());
+/// let chars: Box<dyn Fn(&str) -> usize> = Box::new(|s: &str| s.chars().count() / 4);
+/// let token_counter = max_count(vec![words, chars]);
+/// assert_eq!(token_counter("The quick brown fox"), 4);
+/// assert_eq!(token_counter("Thequickbrownfox"), 4);
+/// let chunker = Chunker::new(4, Box::new(token_counter));
+/// let chunks = chunker.chunk("The quick brown fox jumps over the extraordinarily lazy dog.");
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the", "extraordinarily", "lazy dog."]);
+/// ```
+pub fn max_count<F>(token_counters: impl IntoIterator<Item = F>) -> impl Fn(&str) -> usize
+where
+    F: Fn(&str) -> usize,
+{
+    let token_counters: Vec<F> = token_counters.into_iter().collect();
+    move |text: &str| {
+        token_counters
+            .iter()
+            .map(|token_counter| token_counter(text))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A pool of tokenizers that are not thread-safe, or are expensive to share, from which threads each take their own.
+///
+/// Tokenizers are created as they are needed, so the pool holds no more than the number of threads that counted tokens at once, and are reused once returned. [`TokenizerPool::token_counter`] makes a thread-safe token counter for [`Chunker::new_concurrent`](crate::Chunker::new_concurrent), and [`chunk_parallel_pooled`](crate::parallel::chunk_parallel_pooled) chunks documents on several threads with the pool's tokenizers.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use semchunk_rs::counters::TokenizerPool;
+/// use semchunk_rs::Chunker;
+///
+/// // A stand-in for a tokenizer that cannot be shared across threads.
+/// struct Tokenizer(std::cell::Cell<usize>);
+///
+/// let pool = Arc::new(TokenizerPool::new(|| Tokenizer(std::cell::Cell::new(0))));
+/// let token_counter = pool.token_counter(|_tokenizer: &Tokenizer, s: &str| s.split_whitespace().count());
+/// let chunker = Chunker::new_concurrent(4, token_counter, 4);
+/// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+/// assert_eq!(chunks.len(), 3);
+/// ```
+pub struct TokenizerPool<T> {
+    new_tokenizer: Box<dyn Fn() -> T + Send + Sync>,
+    idle: Mutex<Vec<T>>,
+}
+
+impl<T: Send + 'static> TokenizerPool<T> {
+    /// Creates an empty pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_tokenizer` - A function that creates a tokenizer, such as by loading it or cloning a loaded one.
+    pub fn new(new_tokenizer: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        TokenizerPool {
+            new_tokenizer: Box::new(new_tokenizer),
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `f` with a tokenizer from the pool, creating one if none is free, and returns it to the pool afterwards.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let tokenizer = idle.unwrap_or_else(|| (self.new_tokenizer)());
+        let result = f(&tokenizer);
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tokenizer);
+        result
+    }
+
+    /// Creates a thread-safe token counter that counts with tokenizers from the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - A function that counts the tokens in a string with a tokenizer.
+    pub fn token_counter<F>(
+        self: &Arc<Self>,
+        count: F,
+    ) -> impl Fn(&str) -> usize + Clone + Send + Sync + 'static
+    where
+        F: Fn(&T, &str) -> usize + Send + Sync + 'static,
+    {
+        let (pool, count) = (Arc::clone(self), Arc::new(count));
+        move |text: &str| pool.with(|tokenizer| count(tokenizer, text))
+    }
+}
+
+#[cfg(test)]
+mod counters_tests {
+    use super::*;
+
+    #[cfg(feature = "tokenizers")]
+    #[test]
+    fn test_special_tokens() {
+        use tokenizers::models::wordlevel::WordLevel;
+        use tokenizers::pre_tokenizers::whitespace::Whitespace;
+        use tokenizers::processors::template::TemplateProcessing;
+        use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+        let vocab = [
+            ("[UNK]", 0),
+            ("[CLS]", 1),
+            ("[SEP]", 2),
+            ("[PAD]", 3),
+            ("fox", 4),
+        ]
+        .into_iter()
+        .map(|(token, id)| (token.to_string(), id))
+        .collect();
+        let model = WordLevel::builder()
+            .vocab(vocab)
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let template = TemplateProcessing::builder()
+            .try_single("[CLS] $A [SEP]")
+            .unwrap()
+            .special_tokens(vec![("[CLS]", 1), ("[SEP]", 2)])
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer
+            .with_pre_tokenizer(Some(Whitespace {}))
+            .with_post_processor(Some(template));
+        assert_eq!(special_tokens(&tokenizer), 2);
+
+        // Padding and truncation change the length of encodings, but not the overhead.
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::Fixed(8),
+            pad_id: 3,
+            pad_token: "[PAD]".to_string(),
+            ..PaddingParams::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: 3,
+                ..TruncationParams::default()
+            }))
+            .unwrap();
+        assert_eq!(tokenizer.encode("", true).unwrap().len(), 8);
+        assert_eq!(special_tokens(&tokenizer), 2);
+        assert!(tokenizer.get_padding().is_some());
+    }
+
+    #[cfg(feature = "text-splitter")]
+    #[test]
+    fn test_text_splitter_adapters() {
+        use text_splitter::{ChunkConfig, ChunkSizer, TextSplitter};
+        let text = "The quick brown fox jumps over the lazy dog.";
+
+        let mut counter = SizerCounter(text_splitter::Characters);
+        assert_eq!(counter.count_tokens("héllo"), 5);
+        let chunker = crate::Chunker::new(10, counter);
+        assert!(chunker.chunk(text).iter().all(|c| c.chars().count() <= 10));
+
+        let sizer = CounterSizer::new(|s: &str| s.split_whitespace().count());
+        assert_eq!(sizer.size("The quick brown"), 3);
+        let splitter = TextSplitter::new(ChunkConfig::new(4).with_sizer(&sizer));
+        let chunks: Vec<_> = splitter.chunks(text).collect();
+        assert_eq!(
+            chunks,
+            ["The quick brown fox", "jumps over the lazy", "dog."]
+        );
+        let mut counter = sizer.into_inner();
+        assert_eq!(counter.count_tokens("lazy dog"), 2);
+    }
+
+    #[test]
+    fn test_with_margin() {
+        let token_counter = with_margin(|s: &str| s.chars().count(), 0.9);
+        assert_eq!(token_counter(""), 0);
+        assert_eq!(token_counter(&"a".repeat(9)), 10);
+        assert_eq!(token_counter(&"a".repeat(10)), 12);
+        let token_counter = with_margin(|s: &str| s.chars().count(), 1.0);
+        assert_eq!(token_counter(&"a".repeat(10)), 10);
+        for margin in [0.0, -0.5, 1.5, f64::NAN] {
+            let result = std::panic::catch_unwind(|| with_margin(|s: &str| s.len(), margin));
+            assert!(result.is_err(), "margin {} was accepted", margin);
+        }
+    }
+
+    #[test]
+    fn test_encode_failed() {
+        // Counts words, failing to encode the replacement character.
+        let token_counter = |s: &str| match s.contains('\u{FFFD}') {
+            true => ENCODE_FAILED,
+            false => s.split_whitespace().count(),
+        };
+        let chunker = crate::Chunker::new(4, token_counter);
+        let chunks = chunker.chunk("one two \u{FFFD} three four");
+        assert_eq!(chunks, vec!["one two", "\u{FFFD}", "three four"]);
+        assert_eq!(
+            chunker.violations(&chunks),
+            vec![crate::chunker::Violation {
+                index: 1,
+                tokens: ENCODE_FAILED
+            }]
+        );
+    }
+
+    #[test]
+    fn test_reserve_tokens() {
+        let token_counter = reserve_tokens(|s: &str| s.chars().count(), 3);
+        assert_eq!(token_counter(""), 3);
+        assert_eq!(token_counter("abc"), 6);
+    }
+
+    #[test]
+    fn test_max_count() {
+        let token_counter = max_count([|s: &str| s.len(), |s: &str| s.len() * 2]);
+        assert_eq!(token_counter("abc"), 6);
+        let token_counter = max_count(Vec::<fn(&str) -> usize>::new());
+        assert_eq!(token_counter("abc"), 0);
+    }
+
+    #[test]
+    fn test_tokenizer_pool() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let created = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let created = created.clone();
+            Arc::new(TokenizerPool::new(move || {
+                created.fetch_add(1, Ordering::SeqCst);
+                std::cell::RefCell::new(Vec::<usize>::new())
+            }))
+        };
+        let token_counter = pool.token_counter(|tokenizer: &std::cell::RefCell<Vec<usize>>, s| {
+            let count = s.split_whitespace().count();
+            tokenizer.borrow_mut().push(count);
+            count
+        });
+        assert_eq!(token_counter("The quick brown fox"), 4);
+        assert_eq!(token_counter("jumps"), 1);
+        // Tokenizers are reused once returned.
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let token_counter = token_counter.clone();
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(token_counter("over the lazy dog"), 4);
+                    }
+                });
+            }
+        });
+        assert!(created.load(Ordering::SeqCst) <= 5);
+        let counted: usize = pool
+            .idle
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tokenizer| tokenizer.borrow().len())
+            .sum();
+        assert_eq!(counted, 402);
+    }
+}
@@ -0,0 +1,246 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Exact coverage of a text by its chunks, for pipelines that must show that nothing was lost or changed in chunking.
+//!
+//! [`Chunker::chunk_covering`] records the text between chunks, such as the whitespace chunks are split at, alongside the chunks, so that [`reassemble`] reproduces the text byte for byte, and [`validate`] checks that it does.
+
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+
+/// The chunks of a text and the gaps of text between them, which together cover the text exactly.
+///
+/// # Fields
+///
+/// * `chunks` - The chunks of the text, in order.
+/// * `gaps` - The text before each chunk that no earlier chunk covers, followed by the text after the last chunk, so there is one more gap than there are chunks. Chunks that overlap the chunk before them have an empty gap.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coverage {
+    pub chunks: Vec<Chunk>,
+    pub gaps: Vec<String>,
+}
+
+/// Why chunks and gaps do not cover a text exactly, as found by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CoverageError {
+    /// There is not exactly one more gap than there are chunks.
+    GapCount { chunks: usize, gaps: usize },
+    /// The chunk at the given index is not the text between its offsets.
+    Chunk(usize),
+    /// The gap at the given index is not the text it should cover.
+    Gap(usize),
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoverageError::GapCount { chunks, gaps } => {
+                let expected = chunks + 1;
+                write!(
+                    f,
+                    "{} gaps for {} chunks, expected {}",
+                    gaps, chunks, expected
+                )
+            }
+            CoverageError::Chunk(index) => {
+                write!(f, "chunk {} does not match the text at its offsets", index)
+            }
+            CoverageError::Gap(index) => write!(f, "gap {} does not match the text", index),
+        }
+    }
+}
+
+impl std::error::Error for CoverageError {}
+
+/// Reassembles the text that chunks and gaps cover, joining each gap with the part of the chunk after it that no earlier chunk covers.
+///
+/// For chunks and gaps made with [`Chunker::chunk_covering`], this is the chunked text, byte for byte.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::coverage::reassemble;
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())).overlap(2);
+/// let text = "  The quick brown fox\n\njumps over the lazy dog. ";
+/// assert_eq!(reassemble(&chunker.chunk_covering(text)), text);
+/// ```
+pub fn reassemble(coverage: &Coverage) -> String {
+    let gaps_len: usize = coverage.gaps.iter().map(String::len).sum();
+    let chunks_len: usize = coverage.chunks.iter().map(|chunk| chunk.text.len()).sum();
+    let mut text = String::with_capacity(gaps_len + chunks_len);
+    let mut covered = 0;
+    for (index, chunk) in coverage.chunks.iter().enumerate() {
+        if let Some(gap) = coverage.gaps.get(index) {
+            text.push_str(gap);
+        }
+        covered = covered.max(chunk.start);
+        if let Some(uncovered) = chunk.text.get(covered - chunk.start..) {
+            text.push_str(uncovered);
+        }
+        covered = covered.max(chunk.end);
+    }
+    if let Some(gap) = coverage.gaps.get(coverage.chunks.len()) {
+        text.push_str(gap);
+    }
+    text
+}
+
+/// Checks that chunks and gaps cover a text exactly, so that [`reassemble`] reproduces it byte for byte.
+///
+/// # Arguments
+///
+/// * `coverage` - The chunks and gaps to check.
+/// * `text` - The text they should cover.
+///
+/// # Errors
+///
+/// The first chunk or gap that does not match the text, if any.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::coverage::{validate, CoverageError};
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let mut coverage = chunker.chunk_covering(text);
+/// assert_eq!(validate(&coverage, text), Ok(()));
+/// coverage.gaps[1] = "\n".to_string();
+/// assert_eq!(validate(&coverage, text), Err(CoverageError::Gap(1)));
+/// ```
+pub fn validate(coverage: &Coverage, text: &str) -> Result<(), CoverageError> {
+    if coverage.gaps.len() != coverage.chunks.len() + 1 {
+        return Err(CoverageError::GapCount {
+            chunks: coverage.chunks.len(),
+            gaps: coverage.gaps.len(),
+        });
+    }
+    let mut covered = 0;
+    for (index, chunk) in coverage.chunks.iter().enumerate() {
+        if text.get(covered..chunk.start.max(covered)) != Some(coverage.gaps[index].as_str()) {
+            return Err(CoverageError::Gap(index));
+        }
+        if text.get(chunk.start..chunk.end) != Some(chunk.text.as_str()) {
+            return Err(CoverageError::Chunk(index));
+        }
+        covered = covered.max(chunk.end);
+    }
+    match text.get(covered..) == Some(coverage.gaps[coverage.chunks.len()].as_str()) {
+        true => Ok(()),
+        false => Err(CoverageError::Gap(coverage.chunks.len())),
+    }
+}
+
+impl Chunker {
+    /// Chunks the given text along with the gaps of text between the chunks, which together cover the text exactly.
+    ///
+    /// The chunks are those [`Chunker::chunk_to`] produces with [`Chunker::lossless`] set, so that each is the text between its offsets, and the gaps are the rest of the text, such as the whitespace chunks were split at and any whitespace before the first chunk or after the last one.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks and gaps, from which [`reassemble`] reproduces the text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let coverage = chunker.chunk_covering("The quick brown fox\n\njumps over the lazy dog.");
+    /// assert_eq!(coverage.chunks[1].text, "jumps over the lazy");
+    /// assert_eq!(coverage.gaps, vec!["", "\n\n", " ", ""]);
+    /// ```
+    pub fn chunk_covering(&self, text: &str) -> Coverage {
+        let mut chunks = Vec::new();
+        self.with_lossless(|| self.chunk_to(text, |chunk| chunks.push(chunk)));
+        let mut gaps = Vec::with_capacity(chunks.len() + 1);
+        let mut covered = 0;
+        for chunk in &chunks {
+            gaps.push(text[covered..chunk.start.max(covered)].to_string());
+            covered = covered.max(chunk.end);
+        }
+        gaps.push(text[covered..].to_string());
+        Coverage { chunks, gaps }
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use super::*;
+    use crate::chunker::chunker_tests::for_each_config;
+
+    #[test]
+    fn test_reassemble() {
+        let text = "\n  The  quick\u{A0}brown fox ,\tjumps over…the lazy dog .\r\n\r\n “Pack  my box,”  she said ;five dozen\u{2003}jugs .  ";
+        let chars = |s: &str| s.chars().count();
+        for_each_config(&[1, 4, 9, 16, 200], chars, |chunker| {
+            let coverage = chunker.chunk_covering(text);
+            assert_eq!(validate(&coverage, text), Ok(()));
+            assert_eq!(reassemble(&coverage), text);
+            let chunks: Vec<&str> = coverage
+                .chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect();
+            assert_eq!(chunks, chunker.with_lossless(|| chunker.chunk(text)));
+        });
+        let coverage = Chunker::new(4, Box::new(chars)).chunk_covering("");
+        assert_eq!(reassemble(&coverage), "");
+        assert_eq!(validate(&coverage, ""), Ok(()));
+    }
+
+    #[test]
+    fn test_validate() {
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "one two three four five";
+        let coverage = chunker.chunk_covering(text);
+
+        let mut changed = coverage.clone();
+        changed.chunks[1].text.push('!');
+        assert_eq!(validate(&changed, text), Err(CoverageError::Chunk(1)));
+        let mut changed = coverage.clone();
+        changed.chunks[2].start += 1;
+        assert_eq!(validate(&changed, text), Err(CoverageError::Gap(2)));
+        let mut changed = coverage.clone();
+        changed.gaps.pop();
+        assert_eq!(
+            validate(&changed, text),
+            Err(CoverageError::GapCount { chunks: 3, gaps: 3 })
+        );
+        assert_eq!(
+            validate(&coverage, "one two three four five."),
+            Err(CoverageError::Gap(3))
+        );
+        assert_eq!(
+            CoverageError::GapCount { chunks: 3, gaps: 3 }.to_string(),
+            "3 gaps for 3 chunks, expected 4"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Deduplication of chunks across a batch of documents, such as the boilerplate headers and disclaimers many documents share.
+//!
+//! [`Deduplicator`] finds exact duplicates. With the `minhash` feature, [`NearDeduplicator`] also finds chunks that are nearly identical.
+
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "minhash")]
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+
+/// What to do with chunks whose text duplicates that of an earlier chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Keep duplicates, without flagging them.
+    #[default]
+    Keep,
+    /// Drop duplicates.
+    Drop,
+    /// Keep duplicates, flagging them as such.
+    Flag,
+}
+
+/// A chunk of one of a batch of documents.
+///
+/// # Fields
+///
+/// * `document` - The index of the document the chunk is from.
+/// * `chunk` - The chunk, with offsets into its document.
+/// * `duplicate` - Whether the chunk's text duplicates that of an earlier chunk, if duplicates are flagged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentChunk {
+    pub document: usize,
+    pub chunk: Chunk,
+    pub duplicate: bool,
+}
+
+/// Tells which chunks duplicate a chunk seen before.
+pub trait DuplicateDetector {
+    /// Remembers `text`, returning whether it does not duplicate a text seen before.
+    fn insert(&mut self, text: &str) -> bool;
+}
+
+/// Remembers the texts of the chunks it has seen, by hash, to tell which chunks are exact duplicates.
+///
+/// Texts are remembered by a 64-bit hash rather than in full, so memory stays small across large batches, at a negligible chance of mistaking a chunk for a duplicate.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::dedup::Deduplicator;
+/// let mut deduplicator = Deduplicator::default();
+/// assert!(deduplicator.insert("All rights reserved."));
+/// assert!(!deduplicator.insert("All rights reserved."));
+/// assert_eq!(deduplicator.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Deduplicator {
+    seen: HashSet<u64>,
+}
+
+impl Deduplicator {
+    /// Remembers `text`, returning whether it had not been seen before.
+    pub fn insert(&mut self, text: &str) -> bool {
+        self.seen.insert(hash(text))
+    }
+
+    /// The number of distinct texts seen.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Whether no texts have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Forgets every text seen.
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+
+    /// Remembers the texts another Deduplicator has seen, returning how many of them this one had already seen.
+    pub fn merge(&mut self, other: Deduplicator) -> usize {
+        let n_seen = self.seen.len();
+        let n_other = other.seen.len();
+        self.seen.extend(other.seen);
+        n_seen + n_other - self.seen.len()
+    }
+}
+
+impl DuplicateDetector for Deduplicator {
+    fn insert(&mut self, text: &str) -> bool {
+        Deduplicator::insert(self, text)
+    }
+}
+
+/// The number of hashes in a MinHash signature.
+#[cfg(feature = "minhash")]
+const MINHASH_HASHES: usize = 128;
+
+/// The number of consecutive words hashed together as a shingle.
+#[cfg(feature = "minhash")]
+const SHINGLE_WORDS: usize = 3;
+
+/// Finds chunks that are nearly identical to a chunk seen before, by estimating the Jaccard similarity of their sets of word shingles with MinHash.
+///
+/// Signatures are indexed by locality-sensitive hashing, so each chunk is only compared with chunks likely to be similar to it rather than with every chunk seen.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "minhash")]
+/// # {
+/// use semchunk_rs::dedup::NearDeduplicator;
+/// let mut deduplicator = NearDeduplicator::new(0.5);
+/// assert!(deduplicator.insert("This document is confidential and may not be distributed without permission."));
+/// assert!(!deduplicator.insert("This document is confidential and may not be distributed without prior permission."));
+/// assert!(deduplicator.insert("The quick brown fox jumps over the lazy dog."));
+/// # }
+/// ```
+#[cfg(feature = "minhash")]
+#[derive(Debug, Clone)]
+pub struct NearDeduplicator {
+    threshold: f64,
+    rows: usize,
+    signatures: Vec<[u64; MINHASH_HASHES]>,
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+#[cfg(feature = "minhash")]
+impl NearDeduplicator {
+    /// Creates a NearDeduplicator that treats chunks whose estimated Jaccard similarity to a chunk seen before is at least `threshold` as duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The similarity, between 0 and 1, at which chunks are near-duplicates.
+    pub fn new(threshold: f64) -> Self {
+        // Choose the largest band size, which makes the fewest chunks candidates for comparison, with which texts as similar as the threshold almost always share a band.
+        let rows = (1..=MINHASH_HASHES)
+            .rev()
+            .filter(|&rows| MINHASH_HASHES.is_multiple_of(rows))
+            .find(|&rows| {
+                let bands = (MINHASH_HASHES / rows) as i32;
+                1.0 - (1.0 - threshold.powi(rows as i32)).powi(bands) >= 0.99
+            })
+            .unwrap_or(1);
+        NearDeduplicator {
+            threshold,
+            rows,
+            signatures: Vec::new(),
+            buckets: vec![HashMap::new(); MINHASH_HASHES / rows],
+        }
+    }
+
+    /// Remembers `text` unless it is a near-duplicate, returning whether it is not a near-duplicate of a text seen before.
+    pub fn insert(&mut self, text: &str) -> bool {
+        let signature = minhash(text);
+        let band_keys: Vec<u64> = signature.chunks(self.rows).map(hash).collect();
+        let near_duplicate = band_keys
+            .iter()
+            .zip(&self.buckets)
+            .filter_map(|(key, bucket)| bucket.get(key))
+            .flatten()
+            .any(|&i| similarity(&signature, &self.signatures[i]) >= self.threshold);
+        if near_duplicate {
+            return false;
+        }
+        let i = self.signatures.len();
+        self.signatures.push(signature);
+        for (key, bucket) in band_keys.into_iter().zip(&mut self.buckets) {
+            bucket.entry(key).or_default().push(i);
+        }
+        true
+    }
+
+    /// The number of texts remembered, which excludes near-duplicates.
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Whether no texts have been remembered.
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+#[cfg(feature = "minhash")]
+impl DuplicateDetector for NearDeduplicator {
+    fn insert(&mut self, text: &str) -> bool {
+        NearDeduplicator::insert(self, text)
+    }
+}
+
+/// Hashes a value with a fixed-key hasher, so hashes are the same within one build, but may change with the Rust version, and must not be persisted.
+pub(crate) fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the MinHash signature of the set of word shingles in `text`, or of `text` itself if it has fewer words than a shingle.
+#[cfg(feature = "minhash")]
+fn minhash(text: &str) -> [u64; MINHASH_HASHES] {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let shingles: Vec<u64> = match words.len() {
+        n if n < SHINGLE_WORDS => vec![hash(&words)],
+        _ => words.windows(SHINGLE_WORDS).map(hash).collect(),
+    };
+    let mut signature = [u64::MAX; MINHASH_HASHES];
+    for shingle in shingles {
+        for (i, min) in signature.iter_mut().enumerate() {
+            // Each hash function permutes shingle hashes by mixing them with a different seed.
+            *min = (*min).min(splitmix64(shingle ^ splitmix64(i as u64)));
+        }
+    }
+    signature
+}
+
+/// The estimated Jaccard similarity of the texts with two MinHash signatures.
+#[cfg(feature = "minhash")]
+fn similarity(a: &[u64; MINHASH_HASHES], b: &[u64; MINHASH_HASHES]) -> f64 {
+    a.iter().zip(b).filter(|(a, b)| a == b).count() as f64 / MINHASH_HASHES as f64
+}
+
+/// The SplitMix64 finalizer, which scrambles the bits of a 64-bit value.
+#[cfg(feature = "minhash")]
+fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9E3779B97F4A7C15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+impl Chunker {
+    /// Chunks each of a batch of documents, handling chunks that duplicate an earlier chunk in the batch as `duplicates` says.
+    ///
+    /// # Arguments
+    ///
+    /// * `documents` - The texts of the documents to chunk.
+    /// * `duplicates` - What to do with chunks whose text duplicates that of an earlier chunk.
+    ///
+    /// # Returns
+    ///
+    /// The chunks of every document, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::dedup::Duplicates;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(3, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let documents = ["Hello there.\nAll rights reserved.", "Goodbye.\nAll rights reserved."];
+    /// let chunks = chunker.chunk_documents(documents, Duplicates::Drop);
+    /// let texts: Vec<(usize, &str)> = chunks.iter().map(|c| (c.document, c.chunk.text.as_str())).collect();
+    /// assert_eq!(texts, vec![(0, "Hello there."), (0, "All rights reserved."), (1, "Goodbye.")]);
+    /// ```
+    pub fn chunk_documents<'a>(
+        &self,
+        documents: impl IntoIterator<Item = &'a str>,
+        duplicates: Duplicates,
+    ) -> Vec<DocumentChunk> {
+        self.chunk_documents_with(documents, duplicates, &mut Deduplicator::default())
+    }
+
+    /// Chunks each of a batch of documents like [`Chunker::chunk_documents`], but tells duplicates with `detector`, which may be a [`NearDeduplicator`] or one that remembers chunks from earlier batches.
+    ///
+    /// # Arguments
+    ///
+    /// * `documents` - The texts of the documents to chunk.
+    /// * `duplicates` - What to do with chunks `detector` finds to be duplicates.
+    /// * `detector` - What tells whether a chunk duplicates one seen before.
+    ///
+    /// # Returns
+    ///
+    /// The chunks of every document, in order.
+    pub fn chunk_documents_with<'a>(
+        &self,
+        documents: impl IntoIterator<Item = &'a str>,
+        duplicates: Duplicates,
+        detector: &mut impl DuplicateDetector,
+    ) -> Vec<DocumentChunk> {
+        let mut chunks = Vec::new();
+        for (document, text) in documents.into_iter().enumerate() {
+            self.chunk_to(text, |chunk| {
+                let duplicate = duplicates != Duplicates::Keep && !detector.insert(&chunk.text);
+                if duplicate && duplicates == Duplicates::Drop {
+                    return;
+                }
+                chunks.push(DocumentChunk {
+                    document,
+                    chunk,
+                    duplicate,
+                });
+            });
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod dedup_tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_documents() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let documents = [
+            "Page one.\nConfidential. Do not distribute.",
+            "Page two.\nConfidential. Do not distribute.",
+            "Confidential. Do not distribute.",
+        ];
+        let flags = |duplicates| {
+            chunker
+                .chunk_documents(documents, duplicates)
+                .into_iter()
+                .map(|chunk| (chunk.document, chunk.chunk.start, chunk.duplicate))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            flags(Duplicates::Keep),
+            [
+                (0, 0, false),
+                (0, 10, false),
+                (1, 0, false),
+                (1, 10, false),
+                (2, 0, false)
+            ]
+        );
+        assert_eq!(
+            flags(Duplicates::Flag),
+            [
+                (0, 0, false),
+                (0, 10, false),
+                (1, 0, false),
+                (1, 10, true),
+                (2, 0, true)
+            ]
+        );
+        assert_eq!(
+            flags(Duplicates::Drop),
+            [(0, 0, false), (0, 10, false), (1, 0, false)]
+        );
+    }
+
+    #[cfg(feature = "minhash")]
+    #[test]
+    fn test_near_deduplicator() {
+        let chunker = Chunker::new(25, Box::new(|s: &str| s.split_whitespace().count()));
+        let disclaimer = "This email and any files transmitted with it are confidential and intended solely for the use of the individual to whom they are addressed.";
+        let documents = [
+            format!("Meeting moved to Tuesday.\n{}", disclaimer),
+            format!("Lunch is on me.\n{}", disclaimer.replace("solely", "only")),
+            "Lunch is on me.".to_string(),
+        ];
+        let mut detector = NearDeduplicator::new(0.7);
+        let chunks = chunker.chunk_documents_with(
+            documents.iter().map(String::as_str),
+            Duplicates::Flag,
+            &mut detector,
+        );
+        let flags: Vec<bool> = chunks.iter().map(|chunk| chunk.duplicate).collect();
+        assert_eq!(flags, [false, false, false, true, true]);
+        assert_eq!(detector.len(), 3);
+
+        // Unrelated texts are not near-duplicates.
+        let mut detector = NearDeduplicator::new(0.7);
+        assert!(detector.insert("The quick brown fox jumps over the lazy dog."));
+        assert!(detector.insert("Pack my box with five dozen liquor jugs."));
+        assert!(detector.insert(""));
+        assert!(!detector.insert(" "));
+    }
+}
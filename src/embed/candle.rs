@@ -0,0 +1,158 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::path::Path;
+
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use super::{EmbedError, Embedder};
+
+/// An [`Embedder`] that runs a BERT sentence-transformer, such as `sentence-transformers/all-MiniLM-L6-v2`, locally with candle.
+///
+/// Embeddings are the mean of the token embeddings, normalized to unit length, as in sentence-transformers.
+///
+/// # Examples
+///
+/// ```no_run
+/// use semchunk_rs::embed::{CandleEmbedder, Embedder};
+///
+/// // A directory with the model's config.json, tokenizer.json and model.safetensors.
+/// let embedder = CandleEmbedder::from_dir("models/all-MiniLM-L6-v2").unwrap();
+/// let embeddings = embedder.embed_batch(&["The quick brown fox.", "The lazy dog."]).unwrap();
+/// assert_eq!(embeddings[0].len(), 384);
+/// ```
+pub struct CandleEmbedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl CandleEmbedder {
+    /// Loads a model from a directory on the CPU.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - A directory containing the model's `config.json`, `tokenizer.json` and `model.safetensors`.
+    ///
+    /// # Returns
+    ///
+    /// A new CandleEmbedder, or an error if the model could not be loaded.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, EmbedError> {
+        Self::from_dir_on(dir, Device::Cpu)
+    }
+
+    /// Loads a model from a directory onto the given device.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - A directory containing the model's `config.json`, `tokenizer.json` and `model.safetensors`.
+    /// * `device` - The device to run the model on.
+    ///
+    /// # Returns
+    ///
+    /// A new CandleEmbedder, or an error if the model could not be loaded.
+    pub fn from_dir_on(dir: impl AsRef<Path>, device: Device) -> Result<Self, EmbedError> {
+        let dir = dir.as_ref();
+        let config: Config =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("config.json"))?)?;
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer.with_truncation(Some(TruncationParams {
+            max_length: config.max_position_embeddings,
+            ..Default::default()
+        }))?;
+        // SAFETY: the weights file is not expected to be modified while it is mapped.
+        let weights = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[dir.join("model.safetensors")], DTYPE, &device)?
+        };
+        let model = BertModel::load(weights, &config)?;
+        Ok(CandleEmbedder {
+            model,
+            tokenizer,
+            device,
+        })
+    }
+}
+
+impl Embedder for CandleEmbedder {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let encodings = self.tokenizer.encode_batch(texts.to_vec(), true)?;
+        let ids = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_ids(), &self.device))
+            .collect::<Result<Vec<_>, _>>()?;
+        let masks = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_attention_mask(), &self.device))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids = Tensor::stack(&ids, 0)?;
+        let mask = Tensor::stack(&masks, 0)?;
+        let token_type_ids = ids.zeros_like()?;
+        let hidden = self.model.forward(&ids, &token_type_ids, Some(&mask))?;
+        Ok(normalize(&mean_pool(&hidden, &mask)?)?.to_vec2()?)
+    }
+}
+
+/// Averages the embeddings of the tokens in each sequence, ignoring padding.
+fn mean_pool(hidden: &Tensor, mask: &Tensor) -> candle_core::Result<Tensor> {
+    let mask = mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+    let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+    summed.broadcast_div(&mask.sum(1)?)
+}
+
+/// Scales each row to unit length.
+fn normalize(embeddings: &Tensor) -> candle_core::Result<Tensor> {
+    embeddings.broadcast_div(&embeddings.sqr()?.sum_keepdim(1)?.sqrt()?)
+}
+
+#[cfg(test)]
+mod candle_tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_pool_ignores_padding() {
+        let device = Device::Cpu;
+        let hidden = Tensor::new(
+            &[
+                [[1.0f32, 0.0], [3.0, 4.0], [100.0, 100.0]],
+                [[0.0, 2.0], [0.0, 2.0], [0.0, 2.0]],
+            ],
+            &device,
+        )
+        .unwrap();
+        let mask = Tensor::new(&[[1u32, 1, 0], [1, 1, 1]], &device).unwrap();
+        let pooled = mean_pool(&hidden, &mask).unwrap();
+        assert_eq!(
+            pooled.to_vec2::<f32>().unwrap(),
+            vec![vec![2.0, 2.0], vec![0.0, 2.0]]
+        );
+        let normalized = normalize(&pooled).unwrap().to_vec2::<f32>().unwrap();
+        assert!((normalized[0][0] - 0.5f32.sqrt()).abs() < 1e-6);
+        assert_eq!(normalized[1], vec![0.0, 1.0]);
+    }
+}
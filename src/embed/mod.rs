@@ -0,0 +1,251 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Embedding providers for [`SemanticChunker`](crate::semantic::SemanticChunker).
+//!
+//! Any source of embeddings, local or remote, can be used by implementing [`Embedder`]. With the `rust-bert` feature, it is implemented for rust-bert's `SentenceEmbeddingsModel`. Closures are adapted with [`from_fn`] or [`try_from_fn`], [`Batched`] limits the number of texts embedded at once, and [`Resilient`] retries and rate limits calls to remote APIs.
+
+use std::error::Error;
+
+use crate::resilience::{Guard, Resilience, ResilienceError};
+
+#[cfg(feature = "candle")]
+mod candle;
+#[cfg(feature = "ort")]
+mod ort;
+#[cfg(feature = "rust-bert")]
+mod rust_bert;
+
+#[cfg(feature = "candle")]
+pub use self::candle::CandleEmbedder;
+#[cfg(feature = "ort")]
+pub use self::ort::OrtEmbedder;
+
+/// The error returned by an [`Embedder`].
+pub type EmbedError = Box<dyn Error + Send + Sync>;
+
+/// A source of embeddings.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::embed::{EmbedError, Embedder};
+///
+/// /// Embeds texts by their counts of vowels and consonants.
+/// struct Letters;
+///
+/// impl Embedder for Letters {
+///     fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+///         Ok(texts
+///             .iter()
+///             .map(|text| {
+///                 let vowels = text.chars().filter(|c| "aeiou".contains(*c)).count();
+///                 vec![vowels as f32, (text.len() - vowels) as f32]
+///             })
+///             .collect())
+///     }
+/// }
+///
+/// assert_eq!(Letters.embed_batch(&["banana"]).unwrap(), vec![vec![3.0, 3.0]]);
+/// ```
+pub trait Embedder {
+    /// Embeds a batch of texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - The texts to embed.
+    ///
+    /// # Returns
+    ///
+    /// One embedding per text, in the same order, or an error if the texts could not be embedded.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError>;
+}
+
+impl<E: Embedder + ?Sized> Embedder for Box<E> {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        (**self).embed_batch(texts)
+    }
+}
+
+impl<E: Embedder + ?Sized> Embedder for &E {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        (**self).embed_batch(texts)
+    }
+}
+
+/// An [`Embedder`] backed by an infallible function, created by [`from_fn`].
+pub struct FromFn<F>(F);
+
+impl<F: Fn(&[&str]) -> Vec<Vec<f32>>> Embedder for FromFn<F> {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        Ok((self.0)(texts))
+    }
+}
+
+/// Creates an [`Embedder`] from a function that embeds a batch of texts.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::embed::{from_fn, Embedder};
+/// let embedder = from_fn(|texts| texts.iter().map(|text| vec![text.len() as f32]).collect());
+/// assert_eq!(embedder.embed_batch(&["a", "bc"]).unwrap(), vec![vec![1.0], vec![2.0]]);
+/// ```
+pub fn from_fn<F: Fn(&[&str]) -> Vec<Vec<f32>>>(f: F) -> FromFn<F> {
+    FromFn(f)
+}
+
+/// An [`Embedder`] backed by a fallible function, created by [`try_from_fn`].
+pub struct TryFromFn<F>(F);
+
+impl<F: Fn(&[&str]) -> Result<Vec<Vec<f32>>, EmbedError>> Embedder for TryFromFn<F> {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        (self.0)(texts)
+    }
+}
+
+/// Creates an [`Embedder`] from a fallible function that embeds a batch of texts, such as a call to an embeddings API.
+pub fn try_from_fn<F: Fn(&[&str]) -> Result<Vec<Vec<f32>>, EmbedError>>(f: F) -> TryFromFn<F> {
+    TryFromFn(f)
+}
+
+/// Wraps an [`Embedder`] to embed texts in batches of at most `batch_size`, for models and APIs that limit their inputs.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::Cell;
+/// use semchunk_rs::embed::{from_fn, Batched, Embedder};
+/// let calls = Cell::new(0);
+/// let embedder = from_fn(|texts| {
+///     calls.set(calls.get() + 1);
+///     texts.iter().map(|_| vec![1.0]).collect()
+/// });
+/// let batched = Batched::new(embedder, 2);
+/// assert_eq!(batched.embed_batch(&["a", "b", "c"]).unwrap().len(), 3);
+/// assert_eq!(calls.get(), 2);
+/// ```
+pub struct Batched<E> {
+    embedder: E,
+    batch_size: usize,
+}
+
+impl<E: Embedder> Batched<E> {
+    /// Creates a new Batched embedder.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - The embedder to call with each batch.
+    /// * `batch_size` - The maximum number of texts in a batch.
+    pub fn new(embedder: E, batch_size: usize) -> Self {
+        Batched {
+            embedder,
+            batch_size: batch_size.max(1),
+        }
+    }
+}
+
+impl<E: Embedder> Embedder for Batched<E> {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.batch_size) {
+            embeddings.extend(self.embedder.embed_batch(batch)?);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Wraps an [`Embedder`] to retry failed batches, rate limit them and stop calling an API that keeps failing, as set by a [`Resilience`] policy.
+///
+/// # Examples
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::time::Duration;
+/// use semchunk_rs::embed::{try_from_fn, Embedder, Resilient};
+/// use semchunk_rs::resilience::Resilience;
+/// let calls = Cell::new(0);
+/// let flaky = try_from_fn(|texts| {
+///     calls.set(calls.get() + 1);
+///     match calls.get() {
+///         1 => Err("503 Service Unavailable".into()),
+///         _ => Ok(texts.iter().map(|_| vec![1.0]).collect()),
+///     }
+/// });
+/// let embedder = Resilient::new(flaky, Resilience::default().retries(3, Duration::from_millis(1)));
+/// assert_eq!(embedder.embed_batch(&["a"]).unwrap(), vec![vec![1.0]]);
+/// assert_eq!(calls.get(), 2);
+/// ```
+pub struct Resilient<E> {
+    embedder: E,
+    guard: Guard,
+}
+
+impl<E: Embedder> Resilient<E> {
+    /// Creates a new Resilient embedder.
+    ///
+    /// # Arguments
+    ///
+    /// * `embedder` - The embedder to call with each batch.
+    /// * `policy` - How to retry, rate limit and circuit break its calls.
+    pub fn new(embedder: E, policy: Resilience) -> Self {
+        Resilient {
+            embedder,
+            guard: Guard::new(policy),
+        }
+    }
+}
+
+impl<E: Embedder> Embedder for Resilient<E> {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        self.guard
+            .call(|| self.embedder.embed_batch(texts))
+            .map_err(|e| match e {
+                ResilienceError::Failed(e) => e,
+                ResilienceError::CircuitOpen => ResilienceError::<EmbedError>::CircuitOpen
+                    .to_string()
+                    .into(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod embed_tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_propagate() {
+        let embedder = Batched::new(
+            try_from_fn(|texts| match texts.contains(&"bad") {
+                true => Err("cannot embed 'bad'".into()),
+                false => Ok(texts.iter().map(|_| vec![0.0]).collect()),
+            }),
+            1,
+        );
+        assert_eq!(embedder.embed_batch(&["good", "fine"]).unwrap().len(), 2);
+        let error = embedder.embed_batch(&["good", "bad"]).unwrap_err();
+        assert_eq!(error.to_string(), "cannot embed 'bad'");
+
+        let boxed: Box<dyn Embedder> = Box::new(embedder);
+        assert!(boxed.embed_batch(&["bad"]).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use ort::execution_providers::ExecutionProviderDispatch;
+use ort::session::Session;
+use ort::value::Tensor;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use super::{EmbedError, Embedder};
+
+/// The maximum number of tokens in a text, which is the limit of BERT-style encoders.
+const MAX_TOKENS: usize = 512;
+
+/// An [`Embedder`] that runs an ONNX sentence encoder with ONNX Runtime.
+///
+/// The ONNX Runtime library is loaded when the first model is, from the path in the `ORT_DYLIB_PATH` environment variable, so a GPU build of ONNX Runtime can be used with the matching execution provider.
+///
+/// The model's inputs are filled by name from `input_ids`, `attention_mask` and `token_type_ids`. If its first output is a sequence of token embeddings, they are averaged, ignoring padding. Embeddings are normalized to unit length.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ort::execution_providers::CUDAExecutionProvider;
+/// use semchunk_rs::embed::{Embedder, OrtEmbedder};
+///
+/// // A directory with the model's model.onnx and tokenizer.json.
+/// let embedder = OrtEmbedder::from_dir_with_providers(
+///     "models/all-MiniLM-L6-v2",
+///     &[CUDAExecutionProvider::default().build()],
+/// )
+/// .unwrap();
+/// let embeddings = embedder.embed_batch(&["The quick brown fox.", "The lazy dog."]).unwrap();
+/// ```
+pub struct OrtEmbedder {
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+}
+
+impl OrtEmbedder {
+    /// Loads a model from a directory, running it on the CPU.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - A directory containing the model's `model.onnx` and `tokenizer.json`.
+    ///
+    /// # Returns
+    ///
+    /// A new OrtEmbedder, or an error if the model could not be loaded.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, EmbedError> {
+        Self::from_dir_with_providers(dir, &[])
+    }
+
+    /// Loads a model from a directory, running it with the first available of the given execution providers.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - A directory containing the model's `model.onnx` and `tokenizer.json`.
+    /// * `providers` - The execution providers to try, in order, before falling back to the CPU.
+    ///
+    /// # Returns
+    ///
+    /// A new OrtEmbedder, or an error if the model could not be loaded.
+    pub fn from_dir_with_providers(
+        dir: impl AsRef<Path>,
+        providers: &[ExecutionProviderDispatch],
+    ) -> Result<Self, EmbedError> {
+        let dir = dir.as_ref();
+        let session = Session::builder()?
+            .with_execution_providers(providers)?
+            .commit_from_file(dir.join("model.onnx"))?;
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))?;
+        Self::new(session, tokenizer)
+    }
+
+    /// Creates an OrtEmbedder from a session and its tokenizer, for full control over how the session is built.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The ONNX Runtime session of the model.
+    /// * `tokenizer` - The tokenizer of the model.
+    ///
+    /// # Returns
+    ///
+    /// A new OrtEmbedder, or an error if the tokenizer could not be configured.
+    pub fn new(session: Session, mut tokenizer: Tokenizer) -> Result<Self, EmbedError> {
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer.with_truncation(Some(TruncationParams {
+            max_length: MAX_TOKENS,
+            ..Default::default()
+        }))?;
+        Ok(OrtEmbedder {
+            session: Mutex::new(session),
+            tokenizer,
+        })
+    }
+}
+
+impl Embedder for OrtEmbedder {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let encodings = self.tokenizer.encode_batch(texts.to_vec(), true)?;
+        let shape = [encodings.len(), encodings[0].len()];
+        let column = |get: fn(&tokenizers::Encoding) -> &[u32]| -> Vec<i64> {
+            encodings
+                .iter()
+                .flat_map(|encoding| get(encoding).iter().map(|&id| id as i64))
+                .collect()
+        };
+        let mask = column(|encoding| encoding.get_attention_mask());
+
+        let mut session = self.session.lock().map_err(|e| e.to_string())?;
+        let mut inputs = Vec::new();
+        for input in &session.inputs {
+            let values = match input.name.as_str() {
+                "input_ids" => column(|encoding| encoding.get_ids()),
+                "attention_mask" => mask.clone(),
+                "token_type_ids" => column(|encoding| encoding.get_type_ids()),
+                name => return Err(format!("unsupported model input '{}'", name).into()),
+            };
+            inputs.push((input.name.clone(), Tensor::from_array((shape, values))?));
+        }
+        let outputs = session.run(inputs)?;
+        let (output_shape, output) = outputs[0].try_extract_tensor::<f32>()?;
+        let mut embeddings = match **output_shape {
+            // Token embeddings.
+            [_, sequence_length, dims] => {
+                mean_pool(output, &mask, sequence_length as usize, dims as usize)
+            }
+            // Sentence embeddings.
+            [_, dims] => output.chunks(dims as usize).map(<[f32]>::to_vec).collect(),
+            _ => return Err(format!("unexpected output shape {:?}", output_shape).into()),
+        };
+        embeddings
+            .iter_mut()
+            .for_each(|embedding| normalize(embedding));
+        Ok(embeddings)
+    }
+}
+
+/// Averages the embeddings of the tokens in each sequence, ignoring padding.
+///
+/// `hidden` holds a `dims`-dimensional embedding for each of the `sequence_length` tokens of each sequence, and `mask` is 1 for real tokens and 0 for padding.
+fn mean_pool(hidden: &[f32], mask: &[i64], sequence_length: usize, dims: usize) -> Vec<Vec<f32>> {
+    hidden
+        .chunks(dims * sequence_length)
+        .zip(mask.chunks(sequence_length))
+        .map(|(tokens, mask)| {
+            let mut pooled = vec![0.0; dims];
+            let mut count = 0.0;
+            for (token, _) in tokens.chunks(dims).zip(mask).filter(|(_, &m)| m != 0) {
+                pooled.iter_mut().zip(token).for_each(|(p, t)| *p += t);
+                count += 1.0;
+            }
+            pooled.iter_mut().for_each(|p| *p /= f32::max(count, 1.0));
+            pooled
+        })
+        .collect()
+}
+
+/// Scales a vector to unit length, leaving zero vectors unchanged.
+fn normalize(embedding: &mut [f32]) {
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+#[cfg(test)]
+mod ort_tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_pool_ignores_padding() {
+        let hidden = [
+            1.0, 0.0, 3.0, 4.0, 100.0, 100.0, 0.0, 2.0, 0.0, 2.0, 0.0, 2.0,
+        ];
+        let mask = [1, 1, 0, 1, 1, 1];
+        let mut pooled = mean_pool(&hidden, &mask, 3, 2);
+        assert_eq!(pooled, vec![vec![2.0, 2.0], vec![0.0, 2.0]]);
+        normalize(&mut pooled[0]);
+        assert!((pooled[0][0] - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+}
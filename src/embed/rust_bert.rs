@@ -0,0 +1,47 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use rust_bert::pipelines::sentence_embeddings::SentenceEmbeddingsModel;
+
+use super::{EmbedError, Embedder};
+
+/// Embeds texts with a rust-bert sentence embeddings pipeline, such as `all-MiniLM-L6-v2`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_bert::pipelines::sentence_embeddings::{
+///     SentenceEmbeddingsBuilder, SentenceEmbeddingsModelType,
+/// };
+/// use semchunk_rs::embed::Embedder;
+///
+/// let model = SentenceEmbeddingsBuilder::remote(SentenceEmbeddingsModelType::AllMiniLmL6V2)
+///     .create_model()
+///     .unwrap();
+/// let embeddings = model.embed_batch(&["The quick brown fox.", "The lazy dog."]).unwrap();
+/// assert_eq!(embeddings[0].len(), 384);
+/// ```
+impl Embedder for SentenceEmbeddingsModel {
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        Ok(self.encode(texts)?)
+    }
+}
@@ -0,0 +1,280 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Expanding chunks with the text around them, so small chunks can be retrieved and bigger spans of text around them fed to a model.
+
+use crate::chunk::{Chunk, LinkedChunk};
+use crate::chunker::Chunker;
+
+/// The initial number of bytes per token of budget searched for word boundaries around a chunk, doubled until the text searched is over the budget.
+const SEARCH_BYTES_PER_TOKEN: usize = 16;
+
+impl Chunker {
+    /// Expands a stored chunk by up to `extra_tokens` tokens of the text around it, as counted by the token counter.
+    ///
+    /// See [`Chunker::expand_chunk`] for how the chunk is expanded.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the chunks were produced from.
+    /// * `chunks` - The stored chunks of the text, such as those returned by [`Chunker::chunk_linked`].
+    /// * `chunk_id` - The ID of the chunk to expand.
+    /// * `extra_tokens` - The most tokens of surrounding text to add to the chunk.
+    ///
+    /// # Returns
+    ///
+    /// The expanded chunk, or `None` if no chunk has the ID or the chunk is not a span of the text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let text = "The quick brown fox jumps over the lazy dog.";
+    /// let chunks = chunker.chunk_linked("fox", text);
+    /// assert_eq!(chunks[2].chunk.text, "jumps over");
+    /// let expanded = chunker.expand(text, &chunks, "fox-2", 4).unwrap();
+    /// assert_eq!(expanded.text, "brown fox jumps over the lazy");
+    /// assert_eq!(&text[expanded.start..expanded.end], expanded.text);
+    /// ```
+    pub fn expand(
+        &self,
+        text: &str,
+        chunks: &[LinkedChunk],
+        chunk_id: &str,
+        extra_tokens: usize,
+    ) -> Option<Chunk> {
+        chunks
+            .iter()
+            .find(|chunk| chunk.id == chunk_id)
+            .and_then(|chunk| self.expand_chunk(text, &chunk.chunk, extra_tokens))
+    }
+
+    /// Expands a chunk by up to `extra_tokens` tokens of the text around it, as counted by the token counter.
+    ///
+    /// The chunk is only expanded to word boundaries, so no word is cut off at either end. The tokens are split evenly between the text before and after the chunk, and any one side cannot use goes to the other, such as for the first and last chunks of a text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text the chunk was produced from.
+    /// * `chunk` - The chunk to expand.
+    /// * `extra_tokens` - The most tokens of surrounding text to add to the chunk.
+    ///
+    /// # Returns
+    ///
+    /// The expanded chunk, whose text is the source text from its `start` to its `end`, or `None` if the chunk's text is not the span of the text from its `start` to its `end`, such as when the chunk was produced from a different text.
+    pub fn expand_chunk(&self, text: &str, chunk: &Chunk, extra_tokens: usize) -> Option<Chunk> {
+        if text.get(chunk.start..chunk.end) != Some(chunk.text.as_str()) {
+            return None;
+        }
+        let before_tokens = |start: usize| self.count_tokens(&text[start..chunk.start]);
+        let after_tokens = |end: usize| self.count_tokens(&text[chunk.end..end]);
+        let mut start = self.extend_before(text, chunk.start, extra_tokens / 2);
+        let end = self.extend_after(
+            text,
+            chunk.end,
+            extra_tokens.saturating_sub(before_tokens(start)),
+        );
+        // The text before may take the tokens the text after left unused.
+        if start > 0 {
+            start = self.extend_before(
+                text,
+                chunk.start,
+                extra_tokens.saturating_sub(after_tokens(end)),
+            );
+        }
+        Some(Chunk {
+            text: text[start..end].to_string(),
+            start,
+            end,
+        })
+    }
+
+    /// Returns the earliest start of a word before `start` from which the text up to `start` has at most `budget` tokens, or `start` if there is none.
+    fn extend_before(&self, text: &str, start: usize, budget: usize) -> usize {
+        if budget == 0 || start == 0 {
+            return start;
+        }
+        let fits = |from: usize| self.count_tokens(&text[from..start]) <= budget;
+        let mut window = budget.saturating_mul(SEARCH_BYTES_PER_TOKEN);
+        let mut from = floor_char_boundary(text, start.saturating_sub(window));
+        while from > 0 && fits(from) {
+            window = window.saturating_mul(2);
+            from = floor_char_boundary(text, start.saturating_sub(window));
+        }
+        let starts: Vec<usize> = text[from..start]
+            .char_indices()
+            .map(|(index, _)| from + index)
+            .filter(|&index| is_word_start(text, index))
+            .collect();
+        let first_fitting = starts.partition_point(|&index| !fits(index));
+        starts.get(first_fitting).copied().unwrap_or(start)
+    }
+
+    /// Returns the latest end of a word after `end` up to which the text from `end` has at most `budget` tokens, or `end` if there is none.
+    fn extend_after(&self, text: &str, end: usize, budget: usize) -> usize {
+        if budget == 0 || end == text.len() {
+            return end;
+        }
+        let fits = |to: usize| self.count_tokens(&text[end..to]) <= budget;
+        let mut window = budget.saturating_mul(SEARCH_BYTES_PER_TOKEN);
+        let mut to = ceil_char_boundary(text, end.saturating_add(window));
+        while to < text.len() && fits(to) {
+            window = window.saturating_mul(2);
+            to = ceil_char_boundary(text, end.saturating_add(window));
+        }
+        let ends: Vec<usize> = text[end..to]
+            .char_indices()
+            .map(|(index, _)| end + index)
+            .chain([to])
+            .filter(|&index| index > end && is_word_end(text, index))
+            .collect();
+        match ends.partition_point(|&index| fits(index)) {
+            0 => end,
+            n_fitting => ends[n_fitting - 1],
+        }
+    }
+}
+
+/// Whether a word starts at `index`, which is at the start of the text or of a non-whitespace character after whitespace.
+fn is_word_start(text: &str, index: usize) -> bool {
+    let mut after = text[index..].chars();
+    let mut before = text[..index].chars();
+    match (before.next_back(), after.next()) {
+        (None, _) => true,
+        (Some(before), Some(after)) => before.is_whitespace() && !after.is_whitespace(),
+        (Some(_), None) => false,
+    }
+}
+
+/// Whether a word ends at `index`, which is at the end of the text or of a non-whitespace character before whitespace.
+fn is_word_end(text: &str, index: usize) -> bool {
+    let mut after = text[index..].chars();
+    let mut before = text[..index].chars();
+    match (before.next_back(), after.next()) {
+        (_, None) => true,
+        (Some(before), Some(after)) => !before.is_whitespace() && after.is_whitespace(),
+        (None, Some(_)) => false,
+    }
+}
+
+/// The largest character boundary in the text at or before `index`.
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// The smallest character boundary in the text at or after `index`, or the end of the text.
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod expand_tests {
+    use super::*;
+
+    fn chunk(text: &str, start: usize, end: usize) -> Chunk {
+        Chunk {
+            text: text[start..end].to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_expand_chunk() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "One two three four five six seven";
+        let middle = chunk(text, 14, 23);
+        assert_eq!(middle.text, "four five");
+        assert_eq!(chunker.expand_chunk(text, &middle, 0), Some(middle.clone()));
+        assert_eq!(
+            chunker.expand_chunk(text, &middle, 2).unwrap().text,
+            "three four five six"
+        );
+        assert_eq!(
+            chunker.expand_chunk(text, &middle, 3).unwrap().text,
+            "three four five six seven"
+        );
+        // Tokens one side cannot use go to the other.
+        let first = chunk(text, 0, 7);
+        assert_eq!(
+            chunker.expand_chunk(text, &first, 2).unwrap().text,
+            "One two three four"
+        );
+        let last = chunk(text, 28, 33);
+        assert_eq!(
+            chunker.expand_chunk(text, &last, 3).unwrap().text,
+            "four five six seven"
+        );
+        assert_eq!(chunker.expand_chunk(text, &middle, 100).unwrap().text, text);
+
+        // Chunks of another text are not expanded.
+        assert_eq!(chunker.expand_chunk("One two three", &middle, 2), None);
+        assert_eq!(
+            chunker.expand_chunk("Zero one two three four", &middle, 2),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand_chunk_word_boundaries() {
+        let chars = |s: &str| s.chars().count();
+        let chunker = Chunker::new(4, Box::new(chars));
+        let text = "Übermäßig große Äpfel fallen";
+        let middle = chunk(text, 19, 26);
+        assert_eq!(middle.text, " Äpfel");
+        // Words that do not fit whole are left out.
+        assert_eq!(
+            chunker.expand_chunk(text, &middle, 4).unwrap().text,
+            " Äpfel"
+        );
+        assert_eq!(
+            chunker.expand_chunk(text, &middle, 6).unwrap().text,
+            "große Äpfel"
+        );
+        let expanded = chunker.expand_chunk(text, &middle, 14).unwrap();
+        assert_eq!(expanded.text, "große Äpfel fallen");
+        assert_eq!(&text[expanded.start..expanded.end], expanded.text);
+    }
+
+    #[test]
+    fn test_expand() {
+        let chunker = Chunker::new(1, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "One two three";
+        let chunks = chunker.chunk_linked("doc", text);
+        assert_eq!(
+            chunker
+                .expand(text, &chunks, "doc-1", 2)
+                .map(|chunk| chunk.text),
+            Some(text.to_string())
+        );
+        assert_eq!(chunker.expand(text, &chunks, "doc-3", 2), None);
+    }
+}
@@ -0,0 +1,501 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Conversions from chunks into the document formats of other frameworks, enabled with the `serde` feature.
+//!
+//! * [`LangChainDocument`] for LangChain's `Document`.
+//! * [`TextNode`] for LlamaIndex's `TextNode`.
+//! * [`QdrantPoint`] and [`PineconeVector`] for upserting into Qdrant and Pinecone, with the `vector-db` feature.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::chunk::{chunk_id, Chunk, OffsetConverter};
+
+/// A document in the format of LangChain's `Document`, which its loaders and vector stores accept.
+///
+/// # Fields
+///
+/// * `page_content` - The text of the document.
+/// * `metadata` - Arbitrary metadata about the document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LangChainDocument {
+    pub page_content: String,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+}
+
+impl From<&Chunk> for LangChainDocument {
+    /// Converts a chunk into a document with its byte offsets in the `start_byte` and `end_byte` metadata.
+    fn from(chunk: &Chunk) -> Self {
+        let mut metadata = Map::new();
+        metadata.insert("start_byte".to_string(), json!(chunk.start));
+        metadata.insert("end_byte".to_string(), json!(chunk.end));
+        LangChainDocument {
+            page_content: chunk.text.clone(),
+            metadata,
+        }
+    }
+}
+
+impl Chunk {
+    /// Converts the chunk into a LangChain `Document` object.
+    ///
+    /// The byte offsets of the chunk are kept in the `start_byte` and `end_byte` metadata. Use [`langchain_documents`] for the `start_index` metadata that LangChain's splitters add.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunk;
+    /// let chunk = Chunk { text: "jumps over the lazy".to_string(), start: 20, end: 39 };
+    /// assert_eq!(
+    ///     chunk.to_langchain_json().to_string(),
+    ///     r#"{"metadata":{"end_byte":39,"start_byte":20},"page_content":"jumps over the lazy"}"#
+    /// );
+    /// ```
+    pub fn to_langchain_json(&self) -> Value {
+        serde_json::to_value(LangChainDocument::from(self)).expect("documents are valid JSON")
+    }
+}
+
+/// Converts the chunks of a text into LangChain documents, matching the output of a LangChain text splitter with `add_start_index=True`.
+///
+/// LangChain's `start_index` is in characters rather than bytes, so it is computed from the source text. The byte offsets are also kept, as in [`Chunk::to_langchain_json`].
+///
+/// # Arguments
+///
+/// * `text` - The text the chunks were produced from.
+/// * `chunks` - The chunks, in order.
+///
+/// # Returns
+///
+/// A document for each chunk.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::export::langchain_documents;
+/// use semchunk_rs::{Chunk, Chunker};
+/// let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+/// let text = "Übermäßig große Äpfel";
+/// let mut chunks: Vec<Chunk> = Vec::new();
+/// chunker.chunk_to(text, |chunk| chunks.push(chunk));
+/// let documents = langchain_documents(text, &chunks);
+/// assert_eq!(documents[1].page_content, "Äpfel");
+/// assert_eq!(documents[1].metadata["start_index"], 16);
+/// assert_eq!(documents[1].metadata["start_byte"], 20);
+/// ```
+pub fn langchain_documents(text: &str, chunks: &[Chunk]) -> Vec<LangChainDocument> {
+    let mut char_offsets = OffsetConverter::chars(text);
+    chunks
+        .iter()
+        .map(|chunk| {
+            let mut document = LangChainDocument::from(chunk);
+            let start_index = char_offsets.convert(chunk.start);
+            document
+                .metadata
+                .insert("start_index".to_string(), json!(start_index));
+            document
+        })
+        .collect()
+}
+
+/// A node in the format of LlamaIndex's `TextNode`, which can be loaded with `TextNode.model_validate`.
+///
+/// # Fields
+///
+/// * `id_` - The ID of the node.
+/// * `text` - The text of the node.
+/// * `start_char_idx` - The character offset in the source document at which the node begins.
+/// * `end_char_idx` - The character offset in the source document at which the node ends (not inclusive).
+/// * `metadata` - Arbitrary metadata about the node.
+/// * `relationships` - The nodes related to this node, keyed by LlamaIndex's `NodeRelationship` values: `"1"` for the source document, `"2"` for the previous node and `"3"` for the next node.
+/// * `class_name` - Always `"TextNode"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextNode {
+    pub id_: String,
+    pub text: String,
+    pub start_char_idx: usize,
+    pub end_char_idx: usize,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    #[serde(default)]
+    pub relationships: BTreeMap<String, RelatedNodeInfo>,
+    pub class_name: String,
+}
+
+/// A reference to a related node, in the format of LlamaIndex's `RelatedNodeInfo`.
+///
+/// # Fields
+///
+/// * `node_id` - The ID of the related node.
+/// * `node_type` - LlamaIndex's `ObjectType` of the related node: `"1"` for text nodes and `"4"` for documents.
+/// * `metadata` - Arbitrary metadata about the related node.
+/// * `class_name` - Always `"RelatedNodeInfo"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelatedNodeInfo {
+    pub node_id: String,
+    pub node_type: Option<String>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    pub class_name: String,
+}
+
+impl RelatedNodeInfo {
+    fn new(node_id: String, node_type: &str) -> Self {
+        RelatedNodeInfo {
+            node_id,
+            node_type: Some(node_type.to_string()),
+            metadata: Map::new(),
+            class_name: "RelatedNodeInfo".to_string(),
+        }
+    }
+}
+
+// LlamaIndex's `NodeRelationship` values.
+const SOURCE: &str = "1";
+const PREVIOUS: &str = "2";
+const NEXT: &str = "3";
+// LlamaIndex's `ObjectType` values.
+const TEXT: &str = "1";
+const DOCUMENT: &str = "4";
+
+/// Converts the chunks of a document into LlamaIndex text nodes, linked to the document and to each other.
+///
+/// Node IDs are the [`chunk_id`]s of the chunks, so they are stable across runs.
+///
+/// # Arguments
+///
+/// * `doc_id` - The ID of the document, which becomes the source of every node.
+/// * `text` - The text of the document.
+/// * `chunks` - The chunks of the document, in order.
+///
+/// # Returns
+///
+/// A node for each chunk.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::export::llamaindex_nodes;
+/// use semchunk_rs::{Chunk, Chunker};
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let mut chunks: Vec<Chunk> = Vec::new();
+/// chunker.chunk_to(text, |chunk| chunks.push(chunk));
+/// let nodes = llamaindex_nodes("fox", text, &chunks);
+/// assert_eq!(nodes[1].id_, "fox-1");
+/// assert_eq!((nodes[1].start_char_idx, nodes[1].end_char_idx), (20, 39));
+/// assert_eq!(nodes[1].relationships["1"].node_id, "fox");
+/// assert_eq!(nodes[1].relationships["2"].node_id, "fox-0");
+/// assert_eq!(nodes[1].relationships["3"].node_id, "fox-2");
+/// ```
+pub fn llamaindex_nodes(doc_id: &str, text: &str, chunks: &[Chunk]) -> Vec<TextNode> {
+    let mut char_offsets = OffsetConverter::chars(text);
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut relationships = BTreeMap::new();
+            relationships.insert(
+                SOURCE.to_string(),
+                RelatedNodeInfo::new(doc_id.to_string(), DOCUMENT),
+            );
+            if index > 0 {
+                relationships.insert(
+                    PREVIOUS.to_string(),
+                    RelatedNodeInfo::new(chunk_id(doc_id, index - 1), TEXT),
+                );
+            }
+            if index + 1 < chunks.len() {
+                relationships.insert(
+                    NEXT.to_string(),
+                    RelatedNodeInfo::new(chunk_id(doc_id, index + 1), TEXT),
+                );
+            }
+            TextNode {
+                id_: chunk_id(doc_id, index),
+                text: chunk.text.clone(),
+                start_char_idx: char_offsets.convert(chunk.start),
+                end_char_idx: char_offsets.convert(chunk.end),
+                metadata: Map::new(),
+                relationships,
+                class_name: "TextNode".to_string(),
+            }
+        })
+        .collect()
+}
+
+/// A point in the format of Qdrant's `PointStruct`, ready to upsert once its vector is filled in.
+///
+/// # Fields
+///
+/// * `id` - A UUID derived from the [`chunk_id`] of the chunk, since Qdrant IDs must be UUIDs or integers.
+/// * `vector` - The embedding of the chunk, empty until it is filled in.
+/// * `payload` - The text of the chunk, its byte offsets, its document and index, and the document's metadata.
+#[cfg(feature = "vector-db")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QdrantPoint {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub payload: Map<String, Value>,
+}
+
+/// A vector in the format of Pinecone's upsert request, ready to upsert once its values are filled in.
+///
+/// # Fields
+///
+/// * `id` - The [`chunk_id`] of the chunk.
+/// * `values` - The embedding of the chunk, empty until it is filled in.
+/// * `metadata` - The text of the chunk, its byte offsets, its document and index, and the document's metadata.
+#[cfg(feature = "vector-db")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PineconeVector {
+    pub id: String,
+    pub values: Vec<f32>,
+    pub metadata: Map<String, Value>,
+}
+
+/// Returns the payload of a vector-DB record for the chunk at `index` in a document.
+#[cfg(feature = "vector-db")]
+fn record_payload(
+    doc_id: &str,
+    index: usize,
+    chunk: &Chunk,
+    metadata: &Map<String, Value>,
+) -> Map<String, Value> {
+    let mut payload = metadata.clone();
+    payload.insert("text".to_string(), json!(chunk.text));
+    payload.insert("start_byte".to_string(), json!(chunk.start));
+    payload.insert("end_byte".to_string(), json!(chunk.end));
+    payload.insert("doc_id".to_string(), json!(doc_id));
+    payload.insert("chunk_index".to_string(), json!(index));
+    payload
+}
+
+/// Converts the chunks of a document into Qdrant points.
+///
+/// Point IDs are version 5 UUIDs of the chunks' [`chunk_id`]s, so re-ingesting a document overwrites its points rather than duplicating them.
+///
+/// # Arguments
+///
+/// * `doc_id` - The ID of the document.
+/// * `chunks` - The chunks of the document, in order.
+/// * `metadata` - Metadata about the document, copied into the payload of every point.
+///
+/// # Returns
+///
+/// A point for each chunk, with an empty vector.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::export::qdrant_points;
+/// use semchunk_rs::Chunk;
+/// use serde_json::{json, Map};
+/// let chunks = [Chunk { text: "The quick brown fox".to_string(), start: 0, end: 19 }];
+/// let mut metadata = Map::new();
+/// metadata.insert("source".to_string(), json!("fox.txt"));
+/// let points = qdrant_points("fox", &chunks, &metadata);
+/// assert_eq!(points[0].id, "775817ab-8eb9-5667-8199-36c6c248e529");
+/// assert_eq!(points[0].payload["text"], "The quick brown fox");
+/// assert_eq!(points[0].payload["source"], "fox.txt");
+/// ```
+#[cfg(feature = "vector-db")]
+pub fn qdrant_points(
+    doc_id: &str,
+    chunks: &[Chunk],
+    metadata: &Map<String, Value>,
+) -> Vec<QdrantPoint> {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let id = chunk_id(doc_id, index);
+            QdrantPoint {
+                id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, id.as_bytes()).to_string(),
+                vector: Vec::new(),
+                payload: record_payload(doc_id, index, chunk, metadata),
+            }
+        })
+        .collect()
+}
+
+/// Converts the chunks of a document into Pinecone vectors.
+///
+/// # Arguments
+///
+/// * `doc_id` - The ID of the document.
+/// * `chunks` - The chunks of the document, in order.
+/// * `metadata` - Metadata about the document, copied into the metadata of every vector.
+///
+/// # Returns
+///
+/// A vector for each chunk, with empty values.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::export::pinecone_vectors;
+/// use semchunk_rs::Chunk;
+/// use serde_json::Map;
+/// let chunks = [Chunk { text: "The quick brown fox".to_string(), start: 0, end: 19 }];
+/// let vectors = pinecone_vectors("fox", &chunks, &Map::new());
+/// assert_eq!(vectors[0].id, "fox-0");
+/// assert_eq!(vectors[0].metadata["end_byte"], 19);
+/// ```
+#[cfg(feature = "vector-db")]
+pub fn pinecone_vectors(
+    doc_id: &str,
+    chunks: &[Chunk],
+    metadata: &Map<String, Value>,
+) -> Vec<PineconeVector> {
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| PineconeVector {
+            id: chunk_id(doc_id, index),
+            values: Vec::new(),
+            metadata: record_payload(doc_id, index, chunk, metadata),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+
+    #[test]
+    fn test_langchain_document_round_trip() {
+        let chunk = Chunk {
+            text: "dog.".to_string(),
+            start: 40,
+            end: 44,
+        };
+        let json = chunk.to_langchain_json();
+        let document: LangChainDocument = serde_json::from_value(json).unwrap();
+        assert_eq!(document, LangChainDocument::from(&chunk));
+
+        // Documents from Python may omit the metadata.
+        let document: LangChainDocument =
+            serde_json::from_str(r#"{"page_content": "dog."}"#).unwrap();
+        assert!(document.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_llamaindex_nodes() {
+        let text = "é a b é";
+        let chunks = [
+            Chunk {
+                text: "é a b".to_string(),
+                start: 0,
+                end: 6,
+            },
+            Chunk {
+                text: "b é".to_string(),
+                start: 5,
+                end: 9,
+            },
+        ];
+        let nodes = llamaindex_nodes("doc", text, &chunks);
+        let offsets: Vec<(usize, usize)> = nodes
+            .iter()
+            .map(|node| (node.start_char_idx, node.end_char_idx))
+            .collect();
+        assert_eq!(offsets, vec![(0, 5), (4, 7)]);
+        assert!(!nodes[0].relationships.contains_key(PREVIOUS));
+        assert!(!nodes[1].relationships.contains_key(NEXT));
+
+        let json = serde_json::to_value(&nodes[0]).unwrap();
+        assert_eq!(json["class_name"], "TextNode");
+        assert_eq!(json["relationships"]["1"]["node_type"], "4");
+        assert_eq!(json["relationships"]["3"]["node_id"], "doc-1");
+        assert_eq!(serde_json::from_value::<TextNode>(json).unwrap(), nodes[0]);
+    }
+
+    #[test]
+    fn test_langchain_documents_start_index() {
+        let text = "aé b\ncé d";
+        let chunks = [
+            Chunk {
+                text: "aé b".to_string(),
+                start: 0,
+                end: 5,
+            },
+            Chunk {
+                text: "cé d".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+        let start_indices: Vec<Value> = langchain_documents(text, &chunks)
+            .into_iter()
+            .map(|document| document.metadata["start_index"].clone())
+            .collect();
+        assert_eq!(start_indices, vec![json!(0), json!(5)]);
+    }
+
+    #[cfg(feature = "vector-db")]
+    #[test]
+    fn test_vector_db_records() {
+        let chunks = [
+            Chunk {
+                text: "The quick".to_string(),
+                start: 0,
+                end: 9,
+            },
+            Chunk {
+                text: "brown fox".to_string(),
+                start: 10,
+                end: 19,
+            },
+        ];
+        let mut metadata = Map::new();
+        metadata.insert("title".to_string(), json!("Foxes"));
+        let points = qdrant_points("fox", &chunks, &metadata);
+        assert_ne!(points[0].id, points[1].id);
+        assert_eq!(points, qdrant_points("fox", &chunks, &metadata));
+        assert_eq!(
+            serde_json::to_value(&points[1]).unwrap(),
+            json!({
+                "id": points[1].id,
+                "vector": [],
+                "payload": {
+                    "title": "Foxes",
+                    "text": "brown fox",
+                    "start_byte": 10,
+                    "end_byte": 19,
+                    "doc_id": "fox",
+                    "chunk_index": 1,
+                },
+            })
+        );
+
+        let vectors = pinecone_vectors("fox", &chunks, &metadata);
+        assert_eq!(vectors[1].id, "fox-1");
+        assert_eq!(vectors[1].metadata, points[1].payload);
+        let json = serde_json::to_value(&vectors[1]).unwrap();
+        assert_eq!(json["values"], json!([]));
+    }
+}
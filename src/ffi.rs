@@ -0,0 +1,234 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! C bindings, built with the `ffi` feature. The declarations are in `include/semchunk.h`.
+//!
+//! Chunkers are created with [`semchunk_new`] and freed with [`semchunk_free`]. [`semchunk_chunk`] returns a list of chunks that must be freed with [`semchunk_chunks_free`].
+
+use std::ffi::{c_char, c_void};
+use std::slice;
+
+use crate::chunker::Chunker;
+
+/// A function that counts the number of tokens in `len` bytes of UTF-8 text (not NUL-terminated).
+///
+/// `user_data` is the pointer given to [`semchunk_new`].
+pub type SemchunkTokenCounter =
+    Option<unsafe extern "C" fn(text: *const c_char, len: usize, user_data: *mut c_void) -> usize>;
+
+/// An opaque chunker.
+pub struct SemchunkChunker {
+    chunker: Chunker,
+}
+
+/// A chunk, with its text and the byte range it covers in the source text.
+///
+/// `text` points to `text_len` bytes of UTF-8 followed by a NUL byte, and lives as long as the list it came from.
+#[repr(C)]
+pub struct SemchunkChunk {
+    pub text: *const c_char,
+    pub text_len: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// An opaque list of chunks.
+pub struct SemchunkChunks {
+    chunks: Vec<SemchunkChunk>,
+    // Owns the NUL-terminated text the chunks point to.
+    _texts: Vec<Box<[u8]>>,
+}
+
+/// Creates a chunker, returning NULL if `chunk_size` is zero.
+///
+/// If `counter` is NULL, tokens are counted as whitespace-separated words and `user_data` is ignored.
+///
+/// # Safety
+///
+/// `counter`, if given, must be safe to call with `user_data` for as long as the chunker lives.
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_new(
+    chunk_size: usize,
+    counter: SemchunkTokenCounter,
+    user_data: *mut c_void,
+) -> *mut SemchunkChunker {
+    if chunk_size == 0 {
+        return std::ptr::null_mut();
+    }
+    let token_counter: Box<dyn Fn(&str) -> usize> = match counter {
+        Some(counter) => {
+            Box::new(move |s: &str| counter(s.as_ptr() as *const c_char, s.len(), user_data))
+        }
+        None => Box::new(|s: &str| s.split_whitespace().count()),
+    };
+    Box::into_raw(Box::new(SemchunkChunker {
+        chunker: Chunker::new(chunk_size, token_counter),
+    }))
+}
+
+/// Chunks `len` bytes of UTF-8 text, returning NULL if the text is not valid UTF-8.
+///
+/// # Safety
+///
+/// `chunker` must come from [`semchunk_new`] and `text` must point to at least `len` readable bytes. The chunker keeps state between calls, so it must not be used from two threads at once.
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_chunk(
+    chunker: *mut SemchunkChunker,
+    text: *const c_char,
+    len: usize,
+) -> *mut SemchunkChunks {
+    if chunker.is_null() || (text.is_null() && len > 0) {
+        return std::ptr::null_mut();
+    }
+    let bytes = match len {
+        0 => &[],
+        _ => slice::from_raw_parts(text as *const u8, len),
+    };
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return std::ptr::null_mut();
+    };
+    let mut chunks = Vec::new();
+    let mut texts = Vec::new();
+    (*chunker).chunker.chunk_to(text, |chunk| {
+        let text_len = chunk.text.len();
+        let mut bytes = chunk.text.into_bytes();
+        bytes.push(0);
+        let bytes = bytes.into_boxed_slice();
+        chunks.push(SemchunkChunk {
+            text: bytes.as_ptr() as *const c_char,
+            text_len,
+            start: chunk.start,
+            end: chunk.end,
+        });
+        texts.push(bytes);
+    });
+    Box::into_raw(Box::new(SemchunkChunks {
+        chunks,
+        _texts: texts,
+    }))
+}
+
+/// Returns the number of chunks in the list.
+///
+/// # Safety
+///
+/// `chunks` must come from [`semchunk_chunk`].
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_chunks_len(chunks: *const SemchunkChunks) -> usize {
+    match chunks.as_ref() {
+        Some(chunks) => chunks.chunks.len(),
+        None => 0,
+    }
+}
+
+/// Returns the chunk at `index`, or NULL if it is out of bounds.
+///
+/// # Safety
+///
+/// `chunks` must come from [`semchunk_chunk`].
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_chunks_get(
+    chunks: *const SemchunkChunks,
+    index: usize,
+) -> *const SemchunkChunk {
+    match chunks.as_ref().and_then(|chunks| chunks.chunks.get(index)) {
+        Some(chunk) => chunk,
+        None => std::ptr::null(),
+    }
+}
+
+/// Frees a list of chunks. Does nothing if `chunks` is NULL.
+///
+/// # Safety
+///
+/// `chunks` must come from [`semchunk_chunk`] and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_chunks_free(chunks: *mut SemchunkChunks) {
+    if !chunks.is_null() {
+        drop(Box::from_raw(chunks));
+    }
+}
+
+/// Frees a chunker. Does nothing if `chunker` is NULL.
+///
+/// # Safety
+///
+/// `chunker` must come from [`semchunk_new`] and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn semchunk_free(chunker: *mut SemchunkChunker) {
+    if !chunker.is_null() {
+        drop(Box::from_raw(chunker));
+    }
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    unsafe extern "C" fn count_spaces(
+        text: *const c_char,
+        len: usize,
+        user_data: *mut c_void,
+    ) -> usize {
+        *(user_data as *mut usize) += 1;
+        let text = slice::from_raw_parts(text as *const u8, len);
+        text.iter().filter(|&&b| b == b' ').count() + 1
+    }
+
+    #[test]
+    fn test_ffi_chunk() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let mut calls: usize = 0;
+        unsafe {
+            let chunker = semchunk_new(
+                4,
+                Some(count_spaces),
+                &mut calls as *mut usize as *mut c_void,
+            );
+            assert!(!chunker.is_null());
+            let chunks = semchunk_chunk(chunker, text.as_ptr() as *const c_char, text.len());
+            assert_eq!(semchunk_chunks_len(chunks), 3);
+            let chunk = &*semchunk_chunks_get(chunks, 1);
+            assert_eq!(
+                CStr::from_ptr(chunk.text).to_str().unwrap(),
+                "jumps over the lazy"
+            );
+            assert_eq!((chunk.text_len, chunk.start, chunk.end), (19, 20, 39));
+            assert!(semchunk_chunks_get(chunks, 3).is_null());
+            semchunk_chunks_free(chunks);
+            semchunk_free(chunker);
+        }
+        assert!(calls > 0);
+    }
+
+    #[test]
+    fn test_ffi_invalid_input() {
+        unsafe {
+            assert!(semchunk_new(0, None, std::ptr::null_mut()).is_null());
+            let chunker = semchunk_new(4, None, std::ptr::null_mut());
+            let invalid = [0xffu8, 0xfe];
+            assert!(semchunk_chunk(chunker, invalid.as_ptr() as *const c_char, 2).is_null());
+            semchunk_free(chunker);
+        }
+    }
+}
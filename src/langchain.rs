@@ -0,0 +1,108 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A langchain-rust `TextSplitter` backed by a [`Chunker`], enabled with the `langchain-rust` feature, so that langchain-rust pipelines can chunk documents with semchunk-rs.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use langchain_rust::text_splitter::{TextSplitter, TextSplitterError};
+
+use crate::Chunker;
+
+/// A langchain-rust `TextSplitter` that splits texts with a [`Chunker`].
+///
+/// Text splitters must be `Send + Sync`, which chunkers are not, as token counters are often not thread-safe, so the splitter builds a chunker for each text it splits with `new_chunker`. Chunking is CPU-bound, so it runs on Tokio's blocking threads rather than the async executor, and splitting must be awaited within a Tokio runtime.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "langchain-rust")]
+/// # {
+/// use langchain_rust::text_splitter::TextSplitter;
+/// use semchunk_rs::langchain::SemchunkSplitter;
+/// use semchunk_rs::Chunker;
+///
+/// let splitter = SemchunkSplitter::new(|| Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count())));
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let chunks = runtime.block_on(splitter.split_text("The quick brown fox jumps over the lazy dog.")).unwrap();
+/// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+/// # }
+/// ```
+pub struct SemchunkSplitter {
+    new_chunker: Arc<dyn Fn() -> Chunker + Send + Sync>,
+}
+
+impl SemchunkSplitter {
+    /// Creates a text splitter.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_chunker` - A function that builds the chunker to split a text with.
+    pub fn new(new_chunker: impl Fn() -> Chunker + Send + Sync + 'static) -> Self {
+        SemchunkSplitter {
+            new_chunker: Arc::new(new_chunker),
+        }
+    }
+}
+
+#[async_trait]
+impl TextSplitter for SemchunkSplitter {
+    async fn split_text(&self, text: &str) -> Result<Vec<String>, TextSplitterError> {
+        let new_chunker = self.new_chunker.clone();
+        let text = text.to_string();
+        tokio::task::spawn_blocking(move || new_chunker().chunk(&text))
+            .await
+            .map_err(|e| TextSplitterError::OtherError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod langchain_tests {
+    use std::collections::HashMap;
+
+    use langchain_rust::schemas::Document;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_split_documents() {
+        let splitter = SemchunkSplitter::new(|| {
+            Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))
+        });
+        let text = "The quick brown fox jumps over the lazy dog.";
+        assert_eq!(
+            splitter.split_text(text).await.unwrap(),
+            (splitter.new_chunker)().chunk(text)
+        );
+
+        let metadata = HashMap::from([("source".to_string(), "fox.txt".into())]);
+        let documents = [Document::new(text).with_metadata(metadata.clone())];
+        let chunks = splitter.split_documents(&documents).await.unwrap();
+        let texts: Vec<_> = chunks.iter().map(|d| d.page_content.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["The quick brown fox", "jumps over the lazy", "dog."]
+        );
+        assert!(chunks.iter().all(|d| d.metadata == metadata));
+    }
+}
@@ -0,0 +1,83 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Language detection for chunks, enabled with the `whatlang` feature, for multilingual corpora whose chunks go to language-specific embedders.
+
+pub use whatlang::{Lang, Script};
+
+use crate::chunk::Chunk;
+
+/// Detects the language of a text with whatlang.
+///
+/// # Arguments
+///
+/// * `text` - The text whose language to detect.
+///
+/// # Returns
+///
+/// The detected language, or `None` if the text has no letters to detect it from.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::language::{detect_language, Lang};
+/// assert_eq!(detect_language("Le renard brun saute par-dessus le chien paresseux."), Some(Lang::Fra));
+/// assert_eq!(detect_language("12 345"), None);
+/// ```
+pub fn detect_language(text: &str) -> Option<Lang> {
+    whatlang::detect_lang(text)
+}
+
+impl Chunk {
+    /// Detects the language of the chunk's text with whatlang.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::language::Lang;
+    /// use semchunk_rs::Chunk;
+    /// let chunk = Chunk { text: "Der schnelle braune Fuchs springt über den faulen Hund.".to_string(), start: 0, end: 56 };
+    /// assert_eq!(chunk.language(), Some(Lang::Deu));
+    /// assert_eq!(chunk.language().unwrap().code(), "deu");
+    /// ```
+    pub fn language(&self) -> Option<Lang> {
+        detect_language(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod language_tests {
+    use super::*;
+    use crate::Chunker;
+
+    #[test]
+    fn test_chunk_languages() {
+        let text = "The quick brown fox jumps over the lazy dog.\nEl rápido zorro marrón salta sobre el perro perezoso.\nБыстрая коричневая лиса прыгает через ленивую собаку.";
+        let chunker = Chunker::new(10, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut languages = Vec::new();
+        chunker.chunk_to(text, |chunk| languages.push(chunk.language()));
+        assert_eq!(
+            languages,
+            [Some(Lang::Eng), Some(Lang::Spa), Some(Lang::Rus)]
+        );
+    }
+}
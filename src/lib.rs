@@ -54,8 +54,54 @@
 //! assert_eq!(chunks, vec!["The quick brown fox", "jumps over the", "lazy dog."]);
 //! ```
 
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod checkpoint;
+pub mod chunk;
 pub mod chunker;
+#[cfg(feature = "python-compat")]
+pub mod compat;
+pub mod counters;
+pub mod coverage;
+pub mod dedup;
+pub mod embed;
+pub mod expand;
+#[cfg(feature = "serde")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "langchain-rust")]
+pub mod langchain;
+#[cfg(feature = "whatlang")]
+pub mod language;
+pub mod metrics;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+pub mod models;
+pub mod parallel;
+pub mod plan;
+pub mod preprocess;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod resilience;
+pub mod segment;
+pub mod semantic;
+#[cfg(feature = "serve")]
+pub mod serve;
 pub mod splitter;
+pub mod stats;
+#[cfg(feature = "sqlite")]
+pub mod store;
+pub mod template;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(any(feature = "ndjson", feature = "parquet", feature = "compression"))]
+pub mod writers;
 
-pub use chunker::Chunker;
+pub use chunk::Chunk;
+pub use chunker::{Chunker, Chunking};
+pub use counters::TokenCounter;
 pub use splitter::Splitter;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
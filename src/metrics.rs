@@ -0,0 +1,197 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Metrics for comparing how well chunker configurations chunk a text.
+
+use std::collections::BTreeMap;
+
+use crate::chunk::Chunk;
+use crate::chunker::ends_sentence;
+use crate::splitter::{punctuation_kind, SeparatorKind, Splitter};
+
+/// Quality metrics for the chunks of a text.
+///
+/// # Fields
+///
+/// * `chunks` - The number of chunks.
+/// * `mean_tokens` - The mean number of tokens in a chunk.
+/// * `token_variance` - The variance of the number of tokens in a chunk.
+/// * `utilization` - The mean fraction of the chunk size used by a chunk.
+/// * `mid_sentence_boundaries` - The number of boundaries between chunks that fall inside a sentence.
+/// * `separator_usage` - The number of boundaries between chunks at each level of the hierarchy of separators, counted like [`ChunkStats::separators`](crate::stats::ChunkStats::separators).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    pub chunks: usize,
+    pub mean_tokens: f64,
+    pub token_variance: f64,
+    pub utilization: f64,
+    pub mid_sentence_boundaries: usize,
+    pub separator_usage: BTreeMap<SeparatorKind, usize>,
+}
+
+/// Evaluates the chunks of a text.
+///
+/// # Arguments
+///
+/// * `text` - The text the chunks were produced from.
+/// * `chunks` - The chunks, in order.
+/// * `token_counter` - A function that counts the number of tokens in a string.
+/// * `chunk_size` - The chunk size the chunks were produced with.
+///
+/// # Returns
+///
+/// The metrics of the chunks.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::metrics::evaluate;
+/// use semchunk_rs::splitter::SeparatorKind;
+/// use semchunk_rs::{Chunk, Chunker};
+///
+/// let token_counter = |s: &str| s.split_whitespace().count();
+/// let chunker = Chunker::new(4, Box::new(token_counter));
+/// let text = "The quick brown fox jumps over the lazy dog.";
+/// let mut chunks: Vec<Chunk> = Vec::new();
+/// chunker.chunk_to(text, |chunk| chunks.push(chunk));
+///
+/// let metrics = evaluate(text, &chunks, &token_counter, 4);
+/// assert_eq!(metrics.chunks, 3);
+/// assert_eq!(metrics.utilization, 0.75);
+/// assert_eq!(metrics.mid_sentence_boundaries, 2);
+/// assert_eq!(metrics.separator_usage[&SeparatorKind::Whitespace], 2);
+/// ```
+pub fn evaluate(
+    text: &str,
+    chunks: &[Chunk],
+    token_counter: &dyn Fn(&str) -> usize,
+    chunk_size: usize,
+) -> Metrics {
+    let token_counts: Vec<f64> = chunks
+        .iter()
+        .map(|chunk| token_counter(&chunk.text) as f64)
+        .collect();
+    let mean_tokens = mean(&token_counts);
+    let token_variance = mean(
+        &token_counts
+            .iter()
+            .map(|count| (count - mean_tokens).powi(2))
+            .collect::<Vec<f64>>(),
+    );
+
+    let mut mid_sentence_boundaries = 0;
+    let mut separator_usage = BTreeMap::new();
+    let splitter = Splitter::default();
+    for chunk in chunks.iter().take(chunks.len().saturating_sub(1)) {
+        let kind = boundary_kind(&splitter, text, chunk);
+        *separator_usage.entry(kind).or_insert(0) += 1;
+        if kind != SeparatorKind::ParagraphBreak && !ends_sentence(&[chunk.text.as_str()]) {
+            mid_sentence_boundaries += 1;
+        }
+    }
+
+    Metrics {
+        chunks: chunks.len(),
+        mean_tokens,
+        token_variance,
+        utilization: match chunk_size {
+            0 => 0.0,
+            _ => mean_tokens / chunk_size as f64,
+        },
+        mid_sentence_boundaries,
+        separator_usage,
+    }
+}
+
+/// Returns the kind of the separator at the end of a chunk, which is the whitespace that follows it, as the splitter would split it, or else the punctuation it ends with.
+fn boundary_kind(splitter: &Splitter, text: &str, chunk: &Chunk) -> SeparatorKind {
+    let following = &text[chunk.end..];
+    let whitespace = &following[..following.len() - following.trim_start().len()];
+    match whitespace.is_empty() {
+        false => splitter.splits(whitespace).0.kind,
+        true => punctuation_kind(&chunk.text).unwrap_or(SeparatorKind::None),
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        n => values.iter().sum::<f64>() / n as f64,
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    fn chunk(text: &str, start: usize) -> Chunk {
+        Chunk {
+            text: text.to_string(),
+            start,
+            end: start + text.len(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let text = "One two.\n\nThree (four)\tfive,six";
+        let chunks = [
+            chunk("One two.", 0),
+            chunk("Three (four)", 10),
+            chunk("five,", 23),
+            chunk("six", 28),
+        ];
+        let metrics = evaluate(text, &chunks, &|s: &str| s.split([' ', ',']).count(), 4);
+        assert_eq!(metrics.chunks, 4);
+        assert_eq!(metrics.mean_tokens, 7.0 / 4.0);
+        assert!((metrics.token_variance - 0.1875).abs() < 1e-9);
+        assert_eq!(metrics.mid_sentence_boundaries, 2);
+        assert_eq!(
+            metrics.separator_usage,
+            BTreeMap::from([
+                (SeparatorKind::ParagraphBreak, 1),
+                (SeparatorKind::Tab, 1),
+                (SeparatorKind::ClauseSeparator, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_evaluate_agrees_with_stats() {
+        let token_counter = |s: &str| s.split_whitespace().count();
+        let chunker = crate::Chunker::new(3, Box::new(token_counter));
+        let text = "One two three.\n\nFour five\tsix seven, eight nine ten eleven.";
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk));
+        let (_, stats) = chunker.chunk_with_stats(text);
+        let metrics = evaluate(text, &chunks, &token_counter, 3);
+        assert_eq!(metrics.separator_usage, stats.separators);
+    }
+
+    #[test]
+    fn test_evaluate_empty() {
+        let metrics = evaluate("", &[], &|s: &str| s.len(), 4);
+        assert_eq!(metrics.chunks, 0);
+        assert_eq!(metrics.utilization, 0.0);
+        assert!(metrics.separator_usage.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Swift and Kotlin bindings generated with UniFFI, built with the `uniffi` feature.
+//!
+//! Generate the bindings from the compiled library with the bundled `uniffi-bindgen` binary:
+//!
+//! ```sh
+//! cargo rustc --release --lib --features uniffi --crate-type cdylib
+//! cargo run --features uniffi --bin uniffi-bindgen -- generate \
+//!     --library target/release/libsemchunk_rs.so --language swift --out-dir bindings
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::chunk::OffsetConverter;
+use crate::chunker;
+
+/// A token counter built into the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum BuiltinCounter {
+    /// Whitespace-separated words.
+    Words,
+    /// Unicode code points.
+    Chars,
+}
+
+/// A token counter implemented in Swift or Kotlin.
+#[uniffi::export(with_foreign)]
+pub trait TokenCounter: Send + Sync {
+    fn count_tokens(&self, text: String) -> u64;
+}
+
+/// A chunk with the range it covers in the source text, in UTF-16 code units.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TextChunk {
+    pub text: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// An error creating a chunker.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum ChunkerError {
+    /// The chunker's configuration is invalid, such as a chunk size of zero.
+    InvalidConfig { message: String },
+}
+
+impl fmt::Display for ChunkerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChunkerError::InvalidConfig { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ChunkerError {}
+
+impl From<chunker::ConfigError> for ChunkerError {
+    fn from(error: chunker::ConfigError) -> Self {
+        ChunkerError::InvalidConfig {
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Counter {
+    Builtin(BuiltinCounter),
+    Foreign(Arc<dyn TokenCounter>),
+}
+
+/// A chunker usable from Swift and Kotlin.
+#[derive(uniffi::Object)]
+pub struct Chunker {
+    chunk_size: usize,
+    counter: Counter,
+}
+
+impl Chunker {
+    /// Creates a chunker, failing if a chunker with its configuration cannot be built.
+    fn try_new(chunk_size: u64, counter: Counter) -> Result<Arc<Self>, ChunkerError> {
+        let chunker = Chunker {
+            chunk_size: chunk_size as usize,
+            counter,
+        };
+        chunker::Chunker::try_new(chunker.chunk_size, chunker.token_counter())?;
+        Ok(Arc::new(chunker))
+    }
+
+    // Foreign objects must be shareable across threads, which the chunker's boxed counter is not, so one is built per call.
+    fn chunker(&self) -> chunker::Chunker {
+        chunker::Chunker::new(self.chunk_size, self.token_counter())
+    }
+
+    fn token_counter(&self) -> Box<dyn Fn(&str) -> usize> {
+        match self.counter.clone() {
+            Counter::Builtin(BuiltinCounter::Words) => {
+                Box::new(|s: &str| s.split_whitespace().count())
+            }
+            Counter::Builtin(BuiltinCounter::Chars) => Box::new(|s: &str| s.chars().count()),
+            Counter::Foreign(counter) => {
+                Box::new(move |s: &str| counter.count_tokens(s.to_string()) as usize)
+            }
+        }
+    }
+}
+
+#[uniffi::export]
+impl Chunker {
+    /// Creates a chunker that counts tokens with a built-in counter, failing if the chunk size is zero.
+    #[uniffi::constructor]
+    pub fn new(chunk_size: u64, counter: BuiltinCounter) -> Result<Arc<Self>, ChunkerError> {
+        Chunker::try_new(chunk_size, Counter::Builtin(counter))
+    }
+
+    /// Creates a chunker that counts tokens with a counter implemented in Swift or Kotlin, failing if the chunk size is zero.
+    #[uniffi::constructor]
+    pub fn with_counter(
+        chunk_size: u64,
+        counter: Arc<dyn TokenCounter>,
+    ) -> Result<Arc<Self>, ChunkerError> {
+        Chunker::try_new(chunk_size, Counter::Foreign(counter))
+    }
+
+    /// Chunks the text.
+    pub fn chunk(&self, text: String) -> Vec<String> {
+        self.chunker().chunk(&text)
+    }
+
+    /// Chunks the text, returning each chunk with its UTF-16 offsets in the text.
+    pub fn chunk_with_offsets(&self, text: String) -> Vec<TextChunk> {
+        let mut chunks = Vec::new();
+        let mut offsets = OffsetConverter::utf16(&text);
+        self.chunker().chunk_to(&text, |chunk| {
+            chunks.push(TextChunk {
+                start: offsets.convert(chunk.start) as u64,
+                end: offsets.convert(chunk.end) as u64,
+                text: chunk.text,
+            })
+        });
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod mobile_tests {
+    use super::*;
+
+    struct CountSpaces;
+
+    impl TokenCounter for CountSpaces {
+        fn count_tokens(&self, text: String) -> u64 {
+            text.matches(' ').count() as u64 + 1
+        }
+    }
+
+    #[test]
+    fn test_chunk_with_offsets() {
+        let chunker = Chunker::with_counter(4, Arc::new(CountSpaces)).unwrap();
+        let text = "“Quick” brown fox jumps over the lazy dog.";
+        let chunks = chunker.chunk_with_offsets(text.to_string());
+        assert_eq!(chunks[0].text, "“Quick” brown fox jumps");
+        // The curly quotes are 3 bytes in UTF-8 but one UTF-16 code unit.
+        assert_eq!((chunks[1].start, chunks[1].end), (24, 42));
+
+        let chunker = Chunker::new(4, BuiltinCounter::Words).unwrap();
+        assert_eq!(chunker.chunk(text.to_string()).len(), 2);
+    }
+
+    #[test]
+    fn test_zero_chunk_size() {
+        let expected = ChunkerError::from(chunker::ConfigError::ZeroChunkSize);
+        assert_eq!(
+            Chunker::new(0, BuiltinCounter::Words).err(),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            Chunker::with_counter(0, Arc::new(CountSpaces)).err(),
+            Some(expected)
+        );
+    }
+}
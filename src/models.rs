@@ -0,0 +1,337 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Resolving token counters from model names, as the Python semchunk's `chunkerify` does.
+//!
+//! Model names are looked up in a [`Registry`] of user-registered token counters before falling back to the tokenizers built into the enabled features.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::Chunker;
+
+/// The error returned when a model's tokenizer cannot be resolved or loaded.
+pub type ModelError = Box<dyn Error + Send + Sync>;
+
+/// A thread-safe, boxed token counter resolved from a model name, which implements [`TokenCounter`](crate::counters::TokenCounter) like any closure.
+pub type BoxedTokenCounter = Box<dyn Fn(&str) -> usize + Send + Sync>;
+
+/// A function that loads a token counter, registered under a model name in a [`Registry`].
+pub type TokenCounterFactory = Arc<dyn Fn() -> Result<BoxedTokenCounter, ModelError> + Send + Sync>;
+
+/// A registry mapping model names to token counters, so chunkers can be constructed from a configuration string.
+///
+/// Registered models take precedence over the built-in ones (see [`token_counter`]), which the registry falls back to for unregistered names. Models can be registered at runtime, either in the [global](Registry::global) registry used by [`Chunker::for_model`] or in a standalone one.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::models::Registry;
+/// let registry = Registry::default();
+/// registry.register_fn("words", |s: &str| s.split_whitespace().count());
+/// let chunker = registry.chunker("words", 4).unwrap();
+/// assert_eq!(
+///     chunker.chunk("The quick brown fox jumps over the lazy dog."),
+///     vec!["The quick brown fox", "jumps over the lazy", "dog."]
+/// );
+/// ```
+#[derive(Default)]
+pub struct Registry {
+    factories: RwLock<HashMap<String, TokenCounterFactory>>,
+}
+
+impl Registry {
+    /// The global registry, which [`token_counter`] and [`Chunker::for_model`] resolve model names with.
+    pub fn global() -> &'static Registry {
+        static GLOBAL: OnceLock<Registry> = OnceLock::new();
+        GLOBAL.get_or_init(Registry::default)
+    }
+
+    /// Registers a function that loads the token counter for `model`, replacing any previously registered under that name.
+    ///
+    /// The function is called each time a token counter for the model is resolved, so expensive tokenizers are only loaded when they are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name to register the token counter under.
+    /// * `factory` - A function that loads the token counter.
+    pub fn register<F>(&self, model: &str, factory: F)
+    where
+        F: Fn() -> Result<BoxedTokenCounter, ModelError> + Send + Sync + 'static,
+    {
+        self.factories
+            .write()
+            .unwrap()
+            .insert(model.to_string(), Arc::new(factory));
+    }
+
+    /// Registers a token counter for `model`, which is shared by every chunker resolved from that name.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name to register the token counter under.
+    /// * `counter` - A function that counts the number of tokens in a string.
+    pub fn register_fn<F>(&self, model: &str, counter: F)
+    where
+        F: Fn(&str) -> usize + Send + Sync + 'static,
+    {
+        let counter = Arc::new(counter);
+        self.register(model, move || {
+            let counter = Arc::clone(&counter);
+            Ok(Box::new(move |text: &str| counter(text)))
+        });
+    }
+
+    /// Removes the token counter registered for `model`, returning whether one was registered.
+    pub fn unregister(&self, model: &str) -> bool {
+        self.factories.write().unwrap().remove(model).is_some()
+    }
+
+    /// Returns the names of the registered models, in sorted order.
+    pub fn models(&self) -> Vec<String> {
+        let mut models: Vec<String> = self.factories.read().unwrap().keys().cloned().collect();
+        models.sort();
+        models
+    }
+
+    /// Resolves the token counter for a model name, preferring registered models over the built-in ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model or encoding.
+    ///
+    /// # Returns
+    ///
+    /// The model's token counter, or an error if the model is unknown or its tokenizer fails to load.
+    pub fn token_counter(&self, model: &str) -> Result<BoxedTokenCounter, ModelError> {
+        // Release the lock before loading so factories may use the registry themselves.
+        let factory = self.factories.read().unwrap().get(model).cloned();
+        match factory {
+            Some(factory) => factory(),
+            None => builtin_token_counter(model),
+        }
+    }
+
+    /// Creates a Chunker whose token counter is resolved from a model name.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model or encoding.
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    ///
+    /// # Returns
+    ///
+    /// A new Chunker instance, or an error if the model's token counter cannot be resolved.
+    pub fn chunker(&self, model: &str, chunk_size: usize) -> Result<Chunker, ModelError> {
+        Ok(Chunker::new(chunk_size, self.token_counter(model)?))
+    }
+}
+
+/// Resolves the token counter for a model name.
+///
+/// The name is resolved, in order, as:
+/// 1. A model registered in the [global registry](Registry::global).
+/// 2. An OpenAI model name (e.g. `gpt-4o`) or tiktoken encoding name (e.g. `cl100k_base`), with the `tiktoken-rs` feature.
+/// 3. A path to a `tokenizer.json` file or a Hugging Face Hub model identifier (e.g. `bert-base-uncased`), with the `tokenizers` feature.
+///
+/// Special tokens are not counted, and a text the tokenizer fails to encode is counted as [`ENCODE_FAILED`](crate::counters::ENCODE_FAILED) tokens so that it never fits in a chunk.
+///
+/// # Arguments
+///
+/// * `model` - The name of the model or encoding.
+///
+/// # Returns
+///
+/// The model's token counter, or an error if the model is unknown or its tokenizer fails to load.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "tiktoken-rs")]
+/// # {
+/// use semchunk_rs::models::token_counter;
+/// let counter = token_counter("gpt-4o").unwrap();
+/// assert_eq!(counter("Hello, world!"), 4);
+/// # }
+/// ```
+pub fn token_counter(model: &str) -> Result<BoxedTokenCounter, ModelError> {
+    Registry::global().token_counter(model)
+}
+
+/// Resolves the token counter for a model name from the tokenizers built into the enabled features.
+fn builtin_token_counter(model: &str) -> Result<BoxedTokenCounter, ModelError> {
+    #[cfg(feature = "tiktoken-rs")]
+    if let Some(bpe) = tiktoken_bpe(model) {
+        let bpe = bpe?;
+        return Ok(Box::new(move |text: &str| bpe.encode_ordinary(text).len()));
+    }
+    #[cfg(feature = "tokenizers")]
+    {
+        let path = std::path::Path::new(model);
+        let tokenizer = if path.is_file() {
+            tokenizers::Tokenizer::from_file(path)?
+        } else {
+            tokenizers::Tokenizer::from_pretrained(model, None)?
+        };
+        return Ok(Box::new(move |text: &str| {
+            tokenizer
+                .encode(text, false)
+                .map_or(crate::counters::ENCODE_FAILED, |encoding| encoding.len())
+        }));
+    }
+    #[allow(unreachable_code)]
+    Err(format!("no tokenizer found for model '{}'", model).into())
+}
+
+/// Loads the tiktoken encoding for a model or encoding name, or returns `None` if tiktoken does not know the name.
+#[cfg(feature = "tiktoken-rs")]
+fn tiktoken_bpe(model: &str) -> Option<Result<tiktoken_rs::CoreBPE, ModelError>> {
+    let bpe = match model {
+        "o200k_base" => tiktoken_rs::o200k_base(),
+        "cl100k_base" => tiktoken_rs::cl100k_base(),
+        "p50k_base" => tiktoken_rs::p50k_base(),
+        "p50k_edit" => tiktoken_rs::p50k_edit(),
+        "r50k_base" | "gpt2" => tiktoken_rs::r50k_base(),
+        _ => {
+            tiktoken_rs::tokenizer::get_tokenizer(model)?;
+            tiktoken_rs::get_bpe_from_model(model)
+        }
+    };
+    Some(bpe.map_err(|e| e.into()))
+}
+
+impl Chunker {
+    /// Creates a Chunker whose token counter is resolved from a model name, like the Python semchunk's `chunkerify`.
+    ///
+    /// See [`token_counter`] for how model names are resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The name of the model or encoding.
+    /// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+    ///
+    /// # Returns
+    ///
+    /// A new Chunker instance, or an error if the model's token counter cannot be resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "tiktoken-rs")]
+    /// # {
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::for_model("gpt-4o", 512).unwrap();
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox jumps over the lazy dog."]);
+    /// # }
+    /// ```
+    pub fn for_model(model: &str, chunk_size: usize) -> Result<Self, ModelError> {
+        Ok(Chunker::new(chunk_size, token_counter(model)?))
+    }
+}
+
+#[cfg(test)]
+mod models_tests {
+    use super::*;
+
+    #[cfg(feature = "tiktoken-rs")]
+    #[test]
+    fn test_tiktoken_models() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        for model in [
+            "gpt-4o",
+            "gpt-4",
+            "gpt-3.5-turbo",
+            "cl100k_base",
+            "o200k_base",
+        ] {
+            assert_eq!(token_counter(model).unwrap()(text), 10, "{}", model);
+        }
+
+        let chunker = Chunker::for_model("gpt-4o", 4).unwrap();
+        assert_eq!(
+            chunker.chunk(text),
+            vec!["The quick brown fox", "jumps over the", "lazy dog."]
+        );
+    }
+
+    #[cfg(feature = "tokenizers")]
+    #[test]
+    fn test_tokenizer_file() {
+        let dir = std::env::temp_dir().join("semchunk-rs-models-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tokenizer.json");
+        std::fs::write(
+            &path,
+            r#"{"version": "1.0", "added_tokens": [], "normalizer": null, "pre_tokenizer": {"type": "Whitespace"}, "post_processor": null, "decoder": null, "model": {"type": "WordLevel", "vocab": {"[UNK]": 0}, "unk_token": "[UNK]"}}"#,
+        )
+        .unwrap();
+        let counter = token_counter(path.to_str().unwrap()).unwrap();
+        assert_eq!(counter("The quick brown fox."), 5);
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+        registry.register_fn("words", |s: &str| s.split_whitespace().count());
+        registry.register("chars", || Ok(Box::new(|s: &str| s.chars().count())));
+        registry.register("broken", || Err("failed to load".into()));
+        assert_eq!(registry.models(), ["broken", "chars", "words"]);
+
+        let text = "The quick brown fox";
+        assert_eq!(registry.token_counter("words").unwrap()(text), 4);
+        assert_eq!(registry.token_counter("chars").unwrap()(text), 19);
+        assert!(registry.token_counter("broken").is_err());
+        assert_eq!(
+            registry.chunker("words", 2).unwrap().chunk(text),
+            vec!["The quick", "brown fox"]
+        );
+
+        // Registering a name again replaces it, and registrations shadow built-in models.
+        registry.register_fn("words", |s: &str| s.split_whitespace().count() * 2);
+        registry.register_fn("gpt-4o", |s: &str| s.len());
+        assert_eq!(registry.token_counter("words").unwrap()(text), 8);
+        assert_eq!(registry.token_counter("gpt-4o").unwrap()(text), 19);
+
+        assert!(registry.unregister("gpt-4o"));
+        assert!(!registry.unregister("gpt-4o"));
+        assert!(registry.token_counter("semchunk-rs-unknown-model").is_err());
+    }
+
+    #[test]
+    fn test_global_registry() {
+        Registry::global().register_fn("semchunk-rs-test-words", |s: &str| {
+            s.split_whitespace().count()
+        });
+        let chunker = Chunker::for_model("semchunk-rs-test-words", 4).unwrap();
+        assert_eq!(chunker.chunk_size(), 4);
+        assert_eq!(chunker.count_chunk_tokens("The quick brown fox"), 4);
+        assert!(Registry::global().unregister("semchunk-rs-test-words"));
+    }
+
+    #[cfg(not(any(feature = "tiktoken-rs", feature = "tokenizers")))]
+    #[test]
+    fn test_unknown_model() {
+        assert!(Chunker::for_model("gpt-4o", 512).is_err());
+    }
+}
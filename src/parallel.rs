@@ -0,0 +1,370 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Chunking a stream of documents on a pool of threads, with each document's chunks yielded in input order.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+use crate::counters::TokenizerPool;
+
+/// How many documents past the next one to be yielded each worker may chunk, which bounds the chunks held for reordering.
+const DOCUMENTS_AHEAD_PER_JOB: usize = 2;
+
+/// How far the consumer of a [`ParallelChunks`] has got, which workers wait on so as not to get too far ahead of it.
+#[derive(Default)]
+struct Progress {
+    /// The number of documents yielded, and whether the workers should stop, because the iterator has been dropped or a worker panicked.
+    state: Mutex<(usize, bool)>,
+    changed: Condvar,
+}
+
+impl Progress {
+    /// Waits until the document at `index` may be chunked, returning `false` if the workers should stop.
+    fn wait_for(&self, index: usize, ahead: usize) -> bool {
+        let Ok(state) = self.state.lock() else {
+            return false;
+        };
+        let state = self.changed.wait_while(state, |(yielded, stopped)| {
+            !*stopped && index >= *yielded + ahead
+        });
+        matches!(state, Ok(state) if !state.1)
+    }
+
+    fn update(&self, update: impl FnOnce(&mut (usize, bool))) {
+        if let Ok(mut state) = self.state.lock() {
+            update(&mut state);
+            self.changed.notify_all();
+        }
+    }
+}
+
+/// Stops the other workers if a worker panics.
+///
+/// The consumer waits for the panicked worker's document, so the other workers would otherwise wait for the consumer forever, holding their senders open, and the panic would never reach the consumer.
+struct StopOnPanic(Arc<Progress>);
+
+impl Drop for StopOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.update(|(_, stopped)| *stopped = true);
+        }
+    }
+}
+
+/// An iterator over the chunks of each of a stream of documents, chunked by [`chunk_parallel`].
+///
+/// Dropping the iterator stops the workers once they have chunked the documents they are working on.
+pub struct ParallelChunks {
+    receiver: Receiver<(usize, Vec<Chunk>)>,
+    /// Chunks received ahead of those of earlier documents.
+    pending: BTreeMap<usize, Vec<Chunk>>,
+    next: usize,
+    progress: Arc<Progress>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Iterator for ParallelChunks {
+    type Item = Vec<Chunk>;
+
+    fn next(&mut self) -> Option<Vec<Chunk>> {
+        loop {
+            if let Some(chunks) = self.pending.remove(&self.next) {
+                self.next += 1;
+                let next = self.next;
+                self.progress.update(|(yielded, _)| *yielded = next);
+                return Some(chunks);
+            }
+            match self.receiver.recv() {
+                Ok((index, chunks)) => {
+                    self.pending.insert(index, chunks);
+                }
+                // Every worker has stopped, so the documents have run out, unless a worker panicked.
+                Err(_) => {
+                    for worker in self.workers.drain(..) {
+                        if let Err(panic) = worker.join() {
+                            std::panic::resume_unwind(panic);
+                        }
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ParallelChunks {
+    fn drop(&mut self) {
+        self.progress.update(|(_, stopped)| *stopped = true);
+    }
+}
+
+/// Chunks a stream of documents on a pool of threads, yielding the chunks of each document in the order the documents came in.
+///
+/// Documents are taken from the iterator as workers become free, so they are never all held in memory at once, and workers wait rather than get more than a few documents ahead of the document to be yielded next. Token counters are often not thread-safe, so each worker builds its own [`Chunker`] with `new_chunker`. To share tokenizers between the workers instead, use [`chunk_parallel_pooled`].
+///
+/// # Arguments
+///
+/// * `documents` - The documents to chunk.
+/// * `jobs` - The number of worker threads, at least one, such as [`std::thread::available_parallelism`].
+/// * `new_chunker` - A function that builds a chunker for a worker.
+///
+/// # Returns
+///
+/// An iterator over the chunks of each document, in input order.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::parallel::chunk_parallel;
+/// use semchunk_rs::Chunker;
+///
+/// let documents = vec!["The quick brown fox jumps over the lazy dog.", "Hello, world!"];
+/// let chunks: Vec<Vec<String>> = chunk_parallel(documents, 4, || {
+///     Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()))
+/// })
+/// .map(|chunks| chunks.into_iter().map(|chunk| chunk.text).collect())
+/// .collect();
+/// assert_eq!(chunks, vec![vec!["The quick brown fox", "jumps over the lazy", "dog."], vec!["Hello, world!"]]);
+/// ```
+pub fn chunk_parallel<I, F>(documents: I, jobs: usize, new_chunker: F) -> ParallelChunks
+where
+    I: IntoIterator,
+    I::IntoIter: Send + 'static,
+    I::Item: AsRef<str>,
+    F: Fn() -> Chunker + Send + Sync + 'static,
+{
+    let jobs = jobs.max(1);
+    let ahead = jobs * DOCUMENTS_AHEAD_PER_JOB;
+    // Workers claim documents with their index in order, so their chunks can be reordered.
+    let documents = Arc::new(Mutex::new((documents.into_iter(), 0)));
+    let new_chunker = Arc::new(new_chunker);
+    let progress = Arc::new(Progress::default());
+    let (sender, receiver) = mpsc::channel();
+    let workers = (0..jobs)
+        .map(|_| {
+            let (documents, new_chunker) = (documents.clone(), new_chunker.clone());
+            let (progress, sender) = (progress.clone(), sender.clone());
+            thread::spawn(move || {
+                let _stop_on_panic = StopOnPanic(progress.clone());
+                let chunker = new_chunker();
+                loop {
+                    let (index, document) = {
+                        let Ok(mut documents) = documents.lock() else {
+                            break;
+                        };
+                        let index = documents.1;
+                        if !progress.wait_for(index, ahead) {
+                            break;
+                        }
+                        match documents.0.next() {
+                            Some(document) => {
+                                documents.1 += 1;
+                                (index, document)
+                            }
+                            None => break,
+                        }
+                    };
+                    let mut chunks = Vec::new();
+                    chunker.chunk_to(document.as_ref(), |chunk| chunks.push(chunk));
+                    if sender.send((index, chunks)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    ParallelChunks {
+        receiver,
+        pending: BTreeMap::new(),
+        next: 0,
+        progress,
+        workers,
+    }
+}
+
+/// Chunks a stream of documents on a pool of threads like [`chunk_parallel`], with the workers' chunkers counting tokens with tokenizers from a [`TokenizerPool`].
+///
+/// Each count takes a tokenizer from the pool and returns it afterwards, so the pool holds no more tokenizers than the number of workers, however many documents there are.
+///
+/// # Arguments
+///
+/// * `documents` - The documents to chunk.
+/// * `jobs` - The number of worker threads, at least one, such as [`std::thread::available_parallelism`].
+/// * `pool` - The pool of tokenizers to count tokens with.
+/// * `count` - A function that counts the tokens in a string with a tokenizer.
+/// * `new_chunker` - A function that builds a chunker for a worker from a token counter that counts with the pool's tokenizers.
+///
+/// # Returns
+///
+/// An iterator over the chunks of each document, in input order.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use semchunk_rs::counters::TokenizerPool;
+/// use semchunk_rs::parallel::chunk_parallel_pooled;
+/// use semchunk_rs::Chunker;
+///
+/// // A stand-in for a tokenizer that cannot be shared across threads.
+/// struct Tokenizer(std::cell::Cell<usize>);
+///
+/// let pool = Arc::new(TokenizerPool::new(|| Tokenizer(std::cell::Cell::new(0))));
+/// let documents = vec!["The quick brown fox jumps over the lazy dog."; 8];
+/// let chunks: Vec<_> = chunk_parallel_pooled(
+///     documents,
+///     4,
+///     &pool,
+///     |_tokenizer: &Tokenizer, s: &str| s.split_whitespace().count(),
+///     |token_counter| Chunker::new(4, token_counter),
+/// )
+/// .collect();
+/// assert_eq!(chunks[7].len(), 3);
+/// ```
+pub fn chunk_parallel_pooled<I, T, C, F>(
+    documents: I,
+    jobs: usize,
+    pool: &Arc<TokenizerPool<T>>,
+    count: C,
+    new_chunker: F,
+) -> ParallelChunks
+where
+    I: IntoIterator,
+    I::IntoIter: Send + 'static,
+    I::Item: AsRef<str>,
+    T: Send + 'static,
+    C: Fn(&T, &str) -> usize + Send + Sync + 'static,
+    F: Fn(Box<dyn Fn(&str) -> usize + Send + Sync>) -> Chunker + Send + Sync + 'static,
+{
+    let token_counter = pool.token_counter(count);
+    chunk_parallel(documents, jobs, move || {
+        new_chunker(Box::new(token_counter.clone()))
+    })
+}
+
+#[cfg(test)]
+mod parallel_tests {
+    use super::*;
+
+    fn words_chunker() -> Chunker {
+        Chunker::new(3, Box::new(|s: &str| s.split_whitespace().count()))
+    }
+
+    #[test]
+    fn test_chunk_parallel() {
+        let documents: Vec<String> = (0..100)
+            .map(|i| "The quick brown fox jumps over the lazy dog. ".repeat(i % 7 + 1))
+            .collect();
+        let chunker = words_chunker();
+        let sequential: Vec<Vec<Chunk>> = documents
+            .iter()
+            .map(|document| {
+                let mut chunks = Vec::new();
+                chunker.chunk_to(document, |chunk| chunks.push(chunk));
+                chunks
+            })
+            .collect();
+        for jobs in [0, 1, 4] {
+            let parallel: Vec<Vec<Chunk>> =
+                chunk_parallel(documents.clone(), jobs, words_chunker).collect();
+            assert_eq!(parallel, sequential);
+        }
+        assert_eq!(
+            chunk_parallel(Vec::<String>::new(), 4, words_chunker).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_chunk_parallel_pooled() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let documents: Vec<String> = (0..100)
+            .map(|i| "The quick brown fox jumps over the lazy dog. ".repeat(i % 7 + 1))
+            .collect();
+        let created = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let created = created.clone();
+            Arc::new(TokenizerPool::new(move || {
+                created.fetch_add(1, Ordering::SeqCst);
+                std::cell::Cell::new(0usize)
+            }))
+        };
+        let parallel: Vec<Vec<Chunk>> = chunk_parallel_pooled(
+            documents.clone(),
+            4,
+            &pool,
+            |counted: &std::cell::Cell<usize>, s: &str| {
+                counted.set(counted.get() + 1);
+                s.split_whitespace().count()
+            },
+            |token_counter| Chunker::new(3, token_counter),
+        )
+        .collect();
+        let sequential: Vec<Vec<Chunk>> = chunk_parallel(documents, 1, words_chunker).collect();
+        assert_eq!(parallel, sequential);
+        let created = created.load(Ordering::SeqCst);
+        assert!((1..=4).contains(&created), "created {} tokenizers", created);
+    }
+
+    #[test]
+    fn test_chunk_parallel_panic() {
+        // A worker panicking on one document must stop the others and reach the consumer rather than hang.
+        let documents: Vec<String> = (0..100)
+            .map(|i| match i {
+                20 => "The quick brown fox panics.".to_string(),
+                _ => "The quick brown fox jumps over the lazy dog.".to_string(),
+            })
+            .collect();
+        let result = std::panic::catch_unwind(|| {
+            chunk_parallel(documents, 4, || {
+                Chunker::new(
+                    3,
+                    Box::new(|s: &str| {
+                        assert!(!s.contains("panics"), "cannot count {:?}", s);
+                        s.split_whitespace().count()
+                    }),
+                )
+            })
+            .count()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chunk_parallel_stops_when_dropped() {
+        // An endless stream of documents must not be chunked once the iterator is dropped.
+        let documents = std::iter::repeat("The quick brown fox jumps over the lazy dog.");
+        let mut chunks = chunk_parallel(documents, 4, words_chunker);
+        assert_eq!(chunks.nth(10).unwrap().len(), 3);
+        let workers = std::mem::take(&mut chunks.workers);
+        drop(chunks);
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Chunking in two phases: planning where chunks start and end, and rendering the chunks from the plan.
+//!
+//! A [`ChunkPlan`] holds the boundaries and token counts of the chunks of a text, so callers can inspect or adjust where chunks end, cache plans, and render them with different [`RenderOptions`] without counting tokens again.
+
+use std::ops::Range;
+
+use crate::chunker::Chunker;
+
+/// A planned chunk of a text.
+///
+/// # Fields
+///
+/// * `start` - The byte offset in the text at which the chunk begins.
+/// * `end` - The byte offset in the text at which the chunk ends (not inclusive).
+/// * `tokens` - The number of tokens in the chunk, as counted when it was planned.
+/// * `skipped` - A span of the text within the chunk that is left out of it, such as whitespace before a separator appended to the chunk, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlannedChunk {
+    pub start: usize,
+    pub end: usize,
+    pub tokens: usize,
+    pub skipped: Option<Range<usize>>,
+}
+
+/// The planned chunks of a text, made with [`Chunker::plan`].
+///
+/// # Fields
+///
+/// * `text` - The text the chunks are of.
+/// * `chunks` - The planned chunks, in order.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::plan::RenderOptions;
+/// use semchunk_rs::Chunker;
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let plan = chunker.plan("The quick brown fox jumps over the lazy dog.");
+/// assert_eq!(plan.chunks.iter().map(|chunk| chunk.tokens).collect::<Vec<_>>(), vec![4, 4, 1]);
+/// assert_eq!(plan.separator(0), " ");
+/// let chunks = plan.render(&RenderOptions::default().header("Fable: "));
+/// assert_eq!(chunks, vec!["Fable: The quick brown fox", "Fable: jumps over the lazy", "Fable: dog."]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkPlan<'a> {
+    pub text: &'a str,
+    pub chunks: Vec<PlannedChunk>,
+}
+
+/// How [`ChunkPlan::render`] renders planned chunks. By default, chunks are rendered as they were planned.
+///
+/// A header counts towards the chunk size of the rendered chunks, so for them to stay within it, the chunks must be planned with a chunk size that leaves room for the header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderOptions {
+    trim: bool,
+    header: String,
+}
+
+impl RenderOptions {
+    /// Sets whether whitespace is trimmed from the start and end of each chunk, which defaults to false.
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Sets a header prepended to each chunk, such as the title of the document, which defaults to none.
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = header.into();
+        self
+    }
+}
+
+impl<'a> ChunkPlan<'a> {
+    /// The number of planned chunks.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Whether no chunks are planned.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The text of the chunk at `index`, without rendering options applied.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn chunk_text(&self, index: usize) -> String {
+        let chunk = &self.chunks[index];
+        match &chunk.skipped {
+            Some(skipped) => {
+                let mut text = self.text[chunk.start..skipped.start].to_string();
+                text.push_str(&self.text[skipped.end..chunk.end]);
+                text
+            }
+            None => self.text[chunk.start..chunk.end].to_string(),
+        }
+    }
+
+    /// The text between the chunk at `index` and the next chunk, at which the text is split, or the rest of the text after the last chunk.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn separator(&self, index: usize) -> &'a str {
+        let end = self.chunks[index].end;
+        match self.chunks.get(index + 1) {
+            Some(next) => &self.text[end..next.start.max(end)],
+            None => &self.text[end..],
+        }
+    }
+
+    /// Renders the planned chunks, without counting tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - How the chunks are rendered.
+    ///
+    /// # Returns
+    ///
+    /// The text of each planned chunk, in order.
+    pub fn render(&self, options: &RenderOptions) -> Vec<String> {
+        (0..self.len())
+            .map(|index| {
+                let text = self.chunk_text(index);
+                let text = match options.trim {
+                    true => text.trim(),
+                    false => &text,
+                };
+                format!("{}{}", options.header, text)
+            })
+            .collect()
+    }
+}
+
+impl Chunker {
+    /// Plans the chunks of the given text without rendering them, with the tokens of each chunk, as counted by [`Chunker::chunk_with_tokens`].
+    ///
+    /// The chunks of the plan are those [`Chunker::chunk_to`] would produce, and rendering the plan with the default [`RenderOptions`] gives the same texts as [`Chunker::chunk`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The plan, which borrows the text.
+    pub fn plan<'a>(&self, text: &'a str) -> ChunkPlan<'a> {
+        let mut chunks = Vec::new();
+        for (chunk, tokens) in self.chunk_with_tokens(text) {
+            let span = &text[chunk.start..chunk.end];
+            // A chunk whose text is not a span of the text has a separator appended after a gap.
+            let skipped = (chunk.text != span).then(|| {
+                let kept = span
+                    .bytes()
+                    .zip(chunk.text.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                chunk.start + kept..chunk.end - (chunk.text.len() - kept)
+            });
+            chunks.push(PlannedChunk {
+                start: chunk.start,
+                end: chunk.end,
+                tokens,
+                skipped,
+            });
+        }
+        ChunkPlan { text, chunks }
+    }
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_matches_chunks() {
+        let text = "The quick brown fox jumps.\n\nOver the lazy dog, it leaps .\n\n Pack my box with five dozen liquor jugs !";
+        let chars = |s: &str| s.chars().count();
+        for chunk_size in [3, 8, 20, 100] {
+            let chunker = Chunker::new(chunk_size, Box::new(chars));
+            let plan = chunker.plan(text);
+            assert_eq!(plan.render(&RenderOptions::default()), chunker.chunk(text));
+            for (index, chunk) in plan.chunks.iter().enumerate() {
+                assert_eq!(chunk.tokens, chars(&plan.chunk_text(index)));
+                assert!(chunk.tokens <= chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_options() {
+        let chunker = Chunker::new(2, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut plan = chunker.plan("The quick  brown fox ");
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan.chunk_text(0), "The quick ");
+        assert_eq!(plan.separator(0), " ");
+        assert_eq!(plan.separator(1), "");
+        let options = RenderOptions::default().trim(true).header("> ");
+        assert_eq!(plan.render(&options), vec!["> The quick", "> brown fox"]);
+
+        // Plans can be adjusted before rendering.
+        plan.chunks[0].end = plan.chunks.remove(1).end;
+        assert_eq!(
+            plan.render(&RenderOptions::default()),
+            vec!["The quick  brown fox "]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Preprocessing of texts before they are chunked, such as stripping the boilerplate lines many documents share, or the license blocks of Project Gutenberg eBooks.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::dedup::hash;
+
+/// The lines repeated across a batch of documents, such as headers, footers and cookie banners, which can be stripped from the documents before they are chunked.
+///
+/// Lines are compared with their surrounding whitespace trimmed, and remembered by a 64-bit hash rather than in full. Blank lines are never boilerplate.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::preprocess::Boilerplate;
+/// let documents = [
+///     "Accept cookies?\nThe fox jumps.\nAll rights reserved.",
+///     "Accept cookies?\nThe dog sleeps.\n  All rights reserved.  ",
+///     "The cat naps.",
+/// ];
+/// let boilerplate = Boilerplate::detect(documents, 2);
+/// assert_eq!(boilerplate.len(), 2);
+/// assert_eq!(boilerplate.strip(documents[1]), "The dog sleeps.\n");
+/// assert_eq!(boilerplate.strip(documents[2]), "The cat naps.");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Boilerplate {
+    lines: HashSet<u64>,
+}
+
+impl Boilerplate {
+    /// Detects the lines that occur in at least `min_documents` of a batch of documents.
+    ///
+    /// # Arguments
+    ///
+    /// * `documents` - The texts of the documents in the batch.
+    /// * `min_documents` - The number of documents a line must occur in to be boilerplate, counting each document once however often the line occurs in it.
+    pub fn detect<'a>(documents: impl IntoIterator<Item = &'a str>, min_documents: usize) -> Self {
+        let mut documents_with_line: HashMap<u64, usize> = HashMap::new();
+        let mut document_lines = HashSet::new();
+        for document in documents {
+            document_lines.clear();
+            document_lines.extend(document.lines().filter_map(line_hash));
+            for &line in &document_lines {
+                *documents_with_line.entry(line).or_default() += 1;
+            }
+        }
+        let lines = documents_with_line
+            .into_iter()
+            .filter(|&(_, n_documents)| n_documents >= min_documents.max(1))
+            .map(|(line, _)| line)
+            .collect();
+        Boilerplate { lines }
+    }
+
+    /// Whether a line is boilerplate.
+    pub fn contains(&self, line: &str) -> bool {
+        line_hash(line).is_some_and(|line| self.lines.contains(&line))
+    }
+
+    /// Marks a line as boilerplate, such as one known to occur in documents from a source.
+    pub fn insert(&mut self, line: &str) {
+        self.lines.extend(line_hash(line));
+    }
+
+    /// The number of distinct boilerplate lines.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether no lines are boilerplate.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Strips the boilerplate lines, along with their line breaks, from a text, leaving the rest of it as it is.
+    ///
+    /// # Returns
+    ///
+    /// The text without boilerplate lines, which borrows the text if it has none.
+    pub fn strip<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_empty() || !text.lines().any(|line| self.contains(line)) {
+            return Cow::Borrowed(text);
+        }
+        Cow::Owned(
+            text.split_inclusive('\n')
+                .filter(|line| !self.contains(line))
+                .collect(),
+        )
+    }
+}
+
+/// Hashes a line with its surrounding whitespace trimmed, unless it is blank.
+fn line_hash(line: &str) -> Option<u64> {
+    let line = line.trim();
+    (!line.is_empty()).then(|| hash(line))
+}
+
+/// Strips the Project Gutenberg header and footer from an eBook, so chunks contain only the text of the work.
+///
+/// The header ends with the `*** START OF THE PROJECT GUTENBERG EBOOK ... ***` line, or the `*END*THE SMALL PRINT!` line of older eBooks, and the footer begins with the `End of the Project Gutenberg EBook of ...` line or the `*** END OF THE PROJECT GUTENBERG EBOOK ... ***` line, whichever comes first. Markers are matched without regard to case, and either marker may be missing, such as in texts already stripped.
+///
+/// # Arguments
+///
+/// * `text` - The text of the eBook.
+///
+/// # Returns
+///
+/// The slice of the text between the header and the footer, with surrounding whitespace trimmed.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::preprocess::strip_gutenberg_boilerplate;
+/// let text = "The Project Gutenberg eBook of Emma\n\n*** START OF THE PROJECT GUTENBERG EBOOK EMMA ***\n\nEmma Woodhouse, handsome, clever, and rich.\n\n*** END OF THE PROJECT GUTENBERG EBOOK EMMA ***\n\nUpdated editions will replace the previous one.";
+/// assert_eq!(strip_gutenberg_boilerplate(text), "Emma Woodhouse, handsome, clever, and rich.");
+/// ```
+pub fn strip_gutenberg_boilerplate(text: &str) -> &str {
+    let mut start = 0;
+    let mut end = text.len();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let marker = line.trim().to_ascii_uppercase();
+        let line_end = offset + line.len();
+        if marker.starts_with("*END*THE SMALL PRINT")
+            || (marker.starts_with("***") && marker.contains("START OF THE PROJECT GUTENBERG"))
+            || (marker.starts_with("***") && marker.contains("START OF THIS PROJECT GUTENBERG"))
+        {
+            start = line_end;
+            end = text.len();
+        } else if end == text.len()
+            && (marker.starts_with("END OF THE PROJECT GUTENBERG")
+                || marker.starts_with("END OF PROJECT GUTENBERG")
+                || (marker.starts_with("***") && marker.contains("END OF THE PROJECT GUTENBERG"))
+                || (marker.starts_with("***") && marker.contains("END OF THIS PROJECT GUTENBERG")))
+        {
+            end = offset;
+        }
+        offset = line_end;
+    }
+    text[start..end].trim()
+}
+
+#[cfg(test)]
+mod preprocess_tests {
+    use super::*;
+    use crate::Chunker;
+
+    #[test]
+    fn test_boilerplate() {
+        let footer = "Copyright 2024 Example News. All rights reserved.";
+        let documents = [
+            format!("Example News\r\nStocks rose on Monday.\r\n{}\r\n", footer),
+            format!("Example News\nRain is expected.\n{}", footer),
+            format!("Snow fell overnight.\n\n{}\n", footer),
+            "Example News".to_string(),
+        ];
+        let boilerplate = Boilerplate::detect(documents.iter().map(String::as_str), 3);
+        assert_eq!(boilerplate.len(), 2);
+        assert!(boilerplate.contains(" Example News "));
+        assert!(!boilerplate.contains(""));
+        assert_eq!(
+            boilerplate.strip(&documents[0]),
+            "Stocks rose on Monday.\r\n"
+        );
+        assert_eq!(boilerplate.strip(&documents[1]), "Rain is expected.\n");
+        assert_eq!(boilerplate.strip(&documents[2]), "Snow fell overnight.\n\n");
+        assert_eq!(boilerplate.strip(&documents[3]), "");
+        assert!(matches!(
+            boilerplate.strip("Nothing to strip.\nAt all."),
+            Cow::Borrowed(_)
+        ));
+
+        // Lines repeated within one document are not boilerplate on their own.
+        let boilerplate = Boilerplate::detect(["* * *\nOne.\n* * *\nTwo.", "Three."], 2);
+        assert!(boilerplate.is_empty());
+
+        let mut boilerplate = Boilerplate::default();
+        boilerplate.insert("Subscribe now!");
+        let chunker = Chunker::new(3, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = boilerplate.strip("Subscribe now!\nThe quick brown fox jumps.");
+        assert_eq!(chunker.chunk(&text), ["The quick brown", "fox jumps."]);
+    }
+
+    #[test]
+    fn test_strip_gutenberg_boilerplate() {
+        let text = "\u{FEFF}The Project Gutenberg EBook of Emma, by Jane Austen\r\n\r\n*** START OF THIS PROJECT GUTENBERG EBOOK EMMA ***\r\n\r\nEMMA\r\n\r\nBy Jane Austen\r\n\r\n\r\nEnd of the Project Gutenberg EBook of Emma, by Jane Austen\r\n\r\n*** END OF THIS PROJECT GUTENBERG EBOOK EMMA ***\r\n\r\n***** This file should be named 158.txt *****\r\n";
+        assert_eq!(
+            strip_gutenberg_boilerplate(text),
+            "EMMA\r\n\r\nBy Jane Austen"
+        );
+
+        let text = "Small print.\n*END*THE SMALL PRINT! FOR PUBLIC DOMAIN ETEXTS*Ver.04.29.93*END*\nParadise Lost\nEnd of Project Gutenberg's Paradise Lost, by John Milton\n";
+        assert_eq!(strip_gutenberg_boilerplate(text), "Paradise Lost");
+
+        // Texts without markers, or with a marker-like line in the work itself, are left whole.
+        let text = "Chapter 1\n\nThe end of the Project Gutenberg story came soon.\n";
+        assert_eq!(strip_gutenberg_boilerplate(text), text.trim());
+        assert_eq!(strip_gutenberg_boilerplate(""), "");
+    }
+}
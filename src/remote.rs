@@ -0,0 +1,414 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Token counting with remote APIs, such as a provider's count-tokens endpoint, enabled with the `remote` feature.
+//!
+//! [`RemoteCounter`] batches the texts counted at about the same time into one request and caches their counts, so chunking does not send a request for every candidate chunk.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout_at, Instant};
+
+use crate::resilience::{Guard, Resilience};
+
+/// A text to count, and where to send its count.
+type Request = (String, oneshot::Sender<Result<usize, String>>);
+
+/// Counts of previously counted texts.
+type Cache = Arc<Mutex<CountCache>>;
+
+/// Counts of previously counted texts, keyed by the text, which forgets the oldest once it holds `capacity` of them.
+#[derive(Debug, Default)]
+struct CountCache {
+    counts: HashMap<Arc<str>, usize>,
+    order: VecDeque<Arc<str>>,
+    capacity: usize,
+}
+
+impl CountCache {
+    fn new(capacity: usize) -> Self {
+        CountCache {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn get(&self, text: &str) -> Option<usize> {
+        self.counts.get(text).copied()
+    }
+
+    fn insert(&mut self, text: &str, count: usize) {
+        if self.capacity == 0 || self.counts.contains_key(text) {
+            return;
+        }
+        while self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.counts.remove(&oldest);
+            }
+        }
+        let text: Arc<str> = Arc::from(text);
+        self.counts.insert(text.clone(), count);
+        self.order.push_back(text);
+    }
+}
+
+/// A token counter backed by an async function that counts a batch of texts, such as a request to a remote API.
+///
+/// Configure it with the builder methods, then [`RemoteCounter::start`] it on a Tokio runtime to get a [`CounterHandle`] for a [`Chunker`](crate::Chunker).
+pub struct RemoteCounter<F> {
+    count_batch: F,
+    max_batch: usize,
+    max_delay: Duration,
+    max_cached: usize,
+    resilience: Resilience,
+}
+
+impl<F, Fut, E> RemoteCounter<F>
+where
+    F: Fn(Vec<String>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<usize>, E>> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    /// Creates a remote counter from a function that counts the tokens in each of a batch of texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `count_batch` - An async function returning the number of tokens in each text, in order, or an error.
+    pub fn new(count_batch: F) -> Self {
+        RemoteCounter {
+            count_batch,
+            max_batch: 32,
+            max_delay: Duration::from_millis(5),
+            max_cached: 4096,
+            resilience: Resilience::default().retries(0, Duration::ZERO),
+        }
+    }
+
+    /// Sets the most texts counted in one batch, which defaults to 32.
+    pub fn max_batch(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Sets how long to wait for more texts to add to a batch after the first, which defaults to 5 ms.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets the most counts cached, forgetting the oldest once there are more, which defaults to 4096. With 0, counts are not cached.
+    pub fn max_cached(mut self, max_cached: usize) -> Self {
+        self.max_cached = max_cached;
+        self
+    }
+
+    /// Sets how batches are retried, rate limited and circuit broken. By default, they are not.
+    pub fn resilience(mut self, resilience: Resilience) -> Self {
+        self.resilience = resilience;
+        self
+    }
+
+    /// Starts batching texts to count on the runtime of `handle`.
+    ///
+    /// # Returns
+    ///
+    /// A handle that counts texts, blocking or asynchronously. Batching stops once it and all of its clones are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::remote::RemoteCounter;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// // A stand-in for a request to a count-tokens endpoint.
+    /// let counter = RemoteCounter::new(|texts: Vec<String>| async move {
+    ///     Ok::<_, String>(texts.iter().map(|text| text.split_whitespace().count()).collect())
+    /// })
+    /// .start(runtime.handle());
+    /// let chunker = Chunker::new_concurrent(4, move |s: &str| counter.count(s), 4);
+    /// let chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// ```
+    pub fn start(self, handle: &Handle) -> CounterHandle {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let cache = Arc::new(Mutex::new(CountCache::new(self.max_cached)));
+        handle.spawn(batch_requests(self, receiver, cache.clone()));
+        CounterHandle { requests, cache }
+    }
+}
+
+/// Counts the texts received in batches, until every sender is dropped.
+async fn batch_requests<F, Fut, E>(
+    counter: RemoteCounter<F>,
+    mut receiver: mpsc::UnboundedReceiver<Request>,
+    cache: Cache,
+) where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<usize>, E>>,
+    E: Display,
+{
+    let guard = Guard::new(counter.resilience.clone());
+    while let Some(request) = receiver.recv().await {
+        let deadline = Instant::now() + counter.max_delay;
+        let mut batch = vec![request];
+        while batch.len() < counter.max_batch {
+            match timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(request)) => batch.push(request),
+                _ => break,
+            }
+        }
+        let texts: Vec<String> = batch.iter().map(|(text, _)| text.clone()).collect();
+        let result = guard
+            .call_async(|| (counter.count_batch)(texts.clone()))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|counts| match counts.len() == batch.len() {
+                true => Ok(counts),
+                false => Err(format!(
+                    "counted {} texts in a batch of {}",
+                    counts.len(),
+                    batch.len()
+                )),
+            });
+        match result {
+            Ok(counts) => {
+                let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+                for ((text, reply), count) in batch.into_iter().zip(counts) {
+                    cache.insert(&text, count);
+                    let _ = reply.send(Ok(count));
+                }
+            }
+            Err(e) => {
+                for (_, reply) in batch {
+                    let _ = reply.send(Err(e.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// A handle to a started [`RemoteCounter`] that counts texts with it, either blocking until each is counted, to count tokens for a [`Chunker`](crate::Chunker), or asynchronously.
+///
+/// Chunking is synchronous, so the blocking methods must not be called from an async task: chunk on a thread of your own or with [`tokio::task::spawn_blocking`], and count from async code with [`CounterHandle::count_async`]. Texts counted from several threads or tasks at once, such as the candidates counted by [`Chunker::new_concurrent`](crate::Chunker::new_concurrent), are counted in one batch.
+///
+/// Token counters cannot fail, so a chunker counting with [`CounterHandle::count`] panics out of [`Chunker::chunk`](crate::Chunker::chunk) if the remote counter fails or has stopped.
+#[derive(Clone)]
+pub struct CounterHandle {
+    requests: mpsc::UnboundedSender<Request>,
+    cache: Cache,
+}
+
+/// The error returned once the remote counter has stopped.
+const STOPPED: &str = "the remote counter has stopped";
+
+impl CounterHandle {
+    /// Returns the cached count of `text`, if it has been counted.
+    fn cached(&self, text: &str) -> Option<usize> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(text)
+    }
+
+    /// Sends `text` to be counted in the next batch, returning where its count will be sent.
+    fn send(&self, text: &str) -> Result<oneshot::Receiver<Result<usize, String>>, String> {
+        let (reply, count) = oneshot::channel();
+        self.requests
+            .send((text.to_string(), reply))
+            .map_err(|_| STOPPED.to_string())?;
+        Ok(count)
+    }
+
+    /// Counts the tokens in `text`, blocking until it is counted, and returning an error if the remote counter failed or has stopped.
+    pub fn try_count(&self, text: &str) -> Result<usize, String> {
+        if let Some(count) = self.cached(text) {
+            return Ok(count);
+        }
+        self.send(text)?
+            .blocking_recv()
+            .map_err(|_| STOPPED.to_string())?
+    }
+
+    /// Counts the tokens in `text` without blocking, returning an error if the remote counter failed or has stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::remote::RemoteCounter;
+    ///
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// let counter = RemoteCounter::new(|texts: Vec<String>| async move {
+    ///     Ok::<_, String>(texts.iter().map(|text| text.split_whitespace().count()).collect())
+    /// })
+    /// .start(runtime.handle());
+    /// let count = runtime.block_on(async { counter.count_async("Hello, world!").await });
+    /// assert_eq!(count, Ok(2));
+    /// ```
+    pub async fn count_async(&self, text: &str) -> Result<usize, String> {
+        if let Some(count) = self.cached(text) {
+            return Ok(count);
+        }
+        self.send(text)?.await.map_err(|_| STOPPED.to_string())?
+    }
+
+    /// Counts the tokens in `text`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the remote counter failed or has stopped, including when called by a [`Chunker`](crate::Chunker) while chunking. Use [`CounterHandle::try_count`] to handle failures.
+    pub fn count(&self, text: &str) -> usize {
+        self.try_count(text)
+            .unwrap_or_else(|e| panic!("failed to count tokens: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod remote_tests {
+    use super::*;
+    use crate::Chunker;
+
+    fn words(texts: &[String]) -> Vec<usize> {
+        texts
+            .iter()
+            .map(|text| text.split_whitespace().count())
+            .collect()
+    }
+
+    #[test]
+    fn test_remote_counter() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = batches.clone();
+        let counter = RemoteCounter::new(move |texts: Vec<String>| {
+            recorded.lock().unwrap().push(texts.len());
+            async move { Ok::<_, String>(words(&texts)) }
+        })
+        .max_batch(4)
+        .max_delay(Duration::from_millis(50))
+        .start(runtime.handle());
+
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(10);
+        let remote = {
+            let counter = counter.clone();
+            Chunker::new_concurrent(7, move |s: &str| counter.count(s), 4)
+        };
+        let local = Chunker::new(7, Box::new(|s: &str| s.split_whitespace().count()));
+        assert_eq!(remote.chunk(&text), local.chunk(&text));
+
+        // Concurrently counted candidates are batched, and counted texts are cached.
+        let batches = batches.lock().unwrap().clone();
+        assert!(batches.iter().all(|&size| size <= 4));
+        assert!(batches.iter().any(|&size| size > 1));
+        let requests = batches.len();
+        remote.chunk(&text);
+        assert_eq!(batches.len(), requests);
+    }
+
+    #[test]
+    fn test_count_cache() {
+        let mut cache = CountCache::new(2);
+        cache.insert("one", 1);
+        cache.insert("two", 2);
+        cache.insert("one", 1);
+        cache.insert("three", 3);
+        assert_eq!(cache.get("one"), None);
+        assert_eq!(cache.get("two"), Some(2));
+        assert_eq!(cache.get("three"), Some(3));
+
+        let mut cache = CountCache::new(0);
+        cache.insert("one", 1);
+        assert_eq!(cache.get("one"), None);
+    }
+
+    #[test]
+    fn test_remote_counter_errors() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let counter = RemoteCounter::new(|texts: Vec<String>| async move {
+            match texts.iter().any(|text| text.contains("fail")) {
+                true => Err("service unavailable"),
+                false => Ok(vec![]),
+            }
+        })
+        .start(runtime.handle());
+        assert_eq!(
+            counter.try_count("fail").unwrap_err(),
+            "service unavailable"
+        );
+        assert_eq!(
+            counter.try_count("ok").unwrap_err(),
+            "counted 0 texts in a batch of 1"
+        );
+        drop(runtime);
+        assert!(counter.try_count("ok").is_err());
+    }
+
+    #[test]
+    fn test_count_async() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = batches.clone();
+        let counter = RemoteCounter::new(move |texts: Vec<String>| {
+            recorded.lock().unwrap().push(texts.len());
+            async move { Ok::<_, String>(words(&texts)) }
+        })
+        .max_delay(Duration::from_millis(50))
+        .start(runtime.handle());
+        // Texts counted by concurrent tasks are batched, without blocking the runtime.
+        let counts = runtime.block_on(async {
+            tokio::join!(
+                counter.count_async("The quick brown fox"),
+                counter.count_async("jumps over the lazy dog.")
+            )
+        });
+        assert_eq!(counts, (Ok(4), Ok(5)));
+        assert_eq!(*batches.lock().unwrap(), [2]);
+        assert_eq!(counter.try_count("The quick brown fox"), Ok(4));
+        assert_eq!(*batches.lock().unwrap(), [2]);
+    }
+
+    #[test]
+    fn test_remote_counter_retries() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let calls = Arc::new(Mutex::new(0));
+        let recorded = calls.clone();
+        let counter = RemoteCounter::new(move |texts: Vec<String>| {
+            let mut calls = recorded.lock().unwrap();
+            *calls += 1;
+            let result = match *calls {
+                1 => Err("429 Too Many Requests"),
+                _ => Ok(words(&texts)),
+            };
+            async move { result }
+        })
+        .resilience(Resilience::default().retries(1, Duration::from_millis(1)))
+        .start(runtime.handle());
+        assert_eq!(counter.try_count("The quick brown fox"), Ok(4));
+        assert_eq!(*calls.lock().unwrap(), 2);
+    }
+}
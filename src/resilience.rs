@@ -0,0 +1,359 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Retries with backoff, rate limiting and circuit breaking for calls to remote APIs, so long chunking jobs survive transient failures.
+//!
+//! A [`Resilience`] policy is applied to embedders with [`Resilient`](crate::embed::Resilient) and, with the `remote` feature, to remote token counters with [`RemoteCounter::resilience`](crate::remote::RemoteCounter::resilience).
+
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A policy for retrying, rate limiting and circuit breaking calls to an API.
+///
+/// By default, failed calls are retried up to 3 times, with a backoff of 100 ms that doubles after each retry up to 10 s, and calls are neither rate limited nor circuit broken.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use semchunk_rs::resilience::Resilience;
+/// let policy = Resilience::default()
+///     .retries(5, Duration::from_millis(200))
+///     .rate_limit(10.0)
+///     .circuit_breaker(10, Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Resilience {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    min_interval: Duration,
+    circuit_breaker: Option<(usize, Duration)>,
+}
+
+impl Default for Resilience {
+    fn default() -> Self {
+        Resilience {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            min_interval: Duration::ZERO,
+            circuit_breaker: None,
+        }
+    }
+}
+
+impl Resilience {
+    /// Sets how many times a failed call is retried, and how long to wait before the first retry. The wait doubles after each retry.
+    pub fn retries(mut self, max_retries: usize, initial_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the longest wait between retries, which defaults to 10 s.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Limits calls, including retries, to `per_second` a second, spacing them evenly.
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.min_interval = match per_second > 0.0 {
+            true => Duration::from_secs_f64(1.0 / per_second),
+            false => Duration::ZERO,
+        };
+        self
+    }
+
+    /// Stops making calls for `cooldown` after `failures` consecutive calls fail, failing fast instead of waiting on an API that is down. After the cooldown, a single call is let through to test the API, and other calls keep failing fast until it succeeds, closing the circuit, or fails, opening it for another cooldown.
+    pub fn circuit_breaker(mut self, failures: usize, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((failures.max(1), cooldown));
+        self
+    }
+
+    /// The wait before retrying after `retry` previous retries.
+    fn backoff(&self, retry: usize) -> Duration {
+        let factor = 2u32.saturating_pow(retry.min(31) as u32);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// The error returned by a call made under a [`Resilience`] policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResilienceError<E> {
+    /// The circuit breaker is open, so the call was not made.
+    CircuitOpen,
+    /// The call failed on every attempt, with the error of the last attempt.
+    Failed(E),
+}
+
+impl<E: Display> Display for ResilienceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResilienceError::CircuitOpen => {
+                write!(f, "too many calls failed; the circuit breaker is open")
+            }
+            ResilienceError::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for ResilienceError<E> {}
+
+/// A [`Resilience`] policy and the state of the calls made under it.
+#[derive(Debug)]
+pub(crate) struct Guard {
+    policy: Resilience,
+    state: Mutex<State>,
+    /// Whether a call is testing the API while the circuit breaker is half-open.
+    probing: AtomicBool,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The earliest time the next call may be made under the rate limit.
+    next_call: Instant,
+    consecutive_failures: usize,
+    /// When the circuit breaker closes again, if it is open.
+    open_until: Option<Instant>,
+}
+
+impl Guard {
+    pub(crate) fn new(policy: Resilience) -> Self {
+        Guard {
+            policy,
+            state: Mutex::new(State {
+                next_call: Instant::now(),
+                consecutive_failures: 0,
+                open_until: None,
+            }),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    /// Reserves a call, returning how long to wait before making it and, if the circuit breaker is half-open, the probe it must hold until its outcome is recorded, or `None` if the circuit breaker is open or another call is already probing.
+    fn acquire(&self) -> Option<(Duration, Option<Probe<'_>>)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let probe = match state.open_until {
+            Some(open_until) if now < open_until => return None,
+            Some(_) => {
+                self.probing
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                    .ok()?;
+                Some(Probe(&self.probing))
+            }
+            None => None,
+        };
+        let call = state.next_call.max(now);
+        state.next_call = call + self.policy.min_interval;
+        Some((call - now, probe))
+    }
+
+    /// Records the outcome of a call, opening the circuit breaker after too many consecutive failures.
+    fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if success {
+            state.consecutive_failures = 0;
+            state.open_until = None;
+            return;
+        }
+        state.consecutive_failures += 1;
+        if let Some((failures, cooldown)) = self.policy.circuit_breaker {
+            if state.consecutive_failures >= failures {
+                state.open_until = Some(Instant::now() + cooldown);
+            }
+        }
+    }
+
+    /// Calls `f` under the policy, blocking the thread while waiting.
+    pub(crate) fn call<T, E>(
+        &self,
+        mut f: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, ResilienceError<E>> {
+        let mut retry = 0;
+        loop {
+            let (wait, _probe) = self.acquire().ok_or(ResilienceError::CircuitOpen)?;
+            std::thread::sleep(wait);
+            match f() {
+                Ok(value) => {
+                    self.record(true);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record(false);
+                    if retry >= self.policy.max_retries {
+                        return Err(ResilienceError::Failed(e));
+                    }
+                    std::thread::sleep(self.policy.backoff(retry));
+                    retry += 1;
+                }
+            }
+        }
+    }
+
+    /// Calls `f` under the policy, waiting asynchronously.
+    #[cfg(feature = "remote")]
+    pub(crate) async fn call_async<T, E, Fut>(
+        &self,
+        mut f: impl FnMut() -> Fut,
+    ) -> Result<T, ResilienceError<E>>
+    where
+        Fut: std::future::Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+        loop {
+            let (wait, _probe) = self.acquire().ok_or(ResilienceError::CircuitOpen)?;
+            tokio::time::sleep(wait).await;
+            match f().await {
+                Ok(value) => {
+                    self.record(true);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record(false);
+                    if retry >= self.policy.max_retries {
+                        return Err(ResilienceError::Failed(e));
+                    }
+                    tokio::time::sleep(self.policy.backoff(retry)).await;
+                    retry += 1;
+                }
+            }
+        }
+    }
+}
+
+/// The right to make the single call let through while the circuit breaker is half-open, given up when dropped, even if the call panics or is cancelled.
+struct Probe<'a>(&'a AtomicBool);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod resilience_tests {
+    use super::*;
+
+    fn fail_times(times: usize) -> impl FnMut() -> Result<usize, String> {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            match calls <= times {
+                true => Err(format!("call {} failed", calls)),
+                false => Ok(calls),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retries() {
+        let guard = Guard::new(Resilience::default().retries(2, Duration::from_millis(1)));
+        assert_eq!(guard.call(fail_times(2)), Ok(3));
+        assert_eq!(
+            guard.call(fail_times(3)),
+            Err(ResilienceError::Failed("call 3 failed".to_string()))
+        );
+
+        let policy = Resilience::default()
+            .retries(10, Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(1));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(9), Duration::from_secs(1));
+        assert_eq!(policy.backoff(usize::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let guard = Guard::new(
+            Resilience::default()
+                .retries(0, Duration::ZERO)
+                .circuit_breaker(2, Duration::from_millis(50)),
+        );
+        assert!(guard.call(fail_times(1)).is_err());
+        assert!(guard.call(fail_times(1)).is_err());
+        // The circuit is open, so calls fail without being made.
+        let mut called = false;
+        let result = guard.call(|| {
+            called = true;
+            Ok::<_, String>(())
+        });
+        assert_eq!(result, Err(ResilienceError::CircuitOpen));
+        assert!(!called);
+        // After the cooldown, a successful call closes the circuit.
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(guard.call(fail_times(0)), Ok(1));
+        assert!(guard.call(fail_times(1)).is_err());
+        assert_eq!(guard.call(fail_times(0)), Ok(1));
+    }
+
+    #[test]
+    fn test_circuit_breaker_single_probe() {
+        let guard = Guard::new(
+            Resilience::default()
+                .retries(0, Duration::ZERO)
+                .circuit_breaker(1, Duration::from_millis(20)),
+        );
+        assert!(guard.call(fail_times(1)).is_err());
+        std::thread::sleep(Duration::from_millis(30));
+        // While the circuit is half-open, calls fail fast until the probe's outcome is known.
+        let (started, start) = std::sync::mpsc::channel();
+        let (finish, finished) = std::sync::mpsc::channel::<()>();
+        let guard = &guard;
+        std::thread::scope(|scope| {
+            let probe = scope.spawn(move || {
+                guard.call(|| {
+                    started.send(()).unwrap();
+                    finished.recv().unwrap();
+                    Err::<(), _>("still down")
+                })
+            });
+            start.recv().unwrap();
+            assert_eq!(guard.call(fail_times(0)), Err(ResilienceError::CircuitOpen));
+            finish.send(()).unwrap();
+            assert!(probe.join().unwrap().is_err());
+        });
+        // The failed probe reopens the circuit, and the next probe is let through after the cooldown.
+        assert_eq!(guard.call(fail_times(0)), Err(ResilienceError::CircuitOpen));
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(guard.call(fail_times(0)), Ok(1));
+        assert_eq!(guard.call(fail_times(0)), Ok(1));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let guard = Guard::new(Resilience::default().rate_limit(50.0));
+        let start = Instant::now();
+        for _ in 0..4 {
+            guard.call(fail_times(0)).unwrap();
+        }
+        // The first call is immediate and the rest are 20 ms apart.
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}
@@ -0,0 +1,139 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Segmentation of texts into paragraphs and sentences, by the same rules chunks are split and overlapped at, for uses other than chunking such as snippets and highlighting.
+
+use std::ops::Range;
+
+use crate::chunker::sentence_starts;
+
+/// Returns the byte spans of the paragraphs of a text, in order.
+///
+/// Paragraphs are separated by blank lines, which may hold other whitespace. Spans are trimmed of surrounding whitespace, and paragraphs of only whitespace are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::segment::paragraphs;
+/// let text = "First paragraph.\nStill first.\n \nSecond paragraph.\n\n\n";
+/// let spans = paragraphs(text);
+/// assert_eq!(spans, vec![0..29, 32..49]);
+/// assert_eq!(&text[spans[1].clone()], "Second paragraph.");
+/// ```
+pub fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !c.is_whitespace() {
+            continue;
+        }
+        // The run of whitespace from `i` to `end` separates paragraphs if it holds a blank line.
+        let mut line_breaks = usize::from(c == '\n');
+        let mut end = i + c.len_utf8();
+        while let Some((j, c)) = chars.next_if(|&(_, c)| c.is_whitespace()) {
+            line_breaks += usize::from(c == '\n');
+            end = j + c.len_utf8();
+        }
+        if line_breaks >= 2 {
+            push_trimmed(&mut paragraphs, text, start..i);
+            start = end;
+        }
+    }
+    push_trimmed(&mut paragraphs, text, start..text.len());
+    paragraphs
+}
+
+/// Returns the byte spans of the sentences of a text, in order.
+///
+/// Sentences end at a sentence terminator followed by whitespace, after any closing quotes and brackets, or at a full-width terminator, and never span paragraphs. Spans are trimmed of surrounding whitespace.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::segment::sentences;
+/// let text = "It rained. “Did it pour?” It did!\n\nThe end";
+/// let sentences: Vec<&str> = sentences(text).into_iter().map(|span| &text[span]).collect();
+/// assert_eq!(sentences, vec!["It rained.", "“Did it pour?”", "It did!", "The end"]);
+/// ```
+pub fn sentences(text: &str) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    for paragraph in paragraphs(text) {
+        let paragraph_text = &text[paragraph.clone()];
+        let mut starts = sentence_starts(paragraph_text, 0..paragraph_text.len()).peekable();
+        while let Some(start) = starts.next() {
+            let end = starts.peek().copied().unwrap_or(paragraph_text.len());
+            push_trimmed(
+                &mut sentences,
+                text,
+                paragraph.start + start..paragraph.start + end,
+            );
+        }
+    }
+    sentences
+}
+
+/// Pushes `span` of `text`, trimmed of surrounding whitespace, into `spans` unless it is only whitespace.
+fn push_trimmed(spans: &mut Vec<Range<usize>>, text: &str, span: Range<usize>) {
+    let segment = &text[span.clone()];
+    let trimmed = segment.trim();
+    if !trimmed.is_empty() {
+        let start = span.start + (segment.len() - segment.trim_start().len());
+        spans.push(start..start + trimmed.len());
+    }
+}
+
+#[cfg(test)]
+mod segment_tests {
+    use super::*;
+
+    fn texts(text: &str, spans: Vec<Range<usize>>) -> Vec<&str> {
+        spans.into_iter().map(|span| &text[span]).collect()
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let text = "\r\n  One.\r\n\r\nTwo,\nstill two.\n\t\nThree\u{3000}\n\n";
+        assert_eq!(
+            texts(text, paragraphs(text)),
+            vec!["One.", "Two,\nstill two.", "Three"]
+        );
+        assert!(paragraphs("").is_empty());
+        assert!(paragraphs(" \n\n ").is_empty());
+    }
+
+    #[test]
+    fn test_sentences() {
+        let text = "Dr. Smith arrived.Then left... (Why?) 今日は晴れ。明日は雨。\n\nNo terminator";
+        assert_eq!(
+            texts(text, sentences(text)),
+            vec![
+                "Dr.",
+                "Smith arrived.Then left...",
+                "(Why?)",
+                "今日は晴れ。",
+                "明日は雨。",
+                "No terminator"
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Semantic chunking, which places chunk boundaries where the meaning of the text shifts.
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+use crate::embed::{EmbedError, Embedder};
+use crate::segment;
+
+/// Decides which drops in similarity between consecutive sentences become chunk boundaries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breakpoint {
+    /// Break between sentences whose cosine similarity is below the threshold.
+    Threshold(f32),
+    /// Break between sentences whose cosine distance is above the given percentile (0-100) of the distances in the text.
+    Percentile(f32),
+}
+
+impl Default for Breakpoint {
+    fn default() -> Self {
+        Breakpoint::Percentile(95.0)
+    }
+}
+
+/// A struct for chunking texts at semantic breakpoints: sentences are embedded, and chunks end where consecutive sentences are least similar.
+///
+/// Groups of sentences over the chunk size are chunked further with a [`Chunker`], so every chunk still fits in `chunk_size` tokens.
+///
+/// # Fields
+///
+/// * `chunker` - The Chunker used to count tokens and to chunk oversized groups of sentences.
+/// * `embedder` - The source of sentence embeddings.
+/// * `breakpoint` - How boundaries are chosen from the similarities between sentences.
+///
+/// # Example
+///
+/// ```
+/// use semchunk_rs::embed::from_fn;
+/// use semchunk_rs::semantic::{Breakpoint, SemanticChunker};
+/// use semchunk_rs::Chunker;
+///
+/// // A toy embedding that tells sentences about animals from sentences about weather.
+/// let embedder = from_fn(|texts| {
+///     texts
+///         .iter()
+///         .map(|t| match t.contains("rain") || t.contains("sun") {
+///             true => vec![0.0, 1.0],
+///             false => vec![1.0, 0.0],
+///         })
+///         .collect()
+/// });
+/// let chunker = Chunker::new(64, Box::new(|s: &str| s.split_whitespace().count()));
+/// let chunker = SemanticChunker::new(chunker, embedder).breakpoint(Breakpoint::Threshold(0.5));
+/// let text = "Cats purr. Dogs bark. It may rain today. The sun sets late.";
+/// let chunks = chunker.chunk(text).unwrap();
+/// assert_eq!(chunks, vec!["Cats purr. Dogs bark.", "It may rain today. The sun sets late."]);
+/// ```
+pub struct SemanticChunker {
+    chunker: Chunker,
+    embedder: Box<dyn Embedder>,
+    breakpoint: Breakpoint,
+}
+
+impl SemanticChunker {
+    /// Creates a new SemanticChunker instance that breaks at the 95th percentile of cosine distances.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunker` - The Chunker whose chunk size and token counter are honored, used to chunk groups of sentences over the chunk size.
+    /// * `embedder` - The source of sentence embeddings.
+    ///
+    /// # Returns
+    ///
+    /// A new SemanticChunker instance.
+    pub fn new(chunker: Chunker, embedder: impl Embedder + 'static) -> Self {
+        SemanticChunker {
+            chunker,
+            embedder: Box::new(embedder),
+            breakpoint: Breakpoint::default(),
+        }
+    }
+
+    /// Sets how boundaries are chosen from the similarities between sentences.
+    pub fn breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoint = breakpoint;
+        self
+    }
+
+    /// Splits the text into sentences, returning their byte spans with surrounding whitespace trimmed.
+    fn sentences(&self, text: &str) -> Vec<(usize, usize)> {
+        segment::sentences(text)
+            .into_iter()
+            .map(|span| (span.start, span.end))
+            .collect()
+    }
+
+    /// Returns the indices of the sentences after which a chunk should end.
+    fn breakpoints(&self, sentences: &[&str]) -> Result<Vec<usize>, EmbedError> {
+        if sentences.len() < 2 {
+            return Ok(Vec::new());
+        }
+        let embeddings = self.embedder.embed_batch(sentences)?;
+        if embeddings.len() != sentences.len() {
+            return Err(format!(
+                "expected {} embeddings, got {}",
+                sentences.len(),
+                embeddings.len()
+            )
+            .into());
+        }
+        let distances: Vec<f32> = embeddings
+            .windows(2)
+            .map(|pair| 1.0 - cosine_similarity(&pair[0], &pair[1]))
+            .collect();
+        let cutoff = match self.breakpoint {
+            Breakpoint::Threshold(similarity) => 1.0 - similarity,
+            Breakpoint::Percentile(percentile) => self::percentile(&distances, percentile),
+        };
+        Ok(distances
+            .iter()
+            .enumerate()
+            .filter(|(_, &distance)| distance > cutoff)
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Chunks the given text at semantic breakpoints, passing each chunk to `sink` in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `sink` - A function called with each chunk, in order.
+    ///
+    /// # Returns
+    ///
+    /// An error if the sentences could not be embedded, in which case no chunks are passed to `sink`.
+    pub fn chunk_to<F: FnMut(Chunk)>(&self, text: &str, mut sink: F) -> Result<(), EmbedError> {
+        let sentences = self.sentences(text);
+        let sentence_texts: Vec<&str> = sentences
+            .iter()
+            .map(|&(start, end)| &text[start..end])
+            .collect();
+        let mut group_start = 0;
+        let mut breakpoints = self.breakpoints(&sentence_texts)?;
+        breakpoints.push(sentences.len().saturating_sub(1));
+        for breakpoint in breakpoints {
+            if group_start >= sentences.len() {
+                break;
+            }
+            let (start, end) = (sentences[group_start].0, sentences[breakpoint].1);
+            let group = &text[start..end];
+            if self.chunker.count_chunk_tokens(group) <= self.chunker.content_size() {
+                sink(Chunk {
+                    text: group.to_string(),
+                    start,
+                    end,
+                });
+            } else {
+                self.chunker.chunk_to(group, |chunk| {
+                    sink(Chunk {
+                        text: chunk.text,
+                        start: start + chunk.start,
+                        end: start + chunk.end,
+                    })
+                });
+            }
+            group_start = breakpoint + 1;
+        }
+        Ok(())
+    }
+
+    /// Chunks the given text at semantic breakpoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the chunks of the text, or an error if the sentences could not be embedded.
+    pub fn chunk(&self, text: &str) -> Result<Vec<String>, EmbedError> {
+        let mut chunks = Vec::new();
+        self.chunk_to(text, |chunk| chunks.push(chunk.text))?;
+        Ok(chunks)
+    }
+}
+
+/// Returns the cosine similarity of two vectors, or 0 if either is all zeros.
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    match norm_a * norm_b {
+        norm if norm > 0.0 => dot / norm,
+        _ => 0.0,
+    }
+}
+
+/// Returns the percentile (0-100) of the values, interpolating linearly between the closest ranks.
+fn percentile(values: &[f32], percentile: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f32;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f32)
+}
+
+#[cfg(test)]
+mod semantic_tests {
+    use super::*;
+    use crate::embed::from_fn;
+
+    fn word_chunker(chunk_size: usize) -> Chunker {
+        Chunker::new(chunk_size, Box::new(|s: &str| s.split_whitespace().count()))
+    }
+
+    #[test]
+    fn test_sentences() {
+        let chunker = SemanticChunker::new(word_chunker(8), from_fn(|_| Vec::new()));
+        let text = "  One. \"Two?\" Three!\n\nFour\n\n five";
+        let sentences: Vec<&str> = chunker
+            .sentences(text)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect();
+        assert_eq!(
+            sentences,
+            vec!["One.", "\"Two?\"", "Three!", "Four", "five"]
+        );
+    }
+
+    #[test]
+    fn test_oversized_groups_are_chunked() {
+        let embedder = from_fn(|texts| texts.iter().map(|_| vec![1.0]).collect());
+        let chunker = SemanticChunker::new(word_chunker(4), embedder);
+        let text = "The quick brown fox jumps over the lazy dog. It barked.";
+        let mut chunks = Vec::new();
+        chunker.chunk_to(text, |chunk| chunks.push(chunk)).unwrap();
+        let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "The quick brown fox",
+                "jumps over the lazy",
+                "dog. It barked."
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_embedding_count_mismatch() {
+        let chunker = SemanticChunker::new(word_chunker(4), from_fn(|_| vec![vec![1.0]]));
+        assert!(chunker.chunk("One. Two.").is_err());
+        assert_eq!(chunker.chunk("One.").unwrap(), vec!["One."]);
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[0.4, 0.1, 0.3, 0.2], 0.0), 0.1);
+        assert_eq!(percentile(&[0.4, 0.1, 0.3, 0.2], 100.0), 0.4);
+        assert!((percentile(&[0.4, 0.1, 0.3, 0.2], 50.0) - 0.25).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 0.0]), 0.0);
+    }
+}
@@ -0,0 +1,210 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! An HTTP service for chunking text, enabled with the `serve` feature.
+//!
+//! `POST /chunk` takes a JSON body with the text and the chunking settings, and responds with the chunks and their byte offsets.
+//!
+//! ```text
+//! $ curl -s localhost:8080/chunk -d '{"text": "The quick brown fox jumps over the lazy dog.", "chunk_size": 4}' -H 'content-type: application/json'
+//! {"chunks":[{"text":"The quick brown fox","start":0,"end":19},{"text":"jumps over the lazy","start":20,"end":39},{"text":"dog.","start":40,"end":44}]}
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+use crate::chunk::Chunk;
+use crate::chunker::Chunker;
+
+/// The body of a `POST /chunk` request.
+///
+/// # Fields
+///
+/// * `text` - The text to chunk.
+/// * `chunk_size` - The maximum number of tokens that can be in a chunk.
+/// * `counter` - The token counter: `words` (default), `chars`, or `tiktoken:<encoding>` with the `tiktoken-rs` feature.
+/// * `overlap` - The number of tokens consecutive chunks should share, which must be less than the chunk size. Defaults to 0.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChunkRequest {
+    pub text: String,
+    pub chunk_size: usize,
+    #[serde(default = "default_counter")]
+    pub counter: String,
+    #[serde(default)]
+    pub overlap: usize,
+}
+
+fn default_counter() -> String {
+    "words".to_string()
+}
+
+/// The body of a `POST /chunk` response.
+///
+/// # Fields
+///
+/// * `chunks` - The chunks, in order, with their byte offsets into the request text.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkResponse {
+    pub chunks: Vec<Chunk>,
+}
+
+/// Creates the router for the chunking service, for serving directly or nesting into an existing application.
+///
+/// # Returns
+///
+/// A Router with the `POST /chunk` route.
+pub fn router() -> Router {
+    Router::new().route("/chunk", post(chunk))
+}
+
+/// Serves the chunking service on the given address until the process is stopped.
+///
+/// # Arguments
+///
+/// * `addr` - The address to listen on, e.g. `"127.0.0.1:8080"`.
+pub async fn serve(addr: impl ToSocketAddrs) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router()).await
+}
+
+async fn chunk(
+    Json(request): Json<ChunkRequest>,
+) -> Result<Json<ChunkResponse>, (StatusCode, String)> {
+    let token_counter =
+        token_counter(&request.counter).map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _cancel_on_drop = CancelOnDrop(cancelled.clone());
+    // Chunking is CPU-bound, so it runs off the async worker threads.
+    tokio::task::spawn_blocking(move || {
+        let chunker = Chunker::try_new(request.chunk_size, token_counter)
+            .and_then(|chunker| chunker.try_overlap(request.overlap))
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+        let mut chunks = Vec::new();
+        // The response to a cancelled request is never sent.
+        let _ =
+            chunker.chunk_to_cancellable(&request.text, &*cancelled, |chunk| chunks.push(chunk));
+        Ok(Json(ChunkResponse { chunks }))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+}
+
+/// Cancels chunking when dropped, as the handler is when its client disconnects, so that chunking does not continue for no one.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A token counter that can be moved to a blocking thread.
+type TokenCounter = Box<dyn Fn(&str) -> usize + Send>;
+
+/// Parses a counter name into a token counter.
+fn token_counter(name: &str) -> Result<TokenCounter, String> {
+    match name {
+        "words" => Ok(Box::new(|s: &str| s.split_whitespace().count())),
+        "chars" => Ok(Box::new(|s: &str| s.chars().count())),
+        _ => match name.strip_prefix("tiktoken:") {
+            Some(encoding) => tiktoken_counter(encoding),
+            None => Err(format!(
+                "unknown counter '{}', expected 'words', 'chars' or 'tiktoken:<encoding>'",
+                name
+            )),
+        },
+    }
+}
+
+#[cfg(feature = "tiktoken-rs")]
+fn tiktoken_counter(encoding: &str) -> Result<TokenCounter, String> {
+    use tiktoken_rs::{
+        cl100k_base_singleton, o200k_base_singleton, p50k_base_singleton, p50k_edit_singleton,
+        r50k_base_singleton,
+    };
+
+    let bpe = match encoding {
+        "o200k_base" => o200k_base_singleton(),
+        "cl100k_base" => cl100k_base_singleton(),
+        "p50k_base" => p50k_base_singleton(),
+        "p50k_edit" => p50k_edit_singleton(),
+        "r50k_base" => r50k_base_singleton(),
+        _ => return Err(format!("unknown tiktoken encoding '{}'", encoding)),
+    };
+    Ok(Box::new(move |s: &str| bpe.lock().encode_ordinary(s).len()))
+}
+
+#[cfg(not(feature = "tiktoken-rs"))]
+fn tiktoken_counter(_encoding: &str) -> Result<TokenCounter, String> {
+    Err("the tiktoken counter requires the tiktoken-rs feature".to_string())
+}
+
+#[cfg(test)]
+mod serve_tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn post_chunk(body: &str) -> (StatusCode, Vec<u8>) {
+        let request = Request::post("/chunk")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_route() {
+        let (status, body) = post_chunk(
+            r#"{"text": "The quick brown fox jumps over the lazy dog.", "chunk_size": 4, "overlap": 2}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let chunks = response["chunks"].as_array().unwrap();
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[1]["text"], "brown fox jumps over");
+        assert_eq!(chunks[1]["start"], 10);
+        assert_eq!(chunks[1]["end"], 30);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_route_rejects_bad_settings() {
+        let (status, _) =
+            post_chunk(r#"{"text": "text", "chunk_size": 4, "counter": "bytes"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = post_chunk(r#"{"text": "text", "chunk_size": 0}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = post_chunk(r#"{"text": "text", "chunk_size": 4, "overlap": 4}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
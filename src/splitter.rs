@@ -20,15 +20,218 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use regex::Regex;
+use std::sync::Arc;
 
-const NON_WHITESPACE_SEMANTIC_SEPARATORS: [&str; 25] = [
+use memchr::{memchr, memchr2};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub(crate) const NON_WHITESPACE_SEMANTIC_SEPARATORS: [&str; 25] = [
     ".", "?", "!", "*", // Sentence terminators
     ";", ",", "(", ")", "[", "]", "“", "”", "‘", "’", "'", "\"", "`", // Clause separators.
     ":", "—", "…", // Sentence interrupters.
     "/", "\\", "–", "&", "-", // Word joiners.
 ];
 
+/// The number of sentence terminators at the start of [`NON_WHITESPACE_SEMANTIC_SEPARATORS`], and of sentence terminators, clause separators and sentence interrupters.
+const LATIN_KIND_ENDS: (usize, usize) = (4, 20);
+
+/// Semantically meaningful non-whitespace separators for Chinese, Japanese and Korean text, with full-width punctuation ranked alongside its ASCII counterparts.
+const CJK_SEMANTIC_SEPARATORS: [&str; 43] = [
+    "。", "！", "？", ".", "?", "!", "*", // Sentence terminators
+    "；", ";", "，", "、", ",", "（", "）", "(", ")", "[", "]", "「", "」", "『", "』", "《", "》",
+    "【", "】", "“", "”", "‘", "’", "'", "\"", "`", // Clause separators.
+    "：", ":", "—", "…", // Sentence interrupters.
+    "/", "\\", "–", "&", "-", "・", // Word joiners.
+];
+
+/// The number of sentence terminators at the start of [`CJK_SEMANTIC_SEPARATORS`], and of sentence terminators, clause separators and sentence interrupters.
+const CJK_KIND_ENDS: (usize, usize) = (7, 37);
+
+/// Semantically meaningful non-whitespace separators for Arabic-script text, with Arabic punctuation ranked alongside its Latin counterparts.
+const ARABIC_SEMANTIC_SEPARATORS: [&str; 31] = [
+    ".", "؟", "?", "!", "۔", "*", // Sentence terminators
+    "؛", ";", "،", ",", "(", ")", "[", "]", "«", "»", "“", "”", "‘", "’", "'", "\"",
+    "`", // Clause separators.
+    ":", "—", "…", // Sentence interrupters.
+    "/", "\\", "–", "&", "-", // Word joiners.
+];
+
+/// The number of sentence terminators at the start of [`ARABIC_SEMANTIC_SEPARATORS`], and of sentence terminators, clause separators and sentence interrupters.
+const ARABIC_KIND_ENDS: (usize, usize) = (6, 26);
+
+/// Semantically meaningful non-whitespace separators for source code, from the ends of statements and blocks down to operators.
+const CODE_SEMANTIC_SEPARATORS: [&str; 17] = [
+    ";", "}", "{", // Statement and block delimiters.
+    ",", ")", "(", "]", "[", // Argument and element separators.
+    "=", ":", ".", "&&", "||", "+", "-", "*", "/", // Operators.
+];
+
+/// The number of statement and block delimiters at the start of [`CODE_SEMANTIC_SEPARATORS`], and of delimiters and argument and element separators.
+const CODE_KIND_ENDS: (usize, usize) = (3, 8);
+
+/// Which semantically meaningful non-whitespace separators a [`Splitter`] looks for, suited to the punctuation of a family of scripts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SeparatorProfile {
+    /// Chooses the profile for each text split from the script most of its first letters are in.
+    #[default]
+    Auto,
+    /// Latin punctuation, which also suits Cyrillic, Greek and most other scripts.
+    Latin,
+    /// Full-width and ideographic punctuation, for Chinese, Japanese and Korean.
+    Cjk,
+    /// Arabic punctuation, for Arabic, Persian and Urdu.
+    Arabic,
+    /// Statement, block and operator punctuation, for source code.
+    Code,
+}
+
+impl SeparatorProfile {
+    /// Returns the profile, or with [`SeparatorProfile::Auto`], the profile for the script of `text`.
+    fn resolve(self, text: &str) -> SeparatorProfile {
+        match self {
+            SeparatorProfile::Auto => detect_profile(text),
+            profile => profile,
+        }
+    }
+
+    /// The separators of a resolved profile, in order of most to least desirable.
+    fn separators(self) -> &'static [&'static str] {
+        match self {
+            SeparatorProfile::Auto | SeparatorProfile::Latin => &NON_WHITESPACE_SEMANTIC_SEPARATORS,
+            SeparatorProfile::Cjk => &CJK_SEMANTIC_SEPARATORS,
+            SeparatorProfile::Arabic => &ARABIC_SEMANTIC_SEPARATORS,
+            SeparatorProfile::Code => &CODE_SEMANTIC_SEPARATORS,
+        }
+    }
+
+    /// The kind of the separator at `index` in the separators of a resolved profile.
+    ///
+    /// Sentence interrupters rank with clause separators, and in source code, statement and block delimiters rank as sentence terminators, argument separators as clause separators and operators as word joiners.
+    pub(crate) fn kind(self, index: usize) -> SeparatorKind {
+        let (terminators, clauses) = match self {
+            SeparatorProfile::Auto | SeparatorProfile::Latin => LATIN_KIND_ENDS,
+            SeparatorProfile::Cjk => CJK_KIND_ENDS,
+            SeparatorProfile::Arabic => ARABIC_KIND_ENDS,
+            SeparatorProfile::Code => CODE_KIND_ENDS,
+        };
+        if index < terminators {
+            SeparatorKind::SentenceTerminator
+        } else if index < clauses {
+            SeparatorKind::ClauseSeparator
+        } else {
+            SeparatorKind::WordJoiner
+        }
+    }
+}
+
+/// Returns the kind of the punctuation `text` ends with, in the first of the Latin, CJK and Arabic profiles it is a separator of, if any.
+pub(crate) fn punctuation_kind(text: &str) -> Option<SeparatorKind> {
+    [
+        SeparatorProfile::Latin,
+        SeparatorProfile::Cjk,
+        SeparatorProfile::Arabic,
+    ]
+    .into_iter()
+    .find_map(|profile| {
+        let index = profile
+            .separators()
+            .iter()
+            .position(|separator| text.ends_with(separator))?;
+        Some(profile.kind(index))
+    })
+}
+
+/// The level of the hierarchy of separators that a separator is at, from most to least desirable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SeparatorKind {
+    /// A run of newlines and/or carriage returns.
+    ParagraphBreak,
+    /// A tab.
+    Tab,
+    /// Any other whitespace character.
+    Whitespace,
+    /// A sentence terminator, such as `.`, `?` or `。`.
+    SentenceTerminator,
+    /// A clause separator or sentence interrupter, such as `,`, `;`, `(` or `—`.
+    ClauseSeparator,
+    /// A word joiner, such as `-`, `/` or `&`.
+    WordJoiner,
+    /// No separator, where text is split between words, grapheme clusters or characters, or where it ends.
+    #[default]
+    None,
+}
+
+/// The separator a text was split at, and the level of the hierarchy of separators it is at.
+///
+/// # Fields
+///
+/// * `text` - The text of the separator, which is empty for [`SeparatorKind::None`].
+/// * `kind` - The level of the hierarchy of separators the separator is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separator<'a> {
+    pub text: &'a str,
+    pub kind: SeparatorKind,
+}
+
+impl Separator<'_> {
+    /// Returns whether the separator is whitespace or none at all, in which case it is not kept when splits are merged into chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::splitter::{Separator, SeparatorKind};
+    /// assert!(Separator { text: "\t", kind: SeparatorKind::Tab }.is_whitespace());
+    /// assert!(!Separator { text: ",", kind: SeparatorKind::ClauseSeparator }.is_whitespace());
+    /// ```
+    pub fn is_whitespace(&self) -> bool {
+        matches!(
+            self.kind,
+            SeparatorKind::ParagraphBreak
+                | SeparatorKind::Tab
+                | SeparatorKind::Whitespace
+                | SeparatorKind::None
+        )
+    }
+}
+
+/// The number of letters looked at to detect the script of a text.
+const PROFILE_SAMPLE_LETTERS: usize = 256;
+
+/// Returns the profile for the script most of the first letters of `text` are in.
+fn detect_profile(text: &str) -> SeparatorProfile {
+    let (mut cjk, mut arabic, mut other) = (0, 0, 0);
+    for c in text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .take(PROFILE_SAMPLE_LETTERS)
+    {
+        match c {
+            // Hiragana, Katakana, CJK ideographs and Hangul syllables.
+            '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}' => cjk += 1,
+            // Arabic and its supplements and presentation forms.
+            '\u{0600}'..='\u{06FF}'
+            | '\u{0750}'..='\u{077F}'
+            | '\u{08A0}'..='\u{08FF}'
+            | '\u{FB50}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}' => arabic += 1,
+            _ => other += 1,
+        }
+    }
+    if cjk > arabic && cjk > other {
+        SeparatorProfile::Cjk
+    } else if arabic > other {
+        SeparatorProfile::Arabic
+    } else {
+        SeparatorProfile::Latin
+    }
+}
+
 /// A struct for splitting texts into segments based on the most desirable separator found.
 /// 
 /// # Examples
@@ -37,38 +240,141 @@ const NON_WHITESPACE_SEMANTIC_SEPARATORS: [&str; 25] = [
 /// use semchunk_rs::Splitter;
 /// let splitter = Splitter::default();
 /// let text = "Hello World\nGoodbye World";
-/// let (separator, is_whitespace, segments) = splitter.split_text(text);
-/// assert_eq!(separator, "\n");
-/// assert!(is_whitespace);
+/// let (separator, segments) = splitter.split_text(text);
+/// assert_eq!(separator.text, "\n");
+/// assert!(separator.is_whitespace());
 /// assert_eq!(segments, vec!["Hello World", "Goodbye World"]);
 /// ```
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct Splitter {
-    line_carriage: Regex,
-    tab: Regex,
-    space: Regex,
+    profile: SeparatorProfile,
+    runs: Arc<[&'static str]>,
+    spaces: Arc<[char]>,
+    markdown: bool,
 }
 
+/// The whitespace characters that count as spaces by default: the space and the no-break space, which HTML-derived text is full of.
+const DEFAULT_SPACES: [char; 2] = [' ', '\u{A0}'];
+
 impl Default for Splitter {
     fn default() -> Self {
         Splitter {
-            line_carriage: Regex::new(r"[\n\r]+").unwrap(),
-            tab: Regex::new(r"\t").unwrap(),
-            space: Regex::new(r"\s").unwrap(),
+            profile: SeparatorProfile::default(),
+            runs: Arc::new([]),
+            spaces: Arc::new(DEFAULT_SPACES),
+            markdown: false,
         }
     }
 }
 
 impl Splitter {
+    /// Sets which semantically meaningful non-whitespace separators the splitter looks for, which by default are chosen from the script of each text split.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::splitter::SeparatorProfile;
+    /// use semchunk_rs::Splitter;
+    /// let text = "今日は晴れ。明日は雨。";
+    /// let (separator, segments) = Splitter::default().split_text(text);
+    /// assert_eq!(separator.text, "。");
+    /// assert_eq!(segments, vec!["今日は晴れ", "明日は雨", ""]);
+    /// let (separator, _) = Splitter::default().profile(SeparatorProfile::Latin).split_text(text);
+    /// assert_eq!(separator.text, "");
+    /// ```
+    pub fn profile(mut self, profile: SeparatorProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Sets which non-whitespace separators are matched in runs, so that splitting at one of them also consumes any of them next to it, rather than leaving empty segments between them.
+    ///
+    /// Listing the separators of a level together makes their multigraphs single separators too, e.g. listing the sentence terminators `.`, `?`, `!` and `…` keeps `...` and `?!` whole. By default, every separator is matched on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Splitter;
+    /// let text = "Wait...what?!";
+    /// let (_, segments) = Splitter::default().split_text(text);
+    /// assert_eq!(segments, vec!["Wait", "", "", "what?!"]);
+    /// let splitter = Splitter::default().separator_runs(&[".", "?", "!", "…"]);
+    /// let (separator, segments) = splitter.split_text(text);
+    /// assert_eq!(separator.text, ".");
+    /// assert_eq!(segments, vec!["Wait", "what?!"]);
+    /// ```
+    pub fn separator_runs(mut self, separators: &[&'static str]) -> Self {
+        self.runs = separators
+            .iter()
+            .copied()
+            .filter(|s| !s.is_empty())
+            .collect();
+        self
+    }
+
+    /// Sets which whitespace characters count as spaces, which are all split at together, as the least desirable whitespace separator, rather than ranked by their widths like other whitespace characters.
+    ///
+    /// By default, the space and the no-break space (U+00A0) count as spaces, so that text with a mix of them is split into words rather than first at each no-break space. Characters that are not whitespace are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Splitter;
+    /// let text = "Salut\u{202F}! Ça va\u{A0}?";
+    /// let (separator, segments) = Splitter::default().split_text(text);
+    /// assert_eq!(separator.text, "\u{202F}");
+    /// assert_eq!(segments, vec!["Salut", "! Ça va\u{A0}?"]);
+    /// let splitter = Splitter::default().spaces(&[' ', '\u{A0}', '\u{202F}']);
+    /// let (_, segments) = splitter.split_text(text);
+    /// assert_eq!(segments, vec!["Salut", "!", "Ça", "va", "?"]);
+    /// ```
+    pub fn spaces(mut self, spaces: &[char]) -> Self {
+        self.spaces = spaces
+            .iter()
+            .copied()
+            .filter(|c| c.is_whitespace())
+            .collect();
+        self
+    }
+
+    /// Returns whether the list of separators text is split into words at, set with [`Splitter::spaces`], is empty.
+    pub(crate) fn separators_empty(&self) -> bool {
+        self.spaces.is_empty()
+    }
+
+    /// Sets whether text is split at the blocks of Markdown before any other separator, which defaults to false.
+    ///
+    /// Text is split at the line breaks before headings, from the highest level down, then at those around fenced code blocks, then at those before list items, so that each segment starts with its heading, fence or list marker. Lines in fenced code blocks are never taken for headings or list items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Splitter;
+    /// let text = "# Title\nIntro.\n\n## Usage\n- Install it.\n- Run it.\n\n## License\nMIT";
+    /// let splitter = Splitter::default().markdown(true);
+    /// let (separator, segments) = splitter.split_text(text);
+    /// assert_eq!(separator.text, "\n\n");
+    /// assert_eq!(segments, vec!["# Title\nIntro.", "## Usage\n- Install it.\n- Run it.", "## License\nMIT"]);
+    /// let (_, segments) = splitter.split_text(segments[1]);
+    /// assert_eq!(segments, vec!["## Usage", "- Install it.", "- Run it."]);
+    /// ```
+    pub fn markdown(mut self, markdown: bool) -> Self {
+        self.markdown = markdown;
+        self
+    }
+
     /// Splits the given text into segments based on the most desirable separator found.
     ///
     /// The method prioritizes separators in the following order:
+    /// 0. With [`Splitter::markdown`], the line breaks before Markdown headings, around fenced code blocks or before list items.
     /// 1. The largest sequence of newlines and/or carriage returns.
     /// 2. The largest sequence of tabs.
-    /// 3. The largest sequence of whitespace characters.
-    /// 4. A semantically meaningful non-whitespace separator.
+    /// 3. The widest whitespace character that is not a space, from the splitter's spaces.
+    /// 4. Any space.
+    /// 5. A semantically meaningful non-whitespace separator, from the splitter's [`SeparatorProfile`].
     ///
-    /// If no semantically meaningful separator is found, the text is split into individual characters.
+    /// If no semantically meaningful separator is found, the text is split into individual grapheme clusters, so that emoji sequences, combining marks and flags are never split apart. A single grapheme cluster is left whole, so one over the chunk size becomes a chunk of its own, reported by [`Chunker::violations`](crate::Chunker::violations). With the `icu_segmenter` feature, text in scripts written without spaces, such as Chinese, Japanese, Thai and Khmer, is first split into words with a dictionary.
     ///
     /// # Arguments
     ///
@@ -77,84 +383,399 @@ impl Splitter {
     /// # Returns
     ///
     /// A tuple containing:
-    /// * The separator used for splitting the text.
-    /// * A boolean indicating whether the separator is whitespace.
+    /// * The separator used for splitting the text, and its kind.
     /// * A vector of string slices representing the segments of the split text.
     ///
     /// # Examples
     ///
     /// ```
+    /// use semchunk_rs::splitter::SeparatorKind;
     /// use semchunk_rs::Splitter;
     /// let splitter = Splitter::default();
     /// let text = "Hello World\nGoodbye World";
-    /// let (separator, is_whitespace, segments) = splitter.split_text(text);
-    /// assert_eq!(separator, "\n");
-    /// assert!(is_whitespace);
+    /// let (separator, segments) = splitter.split_text(text);
+    /// assert_eq!(separator.text, "\n");
+    /// assert_eq!(separator.kind, SeparatorKind::ParagraphBreak);
     /// assert_eq!(segments, vec!["Hello World", "Goodbye World"]);
     /// ```
-    pub fn split_text<'a>(&self, text: &'a str) -> (&'a str, bool, Vec<&'a str>) {
-        let mut separator_is_whitespace = true;
-        let mut separator_search_pattern: Option<&Regex> = Option::None;
-        let separator: &str;
+    pub fn split_text<'a>(&self, text: &'a str) -> (Separator<'a>, Vec<&'a str>) {
+        let (separator, splits) = self.splits(text);
+        (separator, splits.collect())
+    }
 
+    /// Splits the given text like [`Splitter::split_text`], but returns the segments as an iterator rather than collecting them.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be split.
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing:
+    /// * The separator used for splitting the text, and its kind.
+    /// * An iterator over the segments of the split text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Splitter;
+    /// let splitter = Splitter::default();
+    /// let (separator, mut segments) = splitter.splits("Hello World\nGoodbye World");
+    /// assert_eq!(separator.text, "\n");
+    /// assert_eq!(segments.next(), Some("Hello World"));
+    /// ```
+    pub fn splits<'a>(&self, text: &'a str) -> (Separator<'a>, Splits<'a>) {
         // Try splitting at, in order of most desirable to least desirable:
         // - The largest sequence of newlines and/or carriage returns;
-        // - The largest sequence of tabs;
-        // - The largest sequence of whitespace characters; and
+        // - A tab;
+        // - The widest whitespace character that is not a space;
+        // - Any space; and
         // - A semantically meaningful non-whitespace separator.
-        if text.contains("\n") || text.contains("\r") {
-            separator_search_pattern = Option::Some(&self.line_carriage);
-            // Find longest line break
-        } else if text.contains("\t") {
-            separator_search_pattern = Option::Some(&self.tab);
-        } else if self.space.is_match(text) {
-            separator_search_pattern = Option::Some(&self.space);
-        }
-
-        match separator_search_pattern {
-            Some(pattern) => {
-                separator = pattern
-                    .find_iter(text)
-                    .map(|m| text.get(m.start()..m.end()).unwrap())
-                    .max_by_key(|&s| s.len())
-                    .unwrap();
+        // Where several are equally desirable, the last one is used.
+        if self.markdown {
+            if let Some(breaks) = markdown_breaks(text) {
+                let &(start, end) = breaks.last().expect("markdown breaks are never empty");
+                let separator = Separator {
+                    text: &text[start..end],
+                    kind: SeparatorKind::ParagraphBreak,
+                };
+                let splits = SplitsInner::Breaks {
+                    text,
+                    start: Some(0),
+                    breaks: breaks.into_iter(),
+                };
+                return (separator, Splits(splits));
+            }
+        }
+        let whitespace_separator = longest_line_break(text)
+            .map(|text| (text, SeparatorKind::ParagraphBreak))
+            .or_else(|| memchr(b'\t', text.as_bytes()).map(|_| ("\t", SeparatorKind::Tab)))
+            .or_else(|| {
+                widest_whitespace(text, &self.spaces).map(|text| (text, SeparatorKind::Whitespace))
+            });
+        if let Some((separator, kind)) = whitespace_separator {
+            let splits = SplitsInner::Separator(text.split(separator));
+            return (
+                Separator {
+                    text: separator,
+                    kind,
+                },
+                Splits(splits),
+            );
+        }
+        let mut spaces = self.spaces.iter().filter(|&&c| text.contains(c));
+        if let Some(start) = spaces.next().and_then(|&space| text.rfind(space)) {
+            let width = text[start..].chars().next().map_or(0, char::len_utf8);
+            let separator = Separator {
+                text: &text[start..start + width],
+                kind: SeparatorKind::Whitespace,
+            };
+            // Texts with only one kind of space are split at it like any other separator.
+            let splits = match spaces.next() {
+                Some(_) => SplitsInner::Spaces {
+                    text,
+                    start: Some(0),
+                    spaces: Arc::clone(&self.spaces),
+                },
+                None => SplitsInner::Separator(text.split(separator.text)),
+            };
+            return (separator, Splits(splits));
+        }
+
+        // Identify the most desirable semantically meaningful non-whitespace separator present in the text.
+        let profile = self.profile.resolve(text);
+        match profile.separators().iter().position(|&c| text.contains(c)) {
+            Some(index) => {
+                let separator = Separator {
+                    text: profile.separators()[index],
+                    kind: profile.kind(index),
+                };
+                let splits = match self.runs.contains(&separator.text) {
+                    true => SplitsInner::Runs {
+                        text,
+                        start: Some(0),
+                        separator: separator.text,
+                        runs: Arc::clone(&self.runs),
+                    },
+                    false => SplitsInner::Separator(text.split(separator.text)),
+                };
+                (separator, Splits(splits))
             }
+            // If no semantically meaningful separator is present in the text, return an empty string as the separator and the text as a list of words, where they can be found, or grapheme clusters.
             None => {
-                // Identify the most desirable semantically meaningful non-whitespace separator present in the text.
-                match NON_WHITESPACE_SEMANTIC_SEPARATORS
+                let separator = Separator {
+                    text: "",
+                    kind: SeparatorKind::None,
+                };
+                #[cfg(feature = "icu_segmenter")]
+                if let Some(boundaries) = word_boundaries(text) {
+                    let splits = SplitsInner::Words(text, boundaries.into_iter(), 0);
+                    return (separator, Splits(splits));
+                }
+                // Grapheme clusters are never split, so a single one is a split of its own.
+                let splits = SplitsInner::Graphemes(text.graphemes(true));
+                (separator, Splits(splits))
+            }
+        }
+    }
+}
+
+/// An iterator over the segments of a text split by [`Splitter::splits`].
+#[derive(Debug, Clone)]
+pub struct Splits<'a>(SplitsInner<'a>);
+
+#[derive(Debug, Clone)]
+enum SplitsInner<'a> {
+    /// The segments between occurrences of a separator.
+    Separator(std::str::Split<'a, &'a str>),
+    /// The segments between runs of separators around occurrences of a separator, from the start of the next segment, if any.
+    Runs {
+        text: &'a str,
+        start: Option<usize>,
+        separator: &'a str,
+        runs: Arc<[&'static str]>,
+    },
+    /// The segments between any of the spaces, from the start of the next segment, if any.
+    Spaces {
+        text: &'a str,
+        start: Option<usize>,
+        spaces: Arc<[char]>,
+    },
+    /// The segments between the given spans of line breaks, from the start of the next segment, if any.
+    Breaks {
+        text: &'a str,
+        start: Option<usize>,
+        breaks: std::vec::IntoIter<(usize, usize)>,
+    },
+    /// The extended grapheme clusters of the text.
+    Graphemes(unicode_segmentation::Graphemes<'a>),
+    /// The words of the text, ending at the given byte offsets, and the start of the next word.
+    #[cfg(feature = "icu_segmenter")]
+    Words(&'a str, std::vec::IntoIter<usize>, usize),
+}
+
+impl<'a> Iterator for Splits<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        match &mut self.0 {
+            SplitsInner::Separator(splits) => splits.next(),
+            SplitsInner::Runs {
+                text,
+                start,
+                separator,
+                runs,
+            } => {
+                let segment_start = (*start)?;
+                let Some(offset) = text[segment_start..].find(*separator) else {
+                    *start = None;
+                    return Some(&text[segment_start..]);
+                };
+                // Extend the separator into the run of separators around it.
+                let mut segment_end = segment_start + offset;
+                let mut run_end = segment_end + separator.len();
+                while let Some(run) = runs
                     .iter()
-                    .find(|&&c| text.contains(c))
-                    .copied()
+                    .find(|run| text[segment_start..segment_end].ends_with(*run))
                 {
-                    Some(c) => {
-                        separator = c;
-                        separator_is_whitespace = false;
+                    segment_end -= run.len();
+                }
+                while let Some(run) = runs.iter().find(|run| text[run_end..].starts_with(*run)) {
+                    run_end += run.len();
+                }
+                *start = Some(run_end);
+                Some(&text[segment_start..segment_end])
+            }
+            SplitsInner::Spaces {
+                text,
+                start,
+                spaces,
+            } => {
+                let segment_start = (*start)?;
+                match text[segment_start..].find(&spaces[..]) {
+                    Some(offset) => {
+                        let segment_end = segment_start + offset;
+                        let width = text[segment_end..].chars().next().map_or(0, char::len_utf8);
+                        *start = Some(segment_end + width);
+                        Some(&text[segment_start..segment_end])
                     }
                     None => {
-                        // If no semantically meaningful separator is present in the text, return an empty string as the separator and the text as a list of characters.
-                        // text.split("") does this obnoxious thing where it includes an empty string at the start and end of the list, so removing that.
-                        return (
-                            "",
-                            true,
-                            text.split("")
-                                .collect::<Vec<&str>>()
-                                .get(1..text.len() + 1)
-                                .unwrap()
-                                .to_vec(),
-                        );
+                        *start = None;
+                        Some(&text[segment_start..])
                     }
                 }
             }
+            SplitsInner::Breaks {
+                text,
+                start,
+                breaks,
+            } => {
+                let segment_start = (*start)?;
+                match breaks.next() {
+                    Some((segment_end, next_start)) => {
+                        *start = Some(next_start);
+                        Some(&text[segment_start..segment_end])
+                    }
+                    None => {
+                        *start = None;
+                        Some(&text[segment_start..])
+                    }
+                }
+            }
+            SplitsInner::Graphemes(graphemes) => graphemes.next(),
+            #[cfg(feature = "icu_segmenter")]
+            SplitsInner::Words(text, boundaries, start) => {
+                let end = boundaries.next()?;
+                Some(&text[std::mem::replace(start, end)..end])
+            }
         }
-        // Return the separator and the split text
-        (
-            separator,
-            separator_is_whitespace,
-            text.split(separator).collect::<Vec<&str>>().clone(),
-        )
     }
 }
 
+/// Returns the byte offsets at which the words of the text end, if it has more than one word.
+///
+/// Words are found with the Unicode word boundary rules, and with dictionaries for Chinese, Japanese, Thai, Khmer, Lao and Burmese.
+#[cfg(feature = "icu_segmenter")]
+fn word_boundaries(text: &str) -> Option<Vec<usize>> {
+    let segmenter = icu_segmenter::WordSegmenter::new_dictionary(Default::default());
+    let boundaries: Vec<usize> = segmenter.segment_str(text).filter(|&i| i > 0).collect();
+    (boundaries.len() > 1).then_some(boundaries)
+}
+
+/// The rank of the line breaks around fenced code blocks among Markdown block boundaries, after those before the six levels of headings.
+const FENCE_RANK: usize = 6;
+/// The rank of the line breaks before list items among Markdown block boundaries.
+const LIST_ITEM_RANK: usize = 7;
+
+/// Returns the spans of the line breaks before the most desirable kind of Markdown block in the text, if any are after its first line.
+///
+/// Blocks rank, from most to least desirable: headings, from `#` to `######`, fenced code blocks opened with ```` ``` ```` or `~~~`, which are also split after, and list items marked with `-`, `*`, `+` or a number.
+fn markdown_breaks(text: &str) -> Option<Vec<(usize, usize)>> {
+    let mut boundaries: Vec<(usize, usize)> = Vec::new();
+    // The marker of the fenced code block the line is in, if any.
+    let mut fence: Option<&str> = None;
+    // The rank of a boundary after a fenced code block, until the next line that is not blank.
+    let mut after_fence = None;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        if let Some(marker) = fence {
+            if content.trim_start().starts_with(marker) {
+                fence = None;
+                after_fence = Some(FENCE_RANK);
+            }
+            continue;
+        }
+        if content.trim().is_empty() {
+            continue;
+        }
+        let trimmed = content.trim_start_matches(' ');
+        let opening_fence = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        let rank = if let Some(marker) = opening_fence {
+            fence = Some(marker);
+            Some(FENCE_RANK)
+        } else if let Some(level) = heading_level(trimmed) {
+            Some(level - 1)
+        } else if is_list_item(trimmed) {
+            Some(LIST_ITEM_RANK)
+        } else {
+            None
+        };
+        let rank = match (rank, after_fence.take()) {
+            (Some(rank), Some(fence_rank)) => Some(rank.min(fence_rank)),
+            (rank, fence_rank) => rank.or(fence_rank),
+        };
+        // The line breaks before the line, including any blank lines.
+        let break_start = text[..start].trim_end_matches(['\n', '\r']).len();
+        if let Some(rank) = rank.filter(|_| break_start > 0) {
+            boundaries.push((rank, break_start));
+        }
+    }
+    let best = boundaries.iter().map(|&(rank, _)| rank).min()?;
+    Some(
+        boundaries
+            .into_iter()
+            .filter(|&(rank, _)| rank == best)
+            .map(|(_, break_start)| {
+                let break_end = break_start + text[break_start..].len()
+                    - text[break_start..].trim_start_matches(['\n', '\r']).len();
+                (break_start, break_end)
+            })
+            .collect(),
+    )
+}
+
+/// Returns the level of the ATX heading the line starts, if it starts one.
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])))
+        .then_some(level)
+}
+
+/// Returns whether the line starts a list item, with a bullet or a number followed by `.` or `)`, and a space.
+fn is_list_item(line: &str) -> bool {
+    let rest = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            match digits {
+                1..=9 => match line[digits..].strip_prefix(['.', ')']) {
+                    Some(rest) => rest,
+                    None => return false,
+                },
+                _ => return false,
+            }
+        }
+    };
+    rest.starts_with([' ', '\t'])
+}
+
+/// Returns the last of the longest sequences of newlines and/or carriage returns in the text.
+fn longest_line_break(text: &str) -> Option<&str> {
+    let bytes = text.as_bytes();
+    let mut longest: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while let Some(offset) = memchr2(b'\n', b'\r', &bytes[i..]) {
+        let start = i + offset;
+        let end = start
+            + bytes[start..]
+                .iter()
+                .take_while(|&&b| b == b'\n' || b == b'\r')
+                .count();
+        match longest {
+            Some((longest_start, longest_end)) if end - start < longest_end - longest_start => {}
+            _ => longest = Some((start, end)),
+        }
+        i = end;
+    }
+    longest.map(|(start, end)| &text[start..end])
+}
+
+/// Returns the last of the widest (in bytes) whitespace characters in the text, other than the spaces.
+fn widest_whitespace<'a>(text: &'a str, spaces: &[char]) -> Option<&'a str> {
+    let mut widest: Option<(usize, usize)> = None;
+    // Searching backwards, the first whitespace character of each width is the last one in the text.
+    for (i, c) in text.char_indices().rev() {
+        if !c.is_whitespace() || spaces.contains(&c) {
+            continue;
+        }
+        match widest {
+            Some((_, width)) if c.len_utf8() <= width => {}
+            _ => widest = Some((i, c.len_utf8())),
+        }
+        // No whitespace character is wider than three bytes.
+        if c.len_utf8() == 3 {
+            break;
+        }
+    }
+    widest.map(|(start, width)| &text[start..start + width])
+}
+
 #[cfg(test)]
 mod splitter_tests {
     use super::*;
@@ -163,28 +784,28 @@ mod splitter_tests {
     fn test_whitespace_split() {
         let splitter = Splitter::default();
         let text = "Hello, World!";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, " ");
-        assert!(separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, " ");
+        assert!(separator.is_whitespace());
         assert_eq!(split_text, ["Hello,", "World!"]);
 
         let text = "Hello, World!\tGoodbye, World!";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "\t");
-        assert!(separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "\t");
+        assert!(separator.is_whitespace());
         assert_eq!(split_text, ["Hello, World!", "Goodbye, World!"]);
 
         let text = "Hello, World!\nGoodbye, World!";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "\n");
-        assert!(separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "\n");
+        assert!(separator.is_whitespace());
         assert_eq!(split_text, ["Hello, World!", "Goodbye, World!"]);
 
         // Prioritize \n\n over \n
         let text = "Hello, World!\n\nGoodbye, World!\n<EOF>";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "\n\n");
-        assert!(separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "\n\n");
+        assert!(separator.is_whitespace());
         assert_eq!(split_text, ["Hello, World!", "Goodbye, World!\n<EOF>"]);
     }
 
@@ -193,29 +814,268 @@ mod splitter_tests {
         // Prioritize ! over ,
         let splitter = Splitter::default();
         let text = "Hello,World!";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "!");
-        assert!(!separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "!");
+        assert!(!separator.is_whitespace());
         assert_eq!(split_text, ["Hello,World", ""]);
 
         // Test with multiple separators
         let text = "Hello,_World!_Goodbye,_World!";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "!");
-        assert!(!separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "!");
+        assert!(!separator.is_whitespace());
         assert_eq!(split_text, ["Hello,_World", "_Goodbye,_World", ""]);
     }
 
+    #[test]
+    fn test_matches_regex_search() {
+        // The separators the splitter used to find with regular expressions.
+        fn regex_separator(text: &str) -> Option<&str> {
+            [r"[\n\r]+", r"\t", r"\s"].iter().find_map(|pattern| {
+                regex::Regex::new(pattern)
+                    .unwrap()
+                    .find_iter(text)
+                    .map(|m| m.as_str())
+                    .max_by_key(|s| s.len())
+            })
+        }
+
+        let splitter = Splitter::default();
+        for text in [
+            "a\r\nb\n\rc",
+            "a\n\nb\r\n\r\nc\n\n",
+            "\r\r\r",
+            "a\tb\t\tc d",
+            "a b\u{0B}c\u{0C}d",
+            "a\u{A0}b\u{3000}c\u{2003}d e",
+            "a\u{85}b c",
+            "no-whitespace",
+        ] {
+            match regex_separator(text) {
+                Some(expected) => {
+                    assert_eq!(splitter.split_text(text).0.text, expected, "{:?}", text)
+                }
+                None => assert!(!splitter.split_text(text).0.is_whitespace(), "{:?}", text),
+            }
+        }
+    }
+
+    #[test]
+    fn test_spaces() {
+        // No-break spaces count as spaces by default.
+        let text = "one\u{A0}two three\u{A0}four";
+        let (separator, split_text) = Splitter::default().split_text(text);
+        assert_eq!(separator.kind, SeparatorKind::Whitespace);
+        assert_eq!(split_text, ["one", "two", "three", "four"]);
+        let (separator, split_text) = Splitter::default().split_text("one\u{A0}\u{A0}two");
+        assert_eq!(separator.text, "\u{A0}");
+        assert_eq!(split_text, ["one", "", "two"]);
+
+        // Other whitespace characters are more desirable than spaces.
+        let (separator, split_text) = Splitter::default().split_text("a b\u{3000}c\u{A0}d");
+        assert_eq!(separator.text, "\u{3000}");
+        assert_eq!(split_text, ["a b", "c\u{A0}d"]);
+
+        // Characters that are not spaces are ranked by their widths.
+        let splitter = Splitter::default().spaces(&[' ']);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "\u{A0}");
+        assert_eq!(split_text, ["one", "two three", "four"]);
+        let splitter = Splitter::default().spaces(&[' ', '\u{A0}', '\u{2009}', '_']);
+        let (separator, split_text) = splitter.split_text("a\u{2009}b c\u{A0}d_e");
+        assert!(separator.is_whitespace());
+        assert_eq!(split_text, ["a", "b", "c", "d_e"]);
+        let splitter = Splitter::default().spaces(&[]);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "\u{A0}");
+        assert_eq!(split_text, ["one", "two three", "four"]);
+    }
+
     #[test]
     fn test_no_match_split() {
         let splitter = Splitter::default();
         let text = "Hello_World";
-        let (separator, separator_is_whitespace, split_text) = splitter.split_text(text);
-        assert_eq!(separator, "");
-        assert!(separator_is_whitespace);
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "");
+        assert!(separator.is_whitespace());
         assert_eq!(
             split_text,
             ["H", "e", "l", "l", "o", "_", "W", "o", "r", "l", "d"]
         );
+
+        let (separator, split_text) = splitter.split_text("añb");
+        assert_eq!(separator.text, "");
+        assert_eq!(split_text, ["a", "ñ", "b"]);
+    }
+
+    #[test]
+    fn test_separator_profiles() {
+        let splitter = Splitter::default();
+        let (separator, split_text) = splitter.split_text("我喜欢猫，也喜欢狗。你呢？");
+        assert_eq!(separator.text, "。");
+        assert_eq!(split_text, ["我喜欢猫，也喜欢狗", "你呢？"]);
+        let (separator, _) = splitter.split_text("我喜欢猫，也喜欢狗");
+        assert_eq!(separator.text, "，");
+        let (separator, _) = splitter.split_text("مرحبا،كيف_حالك؟");
+        assert_eq!(separator.text, "؟");
+        // Latin text keeps the Latin separators, even with some CJK punctuation.
+        let (separator, _) = splitter.split_text("Hello,World。");
+        assert_eq!(separator.text, ",");
+
+        assert_eq!(detect_profile("Hello 世界"), SeparatorProfile::Latin);
+        assert_eq!(
+            detect_profile("こんにちは、世界 world"),
+            SeparatorProfile::Cjk
+        );
+        assert_eq!(detect_profile("안녕하세요"), SeparatorProfile::Cjk);
+        assert_eq!(detect_profile("سلام دنیا"), SeparatorProfile::Arabic);
+        assert_eq!(detect_profile("12345"), SeparatorProfile::Latin);
+    }
+
+    #[test]
+    fn test_separator_kinds() {
+        let splitter = Splitter::default();
+        for (text, expected) in [
+            ("a\n\nb", SeparatorKind::ParagraphBreak),
+            ("a\tb c", SeparatorKind::Tab),
+            ("a\u{A0}b", SeparatorKind::Whitespace),
+            ("a*b,c", SeparatorKind::SentenceTerminator),
+            ("a;b-c", SeparatorKind::ClauseSeparator),
+            ("a…b-c", SeparatorKind::ClauseSeparator),
+            ("a/b", SeparatorKind::WordJoiner),
+            ("我喜欢猫，也喜欢狗", SeparatorKind::ClauseSeparator),
+            ("我喜欢猫…也喜欢狗", SeparatorKind::ClauseSeparator),
+            ("我喜欢猫・也喜欢狗", SeparatorKind::WordJoiner),
+            ("مرحبا۔كيف", SeparatorKind::SentenceTerminator),
+            ("مرحبا…كيف", SeparatorKind::ClauseSeparator),
+            ("مرحبا-كيف", SeparatorKind::WordJoiner),
+            ("ab", SeparatorKind::None),
+        ] {
+            assert_eq!(splitter.split_text(text).0.kind, expected, "{:?}", text);
+        }
+
+        let splitter = Splitter::default().profile(SeparatorProfile::Code);
+        assert_eq!(
+            splitter.split_text("f(){}").0.kind,
+            SeparatorKind::SentenceTerminator
+        );
+        assert_eq!(
+            splitter.split_text("f[0]").0.kind,
+            SeparatorKind::ClauseSeparator
+        );
+        assert_eq!(splitter.split_text("a=b").0.kind, SeparatorKind::WordJoiner);
+
+        // The kinds change where the groups of each profile's separators do.
+        for (profile, first_clause, first_joiner) in [
+            (SeparatorProfile::Latin, ";", "/"),
+            (SeparatorProfile::Cjk, "；", "/"),
+            (SeparatorProfile::Arabic, "؛", "/"),
+            (SeparatorProfile::Code, ",", "="),
+        ] {
+            let separators = profile.separators();
+            let position = |separator| separators.iter().position(|s| *s == separator).unwrap();
+            let (clauses, joiners) = (position(first_clause), position(first_joiner));
+            for (index, _) in separators.iter().enumerate() {
+                let expected = match index {
+                    index if index < clauses => SeparatorKind::SentenceTerminator,
+                    index if index < joiners => SeparatorKind::ClauseSeparator,
+                    _ => SeparatorKind::WordJoiner,
+                };
+                assert_eq!(
+                    profile.kind(index),
+                    expected,
+                    "{:?} {:?}",
+                    profile,
+                    separators[index]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_separator_runs() {
+        let splitter = Splitter::default().separator_runs(&[".", "?", "!", "…", "-"]);
+        let (separator, split_text) = splitter.split_text("...Wait!?...what.?!");
+        assert_eq!(separator.text, ".");
+        assert!(!separator.is_whitespace());
+        assert_eq!(split_text, ["", "Wait", "what", ""]);
+        let (separator, split_text) = splitter.split_text("well--maybe,not");
+        assert_eq!(separator.text, ",");
+        assert_eq!(split_text, ["well--maybe", "not"]);
+        let (separator, split_text) = splitter.split_text("well--maybe");
+        assert_eq!(separator.text, "-");
+        assert_eq!(split_text, ["well", "maybe"]);
+        // Separators not matched in runs are still matched on their own.
+        let (separator, split_text) = splitter.split_text("a,,b");
+        assert_eq!(separator.text, ",");
+        assert_eq!(split_text, ["a", "", "b"]);
+    }
+
+    #[cfg(feature = "icu_segmenter")]
+    #[test]
+    fn test_word_segmentation() {
+        let splitter = Splitter::default();
+        let (separator, split_text) = splitter.split_text("ภาษาไทยง่ายนิดเดียว");
+        assert_eq!(separator.text, "");
+        assert!(separator.is_whitespace());
+        assert_eq!(split_text, ["ภาษา", "ไทย", "ง่าย", "นิด", "เดียว"]);
+        let (_, split_text) = splitter.split_text("我喜欢猫也喜欢狗");
+        assert_eq!(split_text.concat(), "我喜欢猫也喜欢狗");
+        assert!(split_text.len() > 1 && split_text.len() < 8);
+        // A single word is split into characters.
+        let (_, split_text) = splitter.split_text("Hello");
+        assert_eq!(split_text, ["H", "e", "l", "l", "o"]);
+    }
+
+    #[test]
+    fn test_markdown_breaks() {
+        let splitter = Splitter::default().markdown(true);
+        let text = "Intro.\n\n## One\nText.\n### Two\nMore.\r\n## Three";
+        let (separator, splits) = splitter.split_text(text);
+        assert_eq!(separator.text, "\r\n");
+        assert_eq!(separator.kind, SeparatorKind::ParagraphBreak);
+        assert_eq!(
+            splits,
+            ["Intro.", "## One\nText.\n### Two\nMore.", "## Three"]
+        );
+        let (separator, splits) = splitter.split_text(splits[1]);
+        assert_eq!(separator.text, "\n");
+        assert_eq!(splits, ["## One\nText.", "### Two\nMore."]);
+
+        // Code blocks are split around, and lines in them are not headings or list items.
+        let text = "Setup:\n~~~\n# comment\n* star\n~~~\n\nDone.\n1. First";
+        let (separator, splits) = splitter.split_text(text);
+        assert_eq!(separator.text, "\n\n");
+        assert_eq!(
+            splits,
+            ["Setup:", "~~~\n# comment\n* star\n~~~", "Done.\n1. First"]
+        );
+        let (_, splits) = splitter.split_text(splits[2]);
+        assert_eq!(splits, ["Done.", "1. First"]);
+
+        // Text without Markdown blocks is split as usual.
+        let (separator, _) = splitter.split_text("#hashtag and -dash\n\nplain");
+        assert_eq!(separator.text, "\n\n");
+        let (separator, splits) = Splitter::default().split_text("a\n# b");
+        assert_eq!((separator.text, splits), ("\n", vec!["a", "# b"]));
+    }
+
+    #[test]
+    fn test_grapheme_split() {
+        let splitter = Splitter::default();
+        let text = "👨\u{200D}👩\u{200D}👧👍🏽🇯🇵e\u{301}";
+        let (separator, split_text) = splitter.split_text(text);
+        assert_eq!(separator.text, "");
+        assert!(separator.is_whitespace());
+        assert_eq!(
+            split_text,
+            ["👨\u{200D}👩\u{200D}👧", "👍🏽", "🇯🇵", "e\u{301}"]
+        );
+        // A single grapheme cluster is never split into its characters.
+        for cluster in ["👨\u{200D}👩\u{200D}👧", "👍🏽", "🇯🇵", "❤️\u{200D}🔥"]
+        {
+            let (_, split_text) = splitter.split_text(cluster);
+            assert_eq!(split_text, [cluster]);
+        }
     }
 }
@@ -0,0 +1,592 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Statistics about a run of the chunker, for measuring performance work beyond wall time, and [`CorpusReport`]s aggregating them over many documents.
+//!
+//! With the `alloc-stats` feature, installing a [`TrackingAllocator`] as the global allocator also reports the allocations a run makes and the peak memory it allocates.
+
+#[cfg(feature = "alloc-stats")]
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "alloc-stats")]
+use std::cell::Cell;
+use std::collections::BTreeMap;
+#[cfg(feature = "alloc-stats")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::chunker::Chunker;
+use crate::dedup::Deduplicator;
+use crate::splitter::SeparatorKind;
+
+/// Statistics about chunking a text, made with [`Chunker::chunk_with_stats`].
+///
+/// # Fields
+///
+/// * `chunks` - The number of chunks.
+/// * `elapsed` - The time taken to chunk the text.
+/// * `allocations` - The allocations made while chunking the text, if a [`TrackingAllocator`] is the global allocator.
+/// * `separators` - The number of boundaries between chunks at each level of the hierarchy of separators, where [`SeparatorKind::None`] counts chunks cut between words, grapheme clusters or characters.
+/// * `punctuation` - The number of boundaries between chunks at each non-whitespace separator, such as `.` and `,`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkStats {
+    pub chunks: usize,
+    pub elapsed: Duration,
+    pub allocations: Option<AllocationStats>,
+    pub separators: BTreeMap<SeparatorKind, usize>,
+    pub punctuation: BTreeMap<String, usize>,
+}
+
+/// The allocations made on a thread while running some code, as tracked by a [`TrackingAllocator`].
+///
+/// # Fields
+///
+/// * `allocations` - The number of allocations and reallocations.
+/// * `allocated_bytes` - The total number of bytes allocated and reallocated.
+/// * `peak_bytes` - The largest number of bytes allocated at once, beyond those allocated before the code ran, including those of the code's output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocationStats {
+    pub allocations: usize,
+    pub allocated_bytes: usize,
+    pub peak_bytes: usize,
+}
+
+/// A global allocator that tracks the allocations made on each thread for [`ChunkStats`], allocating with another allocator.
+///
+/// # Examples
+///
+/// ```ignore
+/// use std::alloc::System;
+/// use semchunk_rs::stats::TrackingAllocator;
+///
+/// #[global_allocator]
+/// static GLOBAL: TrackingAllocator = TrackingAllocator(System);
+/// ```
+#[cfg(feature = "alloc-stats")]
+#[derive(Debug, Default)]
+pub struct TrackingAllocator<A = System>(pub A);
+
+#[cfg(feature = "alloc-stats")]
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// The allocations made on a thread, with byte counts that wrap when memory allocated on other threads is freed on this one.
+#[cfg(feature = "alloc-stats")]
+struct Tracker {
+    allocations: Cell<usize>,
+    allocated: Cell<usize>,
+    live: Cell<isize>,
+    peak: Cell<isize>,
+}
+
+#[cfg(feature = "alloc-stats")]
+thread_local! {
+    static TRACKER: Tracker = const {
+        Tracker {
+            allocations: Cell::new(0),
+            allocated: Cell::new(0),
+            live: Cell::new(0),
+            peak: Cell::new(0),
+        }
+    };
+}
+
+#[cfg(feature = "alloc-stats")]
+impl Tracker {
+    fn allocate(&self, allocated: usize, freed: usize) {
+        self.allocations.set(self.allocations.get().wrapping_add(1));
+        self.allocated
+            .set(self.allocated.get().wrapping_add(allocated));
+        let live = self
+            .live
+            .get()
+            .wrapping_add_unsigned(allocated)
+            .wrapping_sub_unsigned(freed);
+        self.live.set(live);
+        self.peak.set(self.peak.get().max(live));
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+fn track(allocated: usize, freed: usize) {
+    // Ignores allocations made while the thread's tracker is being destroyed.
+    let _ = TRACKER.try_with(|tracker| match allocated {
+        0 => tracker
+            .live
+            .set(tracker.live.get().wrapping_sub_unsigned(freed)),
+        _ => tracker.allocate(allocated, freed),
+    });
+}
+
+#[cfg(feature = "alloc-stats")]
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        TRACKING.store(true, Ordering::Relaxed);
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            track(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        TRACKING.store(true, Ordering::Relaxed);
+        let ptr = self.0.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            track(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+        track(0, layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.0.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            track(new_size, layout.size());
+        }
+        new_ptr
+    }
+}
+
+/// Statistics aggregated over the chunks of many documents, such as a corpus, for reports and dashboards.
+///
+/// Documents are added with [`Chunker::chunk_reported`], or, where they are chunked elsewhere, with [`CorpusReport::add_document`] and [`CorpusReport::add_chunk`]. Reports of parts of a corpus, such as those chunked on different threads, are combined with [`CorpusReport::merge`]. With the `serde` feature, reports serialize to JSON and the like.
+///
+/// Duplicates are found by remembering the texts of the chunks added, which are not serialized. A deserialized report has forgotten them, so chunks added to it, or to a report it is merged into, are not counted as duplicates of the chunks it was made from, and `duplicates` undercounts.
+///
+/// # Fields
+///
+/// * `documents` - The number of documents.
+/// * `chunks` - The number of chunks.
+/// * `tokens` - The total number of tokens in the chunks.
+/// * `bucket_size` - The number of token counts in each bucket of the token histogram.
+/// * `token_histogram` - The number of chunks in each bucket of token counts, keyed by the smallest token count in the bucket.
+/// * `separators` - The number of boundaries between chunks at each level of the hierarchy of separators.
+/// * `punctuation` - The number of boundaries between chunks at each non-whitespace separator.
+/// * `duplicates` - The number of chunks whose text exactly duplicates that of a chunk added before.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::stats::CorpusReport;
+/// use semchunk_rs::Chunker;
+///
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let mut report = CorpusReport::new(2);
+/// for text in ["The quick brown fox jumps.", "The quick brown fox sleeps."] {
+///     chunker.chunk_reported(text, &mut report);
+/// }
+/// assert_eq!((report.documents, report.chunks, report.tokens), (2, 4, 10));
+/// assert_eq!(report.token_histogram.get(&0), Some(&2));
+/// assert_eq!(report.token_histogram.get(&4), Some(&2));
+/// assert_eq!(report.duplicate_rate(), 0.25);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorpusReport {
+    pub documents: usize,
+    pub chunks: usize,
+    pub tokens: usize,
+    pub bucket_size: usize,
+    pub token_histogram: BTreeMap<usize, usize>,
+    pub separators: BTreeMap<SeparatorKind, usize>,
+    pub punctuation: BTreeMap<String, usize>,
+    pub duplicates: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    seen: Deduplicator,
+}
+
+impl CorpusReport {
+    /// Creates an empty report whose token histogram has buckets of `bucket_size` token counts, or of one if it is 0.
+    pub fn new(bucket_size: usize) -> Self {
+        CorpusReport {
+            bucket_size: bucket_size.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a document, with the boundaries between its chunks from the statistics of chunking it. Its chunks are added with [`CorpusReport::add_chunk`].
+    pub fn add_document(&mut self, stats: &ChunkStats) {
+        self.documents += 1;
+        for (&kind, &count) in &stats.separators {
+            *self.separators.entry(kind).or_insert(0) += count;
+        }
+        for (separator, &count) in &stats.punctuation {
+            *self.punctuation.entry(separator.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Adds a chunk with `tokens` tokens, counting it as a duplicate if a chunk with the same text was added before.
+    pub fn add_chunk(&mut self, text: &str, tokens: usize) {
+        self.chunks += 1;
+        self.tokens += tokens;
+        let bucket_size = self.bucket_size.max(1);
+        *self
+            .token_histogram
+            .entry(tokens / bucket_size * bucket_size)
+            .or_insert(0) += 1;
+        if !self.seen.insert(text) {
+            self.duplicates += 1;
+        }
+    }
+
+    /// Adds the documents and chunks of another report to this one.
+    ///
+    /// Chunks of the other report that duplicate a chunk of this one are counted as duplicates. Token counts are put in this report's buckets by the smallest token count of their bucket in the other report, so reports should share a bucket size for the histogram to be exact.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::stats::CorpusReport;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let (mut first, mut second) = (CorpusReport::new(2), CorpusReport::new(2));
+    /// chunker.chunk_reported("The quick brown fox jumps.", &mut first);
+    /// chunker.chunk_reported("The quick brown fox sleeps.", &mut second);
+    /// first.merge(second);
+    /// assert_eq!((first.documents, first.chunks, first.tokens), (2, 4, 10));
+    /// assert_eq!(first.duplicates, 1);
+    /// ```
+    pub fn merge(&mut self, other: CorpusReport) {
+        self.documents += other.documents;
+        self.chunks += other.chunks;
+        self.tokens += other.tokens;
+        let bucket_size = self.bucket_size.max(1);
+        for (tokens, count) in other.token_histogram {
+            *self
+                .token_histogram
+                .entry(tokens / bucket_size * bucket_size)
+                .or_insert(0) += count;
+        }
+        for (kind, count) in other.separators {
+            *self.separators.entry(kind).or_insert(0) += count;
+        }
+        for (separator, count) in other.punctuation {
+            *self.punctuation.entry(separator).or_insert(0) += count;
+        }
+        self.duplicates += other.duplicates + self.seen.merge(other.seen);
+    }
+
+    /// The mean number of tokens in a chunk, or 0 if there are no chunks.
+    pub fn mean_tokens(&self) -> f64 {
+        match self.chunks {
+            0 => 0.0,
+            chunks => self.tokens as f64 / chunks as f64,
+        }
+    }
+
+    /// The fraction of chunks that duplicate a chunk added before, or 0 if there are no chunks.
+    pub fn duplicate_rate(&self) -> f64 {
+        match self.chunks {
+            0 => 0.0,
+            chunks => self.duplicates as f64 / chunks as f64,
+        }
+    }
+}
+
+/// Runs `f`, tracking the allocations it makes on this thread if a [`TrackingAllocator`] is the global allocator.
+#[cfg(feature = "alloc-stats")]
+fn measure_allocations<T>(f: impl FnOnce() -> T) -> (T, Option<AllocationStats>) {
+    let snapshot = || {
+        TRACKER.with(|tracker| {
+            (
+                tracker.allocations.get(),
+                tracker.allocated.get(),
+                tracker.live.get(),
+                tracker.peak.get(),
+            )
+        })
+    };
+    let (allocations, allocated, live, peak) = snapshot();
+    TRACKER.with(|tracker| tracker.peak.set(live));
+    let output = f();
+    let (allocations_after, allocated_after, _, peak_after) = snapshot();
+    // Restores the peak of any measurement this one is nested in.
+    TRACKER.with(|tracker| tracker.peak.set(peak.max(peak_after)));
+    let stats = AllocationStats {
+        allocations: allocations_after.wrapping_sub(allocations),
+        allocated_bytes: allocated_after.wrapping_sub(allocated),
+        peak_bytes: peak_after.wrapping_sub(live).max(0) as usize,
+    };
+    (output, TRACKING.load(Ordering::Relaxed).then_some(stats))
+}
+
+#[cfg(not(feature = "alloc-stats"))]
+fn measure_allocations<T>(f: impl FnOnce() -> T) -> (T, Option<AllocationStats>) {
+    (f(), None)
+}
+
+impl Chunker {
+    /// Chunks the given text like [`Chunker::chunk`], with statistics about the run.
+    ///
+    /// The statistics count the boundaries between chunks at each kind of separator, which tells whether a corpus is chunked at paragraphs and sentences or falls back to splitting at whitespace and characters. With the `alloc-stats` feature and a [`TrackingAllocator`] as the global allocator, they also include the allocations made on this thread while chunking, so changes to the chunker can be measured by the memory they use as well as the time they take.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks and the statistics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::splitter::SeparatorKind;
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let (chunks, stats) = chunker.chunk_with_stats("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks, vec!["The quick brown fox", "jumps over the lazy", "dog."]);
+    /// assert_eq!(stats.chunks, 3);
+    /// assert_eq!(stats.separators[&SeparatorKind::Whitespace], 2);
+    /// ```
+    pub fn chunk_with_stats(&self, text: &str) -> (Vec<String>, ChunkStats) {
+        let (chunks, stats) = self.chunk_counted_with_stats(text);
+        (chunks.into_iter().map(|(chunk, _)| chunk).collect(), stats)
+    }
+
+    /// Chunks the given text like [`Chunker::chunk_with_stats`], along with the number of tokens in each chunk.
+    ///
+    /// Like [`Chunker::chunk_with_tokens`], counts made while chunking are reused, so most chunks are not counted again.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    ///
+    /// # Returns
+    ///
+    /// The chunks with their numbers of tokens, and the statistics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::Chunker;
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let (chunks, stats) = chunker.chunk_with_tokens_and_stats("The quick brown fox jumps over the lazy dog.");
+    /// assert_eq!(chunks[0], ("The quick brown fox".to_string(), 4));
+    /// assert_eq!(chunks[2], ("dog.".to_string(), 1));
+    /// assert_eq!(stats.chunks, 3);
+    /// ```
+    pub fn chunk_with_tokens_and_stats(&self, text: &str) -> (Vec<(String, usize)>, ChunkStats) {
+        let (chunks, stats) = self.chunk_counted_with_stats(text);
+        let chunks = chunks
+            .into_iter()
+            .map(|(chunk, tokens)| {
+                let tokens = tokens.unwrap_or_else(|| self.count_tokens(&chunk));
+                (chunk, tokens)
+            })
+            .collect();
+        (chunks, stats)
+    }
+
+    /// Chunks the given text like [`Chunker::chunk_with_stats`], along with the number of tokens in each chunk, if they were counted while chunking.
+    fn chunk_counted_with_stats(&self, text: &str) -> (Vec<(String, Option<usize>)>, ChunkStats) {
+        let started = Instant::now();
+        let (chunks, allocations) = measure_allocations(|| self.chunk_with_separators(text));
+        let elapsed = started.elapsed();
+        let mut separators = BTreeMap::new();
+        let mut punctuation = BTreeMap::new();
+        // The last chunk ends with the text rather than at a boundary.
+        for (_, separator, _) in chunks.iter().take(chunks.len().saturating_sub(1)) {
+            *separators.entry(separator.kind).or_insert(0) += 1;
+            if !separator.is_whitespace() {
+                *punctuation.entry(separator.text.to_string()).or_insert(0) += 1;
+            }
+        }
+        let stats = ChunkStats {
+            chunks: chunks.len(),
+            elapsed,
+            allocations,
+            separators,
+            punctuation,
+        };
+        let chunks = chunks
+            .into_iter()
+            .map(|(chunk, _, tokens)| (chunk, tokens))
+            .collect();
+        (chunks, stats)
+    }
+
+    /// Chunks the given text like [`Chunker::chunk_with_stats`], adding it and its chunks to a report.
+    ///
+    /// Chunks are added with their numbers of tokens as counted by [`Chunker::chunk_with_tokens`], so most are not counted again.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `report` - The report to add the text to.
+    ///
+    /// # Returns
+    ///
+    /// The chunks.
+    pub fn chunk_reported(&self, text: &str, report: &mut CorpusReport) -> Vec<String> {
+        let (chunks, stats) = self.chunk_with_tokens_and_stats(text);
+        report.add_document(&stats);
+        chunks
+            .into_iter()
+            .map(|(chunk, tokens)| {
+                report.add_chunk(&chunk, tokens);
+                chunk
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[cfg(feature = "alloc-stats")]
+    #[global_allocator]
+    static GLOBAL: TrackingAllocator = TrackingAllocator(System);
+
+    #[test]
+    fn test_chunk_with_stats() {
+        let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+        let text = "The quick brown fox jumps over the lazy dog.\n\nPack my box with five dozen liquor jugs.";
+        let (chunks, stats) = chunker.chunk_with_stats(text);
+        assert_eq!(chunks, chunker.chunk(text));
+        assert_eq!(stats.chunks, chunks.len());
+        let (counted, _) = chunker.chunk_with_tokens_and_stats(text);
+        let expected: Vec<_> = chunker
+            .chunk_with_tokens(text)
+            .into_iter()
+            .map(|(chunk, tokens)| (chunk.text, tokens))
+            .collect();
+        assert_eq!(counted, expected);
+        assert_eq!(
+            stats.separators,
+            BTreeMap::from([
+                (SeparatorKind::ParagraphBreak, 1),
+                (SeparatorKind::Whitespace, 3),
+            ])
+        );
+        assert!(stats.punctuation.is_empty());
+
+        let chars = |s: &str| s.chars().count();
+        let chunker = Chunker::new(12, Box::new(chars));
+        let (chunks, stats) = chunker.chunk_with_stats("One,two;three.Four,five,six");
+        assert_eq!(chunks, ["One,two;", "three.", "Four,five,", "six"]);
+        assert_eq!(
+            stats.punctuation,
+            BTreeMap::from([
+                (",".to_string(), 1),
+                (";".to_string(), 1),
+                (".".to_string(), 1)
+            ])
+        );
+
+        #[cfg(not(feature = "alloc-stats"))]
+        assert_eq!(stats.allocations, None);
+        #[cfg(feature = "alloc-stats")]
+        {
+            let allocations = stats.allocations.unwrap();
+            let output: usize = chunks.iter().map(String::capacity).sum();
+            assert!(allocations.allocations > chunks.len());
+            assert!(allocations.peak_bytes >= output);
+            assert!(allocations.allocated_bytes >= allocations.peak_bytes);
+        }
+    }
+
+    #[test]
+    fn test_corpus_report() {
+        let chunker = Chunker::new(3, Box::new(|s: &str| s.split_whitespace().count()));
+        let mut report = CorpusReport::new(0);
+        assert_eq!(report.bucket_size, 1);
+        assert_eq!((report.mean_tokens(), report.duplicate_rate()), (0.0, 0.0));
+        let header = "Example News.\n\n";
+        for body in ["Stocks rose, then fell.", "Rain is expected."] {
+            chunker.chunk_reported(&format!("{}{}", header, body), &mut report);
+        }
+        report.add_document(&ChunkStats::default());
+        assert_eq!((report.documents, report.chunks, report.tokens), (3, 5, 11));
+        assert_eq!(report.mean_tokens(), 2.2);
+        assert_eq!(
+            report.token_histogram,
+            BTreeMap::from([(1, 1), (2, 2), (3, 2)])
+        );
+        assert_eq!(
+            report.separators,
+            BTreeMap::from([
+                (SeparatorKind::ParagraphBreak, 2),
+                (SeparatorKind::Whitespace, 1),
+            ])
+        );
+        assert!(report.punctuation.is_empty());
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.duplicate_rate(), 0.2);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["separators"]["ParagraphBreak"], 2);
+            assert_eq!(json["token_histogram"]["3"], 2);
+            assert_eq!(json["duplicates"], 1);
+        }
+
+        // Merging reports of parts of a corpus gives the report of the whole.
+        let texts = [
+            "Stocks rose, then fell.",
+            "Rain is expected.",
+            "Stocks rose, then fell.",
+        ];
+        let mut whole = CorpusReport::new(2);
+        for text in texts {
+            chunker.chunk_reported(text, &mut whole);
+        }
+        let mut first = CorpusReport::new(2);
+        chunker.chunk_reported(texts[0], &mut first);
+        let mut second = CorpusReport::new(2);
+        for text in &texts[1..] {
+            chunker.chunk_reported(text, &mut second);
+        }
+        first.merge(second);
+        assert_eq!(
+            (first.documents, first.chunks, first.tokens),
+            (whole.documents, whole.chunks, whole.tokens)
+        );
+        assert_eq!(first.token_histogram, whole.token_histogram);
+        assert_eq!(first.separators, whole.separators);
+        assert_eq!(first.duplicates, whole.duplicates);
+        assert_eq!(whole.duplicates, 2);
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn test_nested_measurements() {
+        let (inner, outer) = measure_allocations(|| {
+            let outer_data = vec![0u8; 4096];
+            let (_, inner) = measure_allocations(|| vec![0u8; 64]);
+            drop(outer_data);
+            inner
+        });
+        let (inner, outer) = (inner.unwrap(), outer.unwrap());
+        assert_eq!(inner.allocations, 1);
+        assert!(inner.peak_bytes >= 64 && inner.peak_bytes < 4096);
+        assert!(outer.peak_bytes >= 4096 + 64);
+    }
+}
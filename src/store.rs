@@ -0,0 +1,341 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Persisting chunks to SQLite, enabled with the `sqlite` feature.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, Result};
+
+use crate::chunk::{sha256_hex, Chunk};
+
+/// A chunk read back from a [`SqliteChunkStore`].
+///
+/// # Fields
+///
+/// * `doc_id` - The ID of the document the chunk is from.
+/// * `index` - The position of the chunk in its document.
+/// * `chunk` - The chunk, with its byte offsets into its document.
+/// * `tokens` - The number of tokens in the chunk.
+/// * `hash` - The hash of the chunk's text, as returned by [`chunk_hash`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredChunk {
+    pub doc_id: String,
+    pub index: usize,
+    pub chunk: Chunk,
+    pub tokens: usize,
+    pub hash: String,
+}
+
+/// Stores chunks in a SQLite database, as a zero-infrastructure chunk cache for small projects.
+///
+/// Chunks are stored in a `chunks` table with the columns `doc_id`, `idx`, `text`, `start`, `end`, `tokens` and `hash`, with a row per position in a document. With [`SqliteChunkStore::upsert_by_hash`], a row per distinct text is kept instead.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::store::SqliteChunkStore;
+/// use semchunk_rs::Chunker;
+///
+/// let token_counter = |s: &str| s.split_whitespace().count();
+/// let chunker = Chunker::new(4, Box::new(token_counter));
+/// let mut store = SqliteChunkStore::open_in_memory().unwrap();
+/// let mut chunks = Vec::new();
+/// chunker.chunk_into("The quick brown fox jumps over the lazy dog.", &mut chunks);
+/// store.write_document("fox.txt", &chunks, token_counter).unwrap();
+///
+/// let stored = store.chunks("fox.txt").unwrap();
+/// assert_eq!(stored[1].chunk.text, "jumps over the lazy");
+/// assert_eq!(stored[1].tokens, 4);
+/// ```
+pub struct SqliteChunkStore {
+    connection: Connection,
+    upsert_by_hash: bool,
+}
+
+impl SqliteChunkStore {
+    /// Opens the store in the database at the given path, creating the database and the `chunks` table if they do not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the database file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Opens a store in a new in-memory database.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    /// Creates a store in an open database, creating the `chunks` table if it does not exist.
+    pub fn new(connection: Connection) -> Result<Self> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS chunks (
+                doc_id TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                start INTEGER NOT NULL,
+                end INTEGER NOT NULL,
+                tokens INTEGER NOT NULL,
+                hash TEXT NOT NULL
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS chunks_doc_id_idx ON chunks (doc_id, idx);
+            CREATE INDEX IF NOT EXISTS chunks_hash ON chunks (hash);",
+        )?;
+        Ok(SqliteChunkStore {
+            connection,
+            upsert_by_hash: false,
+        })
+    }
+
+    /// Stores each distinct chunk text once, so that writing a chunk whose text is already stored updates that row to the new document and position rather than adding another.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::store::SqliteChunkStore;
+    /// use semchunk_rs::Chunk;
+    ///
+    /// let mut store = SqliteChunkStore::open_in_memory().unwrap().upsert_by_hash();
+    /// let chunk = Chunk { text: "Terms and conditions apply.".to_string(), start: 0, end: 27 };
+    /// store.write("a.txt", 0, &chunk, 4).unwrap();
+    /// store.write("b.txt", 0, &chunk, 4).unwrap();
+    /// assert_eq!(store.len().unwrap(), 1);
+    /// assert_eq!(store.chunks("b.txt").unwrap().len(), 1);
+    /// ```
+    pub fn upsert_by_hash(mut self) -> Self {
+        self.upsert_by_hash = true;
+        self
+    }
+
+    /// Writes a chunk, replacing any chunk stored at the same position in the same document.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc_id` - The ID of the document the chunk is from.
+    /// * `index` - The position of the chunk in its document.
+    /// * `chunk` - The chunk.
+    /// * `tokens` - The number of tokens in the chunk.
+    pub fn write(
+        &mut self,
+        doc_id: &str,
+        index: usize,
+        chunk: &Chunk,
+        tokens: usize,
+    ) -> Result<()> {
+        write_chunk(
+            &self.connection,
+            self.upsert_by_hash,
+            doc_id,
+            index,
+            chunk,
+            tokens,
+        )
+    }
+
+    /// Replaces the chunks of a document with the given chunks in a single transaction, counting the tokens in each with `token_counter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc_id` - The ID of the document.
+    /// * `chunks` - The chunks of the document, in order.
+    /// * `token_counter` - A function that counts the number of tokens in a string.
+    pub fn write_document(
+        &mut self,
+        doc_id: &str,
+        chunks: &[Chunk],
+        token_counter: impl Fn(&str) -> usize,
+    ) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM chunks WHERE doc_id = ?1", params![doc_id])?;
+        for (index, chunk) in chunks.iter().enumerate() {
+            let tokens = token_counter(&chunk.text);
+            write_chunk(
+                &transaction,
+                self.upsert_by_hash,
+                doc_id,
+                index,
+                chunk,
+                tokens,
+            )?;
+        }
+        transaction.commit()
+    }
+
+    /// Reads the chunks of a document, in order.
+    pub fn chunks(&self, doc_id: &str) -> Result<Vec<StoredChunk>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT doc_id, idx, text, start, end, tokens, hash FROM chunks WHERE doc_id = ?1 ORDER BY idx",
+        )?;
+        let rows = statement.query_map(params![doc_id], |row| {
+            Ok(StoredChunk {
+                doc_id: row.get(0)?,
+                index: row.get(1)?,
+                chunk: Chunk {
+                    text: row.get(2)?,
+                    start: row.get(3)?,
+                    end: row.get(4)?,
+                },
+                tokens: row.get(5)?,
+                hash: row.get(6)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Whether a chunk with the given text hash is stored.
+    pub fn contains_hash(&self, hash: &str) -> Result<bool> {
+        self.connection
+            .prepare_cached("SELECT EXISTS (SELECT 1 FROM chunks WHERE hash = ?1)")?
+            .query_row(params![hash], |row| row.get(0))
+    }
+
+    /// Deletes the chunks of a document, returning how many were deleted.
+    pub fn delete_document(&mut self, doc_id: &str) -> Result<usize> {
+        self.connection
+            .execute("DELETE FROM chunks WHERE doc_id = ?1", params![doc_id])
+    }
+
+    /// The number of chunks stored.
+    pub fn len(&self) -> Result<usize> {
+        self.connection
+            .query_row("SELECT COUNT(*) FROM chunks", [], |row| row.get(0))
+    }
+
+    /// Whether no chunks are stored.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The underlying database connection, for queries the store does not provide.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+/// Writes a chunk through a connection or transaction.
+fn write_chunk(
+    connection: &Connection,
+    upsert_by_hash: bool,
+    doc_id: &str,
+    index: usize,
+    chunk: &Chunk,
+    tokens: usize,
+) -> Result<()> {
+    let hash = chunk_hash(&chunk.text);
+    if upsert_by_hash {
+        // Rows with the same text are all moved to the new position, leaving one, which replaces any chunk already there.
+        let updated = connection
+            .prepare_cached(
+                "UPDATE OR REPLACE chunks SET doc_id = ?1, idx = ?2, start = ?3, end = ?4, tokens = ?5
+                WHERE hash = ?6",
+            )?
+            .execute(params![doc_id, index, chunk.start, chunk.end, tokens, hash])?;
+        if updated > 0 {
+            return Ok(());
+        }
+    }
+    connection
+        .prepare_cached(
+            "INSERT OR REPLACE INTO chunks (doc_id, idx, text, start, end, tokens, hash)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?
+        .execute(params![
+            doc_id,
+            index,
+            chunk.text,
+            chunk.start,
+            chunk.end,
+            tokens,
+            hash
+        ])?;
+    Ok(())
+}
+
+/// Returns the hash a [`SqliteChunkStore`] identifies a chunk's text by: its SHA-256 digest in hexadecimal.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::store::chunk_hash;
+/// assert_eq!(
+///     chunk_hash("The quick brown fox"),
+///     "5cac4f980fedc3d3f1f99b4be3472c9b30d56523e632d151237ec9309048bda9"
+/// );
+/// ```
+pub fn chunk_hash(text: &str) -> String {
+    sha256_hex(text)
+}
+
+#[cfg(test)]
+mod store_tests {
+    use super::*;
+    use crate::Chunker;
+
+    #[test]
+    fn test_sqlite_chunk_store() {
+        let token_counter = |s: &str| s.split_whitespace().count();
+        let chunker = Chunker::new(4, Box::new(token_counter));
+        let path = std::env::temp_dir().join(format!("semchunk-store-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut chunks = Vec::new();
+        chunker.chunk_into("The quick brown fox jumps over the lazy dog.", &mut chunks);
+        {
+            let mut store = SqliteChunkStore::open(&path).unwrap();
+            store
+                .write_document("fox.txt", &chunks, token_counter)
+                .unwrap();
+            // Writing a document again replaces its chunks.
+            store
+                .write_document("fox.txt", &chunks[..2], token_counter)
+                .unwrap();
+            store
+                .write_document("dog.txt", &chunks[2..], token_counter)
+                .unwrap();
+        }
+
+        // Chunks persist once the store is reopened.
+        let mut store = SqliteChunkStore::open(&path).unwrap().upsert_by_hash();
+        let stored = store.chunks("fox.txt").unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].doc_id, "fox.txt");
+        assert_eq!(stored[0].index, 0);
+        assert_eq!(stored[0].chunk, chunks[0]);
+        assert_eq!(stored[0].tokens, 4);
+        assert_eq!(stored[0].hash, chunk_hash(&chunks[0].text));
+        assert!(store.contains_hash(&chunk_hash("dog.")).unwrap());
+        assert!(!store.contains_hash(&chunk_hash("cat.")).unwrap());
+
+        // Upserting a stored text moves it rather than duplicating it.
+        store.write("cat.txt", 5, &chunks[0], 4).unwrap();
+        assert_eq!(store.len().unwrap(), 3);
+        assert_eq!(store.chunks("fox.txt").unwrap().len(), 1);
+        assert_eq!(store.chunks("cat.txt").unwrap()[0].index, 5);
+
+        assert_eq!(store.delete_document("dog.txt").unwrap(), 1);
+        assert_eq!(store.len().unwrap(), 2);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Templates for rendering chunks into retrieval-ready passages, such as `"{title} > {heading}\n\n{text}"`, filled in with the fields of a document and the text of each chunk.
+//!
+//! [`Chunker::chunk_with_template`] truncates the fields of a document so that the template takes at most a share of the chunk size, and leaves the rest of each chunk to its text.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunker::Chunker;
+
+/// The text of each chunk marks where the text goes in a template.
+const TEXT_FIELD: &str = "text";
+
+/// Marks the end of a truncated field.
+const ELLIPSIS: char = '…';
+
+/// A template for rendering chunks, with fields in braces and `{text}` where the text of the chunk goes.
+///
+/// Braces are written in the template as `{{` and `}}`. Fields without a value render as empty.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::template::Template;
+/// let template = Template::new("{title} > {heading}\n\n{text}").unwrap();
+/// let fields = [("title", "Fables"), ("heading", "The Fox")];
+/// assert_eq!(template.render(&fields, "The quick brown fox."), "Fables > The Fox\n\nThe quick brown fox.");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+    max_field_tokens: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Field(String),
+    Text,
+}
+
+/// The error returned when parsing an invalid [`Template`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `{` at the given byte offset is never closed.
+    Unclosed(usize),
+    /// A `}` at the given byte offset closes no field.
+    Unopened(usize),
+    /// The template has no `{text}` field.
+    MissingText,
+    /// The template has more than one `{text}` field.
+    RepeatedText,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Unclosed(offset) => write!(f, "unclosed `{{` at byte {}", offset),
+            TemplateError::Unopened(offset) => write!(f, "unopened `}}` at byte {}", offset),
+            TemplateError::MissingText => write!(f, "the template has no `{{text}}` field"),
+            TemplateError::RepeatedText => {
+                write!(f, "the template has more than one `{{text}}` field")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    /// Parses a template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template, with fields in braces and exactly one `{text}`.
+    ///
+    /// # Returns
+    ///
+    /// The template, or an error if a brace is unmatched or `{text}` is missing or repeated.
+    pub fn new(template: &str) -> Result<Self, TemplateError> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut has_text = false;
+        let mut chars = template.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => literal.push('}'),
+                '}' => return Err(TemplateError::Unopened(offset)),
+                '{' => {
+                    let start = offset + 1;
+                    let end = template[start..]
+                        .find(['{', '}'])
+                        .map(|end| start + end)
+                        .filter(|&end| template[end..].starts_with('}'))
+                        .ok_or(TemplateError::Unclosed(offset))?;
+                    while chars.next_if(|&(offset, _)| offset <= end).is_some() {}
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(match &template[start..end] {
+                        TEXT_FIELD if has_text => return Err(TemplateError::RepeatedText),
+                        TEXT_FIELD => {
+                            has_text = true;
+                            Piece::Text
+                        }
+                        name => Piece::Field(name.to_string()),
+                    });
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        match has_text {
+            true => Ok(Template {
+                pieces,
+                max_field_tokens: None,
+            }),
+            false => Err(TemplateError::MissingText),
+        }
+    }
+
+    /// Sets the most tokens the template may take without the text of the chunk, which defaults to half the chunk size. Fields are truncated to fit.
+    pub fn max_field_tokens(mut self, max_field_tokens: usize) -> Self {
+        self.max_field_tokens = Some(max_field_tokens);
+        self
+    }
+
+    /// The names of the fields in the template, other than `text`, in order.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Field(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Renders the template with the given fields and text, without truncating the fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The values of the fields, by name.
+    /// * `text` - The text of the chunk.
+    pub fn render(&self, fields: &[(&str, &str)], text: &str) -> String {
+        let (before, after) = self.frame(fields, None);
+        format!("{}{}{}", before, text, after)
+    }
+
+    /// Renders the parts of the template before and after the text, truncating each field to at most `max_chars` characters.
+    fn frame(&self, fields: &[(&str, &str)], max_chars: Option<usize>) -> (String, String) {
+        let (mut before, mut after) = (String::new(), String::new());
+        let mut rendered = &mut before;
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(literal) => rendered.push_str(literal),
+                Piece::Field(name) => {
+                    let value = fields
+                        .iter()
+                        .find(|(field, _)| field == name)
+                        .map_or("", |(_, value)| value);
+                    match max_chars {
+                        Some(max_chars) => rendered.push_str(&truncate(value, max_chars)),
+                        None => rendered.push_str(value),
+                    }
+                }
+                Piece::Text => rendered = &mut after,
+            }
+        }
+        (before, after)
+    }
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Template::new(template)
+    }
+}
+
+/// Truncates `value` to at most `max_chars` characters, ending truncated values with an ellipsis.
+fn truncate(value: &str, max_chars: usize) -> String {
+    match value.char_indices().nth(max_chars.saturating_sub(1)) {
+        _ if max_chars == 0 => String::new(),
+        Some((end, _)) if value[end..].chars().nth(1).is_some() => {
+            let mut truncated = value[..end].trim_end().to_string();
+            truncated.push(ELLIPSIS);
+            truncated
+        }
+        _ => value.to_string(),
+    }
+}
+
+impl Chunker {
+    /// Chunks the given text and renders each chunk with a template, truncating the fields so that the template takes at most its share of each chunk and chunks still fit in the chunk size.
+    ///
+    /// Fields are truncated to a common number of characters, the largest for which the template without the text fits in [`Template::max_field_tokens`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - A string slice that holds the text to be chunked.
+    /// * `template` - The template each chunk is rendered with.
+    /// * `fields` - The values of the fields of the template, by name.
+    ///
+    /// # Returns
+    ///
+    /// The rendered chunks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use semchunk_rs::template::Template;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(8, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let template = Template::new("[{title}] {text}").unwrap();
+    /// let fields = [("title", "Fables of Aesop, Volume One")];
+    /// let chunks = chunker.chunk_with_template("The quick brown fox jumps over the lazy dog.", &template, &fields);
+    /// assert_eq!(
+    ///     chunks,
+    ///     vec![
+    ///         "[Fables of Aesop, Volume…] The quick brown fox",
+    ///         "[Fables of Aesop, Volume…] jumps over the lazy",
+    ///         "[Fables of Aesop, Volume…] dog.",
+    ///     ]
+    /// );
+    /// ```
+    pub fn chunk_with_template(
+        &self,
+        text: &str,
+        template: &Template,
+        fields: &[(&str, &str)],
+    ) -> Vec<String> {
+        let max_field_tokens = template.max_field_tokens.unwrap_or(self.chunk_size() / 2);
+        let count = |frame: &str| match frame.is_empty() {
+            true => 0,
+            false => self.count_tokens(frame),
+        };
+        let fits = |max_chars: Option<usize>| {
+            let (before, after) = template.frame(fields, max_chars);
+            count(&before) + count(&after) <= max_field_tokens
+        };
+        let max_chars = match fits(None) {
+            true => None,
+            false => {
+                // The template fits with more of each field as long as it fits with fewer.
+                let longest = fields
+                    .iter()
+                    .map(|(_, value)| value.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let (mut low, mut high) = (0, longest);
+                while low < high {
+                    let mid = (low + high).div_ceil(2);
+                    match fits(Some(mid)) {
+                        true => low = mid,
+                        false => high = mid - 1,
+                    }
+                }
+                Some(low)
+            }
+        };
+        let (before, after) = template.frame(fields, max_chars);
+        self.chunk_framed(text, &before, &after)
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let template = Template::new("{{{title}}} > {heading}: {text}").unwrap();
+        assert_eq!(
+            template.fields().collect::<Vec<_>>(),
+            vec!["title", "heading"]
+        );
+        assert_eq!(
+            template.render(&[("title", "Fables")], "The fox."),
+            "{Fables} > : The fox."
+        );
+        assert_eq!("{text}".parse::<Template>().unwrap().render(&[], "é"), "é");
+
+        assert_eq!(Template::new("{title"), Err(TemplateError::Unclosed(0)));
+        assert_eq!(
+            Template::new("{ti{tle} {text}"),
+            Err(TemplateError::Unclosed(0))
+        );
+        assert_eq!(
+            Template::new("title} {text}"),
+            Err(TemplateError::Unopened(5))
+        );
+        assert_eq!(Template::new("{title}"), Err(TemplateError::MissingText));
+        assert_eq!(
+            Template::new("{text}{text}"),
+            Err(TemplateError::RepeatedText)
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Fables", 6), "Fables");
+        assert_eq!(truncate("Fables", 10), "Fables");
+        assert_eq!(truncate("Fables", 5), "Fabl…");
+        assert_eq!(truncate("Of Fables", 4), "Of…");
+        assert_eq!(truncate("Fables", 1), "…");
+        assert_eq!(truncate("Fables", 0), "");
+        assert_eq!(truncate("ééé", 2), "é…");
+    }
+
+    #[test]
+    fn test_chunk_with_template() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let words = |s: &str| s.split_whitespace().count();
+        let template = Template::new("{title} > {heading}\n\n{text}\n").unwrap();
+        let fields = [
+            ("title", "Fables of Aesop"),
+            ("heading", "The Fox and the Dog"),
+        ];
+
+        // Fields that fit are not truncated.
+        let chunker = Chunker::new(20, Box::new(words));
+        let chunks = chunker.chunk_with_template(text, &template, &fields);
+        assert_eq!(
+            chunks,
+            vec!["Fables of Aesop > The Fox and the Dog\n\nThe quick brown fox jumps over the lazy dog.\n"]
+        );
+
+        // Fields that do not fit are truncated to a common length.
+        let chunker = Chunker::new(10, Box::new(words));
+        let chunks = chunker.chunk_with_template(text, &template, &fields);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("Fables o… > The Fox…\n\n"), "{:?}", chunk);
+            assert!(words(chunk) <= 10);
+        }
+        assert_eq!(chunks.len(), 2);
+
+        let template = template.max_field_tokens(1);
+        let chunks = chunker.chunk_with_template(text, &template, &fields);
+        assert_eq!(
+            chunks,
+            vec![" > \n\nThe quick brown fox jumps over the lazy dog.\n"]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! JavaScript bindings, built with the `wasm` feature into a WebAssembly module by the `bindings/wasm` crate.
+//!
+//! ```js
+//! import { Chunker } from "semchunk-wasm";
+//!
+//! const chunker = new Chunker(512, (text) => encoder.encode(text).length);
+//! const chunks = chunker.chunk("The quick brown fox jumps over the lazy dog.");
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Array, Function, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::chunk::OffsetConverter;
+use crate::chunker::Chunker;
+
+/// A chunker usable from JavaScript.
+///
+/// The chunk size must be at least 1. The token counter is either a function taking a string and returning a number, or the name of a built-in counter: `"words"` (whitespace-separated words) or `"chars"` (Unicode code points). If the function throws or returns something other than a non-negative finite number, chunking stops and the error is thrown from `chunk` or `chunkWithOffsets`.
+#[wasm_bindgen(js_name = Chunker)]
+pub struct JsChunker {
+    chunker: Chunker,
+    /// The first error of the token counter function in the current chunking call.
+    counter_error: Rc<RefCell<Option<JsError>>>,
+}
+
+#[wasm_bindgen(js_class = Chunker)]
+impl JsChunker {
+    #[wasm_bindgen(constructor)]
+    pub fn new(chunk_size: usize, token_counter: JsValue) -> Result<JsChunker, JsError> {
+        let counter_error = Rc::new(RefCell::new(None));
+        let token_counter: Box<dyn Fn(&str) -> usize> =
+            if let Some(name) = token_counter.as_string() {
+                match name.as_str() {
+                    "words" => Box::new(|s: &str| s.split_whitespace().count()),
+                    "chars" => Box::new(|s: &str| s.chars().count()),
+                    _ => return Err(JsError::new(&format!("unknown token counter '{}'", name))),
+                }
+            } else if let Some(function) = token_counter.dyn_ref::<Function>() {
+                let function = function.clone();
+                let counter_error = counter_error.clone();
+                Box::new(move |s: &str| {
+                    if counter_error.borrow().is_some() {
+                        // Every text fits once the counter has failed, so chunking finishes without calling it again.
+                        return 0;
+                    }
+                    let count = function
+                        .call1(&JsValue::NULL, &JsValue::from_str(s))
+                        .map_err(|error| {
+                            let message = match error.dyn_ref::<js_sys::Error>() {
+                                Some(error) => String::from(error.message()),
+                                None => format!("{:?}", error),
+                            };
+                            JsError::new(&format!("token counter threw: {}", message))
+                        })
+                        .and_then(|count| {
+                            count
+                                .as_f64()
+                                .ok_or_else(|| JsError::new("token counter must return a number"))
+                        })
+                        .and_then(|count| token_count(count).map_err(JsError::new));
+                    match count {
+                        Ok(count) => count,
+                        Err(error) => {
+                            *counter_error.borrow_mut() = Some(error);
+                            0
+                        }
+                    }
+                })
+            } else {
+                return Err(JsError::new(
+                    "token counter must be a function or the name of a built-in counter",
+                ));
+            };
+        Ok(JsChunker {
+            chunker: Chunker::try_new(chunk_size, token_counter)?,
+            counter_error,
+        })
+    }
+
+    /// Chunks the text, returning an array of strings.
+    pub fn chunk(&self, text: &str) -> Result<Vec<String>, JsError> {
+        let chunks = self.chunker.chunk(text);
+        self.take_counter_error().map(|()| chunks)
+    }
+
+    /// Chunks the text, returning an array of `{ text, start, end }` objects.
+    ///
+    /// Offsets are UTF-16 code unit indices, so `text.slice(start, end)` covers the chunk.
+    #[wasm_bindgen(js_name = chunkWithOffsets)]
+    pub fn chunk_with_offsets(&self, text: &str) -> Result<Array, JsValue> {
+        let chunks = Array::new();
+        let mut result = Ok(());
+        let mut offsets = OffsetConverter::utf16(text);
+        self.chunker.chunk_to(text, |chunk| {
+            if result.is_ok() {
+                let (start, end) = (offsets.convert(chunk.start), offsets.convert(chunk.end));
+                result = chunk_object(&chunk.text, start, end).map(|object| {
+                    chunks.push(&object);
+                });
+            }
+        });
+        self.take_counter_error()?;
+        result.map(|_| chunks)
+    }
+}
+
+impl JsChunker {
+    /// Returns the error of the token counter function in the last chunking call, if any, clearing it for the next call.
+    fn take_counter_error(&self) -> Result<(), JsError> {
+        match self.counter_error.borrow_mut().take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Converts the number returned by a token counter function to a token count, rounding fractions up so that chunks are never undercounted.
+fn token_count(count: f64) -> Result<usize, &'static str> {
+    match count.is_finite() && count >= 0.0 {
+        true => Ok(count.ceil() as usize),
+        false => Err("token counter must return a non-negative finite number"),
+    }
+}
+
+fn chunk_object(text: &str, start: usize, end: usize) -> Result<Object, JsValue> {
+    let object = Object::new();
+    Reflect::set(&object, &"text".into(), &text.into())?;
+    Reflect::set(&object, &"start".into(), &start.into())?;
+    Reflect::set(&object, &"end".into(), &end.into())?;
+    Ok(object)
+}
+
+#[cfg(test)]
+mod wasm_tests {
+    use super::*;
+
+    #[test]
+    fn test_token_count() {
+        assert_eq!(token_count(3.0), Ok(3));
+        assert_eq!(token_count(2.5), Ok(3));
+        assert_eq!(token_count(0.0), Ok(0));
+        for count in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1.0] {
+            assert_eq!(
+                token_count(count),
+                Err("token counter must return a non-negative finite number")
+            );
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+
+/// A compression format for the output of a writer, applied by wrapping its destination with [`Compression::encoder`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// No compression.
+    #[default]
+    None,
+    /// Gzip, at the default level.
+    Gzip,
+    /// Zstandard, at the default level.
+    Zstd,
+}
+
+impl Compression {
+    /// The extension conventionally appended to the names of files compressed in this format.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// Wraps a writer so that everything written to it is compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the compressed output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use semchunk_rs::writers::Compression;
+    /// use semchunk_rs::Chunker;
+    ///
+    /// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+    /// let mut out = Compression::Zstd.encoder(Vec::new()).unwrap();
+    /// chunker.chunk_to("The quick brown fox jumps over the lazy dog.", |chunk| {
+    ///     writeln!(out, "{}", chunk.text).unwrap();
+    /// });
+    /// let compressed = out.finish().unwrap();
+    /// let output = zstd::decode_all(compressed.as_slice()).unwrap();
+    /// assert_eq!(output, b"The quick brown fox\njumps over the lazy\ndog.\n");
+    /// ```
+    pub fn encoder<W: Write>(self, writer: W) -> io::Result<Encoder<W>> {
+        Ok(match self {
+            Compression::None => Encoder::None(writer),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+}
+
+/// A writer that compresses its output. [`Encoder::finish`] must be called to write the end of the stream.
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the compressed stream and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::None(writer) => Ok(writer),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod compression_tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_encoder_round_trip() {
+        let text = "The quick brown fox jumps over the lazy dog.\n".repeat(100);
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let mut encoder = compression.encoder(Vec::new()).unwrap();
+            encoder.write_all(text.as_bytes()).unwrap();
+            let output = encoder.finish().unwrap();
+            let mut decompressed = String::new();
+            match compression {
+                Compression::None => decompressed = String::from_utf8(output).unwrap(),
+                Compression::Gzip => {
+                    GzDecoder::new(output.as_slice())
+                        .read_to_string(&mut decompressed)
+                        .unwrap();
+                }
+                Compression::Zstd => {
+                    decompressed =
+                        String::from_utf8(zstd::decode_all(output.as_slice()).unwrap()).unwrap();
+                }
+            }
+            assert_eq!(decompressed, text, "{:?}", compression);
+        }
+    }
+}
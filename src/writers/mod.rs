@@ -0,0 +1,41 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Writers that serialize chunks as they are produced.
+
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "ndjson")]
+mod ndjson;
+#[cfg(feature = "ndjson")]
+mod openai_batch;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+#[cfg(feature = "compression")]
+pub use self::compression::{Compression, Encoder};
+#[cfg(feature = "ndjson")]
+pub use self::ndjson::{ndjson, ndjson_compressed, ChunkRecord, NdjsonWriter};
+#[cfg(feature = "ndjson")]
+pub use self::openai_batch::{openai_batch, BatchRequest, EmbeddingsBody, OpenAiBatchWriter};
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetChunkWriter;
@@ -0,0 +1,242 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, BufWriter, Write};
+
+use serde::Serialize;
+
+use super::compression::{Compression, Encoder};
+use crate::chunk::Chunk;
+
+/// A chunk and its metadata, as written on each line by [`NdjsonWriter`].
+///
+/// # Fields
+///
+/// * `doc` - The identifier of the document the chunk is from.
+/// * `index` - The position of the chunk in its document.
+/// * `text` - The text of the chunk.
+/// * `start` - The byte offset the chunk starts at.
+/// * `end` - The byte offset the chunk ends at.
+/// * `tokens` - The number of tokens in the chunk.
+/// * `language` - The ISO 639-3 code of the chunk's language, if detected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkRecord<'a> {
+    pub doc: &'a str,
+    pub index: usize,
+    pub text: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'static str>,
+}
+
+/// Writes chunks as newline-delimited JSON, one [`ChunkRecord`] per line.
+///
+/// Output is buffered, so it should be finished with [`NdjsonWriter::finish`] to flush it and, if compressed, write the end of the compressed stream.
+pub struct NdjsonWriter<W: Write> {
+    out: BufWriter<Encoder<W>>,
+    #[cfg(feature = "whatlang")]
+    detect_language: bool,
+}
+
+/// Creates a writer that writes chunks as newline-delimited JSON to `writer`.
+///
+/// # Arguments
+///
+/// * `writer` - The destination of the output.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::{writers, Chunker};
+///
+/// let token_counter = |s: &str| s.split_whitespace().count();
+/// let chunker = Chunker::new(4, Box::new(token_counter));
+/// let mut writer = writers::ndjson(Vec::new());
+/// let mut index = 0;
+/// chunker.chunk_to("The quick brown fox jumps over the lazy dog.", |chunk| {
+///     let tokens = token_counter(&chunk.text);
+///     writer.write("fox.txt", index, &chunk, tokens).unwrap();
+///     index += 1;
+/// });
+/// let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert_eq!(
+///     output.lines().next().unwrap(),
+///     r#"{"doc":"fox.txt","index":0,"text":"The quick brown fox","start":0,"end":19,"tokens":4}"#
+/// );
+/// ```
+pub fn ndjson<W: Write>(writer: W) -> NdjsonWriter<W> {
+    NdjsonWriter {
+        out: BufWriter::new(Encoder::None(writer)),
+        #[cfg(feature = "whatlang")]
+        detect_language: false,
+    }
+}
+
+/// Creates a writer that writes chunks as compressed newline-delimited JSON to `writer`.
+///
+/// # Arguments
+///
+/// * `writer` - The destination of the output.
+/// * `compression` - The compression format of the output.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::writers::{self, Compression};
+/// use semchunk_rs::Chunk;
+///
+/// let mut writer = writers::ndjson_compressed(Vec::new(), Compression::Zstd).unwrap();
+/// let chunk = Chunk { text: "Hello, world!".to_string(), start: 0, end: 13 };
+/// writer.write("hello.txt", 0, &chunk, 2).unwrap();
+/// let output = zstd::decode_all(writer.finish().unwrap().as_slice()).unwrap();
+/// assert!(output.starts_with(br#"{"doc":"hello.txt","index":0"#));
+/// ```
+pub fn ndjson_compressed<W: Write>(
+    writer: W,
+    compression: Compression,
+) -> io::Result<NdjsonWriter<W>> {
+    Ok(NdjsonWriter {
+        out: BufWriter::new(compression.encoder(writer)?),
+        #[cfg(feature = "whatlang")]
+        detect_language: false,
+    })
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    /// Tags each chunk with its detected language, in the `language` field.
+    #[cfg(feature = "whatlang")]
+    pub fn detect_language(mut self) -> Self {
+        self.detect_language = true;
+        self
+    }
+
+    /// Writes a chunk as a line.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The identifier of the document the chunk is from.
+    /// * `index` - The position of the chunk in its document.
+    /// * `chunk` - The chunk.
+    /// * `tokens` - The number of tokens in the chunk.
+    pub fn write(
+        &mut self,
+        doc: &str,
+        index: usize,
+        chunk: &Chunk,
+        tokens: usize,
+    ) -> io::Result<()> {
+        let record = ChunkRecord {
+            doc,
+            index,
+            text: &chunk.text,
+            start: chunk.start,
+            end: chunk.end,
+            tokens,
+            language: self.language(chunk),
+        };
+        serde_json::to_writer(&mut self.out, &record)?;
+        self.out.write_all(b"\n")
+    }
+
+    /// The code of the chunk's language, if languages are detected.
+    #[cfg(feature = "whatlang")]
+    fn language(&self, chunk: &Chunk) -> Option<&'static str> {
+        match self.detect_language {
+            true => chunk.language().map(|lang| lang.code()),
+            false => None,
+        }
+    }
+
+    #[cfg(not(feature = "whatlang"))]
+    fn language(&self, _chunk: &Chunk) -> Option<&'static str> {
+        None
+    }
+
+    /// Flushes the buffered output and, if compressed, writes the end of the compressed stream.
+    ///
+    /// # Returns
+    ///
+    /// The underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.out.into_inner().map_err(|e| e.into_error())?.finish()
+    }
+}
+
+#[cfg(test)]
+mod ndjson_tests {
+    use super::*;
+    use std::io::Read;
+
+    fn write_chunks<W: Write>(mut writer: NdjsonWriter<W>) -> W {
+        let chunks = [("one two", 0, 7), ("three \"four\"", 8, 20)];
+        for (index, (text, start, end)) in chunks.into_iter().enumerate() {
+            let chunk = Chunk {
+                text: text.to_string(),
+                start,
+                end,
+            };
+            writer.write("doc", index, &chunk, 2).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    const EXPECTED: &str = concat!(
+        r#"{"doc":"doc","index":0,"text":"one two","start":0,"end":7,"tokens":2}"#,
+        "\n",
+        r#"{"doc":"doc","index":1,"text":"three \"four\"","start":8,"end":20,"tokens":2}"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_ndjson() {
+        let output = write_chunks(ndjson(Vec::new()));
+        assert_eq!(String::from_utf8(output).unwrap(), EXPECTED);
+    }
+
+    #[test]
+    fn test_ndjson_compressed() {
+        let output = write_chunks(ndjson_compressed(Vec::new(), Compression::Gzip).unwrap());
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(output.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, EXPECTED);
+
+        let output = write_chunks(ndjson_compressed(Vec::new(), Compression::Zstd).unwrap());
+        let decoded = zstd::decode_all(output.as_slice()).unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), EXPECTED);
+    }
+
+    #[cfg(feature = "whatlang")]
+    #[test]
+    fn test_ndjson_language() {
+        let output = write_chunks(ndjson(Vec::new()).detect_language());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(r#""tokens":2,"language":"eng"}"#));
+    }
+}
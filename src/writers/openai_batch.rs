@@ -0,0 +1,155 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, BufWriter, Write};
+
+use serde::Serialize;
+
+use crate::chunk::{chunk_id, Chunk};
+
+/// A request to embed a chunk, as written on each line by [`OpenAiBatchWriter`] in the input file format of OpenAI's Batch API.
+///
+/// # Fields
+///
+/// * `custom_id` - The [`chunk_id`] of the chunk, which the Batch API returns with the embedding.
+/// * `method` - Always `"POST"`.
+/// * `url` - Always `"/v1/embeddings"`.
+/// * `body` - The body of the embeddings request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchRequest<'a> {
+    pub custom_id: String,
+    pub method: &'static str,
+    pub url: &'static str,
+    pub body: EmbeddingsBody<'a>,
+}
+
+/// The body of a request to OpenAI's embeddings endpoint.
+///
+/// # Fields
+///
+/// * `model` - The embedding model.
+/// * `input` - The text of the chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmbeddingsBody<'a> {
+    pub model: &'a str,
+    pub input: &'a str,
+}
+
+/// Writes chunks as requests to embed them with OpenAI's Batch API, one [`BatchRequest`] per line.
+///
+/// The Batch API accepts at most 50,000 requests per file, so large corpora should be split across several writers. Output is buffered, so it should be finished with [`OpenAiBatchWriter::finish`] to flush it.
+pub struct OpenAiBatchWriter<W: Write> {
+    out: BufWriter<W>,
+    model: String,
+}
+
+/// Creates a writer that writes chunks as OpenAI Batch API embedding requests to `writer`.
+///
+/// # Arguments
+///
+/// * `writer` - The destination of the output.
+/// * `model` - The embedding model to request, such as `"text-embedding-3-small"`.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::{writers, Chunker};
+///
+/// let chunker = Chunker::new(4, Box::new(|s: &str| s.split_whitespace().count()));
+/// let mut writer = writers::openai_batch(Vec::new(), "text-embedding-3-small");
+/// let mut index = 0;
+/// chunker.chunk_to("The quick brown fox jumps over the lazy dog.", |chunk| {
+///     writer.write("fox.txt", index, &chunk).unwrap();
+///     index += 1;
+/// });
+/// let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert_eq!(
+///     output.lines().next().unwrap(),
+///     r#"{"custom_id":"fox.txt-0","method":"POST","url":"/v1/embeddings","body":{"model":"text-embedding-3-small","input":"The quick brown fox"}}"#
+/// );
+/// ```
+pub fn openai_batch<W: Write>(writer: W, model: &str) -> OpenAiBatchWriter<W> {
+    OpenAiBatchWriter {
+        out: BufWriter::new(writer),
+        model: model.to_string(),
+    }
+}
+
+impl<W: Write> OpenAiBatchWriter<W> {
+    /// Writes a request to embed a chunk as a line.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The identifier of the document the chunk is from.
+    /// * `index` - The position of the chunk in its document.
+    /// * `chunk` - The chunk.
+    pub fn write(&mut self, doc: &str, index: usize, chunk: &Chunk) -> io::Result<()> {
+        let request = BatchRequest {
+            custom_id: chunk_id(doc, index),
+            method: "POST",
+            url: "/v1/embeddings",
+            body: EmbeddingsBody {
+                model: &self.model,
+                input: &chunk.text,
+            },
+        };
+        serde_json::to_writer(&mut self.out, &request)?;
+        self.out.write_all(b"\n")
+    }
+
+    /// Flushes the buffered output.
+    ///
+    /// # Returns
+    ///
+    /// The underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.out.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+#[cfg(test)]
+mod openai_batch_tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_batch() {
+        let mut writer = openai_batch(Vec::new(), "text-embedding-3-large");
+        let chunks = [("one \"two\"", 0, 9), ("three", 10, 15)];
+        for (index, (text, start, end)) in chunks.into_iter().enumerate() {
+            let chunk = Chunk {
+                text: text.to_string(),
+                start,
+                end,
+            };
+            writer.write("doc", index, &chunk).unwrap();
+        }
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let requests: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["body"]["input"], "one \"two\"");
+        assert_eq!(requests[1]["custom_id"], "doc-1");
+        assert_eq!(requests[1]["body"]["model"], "text-embedding-3-large");
+    }
+}
@@ -0,0 +1,256 @@
+// MIT License
+//
+// Copyright (c) 2024 Dominic Tarro
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::builder::{StringBuilder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::Result;
+use parquet::file::properties::WriterProperties;
+
+use crate::chunk::Chunk;
+
+/// The number of rows buffered before they are written as a row group batch.
+const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// Streams chunks into a zstd-compressed Parquet file, for loading into Spark, DuckDB, Polars and the like.
+///
+/// Every file has the same schema:
+///
+/// | column   | type   | description                                       |
+/// |----------|--------|---------------------------------------------------|
+/// | `doc_id` | utf8   | The identifier of the document the chunk is from. |
+/// | `index`  | uint64 | The position of the chunk in its document.        |
+/// | `text`   | utf8   | The text of the chunk.                            |
+/// | `start`  | uint64 | The byte offset the chunk starts at.              |
+/// | `end`    | uint64 | The byte offset the chunk ends at.                |
+/// | `tokens` | uint64 | The number of tokens in the chunk.                |
+///
+/// Rows are buffered and written in batches, so memory stays bounded regardless of the number of chunks. The file is only valid once [`ParquetChunkWriter::close`] is called.
+///
+/// # Examples
+///
+/// ```
+/// use semchunk_rs::writers::ParquetChunkWriter;
+/// use semchunk_rs::Chunker;
+///
+/// let token_counter = |s: &str| s.split_whitespace().count();
+/// let chunker = Chunker::new(4, Box::new(token_counter));
+/// let mut writer = ParquetChunkWriter::new(Vec::new()).unwrap();
+/// let mut index = 0;
+/// chunker.chunk_to("The quick brown fox jumps over the lazy dog.", |chunk| {
+///     let tokens = token_counter(&chunk.text);
+///     writer.write("fox.txt", index, &chunk, tokens).unwrap();
+///     index += 1;
+/// });
+/// let bytes = writer.close().unwrap();
+/// assert!(bytes.starts_with(b"PAR1"));
+/// ```
+pub struct ParquetChunkWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    batch_size: usize,
+    rows: usize,
+    doc_ids: StringBuilder,
+    indices: UInt64Builder,
+    texts: StringBuilder,
+    starts: UInt64Builder,
+    ends: UInt64Builder,
+    tokens: UInt64Builder,
+}
+
+impl<W: Write + Send> ParquetChunkWriter<W> {
+    /// Creates a new ParquetChunkWriter that writes to `writer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the Parquet file, e.g. a `File` or `Vec<u8>`.
+    ///
+    /// # Returns
+    ///
+    /// A new ParquetChunkWriter, or an error if the file header could not be written.
+    pub fn new(writer: W) -> Result<Self> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        Ok(ParquetChunkWriter {
+            writer: ArrowWriter::try_new(writer, Self::schema(), Some(properties))?,
+            batch_size: DEFAULT_BATCH_SIZE,
+            rows: 0,
+            doc_ids: StringBuilder::new(),
+            indices: UInt64Builder::new(),
+            texts: StringBuilder::new(),
+            starts: UInt64Builder::new(),
+            ends: UInt64Builder::new(),
+            tokens: UInt64Builder::new(),
+        })
+    }
+
+    /// Sets the number of rows buffered before they are written. Defaults to 8192.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The schema of the files written by ParquetChunkWriter.
+    pub fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("doc_id", DataType::Utf8, false),
+            Field::new("index", DataType::UInt64, false),
+            Field::new("text", DataType::Utf8, false),
+            Field::new("start", DataType::UInt64, false),
+            Field::new("end", DataType::UInt64, false),
+            Field::new("tokens", DataType::UInt64, false),
+        ]))
+    }
+
+    /// Writes a chunk as a row.
+    ///
+    /// # Arguments
+    ///
+    /// * `doc_id` - The identifier of the document the chunk is from.
+    /// * `index` - The position of the chunk in its document.
+    /// * `chunk` - The chunk.
+    /// * `tokens` - The number of tokens in the chunk.
+    pub fn write(
+        &mut self,
+        doc_id: &str,
+        index: usize,
+        chunk: &Chunk,
+        tokens: usize,
+    ) -> Result<()> {
+        self.doc_ids.append_value(doc_id);
+        self.indices.append_value(index as u64);
+        self.texts.append_value(&chunk.text);
+        self.starts.append_value(chunk.start as u64);
+        self.ends.append_value(chunk.end as u64);
+        self.tokens.append_value(tokens as u64);
+        self.rows += 1;
+        if self.rows >= self.batch_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered rows to the underlying ArrowWriter.
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.doc_ids.finish()),
+            Arc::new(self.indices.finish()),
+            Arc::new(self.texts.finish()),
+            Arc::new(self.starts.finish()),
+            Arc::new(self.ends.finish()),
+            Arc::new(self.tokens.finish()),
+        ];
+        self.rows = 0;
+        let batch = RecordBatch::try_new(Self::schema(), columns)?;
+        self.writer.write(&batch)
+    }
+
+    /// Writes the remaining rows and the file footer.
+    ///
+    /// # Returns
+    ///
+    /// The underlying writer.
+    pub fn close(mut self) -> Result<W> {
+        self.flush_batch()?;
+        self.writer.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod parquet_tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = ParquetChunkWriter::new(Vec::new()).unwrap().batch_size(2);
+        let chunks = [
+            ("a", 0, "one two", 0, 7),
+            ("a", 1, "three", 8, 13),
+            ("b", 0, "four", 0, 4),
+        ];
+        for (doc_id, index, text, start, end) in chunks {
+            let chunk = Chunk {
+                text: text.to_string(),
+                start,
+                end,
+            };
+            writer
+                .write(doc_id, index, &chunk, text.split(' ').count())
+                .unwrap();
+        }
+        let bytes = bytes::Bytes::from(writer.close().unwrap());
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes).unwrap();
+        assert_eq!(reader.schema(), &ParquetChunkWriter::<Vec<u8>>::schema());
+        let (mut doc_ids, mut texts, mut ends, mut tokens) = (vec![], vec![], vec![], vec![]);
+        for batch in reader.build().unwrap() {
+            let batch = batch.unwrap();
+            doc_ids.extend(
+                batch
+                    .column(0)
+                    .as_string::<i32>()
+                    .iter()
+                    .flatten()
+                    .map(String::from),
+            );
+            texts.extend(
+                batch
+                    .column(2)
+                    .as_string::<i32>()
+                    .iter()
+                    .flatten()
+                    .map(String::from),
+            );
+            ends.extend(
+                batch
+                    .column(4)
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .iter()
+                    .copied(),
+            );
+            tokens.extend(
+                batch
+                    .column(5)
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .iter()
+                    .copied(),
+            );
+        }
+        assert_eq!(doc_ids, vec!["a", "a", "b"]);
+        assert_eq!(texts, vec!["one two", "three", "four"]);
+        assert_eq!(ends, vec![7, 13, 4]);
+        assert_eq!(tokens, vec![2, 1, 1]);
+    }
+}